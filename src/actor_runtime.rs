@@ -1,180 +1,845 @@
-use crate::ast::*;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use crate::parallel::ParallelRuntime;
+use crate::value::{Sendable, Value};
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// Messages an actor handles in one go before its worker moves on, so a busy
+/// actor cannot keep a worker to itself
+const BATCH_SIZE: usize = 32;
+
+/// Messages a mailbox holds before senders have to wait
+pub const DEFAULT_MAILBOX_CAPACITY: usize = 1024;
+
+thread_local! {
+    /// Id of the actor whose message is being handled on this thread
+    static CURRENT_ACTOR: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// How an actor builds its state and handles its messages. The VM implements
+/// this to run the actor's field initializers and methods.
+pub trait ActorBehavior: Send {
+    /// Initial state, from the actor's state fields
+    fn init(&mut self) -> Result<HashMap<String, Value>, String>;
+    
+    /// Runs `method` with `args` against `state`
+    fn handle(&mut self, state: &mut HashMap<String, Value>, method: &str, args: Vec<Value>) -> Result<Value, String>;
+}
 
 /// Actor runtime for message-passing concurrency
+///
+/// Actors have no threads of their own: an actor is scheduled when a message
+/// arrives for it, and handles its messages until its mailbox is empty. At
+/// most one worker runs a given actor at a time, so its messages are handled
+/// one by one in the order they were sent.
+#[derive(Clone)]
 pub struct ActorRuntime {
-    actors: Arc<Mutex<HashMap<String, Arc<Mutex<ActorInstance>>>>>,
+    shared: Arc<RuntimeShared>,
+}
+
+struct RuntimeShared {
+    dispatcher: Dispatcher,
+    mailbox_capacity: usize,
+    next_id: AtomicUsize,
+    /// Number of actors scheduled to run; the runtime is idle at zero
+    active: Mutex<usize>,
+    idle: Condvar,
+    /// Pairs of actor ids where the first cannot go on until the second does:
+    /// it waits for room in the second's mailbox or for its reply, or the
+    /// second runs on the thread where the first waits. Actors on a cycle wait
+    /// for each other forever.
+    blocked_on: Mutex<Vec<(usize, usize)>>,
+}
+
+/// How scheduled actors get to run
+#[derive(Clone)]
+enum Dispatcher {
+    /// As jobs on the work-stealing pool shared with parallel loops
+    Pool(ParallelRuntime),
+    /// One message at a time on whichever thread waits for an actor, taking
+    /// actors in the order they became ready, so every run is the same
+    Inline(Arc<Mutex<VecDeque<Arc<ActorCell>>>>),
+}
+
+/// Why waiting for an actor stopped before the awaited condition held
+enum Stalled {
+    TimedOut,
+    /// Nothing left to run could make the condition hold
+    Deadlock,
 }
 
 impl ActorRuntime {
-    pub fn new() -> Self {
+    /// Actors run on `pool`'s workers
+    pub fn new(pool: ParallelRuntime, mailbox_capacity: usize) -> Self {
+        Self::with_dispatcher(Dispatcher::Pool(pool), mailbox_capacity)
+    }
+    
+    /// Actors run on the thread that waits for them, one message at a time and
+    /// in a fixed order, so that tests see the same interleaving on every run
+    pub fn deterministic(mailbox_capacity: usize) -> Self {
+        Self::with_dispatcher(Dispatcher::Inline(Arc::new(Mutex::new(VecDeque::new()))), mailbox_capacity)
+    }
+    
+    fn with_dispatcher(dispatcher: Dispatcher, mailbox_capacity: usize) -> Self {
         Self {
-            actors: Arc::new(Mutex::new(HashMap::new())),
+            shared: Arc::new(RuntimeShared {
+                dispatcher,
+                mailbox_capacity: mailbox_capacity.max(1),
+                next_id: AtomicUsize::new(0),
+                active: Mutex::new(0),
+                idle: Condvar::new(),
+                blocked_on: Mutex::new(Vec::new()),
+            }),
         }
     }
     
-    /// Create a new actor instance
-    pub fn spawn(&self, name: String, actor_def: &Stmt) -> Result<ActorRef, String> {
-        let instance = ActorInstance::new(actor_def)?;
-        let instance_arc = Arc::new(Mutex::new(instance));
-        
-        {
-            let mut actors = self.actors.lock().unwrap();
-            actors.insert(name.clone(), instance_arc.clone());
-        }
-        
-        // Start actor message loop
-        let actors_clone = self.actors.clone();
-        let name_clone = name.clone();
-        thread::spawn(move || {
-            ActorRuntime::actor_loop(name_clone, actors_clone);
+    pub fn mailbox_capacity(&self) -> usize {
+        self.shared.mailbox_capacity
+    }
+    
+    pub fn is_deterministic(&self) -> bool {
+        matches!(self.shared.dispatcher, Dispatcher::Inline(_))
+    }
+    
+    /// Create a new actor instance, its state initialised by `behavior`
+    pub fn spawn(&self, name: &str, mut behavior: Box<dyn ActorBehavior>) -> Result<ActorRef, String> {
+        let state = behavior.init()?;
+        let id = self.shared.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let cell = Arc::new(ActorCell {
+            id,
+            name: name.to_string(),
+            runtime: Arc::downgrade(&self.shared),
+            mailbox: Mutex::new(VecDeque::new()),
+            space: Condvar::new(),
+            capacity: self.shared.mailbox_capacity,
+            scheduled: AtomicBool::new(false),
+            instance: Mutex::new(ActorInstance {
+                state,
+                behavior,
+            }),
+            supervisor: Mutex::new(None),
+            restart: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            closing: AtomicBool::new(false),
+            terminated: AtomicBool::new(false),
         });
-        
         Ok(ActorRef {
-            name,
-            runtime: self.actors.clone(),
+            id,
+            cell,
         })
     }
     
-    fn actor_loop(name: String, actors: Arc<Mutex<HashMap<String, Arc<Mutex<ActorInstance>>>>>) {
+    /// Puts `children` under a new supervisor that restarts them with
+    /// `strategy` when one fails, at most `max_restarts` times within `within`
+    pub fn supervise(&self, children: Vec<Child>, strategy: RestartStrategy, max_restarts: usize, within: Duration) -> Result<SupervisorRef, String> {
+        for (index, child) in children.iter().enumerate() {
+            if child.has_supervisor() {
+                return Err(format!("{} already has a supervisor", child));
+            }
+            if children[..index].iter().any(|other| other.same_as(child)) {
+                return Err(format!("{} is supervised twice", child));
+            }
+        }
+        
+        let id = self.shared.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let cell = Arc::new(SupervisorCell {
+            id,
+            strategy,
+            max_restarts,
+            within,
+            children,
+            restarts: Mutex::new(VecDeque::new()),
+            parent: Mutex::new(None),
+        });
+        for child in &cell.children {
+            child.set_supervisor(Arc::downgrade(&cell));
+        }
+        Ok(SupervisorRef { cell })
+    }
+    
+    /// Waits until every actor has handled all the messages sent to it
+    pub fn wait_idle(&self) {
+        let shared = &self.shared;
+        // In deterministic mode this runs the remaining messages itself
+        drop(shared.dispatcher.wait_until(&shared.active, &shared.idle, None, |active| *active == 0));
+    }
+}
+
+impl RuntimeShared {
+    fn activate(&self) {
+        *self.active.lock().unwrap() += 1;
+    }
+    
+    fn deactivate(&self) {
+        let mut active = self.active.lock().unwrap();
+        *active -= 1;
+        if *active == 0 {
+            self.idle.notify_all();
+        }
+    }
+    
+    /// Records that actor `from` cannot go on until actor `to` does, unless
+    /// `to` already cannot go on until `from` does; returns whether it was recorded
+    fn block(&self, from: usize, to: usize) -> bool {
+        let mut blocked_on = self.blocked_on.lock().unwrap();
+        let mut reached = vec![to];
+        let mut pending = vec![to];
+        while let Some(id) = pending.pop() {
+            if id == from {
+                return false;
+            }
+            for &(blocked, on) in blocked_on.iter() {
+                if blocked == id && !reached.contains(&on) {
+                    reached.push(on);
+                    pending.push(on);
+                }
+            }
+        }
+        blocked_on.push((from, to));
+        true
+    }
+    
+    fn unblock(&self, from: usize, to: usize) {
+        let mut blocked_on = self.blocked_on.lock().unwrap();
+        if let Some(index) = blocked_on.iter().position(|&pair| pair == (from, to)) {
+            blocked_on.swap_remove(index);
+        }
+    }
+    
+    /// Waits like `Dispatcher::wait_until` for something only actor `target`
+    /// can bring about. When the waiting thread runs an actor that `target`
+    /// itself waits for, directly or through other actors, neither could ever
+    /// go on, so this gives up with a deadlock right away.
+    fn wait_for_actor<'a, T>(
+        &self,
+        target: usize,
+        mutex: &'a Mutex<T>,
+        condvar: &Condvar,
+        deadline: Option<Instant>,
+        mut done: impl FnMut(&T) -> bool,
+    ) -> Result<MutexGuard<'a, T>, Stalled> {
+        let waiter = CURRENT_ACTOR.with(Cell::get);
+        if let Some(waiter) = waiter {
+            if !self.block(waiter, target) {
+                let guard = mutex.lock().unwrap();
+                return if done(&guard) { Ok(guard) } else { Err(Stalled::Deadlock) };
+            }
+        }
+        let result = self.dispatcher.wait_until(mutex, condvar, deadline, done);
+        if let Some(waiter) = waiter {
+            self.unblock(waiter, target);
+        }
+        result
+    }
+}
+
+impl Dispatcher {
+    fn run(&self, cell: Arc<ActorCell>) {
+        match self {
+            Dispatcher::Pool(pool) => pool.spawn_detached(move || cell.run(BATCH_SIZE)),
+            Dispatcher::Inline(ready) => ready.lock().unwrap().push_back(cell),
+        }
+    }
+    
+    /// Runs something else on the current thread while it waits: the next
+    /// inline actor message, or on a pool worker a queued job. Returns whether
+    /// anything ran.
+    fn help(&self) -> bool {
+        match self {
+            Dispatcher::Pool(pool) => pool.help(),
+            Dispatcher::Inline(ready) => {
+                let cell = ready.lock().unwrap().pop_front();
+                match cell {
+                    Some(cell) => {
+                        cell.run(1);
+                        true
+                    },
+                    None => false,
+                }
+            },
+        }
+    }
+    
+    /// Waits on `condvar` until `done` holds for the value behind `mutex`,
+    /// letting actors make progress meanwhile
+    fn wait_until<'a, T>(
+        &self,
+        mutex: &'a Mutex<T>,
+        condvar: &Condvar,
+        deadline: Option<Instant>,
+        mut done: impl FnMut(&T) -> bool,
+    ) -> Result<MutexGuard<'a, T>, Stalled> {
         loop {
-            if let Some(actor) = actors.lock().unwrap().get(&name) {
-                let mut actor_guard = actor.lock().unwrap();
-                actor_guard.process_messages();
+            let guard = mutex.lock().unwrap();
+            if done(&guard) {
+                return Ok(guard);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Stalled::TimedOut);
             }
-            thread::sleep(Duration::from_millis(10));
+            drop(guard);
+            if self.help() {
+                continue;
+            }
+            
+            let on_worker = match self {
+                // Inline, only this thread could have made progress
+                Dispatcher::Inline(_) => return Err(Stalled::Deadlock),
+                Dispatcher::Pool(pool) => pool.on_worker(),
+            };
+            let guard = mutex.lock().unwrap();
+            if done(&guard) {
+                return Ok(guard);
+            }
+            // A worker checks back now and then for jobs it can help with
+            let mut limit = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if on_worker {
+                limit = Some(limit.map_or(Duration::from_millis(1), |limit| limit.min(Duration::from_millis(1))));
+            }
+            match limit {
+                Some(limit) => drop(condvar.wait_timeout(guard, limit).unwrap()),
+                None => drop(condvar.wait(guard).unwrap()),
+            }
+        }
+    }
+}
+
+/// An actor's mailbox and instance. They are locked separately, so messages
+/// can be queued while the actor is handling one.
+struct ActorCell {
+    id: usize,
+    name: String,
+    runtime: Weak<RuntimeShared>,
+    /// Messages waiting to be handled, oldest first
+    mailbox: Mutex<VecDeque<Message>>,
+    /// Signalled when a message leaves a full mailbox
+    space: Condvar,
+    capacity: usize,
+    /// Whether the actor is queued or running; never twice at once
+    scheduled: AtomicBool,
+    instance: Mutex<ActorInstance>,
+    supervisor: Mutex<Option<Weak<SupervisorCell>>>,
+    /// Set when the supervisor restarts the actor, which re-initialises its
+    /// state before handling anything else
+    restart: AtomicBool,
+    /// Set when its supervisor gave up on it; messages are then refused
+    stopped: AtomicBool,
+    /// Set by `stop`: new messages are refused, queued ones still handled
+    closing: AtomicBool,
+    /// Set by `terminate`: queued and new messages are refused
+    terminated: AtomicBool,
+}
+
+impl ActorCell {
+    /// Queues the actor to run unless it already is
+    fn schedule(self: &Arc<Self>) {
+        if self.scheduled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(runtime) = self.runtime.upgrade() {
+            runtime.activate();
+            runtime.dispatcher.run(Arc::clone(self));
         }
     }
     
-    /// Get actor reference by name
-    pub fn get_actor(&self, name: &str) -> Option<ActorRef> {
-        let actors = self.actors.lock().unwrap();
-        if actors.contains_key(name) {
-            Some(ActorRef {
-                name: name.to_string(),
-                runtime: self.actors.clone(),
-            })
+    /// Handles up to `limit` messages, then queues the actor again if there
+    /// is more to do
+    fn run(self: &Arc<Self>, limit: usize) {
+        let runtime = match self.runtime.upgrade() {
+            Some(runtime) => runtime,
+            None => return,
+        };
+        // Run while another actor on this thread waits, which then also waits for this one
+        let previous = CURRENT_ACTOR.with(|current| current.replace(Some(self.id)));
+        if let Some(waiting) = previous {
+            runtime.blocked_on.lock().unwrap().push((waiting, self.id));
+        }
+        for _ in 0..limit {
+            if !self.step() {
+                break;
+            }
+        }
+        if let Some(waiting) = previous {
+            runtime.unblock(waiting, self.id);
+        }
+        CURRENT_ACTOR.with(|current| current.set(previous));
+        
+        self.scheduled.store(false, Ordering::SeqCst);
+        // Still counted as active while it goes back in the queue
+        if self.has_work() && !self.scheduled.swap(true, Ordering::SeqCst) {
+            runtime.dispatcher.run(Arc::clone(self));
         } else {
-            None
+            runtime.deactivate();
+        }
+    }
+    
+    fn has_work(&self) -> bool {
+        self.restart.load(Ordering::SeqCst) || !self.mailbox.lock().unwrap().is_empty()
+    }
+    
+    /// Handles the next message, or a pending restart; false if there was
+    /// nothing to do
+    fn step(&self) -> bool {
+        if self.restart.load(Ordering::SeqCst) {
+            self.reinitialize();
+            return true;
+        }
+        let message = self.mailbox.lock().unwrap().pop_front();
+        let message = match message {
+            Some(message) => message,
+            None => return false,
+        };
+        self.space.notify_all();
+        
+        let result = if self.stopped.load(Ordering::SeqCst) || self.terminated.load(Ordering::SeqCst) {
+            Err(format!("Actor {} is stopped", self.name))
+        } else {
+            let mut instance = self.instance.lock().unwrap();
+            // A panicking method fails like one returning an error
+            let result = panic::catch_unwind(AssertUnwindSafe(|| instance.handle_message(&message)))
+                .unwrap_or_else(|_| Err("Actor method panicked".to_string()));
+            drop(instance);
+            if let Err(e) = &result {
+                self.handle_failure(&message.method, e);
+            }
+            result
+        };
+        if let Some(reply) = message.reply {
+            let result = result.and_then(|value| Sendable::new(value)
+                .map_err(|e| format!("Actor {} cannot reply to '{}': {}", self.name, message.method, e)));
+            reply.fill(result);
+        }
+        true
+    }
+    
+    /// Whether `send` and `ask` are refused
+    fn refuses_messages(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+            || self.terminated.load(Ordering::SeqCst)
+            // A pending restart clears `stopped`
+            || (self.stopped.load(Ordering::SeqCst) && !self.restart.load(Ordering::SeqCst))
+    }
+    
+    /// Reports a failed message to the supervisor, if any; an unsupervised
+    /// actor keeps its state and goes on with its next message
+    fn handle_failure(&self, method: &str, error: &str) {
+        eprintln!("❌ Actor {} failed in '{}': {}", self.name, method, error);
+        let supervisor = self.supervisor.lock().unwrap().as_ref().and_then(Weak::upgrade);
+        if let Some(supervisor) = supervisor {
+            supervisor.child_failed(self.id);
+        }
+    }
+    
+    /// Replaces the state with a fresh one from the actor's initializers. An
+    /// actor stopped with `stop` or `terminate` stays stopped.
+    fn reinitialize(&self) {
+        self.restart.store(false, Ordering::SeqCst);
+        if self.closing.load(Ordering::SeqCst) || self.terminated.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut instance = self.instance.lock().unwrap();
+        let state = panic::catch_unwind(AssertUnwindSafe(|| instance.behavior.init()))
+            .unwrap_or_else(|_| Err("Actor initializer panicked".to_string()));
+        match state {
+            Ok(state) => {
+                instance.state = state;
+                self.stopped.store(false, Ordering::SeqCst);
+            },
+            Err(e) => {
+                eprintln!("❌ Actor {} failed to restart: {}", self.name, e);
+                self.stopped.store(true, Ordering::SeqCst);
+            },
         }
     }
 }
 
 /// Reference to an actor for sending messages
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ActorRef {
-    name: String,
-    runtime: Arc<Mutex<HashMap<String, Arc<Mutex<ActorInstance>>>>>,
+    id: usize,
+    cell: Arc<ActorCell>,
 }
 
 impl ActorRef {
-    /// Send a message to the actor
+    /// Send a message to the actor; it is handled after the ones sent before it.
+    /// While the actor's mailbox is full, this waits for room.
     pub fn send(&self, method: String, args: Vec<Value>) -> Result<(), String> {
-        if let Some(actor) = self.runtime.lock().unwrap().get(&self.name) {
-            let mut actor_guard = actor.lock().unwrap();
-            actor_guard.enqueue_message(Message {
-                method,
-                args,
-            });
-            Ok(())
-        } else {
-            Err(format!("Actor {} not found", self.name))
+        let args = self.sendable(&method, args)?;
+        self.enqueue(Message {
+            method,
+            args,
+            reply: None,
+        })
+    }
+    
+    /// Send a message whose result the actor delivers to the returned reply
+    pub fn ask(&self, method: String, args: Vec<Value>) -> Result<Reply, String> {
+        let args = self.sendable(&method, args)?;
+        let slot = Arc::new(ReplySlot {
+            result: Mutex::new(None),
+            ready: Condvar::new(),
+        });
+        let reply = Reply {
+            actor_id: self.id,
+            actor: self.cell.name.clone(),
+            method: method.clone(),
+            slot: Arc::clone(&slot),
+            runtime: self.cell.runtime.clone(),
+            timeout: None,
+        };
+        self.enqueue(Message {
+            method,
+            args,
+            reply: Some(slot),
+        })?;
+        Ok(reply)
+    }
+    
+    /// Stops the actor once it has handled the messages already sent to it;
+    /// sending to it fails from now on
+    pub fn stop(&self) {
+        self.cell.closing.store(true, Ordering::SeqCst);
+    }
+    
+    /// Stops the actor after the message it is handling, if any. Messages
+    /// still queued fail, as does sending to it from now on.
+    pub fn terminate(&self) {
+        self.cell.terminated.store(true, Ordering::SeqCst);
+        // Fails the queued messages' replies
+        self.cell.schedule();
+    }
+    
+    /// Arguments must not share state with the sender once the actor has them
+    fn sendable(&self, method: &str, args: Vec<Value>) -> Result<Vec<Sendable>, String> {
+        args.into_iter()
+            .map(|arg| Sendable::new(arg)
+                .map_err(|e| format!("Cannot send '{}' to actor {}: {}", method, self.cell.name, e)))
+            .collect()
+    }
+    
+    fn enqueue(&self, message: Message) -> Result<(), String> {
+        let cell = &self.cell;
+        let runtime = cell.runtime.upgrade()
+            .ok_or_else(|| format!("Actor {} is stopped", cell.name))?;
+        let mut mailbox = cell.mailbox.lock().unwrap();
+        if mailbox.len() >= cell.capacity {
+            // Waiting for itself to make room would never end
+            if CURRENT_ACTOR.with(|current| current.get()) == Some(cell.id) {
+                return Err(format!("Mailbox of actor {} is full", cell.name));
+            }
+            drop(mailbox);
+            mailbox = runtime
+                .wait_for_actor(cell.id, &cell.mailbox, &cell.space, None, |mailbox| {
+                    mailbox.len() < cell.capacity || cell.refuses_messages()
+                })
+                .map_err(|_| format!("Mailbox of actor {} is full and nothing can empty it", cell.name))?;
         }
+        if cell.refuses_messages() {
+            return Err(format!("Actor {} is stopped", cell.name));
+        }
+        mailbox.push_back(message);
+        drop(mailbox);
+        cell.schedule();
+        Ok(())
     }
     
     /// Get actor name
     pub fn name(&self) -> &str {
-        &self.name
+        &self.cell.name
+    }
+    
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl fmt::Debug for ActorRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<actor {} {}>", self.cell.name, self.id)
+    }
+}
+
+/// The result of a message sent with `ask`, filled in once the actor handles it
+struct ReplySlot {
+    result: Mutex<Option<Result<Sendable, String>>>,
+    ready: Condvar,
+}
+
+impl ReplySlot {
+    fn fill(&self, result: Result<Sendable, String>) {
+        *self.result.lock().unwrap() = Some(result);
+        self.ready.notify_all();
+    }
+}
+
+/// Pending result of a method call on an actor
+#[derive(Clone)]
+pub struct Reply {
+    actor_id: usize,
+    actor: String,
+    method: String,
+    slot: Arc<ReplySlot>,
+    runtime: Weak<RuntimeShared>,
+    /// How long `wait` waits for the actor; forever if None
+    timeout: Option<Duration>,
+}
+
+impl Reply {
+    /// The same reply, waited for at most `timeout`
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+    
+    /// Blocks until the actor has handled the message and returns the method's
+    /// result. Waiting again gives the same result.
+    pub fn wait(&self) -> Result<Value, String> {
+        if let Some(result) = self.slot.result.lock().unwrap().as_ref() {
+            return result.clone().map(Sendable::into_value);
+        }
+        if CURRENT_ACTOR.with(|current| current.get()) == Some(self.actor_id) {
+            return Err(format!("Actor {} cannot wait for its own reply to '{}'", self.actor, self.method));
+        }
+        let runtime = self.runtime.upgrade()
+            .ok_or_else(|| format!("Actor {} is stopped", self.actor))?;
+        
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        match runtime.wait_for_actor(self.actor_id, &self.slot.result, &self.slot.ready, deadline, Option::is_some) {
+            Ok(result) => result.clone().expect("waited until filled").map(Sendable::into_value),
+            Err(Stalled::TimedOut) => Err(format!("Actor {} did not reply to '{}' within {}ms",
+                self.actor, self.method, self.timeout.unwrap_or_default().as_millis())),
+            Err(Stalled::Deadlock) => Err(format!("Deadlock: actor {} can never reply to '{}'", self.actor, self.method)),
+        }
+    }
+    
+    pub fn actor(&self) -> &str {
+        &self.actor
+    }
+    
+    pub fn method(&self) -> &str {
+        &self.method
     }
 }
 
-/// Actor instance with state and message queue
-#[derive(Debug)]
+impl fmt::Debug for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<reply {}.{}>", self.actor, self.method)
+    }
+}
+
+/// Actor instance with its state and behavior
 pub struct ActorInstance {
     pub state: HashMap<String, Value>,
-    message_queue: Vec<Message>,
-    methods: Vec<Stmt>,
-    pub supervisor: Option<String>, // Supervisor actor name
+    behavior: Box<dyn ActorBehavior>,
 }
 
 impl ActorInstance {
-    fn new(actor_def: &Stmt) -> Result<Self, String> {
-        if let Stmt::Actor { fields, methods, .. } = actor_def {
-            let mut state = HashMap::new();
-            
-            // Initialize state from fields
-            for (name, _type_annot, value) in fields {
-                // Evaluate initial value (simplified - would need VM context)
-                state.insert(name.clone(), Value::None);
-            }
-            
-            Ok(Self {
-                state,
-                message_queue: Vec::new(),
-                methods: methods.clone(),
-                supervisor: None,
-            })
-        } else {
-            Err("Not an actor definition".to_string())
+    fn handle_message(&mut self, message: &Message) -> Result<Value, String> {
+        let args = message.args.iter().cloned().map(Sendable::into_value).collect();
+        self.behavior.handle(&mut self.state, &message.method, args)
+    }
+}
+
+/// Which children a supervisor restarts when one of them fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestartStrategy {
+    /// Only the failed child
+    OneForOne,
+    /// Every child
+    OneForAll,
+    /// The failed child and the children supervised after it
+    RestForOne,
+}
+
+impl RestartStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "one_for_one" => Some(RestartStrategy::OneForOne),
+            "one_for_all" => Some(RestartStrategy::OneForAll),
+            "rest_for_one" => Some(RestartStrategy::RestForOne),
+            _ => None,
         }
     }
     
-    fn enqueue_message(&mut self, message: Message) {
-        self.message_queue.push(message);
+    pub fn name(&self) -> &'static str {
+        match self {
+            RestartStrategy::OneForOne => "one_for_one",
+            RestartStrategy::OneForAll => "one_for_all",
+            RestartStrategy::RestForOne => "rest_for_one",
+        }
+    }
+}
+
+/// Something a supervisor looks after: an actor, or another supervisor with
+/// its own children
+#[derive(Clone)]
+pub enum Child {
+    Actor(ActorRef),
+    Supervisor(SupervisorRef),
+}
+
+impl Child {
+    fn has_supervisor(&self) -> bool {
+        match self {
+            Child::Actor(actor) => actor.cell.supervisor.lock().unwrap().is_some(),
+            Child::Supervisor(supervisor) => supervisor.cell.parent.lock().unwrap().is_some(),
+        }
     }
     
-    fn process_messages(&mut self) {
-        while let Some(message) = self.message_queue.pop() {
-            self.handle_message(message);
+    fn set_supervisor(&self, supervisor: Weak<SupervisorCell>) {
+        match self {
+            Child::Actor(actor) => *actor.cell.supervisor.lock().unwrap() = Some(supervisor),
+            Child::Supervisor(child) => *child.cell.parent.lock().unwrap() = Some(supervisor),
         }
     }
     
-    fn handle_message(&mut self, message: Message) {
-        // Find method and execute (simplified - would need full VM integration)
-        for method in &self.methods {
-            if let Stmt::Fn { name, .. } = method {
-                if name == &message.method {
-                    // Execute method with message args
-                    // This would integrate with VM for full execution
-                    // For now, just update state if needed
-                }
-            }
+    fn same_as(&self, other: &Child) -> bool {
+        match (self, other) {
+            (Child::Actor(a), Child::Actor(b)) => Arc::ptr_eq(&a.cell, &b.cell),
+            (Child::Supervisor(a), Child::Supervisor(b)) => Arc::ptr_eq(&a.cell, &b.cell),
+            _ => false,
         }
     }
     
-    /// Set supervisor for fault tolerance
-    pub fn set_supervisor(&mut self, supervisor: String) {
-        self.supervisor = Some(supervisor);
+    fn id(&self) -> usize {
+        match self {
+            Child::Actor(actor) => actor.id,
+            Child::Supervisor(supervisor) => supervisor.cell.id,
+        }
+    }
+    
+    /// Asks the child to start over from a fresh state
+    fn restart(&self) {
+        match self {
+            Child::Actor(actor) => {
+                actor.cell.restart.store(true, Ordering::SeqCst);
+                actor.cell.schedule();
+            },
+            Child::Supervisor(supervisor) => supervisor.cell.restart_all(),
+        }
     }
     
-    /// Handle actor failure (would notify supervisor)
-    pub fn handle_failure(&mut self, error: String) {
-        if let Some(ref supervisor) = self.supervisor {
-            // Notify supervisor (simplified)
+    /// Stops the child until its own supervisor restarts it, refusing its
+    /// pending and future messages
+    fn stop(&self) {
+        match self {
+            Child::Actor(actor) => {
+                actor.cell.restart.store(false, Ordering::SeqCst);
+                actor.cell.stopped.store(true, Ordering::SeqCst);
+                actor.cell.schedule();
+            },
+            Child::Supervisor(supervisor) => supervisor.cell.stop_all(),
         }
     }
 }
 
-/// Message sent to an actor
-#[derive(Debug)]
-struct Message {
-    method: String,
-    args: Vec<Value>,
+impl fmt::Display for Child {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Child::Actor(actor) => write!(f, "Actor {}", actor.name()),
+            Child::Supervisor(supervisor) => write!(f, "Supervisor {}", supervisor.cell.id),
+        }
+    }
 }
 
-/// Runtime value types
-#[derive(Debug, Clone)]
-pub enum Value {
-    Number(f64),
-    String(String),
-    Boolean(bool),
-    List(Vec<Value>),
-    Map(HashMap<String, Value>),
-    Actor(ActorRef),
-    None,
+/// Reference to a supervisor
+#[derive(Clone)]
+pub struct SupervisorRef {
+    cell: Arc<SupervisorCell>,
 }
 
+impl SupervisorRef {
+    pub fn id(&self) -> usize {
+        self.cell.id
+    }
+    
+    pub fn strategy(&self) -> RestartStrategy {
+        self.cell.strategy
+    }
+}
+
+impl fmt::Debug for SupervisorRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<supervisor {} {}>", self.cell.strategy.name(), self.cell.id)
+    }
+}
 
+struct SupervisorCell {
+    id: usize,
+    strategy: RestartStrategy,
+    max_restarts: usize,
+    within: Duration,
+    /// In start order, which `RestForOne` follows
+    children: Vec<Child>,
+    /// When the recent restarts happened, oldest first; also serialises failures
+    restarts: Mutex<VecDeque<Instant>>,
+    parent: Mutex<Option<Weak<SupervisorCell>>>,
+}
+
+impl SupervisorCell {
+    /// Restarts children by the strategy after the child `id` failed. Past
+    /// `max_restarts` within `within`, the supervisor gives up: it stops all
+    /// of its children and fails in turn, so its own supervisor can act.
+    fn child_failed(&self, id: usize) {
+        let mut restarts = self.restarts.lock().unwrap();
+        let index = match self.children.iter().position(|child| child.id() == id) {
+            Some(index) => index,
+            None => return,
+        };
+        
+        let now = Instant::now();
+        while restarts.front().is_some_and(|restart| now.duration_since(*restart) > self.within) {
+            restarts.pop_front();
+        }
+        if restarts.len() >= self.max_restarts {
+            restarts.clear();
+            drop(restarts);
+            eprintln!("❌ Supervisor {} gave up after {} restarts within {}ms",
+                self.id, self.max_restarts, self.within.as_millis());
+            self.stop_all();
+            let parent = self.parent.lock().unwrap().as_ref().and_then(Weak::upgrade);
+            if let Some(parent) = parent {
+                parent.child_failed(self.id);
+            }
+            return;
+        }
+        restarts.push_back(now);
+        
+        let restarted = match self.strategy {
+            RestartStrategy::OneForOne => &self.children[index..=index],
+            RestartStrategy::OneForAll => &self.children[..],
+            RestartStrategy::RestForOne => &self.children[index..],
+        };
+        for child in restarted {
+            child.restart();
+        }
+    }
+    
+    /// Restarts every child, with a clean restart history
+    fn restart_all(&self) {
+        self.restarts.lock().unwrap().clear();
+        for child in &self.children {
+            child.restart();
+        }
+    }
+    
+    fn stop_all(&self) {
+        for child in &self.children {
+            child.stop();
+        }
+    }
+}
+
+/// Message sent to an actor
+struct Message {
+    method: String,
+    args: Vec<Sendable>,
+    /// Where to deliver the result, for messages sent with `ask`
+    reply: Option<Arc<ReplySlot>>,
+}
@@ -1,5 +1,45 @@
+use std::ops::RangeInclusive;
+
+/// Where a node starts in the source: the 1-based line and column of the token
+/// it is reported at. Nodes synthesized by the parser have line 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+
+    /// Whether the span points into the source
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+}
+
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+/// Expressions built outside the parser have no span
+impl From<ExprKind> for Expr {
+    fn from(kind: ExprKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Number(f64),
     String(String),
     Boolean(bool),
@@ -13,10 +53,20 @@ pub enum Expr {
         op: UnaryOp,
         expr: Box<Expr>,
     },
+    /// Numeric conversion: `count as f64`
+    Cast {
+        expr: Box<Expr>,
+        target: String,
+    },
     Call {
-        callee: String,
+        callee: Box<Expr>,
         args: Vec<Expr>,
     },
+    /// Anonymous function: `(x, y) => x + y` or `(x) => { ... }`
+    Lambda {
+        params: Vec<(String, Option<String>)>,
+        body: Vec<Stmt>,
+    },
     Member {
         object: Box<Expr>,
         member: String,
@@ -27,6 +77,23 @@ pub enum Expr {
     },
     List(Vec<Expr>),
     Map(Vec<(Expr, Expr)>),
+    /// Struct construction: `Point { x: 1.0, y: 2.0 }`
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Expr)>,
+    },
+    /// Enum construction: `Shape::Circle(1.0)`, or `Shape::Empty` without payload
+    EnumVariant {
+        enum_name: String,
+        variant: String,
+        args: Vec<Expr>,
+    },
+    /// `target = value`, or `target op= value` when `op` is set
+    Assign {
+        target: Box<Expr>,
+        op: Option<BinaryOp>,
+        value: Box<Expr>,
+    },
     None,
     // Pattern matching
     Match {
@@ -39,8 +106,9 @@ pub enum Expr {
         value: Box<Expr>,
     },
     // Async/await
+    /// Block run as a task; evaluates to a handle that `await` waits on
     Async {
-        body: Box<Expr>,
+        body: Vec<Stmt>,
     },
     Await {
         expr: Box<Expr>,
@@ -54,10 +122,44 @@ pub enum Expr {
     Coroutine {
         body: Vec<Stmt>,
     },
-    Suspend,
+    /// Pauses the running coroutine, handing the value to `resume`
+    Suspend(Option<Box<Expr>>),
     Resume {
         coroutine: Box<Expr>,
     },
+    // Parallel execution
+    /// Evaluates the body for every item of a list on the worker pool,
+    /// giving the list of results in input order
+    ParallelMap {
+        var: String,
+        iterable: Box<Expr>,
+        body: Vec<Stmt>,
+    },
+    /// Folds a list with the body on the worker pool: each chunk starts from
+    /// `initial` bound to `acc`, and the chunks' results are merged with the
+    /// same body, so it should be associative with `initial` as its identity
+    ParallelReduce {
+        acc: String,
+        initial: Box<Expr>,
+        var: String,
+        iterable: Box<Expr>,
+        body: Vec<Stmt>,
+        /// Merges the results of chunks folded on different workers; without
+        /// it the items are folded one after another
+        combine: Option<ReduceCombine>,
+    },
+    // Actors
+    /// Starts an actor; given fields replace their initializers
+    Spawn {
+        actor: String,
+        fields: Vec<(String, Expr)>,
+    },
+    /// Queues a method call on an actor without waiting for its result
+    Send {
+        actor: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -84,20 +186,43 @@ pub enum UnaryOp {
 }
 
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl From<StmtKind> for Stmt {
+    fn from(kind: StmtKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Let {
         name: String,
         type_annot: Option<String>,
+        /// Where the annotation is written, so diagnostics can point at it
+        annot_span: Span,
         value: Expr,
     },
     Const {
         name: String,
         type_annot: Option<String>,
+        annot_span: Span,
         value: Expr,
     },
     Expr(Expr),
     Print(Expr),
     Return(Option<Expr>),
+    Break,
+    Continue,
     If {
         condition: Expr,
         then_branch: Vec<Stmt>,
@@ -117,7 +242,12 @@ pub enum Stmt {
         name: String,
         params: Vec<(String, Option<String>)>,
         return_type: Option<String>,
+        /// Where the return annotation is written, so diagnostics can point at it
+        return_span: Span,
         body: Vec<Stmt>,
+        /// Set when the body uses `yield`; the body is then a single `return` of a
+        /// generator over the function's statements, so each call returns an iterator
+        generator: bool,
     },
     Import {
         module: String,
@@ -126,6 +256,11 @@ pub enum Stmt {
     Module {
         name: String,
     },
+    /// Top-level declaration marked `export`, visible to modules that import this file
+    Export(Box<Stmt>),
+    /// `export default Name`: exports a declaration made elsewhere in the file,
+    /// such as a component
+    ExportDefault(String),
     Actor {
         name: String,
         fields: Vec<(String, Option<String>, Expr)>,
@@ -136,6 +271,16 @@ pub enum Stmt {
         fields: Vec<(String, Option<String>, Expr)>,
         methods: Vec<Stmt>,
     },
+    /// `struct Point { x: f64, y: f64 }`: field names with their type annotations
+    Struct {
+        name: String,
+        fields: Vec<(String, String)>,
+    },
+    /// `enum Shape { Circle(f64), Empty }`: variants with their payload types
+    Enum {
+        name: String,
+        variants: Vec<(String, Vec<String>)>,
+    },
     Component {
         name: String,
         body: Vec<Stmt>,
@@ -162,24 +307,22 @@ pub enum Stmt {
         name: String,
         params: Vec<(String, Option<String>)>,
         return_type: Option<String>,
+        return_span: Span,
         body: Vec<Stmt>,
     },
     // Parallel execution
+    /// Runs the body for every item of a list on the worker pool
     ParallelFor {
         var: String,
         iterable: Expr,
         body: Vec<Stmt>,
     },
-    ParallelMap {
-        var: String,
-        iterable: Expr,
-        body: Vec<Stmt>,
-    },
     // Coroutine
     CoroutineFn {
         name: String,
         params: Vec<(String, Option<String>)>,
         return_type: Option<String>,
+        return_span: Span,
         body: Vec<Stmt>,
     },
 }
@@ -189,6 +332,16 @@ pub struct Program {
     pub mode: ExecutionMode,
     pub target: Option<String>,
     pub statements: Vec<Stmt>,
+    /// Warning codes silenced by `#[allow(...)]` comments
+    pub allows: Vec<Allow>,
+}
+
+/// Warning codes an `#[allow(code, ...)]` comment silences on the lines of the
+/// item after it; `#![allow(...)]` covers every line of the file
+#[derive(Debug, Clone)]
+pub struct Allow {
+    pub codes: Vec<String>,
+    pub lines: RangeInclusive<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -211,6 +364,12 @@ pub enum Pattern {
         name: String,
         fields: Vec<(String, Pattern)>,
     },
+    /// `Shape::Circle(r)`
+    Variant {
+        enum_name: String,
+        variant: String,
+        fields: Vec<Pattern>,
+    },
     Wildcard,
     Binding {
         name: String,
@@ -223,6 +382,14 @@ pub enum Pattern {
     },
 }
 
+/// `combine (left, right) { ... }` of a `parallel reduce`
+#[derive(Debug, Clone)]
+pub struct ReduceCombine {
+    pub left: String,
+    pub right: String,
+    pub body: Vec<Stmt>,
+}
+
 /// Match arm for pattern matching
 #[derive(Debug, Clone)]
 pub struct MatchArm {
@@ -1,94 +1,269 @@
-use crate::vm::Value;
-use std::collections::VecDeque;
+use crate::coroutine::{Coroutine, Resumed};
+use crate::parallel::ParallelRuntime;
+use crate::value::Value;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-/// Async runtime for managing async operations
+/// Async runtime for managing async operations.
+///
+/// Every task is a coroutine that runs until it awaits something unfinished.
+/// Each round, the executor takes the oldest ready tasks, one per worker of the
+/// work-stealing pool shared with parallel loops and actors, runs each as a job
+/// on the pool, then records how the tasks stopped in the order they became
+/// ready. Timers fire in deadline order on a logical clock (ties in creation
+/// order), so which tasks wake together, and the order results are recorded
+/// in, is the same on every run. Wall-clock time only paces the clock: when
+/// every task is waiting, the executor sleeps until the next timer is due.
+///
+/// A deterministic runtime instead runs one task at a time on the thread that
+/// drives it, so tasks also interleave their output the same way on every run.
+#[derive(Clone)]
 pub struct AsyncRuntime {
-    task_queue: Arc<Mutex<VecDeque<AsyncTask>>>,
-    completed_tasks: Arc<Mutex<Vec<(usize, Value)>>>,
-    next_task_id: Arc<Mutex<usize>>,
+    executor: Arc<Mutex<Executor>>,
+    /// Runs ready tasks; None runs them one at a time on the driving thread
+    pool: Option<ParallelRuntime>,
+}
+
+struct Executor {
+    tasks: HashMap<usize, Task>,
+    ready: VecDeque<usize>,
+    /// Pending timers as (deadline, task), earliest first
+    timers: Vec<(u64, usize)>,
+    /// Logical time in milliseconds since the runtime started
+    now: u64,
+    started: Instant,
+    next_id: usize,
+    /// Number of tasks finished so far, used to order completions
+    completed: u64,
+}
+
+struct Task {
+    /// None for timers, and while the executor is running the task
+    coroutine: Option<Coroutine>,
+    state: TaskState,
+    /// Tasks to wake when this one finishes
+    waiters: Vec<usize>,
+}
+
+/// How running a task ended: its coroutine with how the body stopped, or an
+/// error if the job running it panicked
+type Step = Result<(Coroutine, Result<Resumed, String>), String>;
+
+enum TaskState {
+    Ready,
+    Running,
+    /// Suspended until a task it awaits finishes
+    Waiting,
+    /// Finished as the n-th task, with its result
+    Done(u64, Result<Value, String>),
 }
 
 impl AsyncRuntime {
-    pub fn new() -> Self {
-        let runtime = Self {
-            task_queue: Arc::new(Mutex::new(VecDeque::new())),
-            completed_tasks: Arc::new(Mutex::new(Vec::new())),
-            next_task_id: Arc::new(Mutex::new(0)),
-        };
-        
-        // Start event loop
-        runtime.start_event_loop();
-        runtime
+    /// Tasks run on `pool`'s workers
+    pub fn new(pool: ParallelRuntime) -> Self {
+        Self::with_pool(Some(pool))
     }
-    
-    fn start_event_loop(&self) {
-        let queue = self.task_queue.clone();
-        let completed = self.completed_tasks.clone();
-        
-        thread::spawn(move || {
-            loop {
-                // Process tasks
-                if let Some(mut task) = queue.lock().unwrap().pop_front() {
-                    // Simulate async execution
-                    thread::sleep(Duration::from_millis(10));
-                    
-                    // Execute task (simplified - would need full VM integration)
-                    let result = Value::None; // Placeholder
-                    
-                    completed.lock().unwrap().push((task.id, result));
-                } else {
-                    thread::sleep(Duration::from_millis(1));
-                }
-            }
+
+    /// Tasks run one at a time on the thread that drives the executor, in the
+    /// order they became ready
+    pub fn deterministic() -> Self {
+        Self::with_pool(None)
+    }
+
+    fn with_pool(pool: Option<ParallelRuntime>) -> Self {
+        Self {
+            executor: Arc::new(Mutex::new(Executor {
+                tasks: HashMap::new(),
+                ready: VecDeque::new(),
+                timers: Vec::new(),
+                now: 0,
+                started: Instant::now(),
+                next_id: 0,
+                completed: 0,
+            })),
+            pool,
+        }
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.pool.is_none()
+    }
+
+    /// Spawn an async task from the coroutine `start` builds for the new task's id.
+    /// The task is queued and first runs when something drives the executor.
+    pub fn spawn<F>(&self, start: F) -> Result<usize, String>
+    where
+        F: FnOnce(usize) -> Result<Coroutine, String>,
+    {
+        let id = self.executor.lock().unwrap().next_id();
+        let coroutine = start(id)?;
+
+        let mut executor = self.executor.lock().unwrap();
+        executor.tasks.insert(id, Task {
+            coroutine: Some(coroutine),
+            state: TaskState::Ready,
+            waiters: Vec::new(),
         });
+        executor.ready.push_back(id);
+        Ok(id)
     }
-    
-    /// Spawn an async task
-    pub fn spawn(&self, task: AsyncTask) -> usize {
-        let id = {
-            let mut next_id = self.next_task_id.lock().unwrap();
-            *next_id += 1;
-            *next_id
-        };
-        
-        let mut task_with_id = task;
-        task_with_id.id = id;
-        
-        self.task_queue.lock().unwrap().push_back(task_with_id);
+
+    /// A task that finishes with None once `millis` have passed
+    pub fn sleep(&self, millis: u64) -> usize {
+        let mut executor = self.executor.lock().unwrap();
+        let id = executor.next_id();
+        executor.tasks.insert(id, Task {
+            coroutine: None,
+            state: TaskState::Waiting,
+            waiters: Vec::new(),
+        });
+
+        let deadline = executor.now + millis;
+        let position = executor.timers.partition_point(|(due, _)| *due <= deadline);
+        executor.timers.insert(position, (deadline, id));
         id
     }
-    
-    /// Wait for a task to complete
-    pub fn await_task(&self, task_id: usize) -> Option<Value> {
-        loop {
-            let mut completed = self.completed_tasks.lock().unwrap();
-            if let Some(pos) = completed.iter().position(|(id, _)| *id == task_id) {
-                let (_, result) = completed.remove(pos);
-                return Some(result);
+
+    /// The task among `tasks` that finished first, as its position and result,
+    /// or None while all of them are still running
+    pub fn first_finished(&self, tasks: &[usize]) -> Result<Option<(usize, Value)>, String> {
+        let executor = self.executor.lock().unwrap();
+        let mut first: Option<(u64, usize, &Result<Value, String>)> = None;
+        for (position, id) in tasks.iter().enumerate() {
+            let task = executor.tasks.get(id).ok_or_else(|| format!("Task {} not found", id))?;
+            if let TaskState::Done(order, result) = &task.state {
+                if first.is_none_or(|(earliest, _, _)| *order < earliest) {
+                    first = Some((*order, position, result));
+                }
             }
-            drop(completed);
-            thread::sleep(Duration::from_millis(1));
+        }
+
+        match first {
+            Some((_, position, Ok(value))) => Ok(Some((position, value.clone()))),
+            Some((_, _, Err(e))) => Err(e.clone()),
+            None => Ok(None),
         }
     }
-}
 
-/// Async task
-pub struct AsyncTask {
-    pub id: usize,
-    pub function: String,
-    pub args: Vec<Value>,
-}
+    /// Called from inside the running task `current` just before it suspends:
+    /// it becomes ready again as soon as one of `tasks` finishes
+    pub fn wait_for(&self, current: usize, tasks: &[usize]) {
+        let mut executor = self.executor.lock().unwrap();
+        for id in tasks {
+            if let Some(task) = executor.tasks.get_mut(id) {
+                task.waiters.push(current);
+            }
+        }
+        if let Some(task) = executor.tasks.get_mut(&current) {
+            task.state = TaskState::Waiting;
+        }
+    }
 
-impl AsyncTask {
-    pub fn new(function: String, args: Vec<Value>) -> Self {
-        Self {
-            id: 0,
-            function,
-            args,
+    /// Runs the oldest ready tasks, one per worker (just one when deterministic),
+    /// until each suspends or finishes, or else fires the next due timers.
+    /// Returns false when there is nothing left to do.
+    pub fn run_once(&self) -> bool {
+        let batch: Vec<(usize, Coroutine)> = {
+            let mut executor = self.executor.lock().unwrap();
+            let workers = self.pool.as_ref().map_or(1, ParallelRuntime::threads);
+            let count = executor.ready.len().min(workers);
+            let ids: Vec<usize> = executor.ready.drain(..count).collect();
+            ids.into_iter().map(|id| {
+                let task = executor.tasks.get_mut(&id).expect("ready tasks are registered");
+                task.state = TaskState::Running;
+                (id, task.coroutine.take().expect("ready tasks have a body"))
+            }).collect()
+        };
+
+        if !batch.is_empty() {
+            // The executor stays unlocked while tasks run, so they can spawn and await
+            let steps: Vec<(usize, Step)> = match &self.pool {
+                Some(pool) => {
+                    let handles: Vec<_> = batch.into_iter().map(|(id, mut coroutine)| {
+                        let helper = pool.clone();
+                        // A worker waiting for its task runs other jobs, such as the task's parallel loops
+                        (id, pool.spawn(move || {
+                            let outcome = coroutine.resume_with(|| helper.help());
+                            (coroutine, outcome)
+                        }))
+                    }).collect();
+                    handles.into_iter().map(|(id, handle)| (id, handle.join())).collect()
+                },
+                None => batch.into_iter().map(|(id, mut coroutine)| {
+                    let outcome = coroutine.resume();
+                    (id, Ok((coroutine, outcome)))
+                }).collect(),
+            };
+
+            let mut executor = self.executor.lock().unwrap();
+            for (id, step) in steps {
+                match step {
+                    Ok((coroutine, Ok(Resumed::Suspended(_)))) => {
+                        let task = executor.tasks.get_mut(&id).expect("running tasks are registered");
+                        task.coroutine = Some(coroutine);
+                        if matches!(task.state, TaskState::Running) {
+                            // Suspended without waiting on anything: run again after the others
+                            task.state = TaskState::Ready;
+                            executor.ready.push_back(id);
+                        }
+                    },
+                    Ok((_, Ok(Resumed::Completed(value)))) => executor.finish(id, Ok(value)),
+                    Ok((_, Err(e))) | Err(e) => executor.finish(id, Err(e)),
+                }
+            }
+            return true;
         }
+
+        let (deadline, started) = {
+            let executor = self.executor.lock().unwrap();
+            match executor.timers.first() {
+                Some((deadline, _)) => (*deadline, executor.started),
+                None => return false,
+            }
+        };
+        let due = started + Duration::from_millis(deadline);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+
+        let mut executor = self.executor.lock().unwrap();
+        executor.now = executor.now.max(deadline);
+        while executor.timers.first().is_some_and(|(due, _)| *due <= deadline) {
+            let (_, id) = executor.timers.remove(0);
+            executor.finish(id, Ok(Value::None));
+        }
+        true
     }
 }
 
+impl Executor {
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Records a task's result and wakes the tasks waiting for it
+    fn finish(&mut self, id: usize, result: Result<Value, String>) {
+        self.completed += 1;
+        let waiters = match self.tasks.get_mut(&id) {
+            Some(task) => {
+                task.state = TaskState::Done(self.completed, result);
+                task.coroutine = None;
+                std::mem::take(&mut task.waiters)
+            },
+            None => return,
+        };
+
+        for waiter in waiters {
+            if let Some(task) = self.tasks.get_mut(&waiter) {
+                // A task waiting on several others is woken only once
+                if matches!(task.state, TaskState::Waiting) {
+                    task.state = TaskState::Ready;
+                    self.ready.push_back(waiter);
+                }
+            }
+        }
+    }
+}
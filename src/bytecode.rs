@@ -0,0 +1,1247 @@
+// Bytecode compiler and stack-based virtual machine for JOEL
+//
+// `BytecodeCompiler` lowers an `ast::Program` into `FunctionProto`s whose variables
+// are resolved at compile time: locals become slots in the call frame, captured
+// variables become indices into the closure, and top-level names become global
+// slots. A local that a nested function captures lives in a `Slot` shared with
+// the closure, so assignments on either side are seen by both. `BytecodeVM` runs the result with a single value stack and a dispatch
+// loop instead of the tree-walking interpreter's HashMap-per-scope lookups.
+// Selected with `joel run --vm=bytecode`.
+
+use crate::ast::*;
+use crate::exhaustiveness_checker::ExhaustivenessChecker;
+use crate::pattern_matcher::PatternMatcher;
+use crate::stdlib;
+use crate::value::{Slot, Value};
+use crate::vm::VM;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Maximum call depth before a stack overflow is reported
+const MAX_FRAMES: usize = 10_000;
+
+/// Where a variable lives at runtime
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VarRef {
+    /// Slot in the current call frame
+    Local(usize),
+    /// Slot in the current call frame whose value a nested function captures, so
+    /// it is kept in a `Slot` of the frame instead
+    Boxed(usize),
+    /// Variable shared with the current closure when it was created
+    Capture(usize),
+    /// The closure being executed; lets local named functions call themselves
+    Current,
+    Global(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum OpCode {
+    Constant(usize),
+    PushNone,
+    Pop,
+    PopN(usize),
+    /// Removes `n` values beneath the top of the stack, keeping the top
+    PopBelow(usize),
+    Get(VarRef),
+    /// Stores the top of the stack into a variable, leaving it on the stack
+    Set(VarRef),
+    /// Pops a value into a global slot
+    DefineGlobal(usize),
+    /// Moves the value of a local that nested functions capture into a new `Slot`
+    Box(usize),
+    /// Stores the top of the stack through a member/index path of a variable.
+    /// The `depth` path keys sit beneath the value, outermost first.
+    SetPath { target: VarRef, depth: usize },
+    /// Pushes the value at a member/index path of a variable, leaving the
+    /// `depth` path keys on the stack for a following `SetPath`
+    GetPath { target: VarRef, depth: usize },
+    Binary(BinaryOp),
+    Unary(UnaryOp),
+    /// Converts the number on top of the stack to the named numeric type
+    Cast(String),
+    Jump(usize),
+    /// Pops the condition and jumps if it is falsy
+    JumpIfFalse(usize),
+    /// Turns the value on top of the stack into the list a `for` loop iterates over
+    IterPrepare,
+    /// Iterates the list in local `slot` using the index in local `slot + 1`:
+    /// pushes the next element, or jumps to `exit` once the list is exhausted
+    ForNext { slot: usize, exit: usize },
+    MakeList(usize),
+    MakeMap(Vec<String>),
+    /// Registers a struct declaration with its field names
+    DefineStruct { name: String, fields: Vec<String> },
+    /// Pops one value per field, in the order given, into a struct
+    MakeStruct { name: String, fields: Vec<String> },
+    /// Registers an enum declaration with its variants' payload sizes
+    DefineEnum { name: String, variants: Vec<(String, usize)> },
+    /// Pops `argc` payload values into an enum variant
+    MakeVariant { enum_name: String, variant: String, argc: usize },
+    Member(String),
+    Index,
+    Call(usize),
+    CallBuiltin { name: String, argc: usize },
+    MakeClosure(usize),
+    Return,
+    Print,
+    /// Prints a fixed message, e.g. the banner for a module declaration
+    Echo(String),
+    /// Pops a value and pushes whether it matches the pattern
+    TestPattern(usize),
+    /// Pops a matched value and pushes its bindings in the pattern's binding order
+    BindPattern(usize),
+    Fail(String),
+}
+
+/// Compiled function body
+#[derive(Debug)]
+pub struct FunctionProto {
+    pub name: String,
+    pub arity: usize,
+    pub code: Vec<OpCode>,
+    constants: Vec<Value>,
+    /// Match patterns with the names they bind
+    patterns: Vec<(Pattern, Vec<String>)>,
+    /// Variables of the enclosing function shared with each closure of this function
+    captures: Vec<VarRef>,
+    /// Nested functions and lambdas, referenced by `MakeClosure`
+    functions: Vec<Arc<FunctionProto>>,
+}
+
+impl FunctionProto {
+    fn new(name: &str, arity: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            code: Vec::new(),
+            constants: Vec::new(),
+            patterns: Vec::new(),
+            captures: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+}
+
+/// Function value created by the bytecode VM
+#[derive(Debug)]
+pub struct Closure {
+    pub proto: Arc<FunctionProto>,
+    pub captures: Vec<Slot>,
+}
+
+pub struct BytecodeProgram {
+    mode: ExecutionMode,
+    target: Option<String>,
+    script: Arc<FunctionProto>,
+    globals: Vec<String>,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    boxed: bool,
+}
+
+struct LoopContext {
+    /// Number of locals that live outside the loop body
+    locals: usize,
+    continue_target: usize,
+    breaks: Vec<usize>,
+}
+
+/// Compiler state for one function being compiled
+struct FunctionState {
+    proto: FunctionProto,
+    locals: Vec<Local>,
+    depth: usize,
+    loops: Vec<LoopContext>,
+    capture_names: Vec<String>,
+    /// Name under which a local named function refers to itself
+    self_name: Option<String>,
+    /// Names of locals to keep in a `Slot`, because nested functions capture them
+    boxed: HashSet<String>,
+    /// Names of locals that nested functions captured while compiling
+    captured: HashSet<String>,
+}
+
+impl FunctionState {
+    fn new(name: &str, arity: usize, depth: usize, self_name: Option<String>) -> Self {
+        Self {
+            proto: FunctionProto::new(name, arity),
+            locals: Vec::new(),
+            depth,
+            loops: Vec::new(),
+            capture_names: Vec::new(),
+            self_name,
+            boxed: HashSet::new(),
+            captured: HashSet::new(),
+        }
+    }
+}
+
+pub struct BytecodeCompiler {
+    functions: Vec<FunctionState>,
+    globals: Vec<String>,
+    global_slots: HashMap<String, usize>,
+    /// Names declared at the top level; these shadow builtins such as `print`
+    declared_globals: HashSet<String>,
+}
+
+impl BytecodeCompiler {
+    pub fn compile(program: &Program) -> Result<BytecodeProgram, String> {
+        let mut compiler = Self {
+            functions: Vec::new(),
+            globals: Vec::new(),
+            global_slots: HashMap::new(),
+            declared_globals: HashSet::new(),
+        };
+
+        for stmt in &program.statements {
+            match &stmt.kind {
+                StmtKind::Let { name, .. } | StmtKind::Const { name, .. } |
+                StmtKind::Fn { name, .. } | StmtKind::AsyncFn { name, .. } | StmtKind::CoroutineFn { name, .. } => {
+                    compiler.declared_globals.insert(name.clone());
+                },
+                _ => {},
+            }
+        }
+
+        let script = compiler.function_body(|| FunctionState::new("<script>", 0, 0, None), |compiler| {
+            for stmt in &program.statements {
+                compiler.statement(stmt, false)?;
+            }
+            compiler.emit(OpCode::PushNone);
+            compiler.emit(OpCode::Return);
+            Ok(())
+        })?;
+        Ok(BytecodeProgram {
+            mode: program.mode.clone(),
+            target: program.target.clone(),
+            script: Arc::new(script),
+            globals: compiler.globals,
+        })
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().expect("function being compiled")
+    }
+
+    fn emit(&mut self, op: OpCode) -> usize {
+        let code = &mut self.current().proto.code;
+        code.push(op);
+        code.len() - 1
+    }
+
+    fn next_index(&mut self) -> usize {
+        self.current().proto.code.len()
+    }
+
+    /// Points the jump at `at` to `target`
+    fn patch_jump(&mut self, at: usize, target: usize) {
+        match &mut self.current().proto.code[at] {
+            OpCode::Jump(dest) | OpCode::JumpIfFalse(dest) | OpCode::ForNext { exit: dest, .. } => *dest = target,
+            op => unreachable!("patching non-jump instruction {:?}", op),
+        }
+    }
+
+    fn constant(&mut self, value: Value) {
+        let constants = &mut self.current().proto.constants;
+        constants.push(value);
+        let index = constants.len() - 1;
+        self.emit(OpCode::Constant(index));
+    }
+
+    fn at_global_scope(&self) -> bool {
+        self.functions.len() == 1 && self.functions[0].depth == 0
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().depth += 1;
+    }
+
+    /// Leaves a scope, popping its locals. With `keep_top`, the value on top of
+    /// the stack (the block's result) survives.
+    fn end_scope(&mut self, keep_top: bool) {
+        let state = self.current();
+        state.depth -= 1;
+        let depth = state.depth;
+        let mut count = 0;
+        while state.locals.last().is_some_and(|local| local.depth > depth) {
+            state.locals.pop();
+            count += 1;
+        }
+        if count > 0 {
+            self.emit(if keep_top { OpCode::PopBelow(count) } else { OpCode::PopN(count) });
+        }
+    }
+
+    /// Declares a local for the value on top of the stack
+    fn add_local(&mut self, name: &str) -> usize {
+        let state = self.current();
+        let boxed = state.boxed.contains(name);
+        state.locals.push(Local {
+            name: name.to_string(),
+            depth: state.depth,
+            boxed,
+        });
+        let slot = state.locals.len() - 1;
+        if boxed {
+            self.emit(OpCode::Box(slot));
+        }
+        slot
+    }
+
+    /// Reference to the local in `slot` of the current function
+    fn local(&mut self, slot: usize) -> VarRef {
+        if self.current().locals[slot].boxed { VarRef::Boxed(slot) } else { VarRef::Local(slot) }
+    }
+
+    fn global_slot(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.global_slots.get(name) {
+            return slot;
+        }
+        self.globals.push(name.to_string());
+        self.global_slots.insert(name.to_string(), self.globals.len() - 1);
+        self.globals.len() - 1
+    }
+
+    fn resolve(&mut self, name: &str) -> VarRef {
+        let index = self.functions.len() - 1;
+        match self.resolve_in(index, name) {
+            Some(var) => var,
+            None => VarRef::Global(self.global_slot(name)),
+        }
+    }
+
+    /// Resolves `name` as a local, self reference or capture of function `index`,
+    /// adding captures along the chain of enclosing functions as needed
+    fn resolve_in(&mut self, index: usize, name: &str) -> Option<VarRef> {
+        let state = &self.functions[index];
+        if let Some(slot) = state.locals.iter().rposition(|local| local.name == name) {
+            return Some(if state.locals[slot].boxed { VarRef::Boxed(slot) } else { VarRef::Local(slot) });
+        }
+        if state.self_name.as_deref() == Some(name) {
+            return Some(VarRef::Current);
+        }
+        if let Some(capture) = state.capture_names.iter().position(|n| n == name) {
+            return Some(VarRef::Capture(capture));
+        }
+        if index == 0 {
+            return None;
+        }
+
+        let outer = self.resolve_in(index - 1, name)?;
+        if let VarRef::Local(_) | VarRef::Boxed(_) = outer {
+            self.functions[index - 1].captured.insert(name.to_string());
+        }
+        let state = &mut self.functions[index];
+        state.proto.captures.push(outer);
+        state.capture_names.push(name.to_string());
+        Some(VarRef::Capture(state.capture_names.len() - 1))
+    }
+
+    fn unsupported(what: &str) -> String {
+        format!("{} not supported by the bytecode VM yet; run with --vm=tree", what)
+    }
+
+    /// Compiles a statement. With `want_value`, the statement's value is left on the
+    /// stack, matching the tree-walker's "value of the last statement" semantics.
+    fn statement(&mut self, stmt: &Stmt, want_value: bool) -> Result<(), String> {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } | StmtKind::Const { name, value, .. } => {
+                self.expression(value)?;
+                if self.at_global_scope() {
+                    let slot = self.global_slot(name);
+                    self.emit(OpCode::DefineGlobal(slot));
+                    if want_value {
+                        self.emit(OpCode::Get(VarRef::Global(slot)));
+                    }
+                } else {
+                    let slot = self.add_local(name);
+                    if want_value {
+                        let var = self.local(slot);
+                        self.emit(OpCode::Get(var));
+                    }
+                }
+                return Ok(());
+            },
+            StmtKind::Expr(expr) => {
+                self.expression(expr)?;
+                if !want_value {
+                    self.emit(OpCode::Pop);
+                }
+                return Ok(());
+            },
+            StmtKind::Print(expr) => {
+                self.expression(expr)?;
+                self.emit(OpCode::Print);
+            },
+            StmtKind::Return(expr) => {
+                match expr {
+                    Some(expr) => self.expression(expr)?,
+                    None => {
+                        self.emit(OpCode::PushNone);
+                    },
+                }
+                self.emit(OpCode::Return);
+                return Ok(());
+            },
+            StmtKind::Break | StmtKind::Continue => {
+                let state = self.current();
+                let (loop_locals, continue_target) = match state.loops.last() {
+                    Some(context) => (context.locals, context.continue_target),
+                    None => return Err("'break' or 'continue' outside of a loop".to_string()),
+                };
+                let count = state.locals.len() - loop_locals;
+                if count > 0 {
+                    self.emit(OpCode::PopN(count));
+                }
+                if matches!(&stmt.kind, StmtKind::Break) {
+                    let jump = self.emit(OpCode::Jump(0));
+                    self.current().loops.last_mut().expect("enclosing loop").breaks.push(jump);
+                } else {
+                    self.emit(OpCode::Jump(continue_target));
+                }
+                return Ok(());
+            },
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.expression(condition)?;
+                let to_else = self.emit(OpCode::JumpIfFalse(0));
+                self.block(then_branch, want_value)?;
+                let to_end = self.emit(OpCode::Jump(0));
+                let else_start = self.next_index();
+                self.patch_jump(to_else, else_start);
+                match else_branch {
+                    Some(else_branch) => self.block(else_branch, want_value)?,
+                    None if want_value => {
+                        self.emit(OpCode::PushNone);
+                    },
+                    None => {},
+                }
+                let end = self.next_index();
+                self.patch_jump(to_end, end);
+                return Ok(());
+            },
+            StmtKind::While { condition, body } => {
+                let loop_start = self.next_index();
+                self.expression(condition)?;
+                let exit_jump = self.emit(OpCode::JumpIfFalse(0));
+
+                let locals = self.current().locals.len();
+                self.current().loops.push(LoopContext {
+                    locals,
+                    continue_target: loop_start,
+                    breaks: Vec::new(),
+                });
+                self.block(body, false)?;
+                self.emit(OpCode::Jump(loop_start));
+
+                let exit = self.next_index();
+                self.patch_jump(exit_jump, exit);
+                let context = self.current().loops.pop().expect("loop context");
+                for jump in context.breaks {
+                    self.patch_jump(jump, exit);
+                }
+            },
+            StmtKind::For { var, iterable, body } => {
+                self.begin_scope();
+                self.expression(iterable)?;
+                self.emit(OpCode::IterPrepare);
+                let slot = self.add_local("(iter)");
+                self.constant(Value::Number(0.0));
+                self.add_local("(index)");
+
+                let loop_start = self.emit(OpCode::ForNext { slot, exit: 0 });
+                let locals = self.current().locals.len();
+                self.current().loops.push(LoopContext {
+                    locals,
+                    continue_target: loop_start,
+                    breaks: Vec::new(),
+                });
+
+                self.begin_scope();
+                self.add_local(var);
+                self.block(body, false)?;
+                self.end_scope(false);
+                self.emit(OpCode::Jump(loop_start));
+
+                let exit = self.next_index();
+                self.patch_jump(loop_start, exit);
+                let context = self.current().loops.pop().expect("loop context");
+                for jump in context.breaks {
+                    self.patch_jump(jump, exit);
+                }
+                self.end_scope(false);
+            },
+            StmtKind::Block(statements) => {
+                return self.block(statements, want_value);
+            },
+            StmtKind::CoroutineFn { .. } => return Err(Self::unsupported("Coroutines are")),
+            StmtKind::AsyncFn { .. } => return Err(Self::unsupported("async/await is")),
+            StmtKind::Fn { name, params, body, .. } => {
+                if self.at_global_scope() {
+                    let index = self.function(name, params, body, None)?;
+                    self.emit(OpCode::MakeClosure(index));
+                    let slot = self.global_slot(name);
+                    self.emit(OpCode::DefineGlobal(slot));
+                } else {
+                    let index = self.function(name, params, body, Some(name.clone()))?;
+                    self.emit(OpCode::MakeClosure(index));
+                    // Stored into the local declared for it when the scope started
+                    let var = self.resolve(name);
+                    self.emit(OpCode::Set(var));
+                    self.emit(OpCode::Pop);
+                }
+            },
+            StmtKind::MatchStmt { expr, arms } => {
+                return self.match_statement(expr, arms, want_value);
+            },
+            StmtKind::Module { name } => {
+                self.emit(OpCode::Echo(format!("📦 Module: {}", name)));
+            },
+            StmtKind::Export(declaration) => {
+                return self.statement(declaration, want_value);
+            },
+            StmtKind::ExportDefault(_) => {},
+            StmtKind::Import { module, alias } => {
+                let import_name = alias.as_ref().unwrap_or(module);
+                self.emit(OpCode::Echo(format!("📥 Import: {} as {}", module, import_name)));
+                if let Some(natives) = stdlib::module_value(module) {
+                    self.constant(natives);
+                    if self.at_global_scope() {
+                        let slot = self.global_slot(import_name);
+                        self.emit(OpCode::DefineGlobal(slot));
+                    } else {
+                        self.add_local(import_name);
+                    }
+                }
+            },
+            StmtKind::Actor { .. } => {
+                return Err(Self::unsupported("Actors are"));
+            },
+            StmtKind::Contract { name, fields, methods } => {
+                self.emit(OpCode::Echo(format!("📜 Contract: {} ({} fields, {} methods)", name, fields.len(), methods.len())));
+            },
+            StmtKind::Struct { name, fields } => {
+                self.emit(OpCode::DefineStruct {
+                    name: name.clone(),
+                    fields: fields.iter().map(|(field, _)| field.clone()).collect(),
+                });
+            },
+            StmtKind::Enum { name, variants } => {
+                self.emit(OpCode::DefineEnum {
+                    name: name.clone(),
+                    variants: variants.iter()
+                        .map(|(variant, payload)| (variant.clone(), payload.len()))
+                        .collect(),
+                });
+            },
+            StmtKind::Component { name, body } => {
+                self.emit(OpCode::Echo(format!("🎨 Component: {} ({} statements)", name, body.len())));
+            },
+            StmtKind::Flow { name, nodes } => {
+                self.emit(OpCode::Echo(format!("🔄 Flow: {} ({} nodes)", name, nodes.len())));
+            },
+            StmtKind::Deployment { name, config } => {
+                self.emit(OpCode::Echo(format!("🐳 Deployment: {} ({} config items)", name, config.len())));
+            },
+            StmtKind::Cluster { name, config } => {
+                self.emit(OpCode::Echo(format!("☸️  Cluster: {} ({} config items)", name, config.len())));
+            },
+            StmtKind::ParallelFor { .. } => {
+                return Err(Self::unsupported("Parallel loops are"));
+            },
+        }
+
+        // Statements without a value of their own evaluate to None
+        if want_value {
+            self.emit(OpCode::PushNone);
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &[Stmt], want_value: bool) -> Result<(), String> {
+        self.begin_scope();
+        self.declare_functions(statements);
+        for (i, stmt) in statements.iter().enumerate() {
+            self.statement(stmt, want_value && i == statements.len() - 1)?;
+        }
+        if statements.is_empty() && want_value {
+            self.emit(OpCode::PushNone);
+        }
+        self.end_scope(want_value);
+        Ok(())
+    }
+
+    /// Declares a local for each function of a local scope before its statements,
+    /// so a function can capture one declared after it
+    fn declare_functions(&mut self, statements: &[Stmt]) {
+        if self.at_global_scope() {
+            return;
+        }
+        for stmt in statements {
+            if let StmtKind::Fn { name, .. } = &stmt.kind {
+                self.emit(OpCode::PushNone);
+                self.add_local(name);
+            }
+        }
+    }
+
+    /// Compiles a function body into a nested prototype of the current function
+    fn function(&mut self, name: &str, params: &[(String, Option<String>)], body: &[Stmt], self_name: Option<String>) -> Result<usize, String> {
+        let new_state = || FunctionState::new(name, params.len(), 1, self_name.clone());
+        let proto = self.function_body(new_state, |compiler| {
+            for (param_name, _) in params {
+                compiler.add_local(param_name);
+            }
+            compiler.declare_functions(body);
+
+            // Without an explicit return, a function yields the value of its last statement
+            for (i, stmt) in body.iter().enumerate() {
+                compiler.statement(stmt, i == body.len() - 1)?;
+            }
+            if body.is_empty() {
+                compiler.emit(OpCode::PushNone);
+            }
+            compiler.emit(OpCode::Return);
+            Ok(())
+        })?;
+
+        let functions = &mut self.current().proto.functions;
+        functions.push(Arc::new(proto));
+        Ok(functions.len() - 1)
+    }
+
+    /// Compiles a function with `compile`. Which of its locals nested functions
+    /// capture is only known once those are compiled, and such locals must be
+    /// boxed from their declaration on, so the function is compiled again when
+    /// a nested function captured one that was not boxed.
+    fn function_body(&mut self, new_state: impl Fn() -> FunctionState, compile: impl Fn(&mut Self) -> Result<(), String>) -> Result<FunctionProto, String> {
+        let mut boxed = HashSet::new();
+        loop {
+            let mut state = new_state();
+            state.boxed = boxed.clone();
+            self.functions.push(state);
+            let result = compile(self);
+            let state = self.functions.pop().expect("function being compiled");
+            result?;
+            if state.captured.is_subset(&boxed) {
+                return Ok(state.proto);
+            }
+            boxed.extend(state.captured);
+        }
+    }
+
+    fn match_statement(&mut self, expr: &Expr, arms: &[MatchArm], want_value: bool) -> Result<(), String> {
+        if let Err(e) = ExhaustivenessChecker::check_overlap(arms) {
+            return Err(format!("Pattern matching error: {}", e));
+        }
+
+        self.begin_scope();
+        self.expression(expr)?;
+        let subject = self.add_local("(match)");
+
+        let mut end_jumps = Vec::new();
+        for arm in arms {
+            let mut names = Vec::new();
+            pattern_bindings(&arm.pattern, &mut names);
+            let patterns = &mut self.current().proto.patterns;
+            patterns.push((arm.pattern.clone(), names.clone()));
+            let pattern = patterns.len() - 1;
+
+            self.emit(OpCode::Get(VarRef::Local(subject)));
+            self.emit(OpCode::TestPattern(pattern));
+            let next_arm = self.emit(OpCode::JumpIfFalse(0));
+
+            self.begin_scope();
+            self.emit(OpCode::Get(VarRef::Local(subject)));
+            self.emit(OpCode::BindPattern(pattern));
+            for name in &names {
+                self.add_local(name);
+            }
+
+            let guard_failed = match &arm.guard {
+                Some(guard) => {
+                    self.expression(guard)?;
+                    Some(self.emit(OpCode::JumpIfFalse(0)))
+                },
+                None => None,
+            };
+
+            self.block(&arm.body, want_value)?;
+            self.end_scope(want_value);
+            end_jumps.push(self.emit(OpCode::Jump(0)));
+
+            if let Some(guard_failed) = guard_failed {
+                let target = self.next_index();
+                self.patch_jump(guard_failed, target);
+                if !names.is_empty() {
+                    self.emit(OpCode::PopN(names.len()));
+                }
+            }
+            let target = self.next_index();
+            self.patch_jump(next_arm, target);
+        }
+        self.emit(OpCode::Fail("No pattern matched".to_string()));
+
+        let end = self.next_index();
+        for jump in end_jumps {
+            self.patch_jump(jump, end);
+        }
+        self.end_scope(want_value);
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), String> {
+        match &expr.kind {
+            ExprKind::Number(n) => self.constant(Value::Number(*n)),
+            ExprKind::String(s) => self.constant(Value::String(s.clone())),
+            ExprKind::Boolean(b) => self.constant(Value::Boolean(*b)),
+            ExprKind::None => {
+                self.emit(OpCode::PushNone);
+            },
+            ExprKind::Identifier(name) => {
+                let var = self.resolve(name);
+                self.emit(OpCode::Get(var));
+            },
+            ExprKind::Binary { left, op, right } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit(OpCode::Binary(op.clone()));
+            },
+            ExprKind::Unary { op, expr } => {
+                self.expression(expr)?;
+                self.emit(OpCode::Unary(op.clone()));
+            },
+            ExprKind::Cast { expr, target } => {
+                self.expression(expr)?;
+                self.emit(OpCode::Cast(target.clone()));
+            },
+            ExprKind::Call { callee, args } => {
+                // Built-in functions, unless shadowed by a variable
+                if let ExprKind::Identifier(name) = &callee.kind {
+                    let index = self.functions.len() - 1;
+                    if !self.declared_globals.contains(name) && self.resolve_in(index, name).is_none() {
+                        for arg in args {
+                            self.expression(arg)?;
+                        }
+                        self.emit(OpCode::CallBuiltin {
+                            name: name.clone(),
+                            argc: args.len(),
+                        });
+                        return Ok(());
+                    }
+                }
+
+                self.expression(callee)?;
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                self.emit(OpCode::Call(args.len()));
+            },
+            ExprKind::Member { object, member } => {
+                self.expression(object)?;
+                self.emit(OpCode::Member(member.clone()));
+            },
+            ExprKind::Index { object, index } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.emit(OpCode::Index);
+            },
+            ExprKind::List(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+                self.emit(OpCode::MakeList(elements.len()));
+            },
+            ExprKind::Map(pairs) => {
+                let mut keys = Vec::with_capacity(pairs.len());
+                for (key, value) in pairs {
+                    match &key.kind {
+                        ExprKind::String(s) | ExprKind::Identifier(s) => keys.push(s.clone()),
+                        _ => return Err("Map keys must be strings or identifiers".to_string()),
+                    }
+                    self.expression(value)?;
+                }
+                self.emit(OpCode::MakeMap(keys));
+            },
+            ExprKind::StructLiteral { name, fields } => {
+                for (_, value) in fields {
+                    self.expression(value)?;
+                }
+                self.emit(OpCode::MakeStruct {
+                    name: name.clone(),
+                    fields: fields.iter().map(|(field, _)| field.clone()).collect(),
+                });
+            },
+            ExprKind::EnumVariant { enum_name, variant, args } => {
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                self.emit(OpCode::MakeVariant {
+                    enum_name: enum_name.clone(),
+                    variant: variant.clone(),
+                    argc: args.len(),
+                });
+            },
+            ExprKind::Assign { target, op, value } => {
+                match &target.kind {
+                    ExprKind::Identifier(name) => {
+                        let var = self.resolve(name);
+                        if var == VarRef::Current {
+                            return Err(format!("Cannot assign to function '{}' inside its own body", name));
+                        }
+                        if let Some(op) = op {
+                            self.emit(OpCode::Get(var));
+                            self.expression(value)?;
+                            self.emit(OpCode::Binary(op.clone()));
+                        } else {
+                            self.expression(value)?;
+                        }
+                        self.emit(OpCode::Set(var));
+                    },
+                    _ => {
+                        // The keys are evaluated once; a compound assignment reads through them too
+                        let (root, depth) = self.assignment_path(target)?;
+                        if let Some(op) = op {
+                            self.emit(OpCode::GetPath { target: root, depth });
+                            self.expression(value)?;
+                            self.emit(OpCode::Binary(op.clone()));
+                        } else {
+                            self.expression(value)?;
+                        }
+                        self.emit(OpCode::SetPath { target: root, depth });
+                    },
+                }
+            },
+            ExprKind::Lambda { params, body } => {
+                let index = self.function("lambda", params, body, None)?;
+                self.emit(OpCode::MakeClosure(index));
+            },
+            ExprKind::Match { .. } => return Err(Self::unsupported("Match expressions are")),
+            ExprKind::Destructure { .. } => return Err(Self::unsupported("Destructuring is")),
+            ExprKind::Async { .. } | ExprKind::Await { .. } => return Err(Self::unsupported("async/await is")),
+            ExprKind::Yield(_) | ExprKind::Generator { .. } => return Err(Self::unsupported("Generators are")),
+            ExprKind::ParallelMap { .. } | ExprKind::ParallelReduce { .. } => return Err(Self::unsupported("Parallel loops are")),
+            ExprKind::Spawn { .. } | ExprKind::Send { .. } => return Err(Self::unsupported("Actors are")),
+            ExprKind::Coroutine { .. } | ExprKind::Suspend(_) | ExprKind::Resume { .. } => {
+                return Err(Self::unsupported("Coroutines are"));
+            },
+        }
+        Ok(())
+    }
+
+    /// Emits the keys of a member/index assignment target and returns its root variable
+    fn assignment_path(&mut self, target: &Expr) -> Result<(VarRef, usize), String> {
+        match &target.kind {
+            ExprKind::Identifier(name) => match self.resolve(name) {
+                VarRef::Current => Err(format!("Cannot assign to function '{}' inside its own body", name)),
+                var => Ok((var, 0)),
+            },
+            ExprKind::Member { object, member } => {
+                let (root, depth) = self.assignment_path(object)?;
+                self.constant(Value::String(member.clone()));
+                Ok((root, depth + 1))
+            },
+            ExprKind::Index { object, index } => {
+                let (root, depth) = self.assignment_path(object)?;
+                self.expression(index)?;
+                Ok((root, depth + 1))
+            },
+            _ => Err("Invalid assignment target".to_string()),
+        }
+    }
+}
+
+/// Names bound by a pattern, in the order `PatternMatcher::extract_bindings` produces them
+fn pattern_bindings(pattern: &Pattern, names: &mut Vec<String>) {
+    let add = |name: &String, names: &mut Vec<String>| {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    };
+    match pattern {
+        Pattern::Identifier(name) => add(name, names),
+        Pattern::Binding { name, pattern } => {
+            add(name, names);
+            pattern_bindings(pattern, names);
+        },
+        Pattern::Tuple(patterns) | Pattern::List(patterns) | Pattern::Or(patterns) |
+        Pattern::Variant { fields: patterns, .. } => {
+            for pattern in patterns {
+                pattern_bindings(pattern, names);
+            }
+        },
+        Pattern::Struct { fields, .. } => {
+            for (_, pattern) in fields {
+                pattern_bindings(pattern, names);
+            }
+        },
+        Pattern::Guard { pattern, .. } => pattern_bindings(pattern, names),
+        Pattern::Number(_) | Pattern::String(_) | Pattern::Boolean(_) | Pattern::Wildcard => {},
+    }
+}
+
+struct CallFrame {
+    closure: Arc<Closure>,
+    /// Where to resume once a call made from this frame returns
+    ip: usize,
+    /// Stack index of local slot 0
+    base: usize,
+    /// Boxed locals, by slot
+    boxes: Vec<Option<Slot>>,
+}
+
+pub struct BytecodeVM {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: Vec<Option<Value>>,
+    global_names: Vec<String>,
+    /// Declared structs and their field names
+    structs: HashMap<String, Vec<String>>,
+    /// Declared enums and their variants with payload sizes
+    enums: HashMap<String, Vec<(String, usize)>>,
+}
+
+impl BytecodeVM {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: Vec::new(),
+            global_names: Vec::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+        }
+    }
+
+    pub fn run(&mut self, program: &BytecodeProgram) -> Result<(), String> {
+        println!("🚀 JOEL Runtime - Mode: {:?}", program.mode);
+        if let Some(ref target) = program.target {
+            println!("   Target: {}", target);
+        }
+        println!();
+
+        self.globals = vec![None; program.globals.len()];
+        self.global_names = program.globals.clone();
+        self.frames.push(CallFrame {
+            closure: Arc::new(Closure {
+                proto: Arc::clone(&program.script),
+                captures: Vec::new(),
+            }),
+            ip: 0,
+            base: 0,
+            boxes: Vec::new(),
+        });
+
+        let result = self.execute();
+        self.stack.clear();
+        self.frames.clear();
+        result
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("bytecode stack underflow")
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("active call frame")
+    }
+
+    fn execute(&mut self) -> Result<(), String> {
+        let mut closure = Arc::clone(&self.frames.last().expect("script frame").closure);
+        let mut ip = 0;
+        let mut base = 0;
+
+        loop {
+            let op = &closure.proto.code[ip];
+            ip += 1;
+
+            match op {
+                OpCode::Constant(index) => {
+                    self.stack.push(closure.proto.constants[*index].clone());
+                },
+                OpCode::PushNone => self.stack.push(Value::None),
+                OpCode::Pop => {
+                    self.pop();
+                },
+                OpCode::PopN(count) => {
+                    let len = self.stack.len();
+                    self.stack.truncate(len - count);
+                },
+                OpCode::PopBelow(count) => {
+                    let top = self.pop();
+                    let len = self.stack.len();
+                    self.stack.truncate(len - count);
+                    self.stack.push(top);
+                },
+                OpCode::Get(var) => {
+                    let value = match var {
+                        VarRef::Local(slot) => self.stack[base + slot].clone(),
+                        VarRef::Boxed(slot) => self.boxed(*slot).get(),
+                        VarRef::Capture(index) => closure.captures[*index].get(),
+                        VarRef::Current => Value::Closure(Arc::clone(&closure)),
+                        VarRef::Global(slot) => match &self.globals[*slot] {
+                            Some(value) => value.clone(),
+                            None => return Err(format!("Undefined variable: {}", self.global_names[*slot])),
+                        },
+                    };
+                    self.stack.push(value);
+                },
+                OpCode::Set(var) => {
+                    let value = self.stack.last().expect("value to assign").clone();
+                    self.update(*var, &closure, base, |slot| {
+                        *slot = value;
+                        Ok(())
+                    })?;
+                },
+                OpCode::DefineGlobal(slot) => {
+                    let value = self.pop();
+                    self.globals[*slot] = Some(value);
+                },
+                OpCode::Box(slot) => {
+                    let value = std::mem::replace(&mut self.stack[base + slot], Value::None);
+                    let boxes = &mut self.frame().boxes;
+                    if boxes.len() <= *slot {
+                        boxes.resize(slot + 1, None);
+                    }
+                    boxes[*slot] = Some(Slot::new(value));
+                },
+                OpCode::SetPath { target, depth } => {
+                    let value = self.pop();
+                    let keys = self.stack.split_off(self.stack.len() - depth);
+                    self.update(*target, &closure, base, |slot| VM::store_at_path(slot, &keys, value.clone()))?;
+                    self.stack.push(value);
+                },
+                OpCode::GetPath { target, depth } => {
+                    let root = match target {
+                        VarRef::Local(slot) => self.stack[base + slot].clone(),
+                        VarRef::Boxed(slot) => self.boxed(*slot).get(),
+                        VarRef::Capture(index) => closure.captures[*index].get(),
+                        VarRef::Global(slot) => self.globals[*slot].clone()
+                            .ok_or_else(|| format!("Undefined variable: {}", self.global_names[*slot]))?,
+                        VarRef::Current => unreachable!("functions cannot be assignment targets"),
+                    };
+                    let keys = &self.stack[self.stack.len() - depth..];
+                    let value = VM::load_at_path(root, keys)?;
+                    self.stack.push(value);
+                },
+                OpCode::Binary(op) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(VM::binary_op(&left, op, &right)?);
+                },
+                OpCode::Unary(op) => {
+                    let value = self.pop();
+                    self.stack.push(VM::unary_op(op, &value)?);
+                },
+                OpCode::Cast(target) => {
+                    let value = self.pop();
+                    self.stack.push(VM::cast(&value, target)?);
+                },
+                OpCode::Jump(target) => ip = *target,
+                OpCode::JumpIfFalse(target) => {
+                    let condition = self.pop();
+                    if !VM::is_truthy(&condition) {
+                        ip = *target;
+                    }
+                },
+                OpCode::IterPrepare => {
+                    let list = match self.pop() {
+                        Value::List(list) => list,
+                        // Range iteration: for i in n
+                        Value::Number(end) => (0..end as i64).map(|i| Value::Number(i as f64)).collect(),
+                        other => return Err(format!("For loop expects a list or range, got {:?}", other)),
+                    };
+                    self.stack.push(Value::List(list));
+                },
+                OpCode::ForNext { slot, exit } => {
+                    let index = match &self.stack[base + slot + 1] {
+                        Value::Number(n) => *n as usize,
+                        _ => unreachable!("for loop index is a number"),
+                    };
+                    let next = match &self.stack[base + slot] {
+                        Value::List(list) => list.get(index).cloned(),
+                        _ => unreachable!("for loop iterates a list"),
+                    };
+                    match next {
+                        Some(item) => {
+                            self.stack[base + slot + 1] = Value::Number((index + 1) as f64);
+                            self.stack.push(item);
+                        },
+                        None => ip = *exit,
+                    }
+                },
+                OpCode::MakeList(count) => {
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::List(items));
+                },
+                OpCode::MakeMap(keys) => {
+                    let values = self.stack.split_off(self.stack.len() - keys.len());
+                    let map: HashMap<String, Value> = keys.iter().cloned().zip(values).collect();
+                    self.stack.push(Value::Map(map));
+                },
+                OpCode::DefineStruct { name, fields } => {
+                    self.structs.insert(name.clone(), fields.clone());
+                },
+                OpCode::MakeStruct { name, fields } => {
+                    let values = self.stack.split_off(self.stack.len() - fields.len());
+                    let declared = self.structs.get(name)
+                        .ok_or_else(|| format!("Unknown struct '{}'", name))?;
+                    let value = VM::construct_struct(name, declared, fields.iter().cloned().zip(values).collect())?;
+                    self.stack.push(value);
+                },
+                OpCode::DefineEnum { name, variants } => {
+                    self.enums.insert(name.clone(), variants.clone());
+                },
+                OpCode::MakeVariant { enum_name, variant, argc } => {
+                    let values = self.stack.split_off(self.stack.len() - argc);
+                    let declared = self.enums.get(enum_name)
+                        .ok_or_else(|| format!("Unknown enum '{}'", enum_name))?;
+                    self.stack.push(VM::construct_variant(enum_name, variant, declared, values)?);
+                },
+                OpCode::Member(member) => {
+                    let object = self.pop();
+                    self.stack.push(VM::member(object, member)?);
+                },
+                OpCode::Index => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let value = match (object, index) {
+                        (Value::List(list), Value::Number(n)) => {
+                            let i = VM::list_index(n)?;
+                            list.get(i).cloned().ok_or_else(|| format!("Index {} out of bounds", i))?
+                        },
+                        (Value::Map(map), Value::String(key)) => {
+                            map.get(&key).cloned().ok_or_else(|| format!("No member '{}'", key))?
+                        },
+                        _ => return Err("Invalid index operation".to_string()),
+                    };
+                    self.stack.push(value);
+                },
+                OpCode::Call(argc) => {
+                    let callee_index = self.stack.len() - argc - 1;
+                    let callee = match &self.stack[callee_index] {
+                        Value::Closure(callee) => Arc::clone(callee),
+                        Value::NativeFunction(native) => {
+                            let native = *native;
+                            let args = self.stack.split_off(callee_index + 1);
+                            let result = native.call(&args)?;
+                            self.stack[callee_index] = result;
+                            continue;
+                        },
+                        other => return Err(format!("Cannot call {}", other)),
+                    };
+                    if callee.proto.arity != *argc {
+                        return Err(format!("Function '{}' expects {} arguments, got {}",
+                            callee.proto.name, callee.proto.arity, argc));
+                    }
+                    if self.frames.len() >= MAX_FRAMES {
+                        return Err(format!("Stack overflow in function '{}'", callee.proto.name));
+                    }
+
+                    self.frame().ip = ip;
+                    base = callee_index + 1;
+                    ip = 0;
+                    self.frames.push(CallFrame {
+                        closure: Arc::clone(&callee),
+                        ip: 0,
+                        base,
+                        boxes: Vec::new(),
+                    });
+                    closure = callee;
+                },
+                OpCode::CallBuiltin { name, argc } => {
+                    let args = self.stack.split_off(self.stack.len() - argc);
+                    let result = match name.as_str() {
+                        "print" => {
+                            let parts: Vec<String> = args.iter().map(Value::to_string).collect();
+                            println!("{}", parts.join(" "));
+                            Value::None
+                        },
+                        "range" => stdlib::core::range(&args)?,
+                        "take" => stdlib::core::take(&args)?,
+                        "next" => stdlib::core::next(&args)?,
+                        "collect" => stdlib::core::collect(&args)?,
+                        _ => return Err(format!("Unknown function: {}", name)),
+                    };
+                    self.stack.push(result);
+                },
+                OpCode::MakeClosure(index) => {
+                    let proto = Arc::clone(&closure.proto.functions[*index]);
+                    let mut captures = Vec::with_capacity(proto.captures.len());
+                    for source in &proto.captures {
+                        captures.push(match source {
+                            VarRef::Boxed(slot) => self.boxed(*slot).clone(),
+                            VarRef::Capture(index) => closure.captures[*index].clone(),
+                            VarRef::Current => Slot::new(Value::Closure(Arc::clone(&closure))),
+                            VarRef::Local(_) => unreachable!("captured locals are boxed"),
+                            VarRef::Global(_) => unreachable!("globals are never captured"),
+                        });
+                    }
+                    self.stack.push(Value::Closure(Arc::new(Closure { proto, captures })));
+                },
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("returning frame");
+                    if self.frames.is_empty() {
+                        // The script itself returned
+                        return Ok(());
+                    }
+
+                    // Drop the callee, its arguments and locals
+                    self.stack.truncate(frame.base - 1);
+                    self.stack.push(result);
+
+                    let caller = self.frames.last().expect("calling frame");
+                    closure = Arc::clone(&caller.closure);
+                    ip = caller.ip;
+                    base = caller.base;
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", value);
+                },
+                OpCode::Echo(message) => println!("{}", message),
+                OpCode::TestPattern(index) => {
+                    let value = self.pop();
+                    let (pattern, _) = &closure.proto.patterns[*index];
+                    self.stack.push(Value::Boolean(PatternMatcher::matches_nested(pattern, &value)));
+                },
+                OpCode::BindPattern(index) => {
+                    let value = self.pop();
+                    let (pattern, names) = &closure.proto.patterns[*index];
+                    let bindings = PatternMatcher::extract_bindings(pattern, &value);
+                    for name in names {
+                        let bound = bindings.iter().rev()
+                            .find(|(bound_name, _)| bound_name == name)
+                            .map(|(_, value)| value.clone())
+                            .unwrap_or(Value::None);
+                        self.stack.push(bound);
+                    }
+                },
+                OpCode::Fail(message) => return Err(message.clone()),
+            }
+        }
+    }
+
+    /// The boxed local in `slot` of the current frame
+    fn boxed(&mut self, slot: usize) -> &Slot {
+        self.frame().boxes[slot].as_ref().expect("boxed local is declared")
+    }
+
+    /// Runs `update` on a variable of the running `closure`
+    fn update(&mut self, var: VarRef, closure: &Closure, base: usize, update: impl FnOnce(&mut Value) -> Result<(), String>) -> Result<(), String> {
+        match var {
+            VarRef::Local(slot) => update(&mut self.stack[base + slot]),
+            VarRef::Boxed(slot) => self.boxed(slot).update(update),
+            VarRef::Capture(index) => closure.captures[index].update(update),
+            VarRef::Global(slot) => match &mut self.globals[slot] {
+                Some(value) => update(value),
+                None => Err(format!("Undefined variable: {}", self.global_names[slot])),
+            },
+            VarRef::Current => Err("Cannot assign to the running function".to_string()),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::ast::*;
+use crate::diagnostics::status;
 use std::path::Path;
 use std::fs;
 
@@ -69,7 +70,7 @@ impl Compiler {
     }
     
     fn compile_native(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating LLVM IR for native target...");
+        status("🔨 Generating LLVM IR for native target...");
         
         let arch = self.arch.as_deref().unwrap_or("x86_64-unknown-linux-gnu");
         let mut llvm_compiler = llvm_backend::LLVMCompiler::new(arch, self.optimize, self.debug);
@@ -80,15 +81,15 @@ impl Compiler {
             .map_err(|e| format!("Failed to write LLVM IR: {}", e))?;
         
         if self.optimize {
-            println!("✅ LLVM IR generated with optimizations");
+            status("✅ LLVM IR generated with optimizations");
         } else {
-            println!("✅ LLVM IR generated successfully");
+            status("✅ LLVM IR generated successfully");
         }
         Ok(())
     }
     
     fn compile_wasm(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating WebAssembly for wasm32 target...");
+        status("🔨 Generating WebAssembly for wasm32 target...");
         
         let mut wasm_compiler = wasm_backend::WASMCompiler::new(self.debug);
         let (wasm_binary, source_map) = wasm_compiler.compile(program)?;
@@ -102,15 +103,15 @@ impl Compiler {
             let source_map_path = output_path.with_extension("wasm.map");
             fs::write(&source_map_path, source_map)
                 .map_err(|e| format!("Failed to write source map: {}", e))?;
-            println!("✅ WebAssembly and source map generated successfully");
+            status("✅ WebAssembly and source map generated successfully");
         } else {
-            println!("✅ WebAssembly generated successfully");
+            status("✅ WebAssembly generated successfully");
         }
         Ok(())
     }
     
     fn compile_evm(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating EVM bytecode...");
+        status("🔨 Generating EVM bytecode...");
         
         let mut evm_compiler = evm_backend::EVMCompiler::new();
         let (bytecode, abi) = evm_compiler.compile(program)?;
@@ -124,12 +125,12 @@ impl Compiler {
         fs::write(&abi_path, abi)
             .map_err(|e| format!("Failed to write ABI: {}", e))?;
         
-        println!("✅ EVM bytecode and ABI generated successfully");
+        status("✅ EVM bytecode and ABI generated successfully");
         Ok(())
     }
     
     fn compile_solana(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating Solana BPF program...");
+        status("🔨 Generating Solana BPF program...");
         
         let mut solana_compiler = solana_backend::SolanaCompiler::new();
         let bpf_binary = solana_compiler.compile(program)?;
@@ -138,12 +139,12 @@ impl Compiler {
         fs::write(output_path, bpf_binary)
             .map_err(|e| format!("Failed to write Solana BPF binary: {}", e))?;
         
-        println!("✅ Solana BPF program generated successfully");
+        status("✅ Solana BPF program generated successfully");
         Ok(())
     }
     
     fn compile_ios(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating iOS framework...");
+        status("🔨 Generating iOS framework...");
         
         // For iOS, we compile to LLVM IR with iOS-specific target
         let arch = self.arch.as_deref().unwrap_or("arm64-apple-ios");
@@ -154,12 +155,12 @@ impl Compiler {
         fs::write(output_path, ir)
             .map_err(|e| format!("Failed to write iOS LLVM IR: {}", e))?;
         
-        println!("✅ iOS framework generated successfully");
+        status("✅ iOS framework generated successfully");
         Ok(())
     }
     
     fn compile_android(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating Android library...");
+        status("🔨 Generating Android library...");
         
         // For Android, we compile to LLVM IR with Android-specific target
         let arch = self.arch.as_deref().unwrap_or("aarch64-linux-android");
@@ -170,12 +171,12 @@ impl Compiler {
         fs::write(output_path, ir)
             .map_err(|e| format!("Failed to write Android LLVM IR: {}", e))?;
         
-        println!("✅ Android library generated successfully");
+        status("✅ Android library generated successfully");
         Ok(())
     }
     
     fn compile_cosmos(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating Cosmos SDK smart contract...");
+        status("🔨 Generating Cosmos SDK smart contract...");
         
         let mut cosmos_compiler = cosmos_backend::CosmosCompiler::new();
         let wasm = cosmos_compiler.compile(program)?;
@@ -184,12 +185,12 @@ impl Compiler {
         fs::write(output_path, wasm)
             .map_err(|e| format!("Failed to write Cosmos WASM: {}", e))?;
         
-        println!("✅ Cosmos SDK contract generated successfully");
+        status("✅ Cosmos SDK contract generated successfully");
         Ok(())
     }
     
     fn compile_polkadot(&self, program: &Program, output_path: &Path) -> Result<(), String> {
-        println!("🔨 Generating Polkadot/Substrate runtime module...");
+        status("🔨 Generating Polkadot/Substrate runtime module...");
         
         let mut polkadot_compiler = polkadot_backend::PolkadotCompiler::new();
         let wasm = polkadot_compiler.compile(program)?;
@@ -198,7 +199,7 @@ impl Compiler {
         fs::write(output_path, wasm)
             .map_err(|e| format!("Failed to write Polkadot WASM: {}", e))?;
         
-        println!("✅ Polkadot runtime module generated successfully");
+        status("✅ Polkadot runtime module generated successfully");
         Ok(())
     }
 }
@@ -255,7 +256,11 @@ pub mod llvm_backend {
             
             // Generate functions
             for stmt in &program.statements {
-                if let Stmt::Fn { name, params, return_type, body } = stmt {
+                let stmt = match &stmt.kind {
+                    StmtKind::Export(declaration) => declaration.as_ref(),
+                    _ => stmt,
+                };
+                if let StmtKind::Fn { name, params, return_type, body, .. } = &stmt.kind {
                     let func_ir = self.compile_function(name, params, return_type, body)?;
                     ir.push_str(&func_ir);
                     ir.push_str("\n");
@@ -268,16 +273,16 @@ pub mod llvm_backend {
             
             // Generate code for top-level statements
             for stmt in &program.statements {
-                match stmt {
-                    Stmt::Expr(expr) => {
+                match &stmt.kind {
+                    StmtKind::Expr(expr) => {
                         let code = self.compile_expr(expr)?;
                         ir.push_str(&code);
                     },
-                    Stmt::Print(expr) => {
+                    StmtKind::Print(expr) => {
                         let code = self.compile_print(expr)?;
                         ir.push_str(&code);
                     },
-                    Stmt::Let { name, value, .. } => {
+                    StmtKind::Let { name, value, .. } => {
                         let code = self.compile_let(name, value)?;
                         ir.push_str(&code);
                     },
@@ -439,9 +444,9 @@ pub mod llvm_backend {
         }
         
         fn compile_stmt(&mut self, stmt: &Stmt) -> Result<String, String> {
-            match stmt {
-                Stmt::Let { name, value, .. } => self.compile_let(name, value),
-                Stmt::Return(expr) => {
+            match &stmt.kind {
+                StmtKind::Let { name, value, .. } => self.compile_let(name, value),
+                StmtKind::Return(expr) => {
                     match expr {
                         Some(e) => {
                             let code = self.compile_expr(e)?;
@@ -450,8 +455,8 @@ pub mod llvm_backend {
                         None => Ok("  ret void\n".to_string()),
                     }
                 },
-                Stmt::Print(expr) => self.compile_print(expr),
-                Stmt::Expr(expr) => {
+                StmtKind::Print(expr) => self.compile_print(expr),
+                StmtKind::Expr(expr) => {
                     let code = self.compile_expr(expr)?;
                     Ok(format!("  {}\n", code))
                 },
@@ -466,13 +471,13 @@ pub mod llvm_backend {
         }
         
         fn compile_print(&mut self, expr: &Expr) -> Result<String, String> {
-            match expr {
-                Expr::Number(n) => {
+            match &expr.kind {
+                ExprKind::Number(n) => {
                     let var = self.next_var();
                     Ok(format!("  {} = add i64 0, {}\n  call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str, i64 0, i64 0), i64 {})\n", 
                         var, *n as i64, *n as i64))
                 },
-                Expr::String(s) => {
+                ExprKind::String(s) => {
                     // For strings, we'd need to create a constant
                     Ok(format!("  ; print string: {}\n", s))
                 },
@@ -484,11 +489,11 @@ pub mod llvm_backend {
         }
         
         fn compile_expr(&mut self, expr: &Expr) -> Result<String, String> {
-            match expr {
-                Expr::Number(n) => Ok(format!("{}", *n as i64)),
-                Expr::Boolean(b) => Ok(if *b { "1".to_string() } else { "0".to_string() }),
-                Expr::Identifier(name) => Ok(format!("%{}", name)),
-                Expr::Binary { left, op, right } => {
+            match &expr.kind {
+                ExprKind::Number(n) => Ok(format!("{}", *n as i64)),
+                ExprKind::Boolean(b) => Ok(if *b { "1".to_string() } else { "0".to_string() }),
+                ExprKind::Identifier(name) => Ok(format!("%{}", name)),
+                ExprKind::Binary { left, op, right } => {
                     let left_code = self.compile_expr(left)?;
                     let right_code = self.compile_expr(right)?;
                     let var = self.next_var();
@@ -502,22 +507,30 @@ pub mod llvm_backend {
                     };
                     Ok(format!("{} = {} i64 {}, {}", var, op_llvm, left_code, right_code))
                 },
-                Expr::Call { callee, args } => {
+                ExprKind::Call { callee, args } => {
+                    let callee = match &callee.kind {
+                        ExprKind::Identifier(name) => name.clone(),
+                        _ => return Err("Only named function calls are supported in LLVM backend".to_string()),
+                    };
                     let arg_list: Vec<String> = args.iter()
                         .map(|a| self.compile_expr(a))
                         .collect::<Result<_, _>>()?;
                     let var = self.next_var();
                     Ok(format!("{} = call i64 @{}({})", var, callee, arg_list.join(", ")))
                 },
-                Expr::String(s) => {
+                ExprKind::String(s) => {
                     // String literals (simplified)
                     Ok(format!("\"{}\"", s))
                 },
-                Expr::List(_) | Expr::Map(_) | Expr::None | 
-                Expr::Match { .. } | Expr::Destructure { .. } | Expr::Async { .. } | 
-                Expr::Await { .. } | Expr::Yield(_) | Expr::Generator { .. } |
-                Expr::Coroutine { .. } | Expr::Suspend | Expr::Resume { .. } |
-                Expr::Unary { .. } | Expr::Member { .. } | Expr::Index { .. } => {
+                ExprKind::List(_) | ExprKind::Map(_) | ExprKind::None | 
+                ExprKind::Match { .. } | ExprKind::Destructure { .. } | ExprKind::Async { .. } | 
+                ExprKind::Await { .. } | ExprKind::Yield(_) | ExprKind::Generator { .. } |
+                ExprKind::Coroutine { .. } | ExprKind::Suspend(_) | ExprKind::Resume { .. } |
+                ExprKind::ParallelMap { .. } | ExprKind::ParallelReduce { .. } |
+                ExprKind::Spawn { .. } | ExprKind::Send { .. } |
+                ExprKind::Unary { .. } | ExprKind::Cast { .. } | ExprKind::Member { .. } | ExprKind::Index { .. } |
+                ExprKind::Assign { .. } | ExprKind::Lambda { .. } | ExprKind::StructLiteral { .. } |
+                ExprKind::EnumVariant { .. } => {
                     Err("Unsupported expression type in LLVM backend".to_string())
                 },
             }
@@ -576,7 +589,7 @@ pub mod wasm_backend {
             
             // Function section (section 3)
             let func_count = program.statements.iter()
-                .filter(|s| matches!(s, Stmt::Fn { .. }))
+                .filter(|s| matches!(&s.kind, StmtKind::Fn { .. }))
                 .count() as u32 + 1; // +1 for main
             
             let mut func_section = Vec::new();
@@ -611,8 +624,8 @@ pub mod wasm_backend {
             main_code.push(0x00); // local count
             // Generate code for statements
             for stmt in &program.statements {
-                match stmt {
-                    Stmt::Print(Expr::Number(n)) => {
+                match &stmt.kind {
+                    StmtKind::Print(Expr { kind: ExprKind::Number(n), .. }) => {
                         // Call console.log equivalent (simplified)
                         main_code.push(0x41); // i32.const
                         main_code.extend_from_slice(&self.encode_sleb128(*n as i64));
@@ -713,7 +726,7 @@ pub mod evm_backend {
             // Find contracts in program
             let mut has_contracts = false;
             for stmt in &program.statements {
-                if let Stmt::Contract { .. } = stmt {
+                if let StmtKind::Contract { .. } = &stmt.kind {
                     has_contracts = true;
                     self.compile_contract(stmt)?;
                 }
@@ -783,8 +796,8 @@ pub mod solana_backend {
             
             // Generate code for program
             for stmt in &program.statements {
-                match stmt {
-                    Stmt::Contract { .. } => {
+                match &stmt.kind {
+                    StmtKind::Contract { .. } => {
                         // Compile Solana program
                     },
                     _ => {}
@@ -822,7 +835,7 @@ pub mod cosmos_backend {
             // Cosmos-specific contract structure
             // This is a simplified version
             for stmt in &program.statements {
-                if let Stmt::Contract { .. } = stmt {
+                if let StmtKind::Contract { .. } = &stmt.kind {
                     // Compile Cosmos contract
                 }
             }
@@ -858,8 +871,8 @@ pub mod polkadot_backend {
             // Substrate runtime module structure
             // This is a simplified version
             for stmt in &program.statements {
-                match stmt {
-                    Stmt::Contract { .. } => {
+                match &stmt.kind {
+                    StmtKind::Contract { .. } => {
                         // Compile Substrate runtime module
                     },
                     _ => {}
@@ -1,8 +1,197 @@
-use crate::vm::Value;
+use crate::value::Value;
 use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Stack size for coroutine threads, matching the main thread's usual limit so
+/// recursion inside a coroutine body goes as deep as it would outside
+const COROUTINE_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Error a suspended body sees when its coroutine is cancelled or dropped
+pub const CANCELLED: &str = "coroutine cancelled";
+
+/// What a coroutine reports when it stops running
+pub enum Resumed {
+    /// Paused at `suspend` (or a generator's `yield`) with this value
+    Suspended(Value),
+    /// Ran to the end of its body, producing this value
+    Completed(Value),
+}
+
+/// Sent from the body's thread back to whoever resumed it
+enum Event {
+    Suspended(Value),
+    Completed(Value),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Suspended,
+    Completed,
+    Cancelled,
+}
+
+/// A body of code with its own call stack that runs only while resumed.
+/// The body runs on a dedicated thread, but the two sides hand control back
+/// and forth so exactly one of them runs at a time; its frames stay intact on
+/// that thread while it is suspended.
+pub struct Coroutine {
+    resume: Option<Sender<()>>,
+    events: Receiver<Event>,
+    status: Status,
+}
+
+/// The body's side of a `Coroutine`
+pub struct Suspender {
+    events: Sender<Event>,
+    resume: Receiver<()>,
+}
+
+impl Suspender {
+    /// Hands `value` to whoever resumed the coroutine and waits to be resumed
+    /// again. Fails with `CANCELLED` once that will never happen, so the body
+    /// unwinds and its frames are released.
+    pub fn suspend(&self, value: Value) -> Result<(), String> {
+        self.events.send(Event::Suspended(value)).map_err(|_| CANCELLED.to_string())?;
+        self.resume.recv().map_err(|_| CANCELLED.to_string())
+    }
+}
+
+impl Coroutine {
+    /// Starts `body` suspended; it first runs when `resume` is called
+    pub fn spawn<F>(body: F) -> Result<Self, String>
+    where
+        F: FnOnce(Suspender) -> Result<Value, String> + Send + 'static,
+    {
+        let (resume_tx, resume_rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        let suspender = Suspender {
+            events: events_tx.clone(),
+            resume: resume_rx,
+        };
+        
+        thread::Builder::new()
+            .name("joel-coroutine".to_string())
+            .stack_size(COROUTINE_STACK_SIZE)
+            .spawn(move || {
+                if suspender.resume.recv().is_err() {
+                    return; // Cancelled before it ever ran
+                }
+                let event = match body(suspender) {
+                    Ok(value) => Event::Completed(value),
+                    Err(e) => Event::Failed(e),
+                };
+                let _ = events_tx.send(event);
+            })
+            .map_err(|e| format!("Cannot start coroutine: {}", e))?;
+        
+        Ok(Self {
+            resume: Some(resume_tx),
+            events: events_rx,
+            status: Status::Suspended,
+        })
+    }
+    
+    /// Runs the body until it suspends or finishes. An error from the body
+    /// completes the coroutine.
+    pub fn resume(&mut self) -> Result<Resumed, String> {
+        self.start()?;
+        let event = self.events.recv().unwrap_or_else(|_| Self::stopped());
+        self.step_ended(event)
+    }
+    
+    /// Like `resume`, but while the body runs calls `help` until it returns
+    /// false, and again every millisecond, so a worker thread resuming a task
+    /// runs other queued jobs instead of blocking them
+    pub fn resume_with(&mut self, mut help: impl FnMut() -> bool) -> Result<Resumed, String> {
+        self.start()?;
+        let event = loop {
+            match self.events.try_recv() {
+                Ok(event) => break event,
+                Err(TryRecvError::Disconnected) => break Self::stopped(),
+                Err(TryRecvError::Empty) => {},
+            }
+            if help() {
+                continue;
+            }
+            match self.events.recv_timeout(Duration::from_millis(1)) {
+                Ok(event) => break event,
+                Err(RecvTimeoutError::Disconnected) => break Self::stopped(),
+                Err(RecvTimeoutError::Timeout) => {},
+            }
+        };
+        self.step_ended(event)
+    }
+    
+    /// Lets a suspended body run
+    fn start(&mut self) -> Result<(), String> {
+        match self.status {
+            Status::Suspended => {},
+            Status::Completed => return Err("Coroutine already completed".to_string()),
+            Status::Cancelled => return Err("Coroutine was cancelled".to_string()),
+        }
+        if self.resume.as_ref().is_none_or(|resume| resume.send(()).is_err()) {
+            self.finish(Status::Completed);
+            return Err("Coroutine stopped unexpectedly".to_string());
+        }
+        Ok(())
+    }
+    
+    fn stopped() -> Event {
+        Event::Failed("Coroutine stopped unexpectedly".to_string())
+    }
+    
+    /// Records how the body stopped running
+    fn step_ended(&mut self, event: Event) -> Result<Resumed, String> {
+        match event {
+            Event::Suspended(value) => Ok(Resumed::Suspended(value)),
+            Event::Completed(value) => {
+                self.finish(Status::Completed);
+                Ok(Resumed::Completed(value))
+            },
+            Event::Failed(e) => {
+                self.finish(Status::Completed);
+                Err(e)
+            },
+        }
+    }
+    
+    /// Stops a suspended coroutine for good. Its body unwinds from the point
+    /// where it is suspended; this waits until it has done so.
+    pub fn cancel(&mut self) {
+        if self.status != Status::Suspended {
+            return;
+        }
+        self.finish(Status::Cancelled);
+        // The body reports its unwinding as a failure, or never ran at all
+        let _ = self.events.recv();
+    }
+    
+    pub fn is_suspended(&self) -> bool {
+        self.status == Status::Suspended
+    }
+    
+    /// Whether the body has finished, failed or been cancelled
+    pub fn is_completed(&self) -> bool {
+        self.status != Status::Suspended
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.status == Status::Cancelled
+    }
+    
+    fn finish(&mut self, status: Status) {
+        self.status = status;
+        // Closing the channel is what wakes a suspended body with `CANCELLED`
+        self.resume = None;
+    }
+}
 
 /// Coroutine runtime for cooperative multitasking
+#[derive(Clone)]
 pub struct CoroutineRuntime {
     coroutines: Arc<Mutex<HashMap<usize, CoroutineState>>>,
     next_id: Arc<Mutex<usize>>,
@@ -16,8 +205,8 @@ impl CoroutineRuntime {
         }
     }
     
-    /// Create a new coroutine
-    pub fn create(&self) -> usize {
+    /// Register a new coroutine, suspended before its first statement
+    pub fn create(&self, coroutine: Coroutine) -> usize {
         let id = {
             let mut next = self.next_id.lock().unwrap();
             *next += 1;
@@ -25,75 +214,65 @@ impl CoroutineRuntime {
         };
         
         let state = CoroutineState {
-            id,
-            stack: Vec::new(),
-            variables: HashMap::new(),
-            suspended: false,
-            completed: false,
+            coroutine: Arc::new(Mutex::new(coroutine)),
         };
         
         self.coroutines.lock().unwrap().insert(id, state);
         id
     }
     
-    /// Suspend a coroutine
-    pub fn suspend(&self, id: usize) -> Result<(), String> {
-        if let Some(state) = self.coroutines.lock().unwrap().get_mut(&id) {
-            state.suspended = true;
-            Ok(())
-        } else {
-            Err(format!("Coroutine {} not found", id))
-        }
-    }
-    
-    /// Resume a coroutine
+    /// Resume a coroutine, returning the value it suspends with or, once it
+    /// finishes, the value of its body
     pub fn resume(&self, id: usize) -> Result<Value, String> {
-        if let Some(state) = self.coroutines.lock().unwrap().get_mut(&id) {
-            if state.completed {
-                return Err(format!("Coroutine {} already completed", id));
-            }
-            state.suspended = false;
-            Ok(Value::None) // Simplified - would return actual value
-        } else {
-            Err(format!("Coroutine {} not found", id))
+        let coroutine = self.get(id)?;
+        // The table stays unlocked while the body runs, so it can use other coroutines
+        let mut coroutine = coroutine.try_lock()
+            .map_err(|_| format!("Coroutine {} is already running", id))?;
+        if coroutine.is_cancelled() {
+            return Err(format!("Coroutine {} was cancelled", id));
+        }
+        if coroutine.is_completed() {
+            return Err(format!("Coroutine {} already completed", id));
+        }
+        match coroutine.resume()? {
+            Resumed::Suspended(value) | Resumed::Completed(value) => Ok(value),
         }
     }
     
     /// Check if coroutine is suspended
     pub fn is_suspended(&self, id: usize) -> bool {
-        if let Some(state) = self.coroutines.lock().unwrap().get(&id) {
-            state.suspended
-        } else {
-            false
-        }
+        self.get(id).ok()
+            .and_then(|coroutine| coroutine.try_lock().ok().map(|coroutine| coroutine.is_suspended()))
+            .unwrap_or(false)
     }
     
     /// Check if coroutine is completed
     pub fn is_completed(&self, id: usize) -> bool {
-        if let Some(state) = self.coroutines.lock().unwrap().get(&id) {
-            state.completed
-        } else {
-            false
+        match self.get(id) {
+            // A running coroutine holds its own lock, and is not completed
+            Ok(coroutine) => coroutine.try_lock().is_ok_and(|coroutine| coroutine.is_completed()),
+            Err(_) => false,
         }
     }
     
-    /// Cancel a coroutine
+    /// Cancel a coroutine, unwinding its body if it is suspended
     pub fn cancel(&self, id: usize) -> Result<(), String> {
-        if let Some(state) = self.coroutines.lock().unwrap().get_mut(&id) {
-            state.completed = true;
-            Ok(())
-        } else {
-            Err(format!("Coroutine {} not found", id))
-        }
+        let coroutine = self.get(id)?;
+        let mut coroutine = coroutine.try_lock()
+            .map_err(|_| format!("Coroutine {} cannot cancel itself while running", id))?;
+        coroutine.cancel();
+        Ok(())
+    }
+    
+    fn get(&self, id: usize) -> Result<Arc<Mutex<Coroutine>>, String> {
+        self.coroutines.lock().unwrap().get(&id)
+            .map(|state| Arc::clone(&state.coroutine))
+            .ok_or_else(|| format!("Coroutine {} not found", id))
     }
 }
 
 /// Coroutine state
 pub struct CoroutineState {
-    pub id: usize,
-    pub stack: Vec<HashMap<String, Value>>,
-    pub variables: HashMap<String, Value>,
-    pub suspended: bool,
-    pub completed: bool,
+    /// The suspended body with its frames, locked while it runs
+    coroutine: Arc<Mutex<Coroutine>>,
 }
-
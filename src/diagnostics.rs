@@ -1,4 +1,111 @@
+use crate::ast::{Allow, Span};
 use crate::lexer::Token;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// How `DiagnosticReporter::print_all` reports diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    /// Text with the source line and a caret
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+/// Chosen once from the command line; human text until then
+static MESSAGE_FORMAT: OnceLock<MessageFormat> = OnceLock::new();
+
+/// Every diagnostic reported so far, kept when a SARIF log was requested
+static SARIF_RESULTS: Mutex<Option<Vec<Diagnostic>>> = Mutex::new(None);
+
+pub fn set_message_format(format: MessageFormat) {
+    let _ = MESSAGE_FORMAT.set(format);
+}
+
+pub fn message_format() -> MessageFormat {
+    MESSAGE_FORMAT.get().copied().unwrap_or(MessageFormat::Human)
+}
+
+/// Prints a progress line. With `--message-format=json` it goes to stderr so
+/// stdout carries only diagnostics and program output.
+pub fn status(message: &str) {
+    match message_format() {
+        MessageFormat::Human => println!("{}", message),
+        MessageFormat::Json => eprintln!("{}", message),
+    }
+}
+
+/// Starts keeping reported diagnostics for `write_sarif`
+pub fn collect_sarif() {
+    *SARIF_RESULTS.lock().unwrap() = Some(Vec::new());
+}
+
+/// Writes the diagnostics reported since `collect_sarif` as a SARIF 2.1.0 log
+pub fn write_sarif(path: &Path) -> Result<(), String> {
+    let results = SARIF_RESULTS.lock().unwrap().clone().unwrap_or_default();
+    let log = serde_json::to_string_pretty(&sarif_log(&results))
+        .map_err(|e| format!("Cannot encode SARIF log: {}", e))?;
+    std::fs::write(path, log).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+/// A SARIF log with one run of the JOEL checkers over `diagnostics`
+fn sarif_log(diagnostics: &[Diagnostic]) -> Value {
+    let results: Vec<Value> = diagnostics.iter().map(|diag| {
+        let mut text = diag.message.clone();
+        for note in &diag.notes {
+            text.push_str(&format!("\n{}", note));
+        }
+        let mut result = json!({
+            "level": match diag.level {
+                DiagnosticLevel::Error => "error",
+                DiagnosticLevel::Warning => "warning",
+                DiagnosticLevel::Info => "note",
+            },
+            "message": { "text": text },
+        });
+        if let Some(code) = &diag.code {
+            result["ruleId"] = json!(code);
+        }
+        if let Some(loc) = &diag.location {
+            result["locations"] = json!([{ "physicalLocation": sarif_location(loc) }]);
+        }
+        if !diag.labels.is_empty() {
+            result["relatedLocations"] = diag.labels.iter().enumerate()
+                .map(|(id, label)| json!({
+                    "id": id,
+                    "message": { "text": label.message },
+                    "physicalLocation": sarif_location(&label.location),
+                }))
+                .collect();
+        }
+        result
+    }).collect();
+    
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "joel",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_location(loc: &SourceLocation) -> Value {
+    let mut location = json!({
+        "region": { "startLine": loc.line, "startColumn": loc.col },
+    });
+    if let Some(file) = &loc.file {
+        location["artifactLocation"] = json!({ "uri": file.replace('\\', "/") });
+    }
+    location
+}
 
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
@@ -6,6 +113,16 @@ pub struct Diagnostic {
     pub message: String,
     pub location: Option<SourceLocation>,
     pub notes: Vec<String>,
+    /// Stable identifier of this kind of diagnostic, if it has one
+    pub code: Option<String>,
+    /// Secondary locations that explain the error, such as where a variable was declared
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub location: SourceLocation,
+    pub message: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,6 +139,17 @@ pub struct SourceLocation {
     pub file: Option<String>,
 }
 
+impl SourceLocation {
+    /// Location of a parsed node; None for nodes without a span
+    pub fn at(span: Span) -> Option<Self> {
+        span.is_known().then_some(Self {
+            line: span.line,
+            col: span.col,
+            file: None,
+        })
+    }
+}
+
 impl Diagnostic {
     pub fn error(message: String, location: Option<SourceLocation>) -> Self {
         Self {
@@ -29,6 +157,8 @@ impl Diagnostic {
             message,
             location,
             notes: Vec::new(),
+            code: None,
+            labels: Vec::new(),
         }
     }
     
@@ -38,14 +168,30 @@ impl Diagnostic {
             message,
             location,
             notes: Vec::new(),
+            code: None,
+            labels: Vec::new(),
         }
     }
     
+    /// Tags the diagnostic with its code from `error_codes`
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+    
     pub fn with_note(mut self, note: String) -> Self {
         self.notes.push(note);
         self
     }
     
+    /// Points at a second place in the source; does nothing for nodes without a span
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        if let Some(location) = SourceLocation::at(span) {
+            self.labels.push(Label { location, message });
+        }
+        self
+    }
+    
     pub fn from_token(level: DiagnosticLevel, message: String, token: &Token) -> Self {
         Self {
             level,
@@ -56,9 +202,64 @@ impl Diagnostic {
                 file: None,
             }),
             notes: Vec::new(),
+            code: None,
+            labels: Vec::new(),
+        }
+    }
+    
+    /// Whether this is a warning that an `#[allow]` covering its line silences.
+    /// Errors cannot be allowed.
+    pub fn is_allowed(&self, allows: &[Allow]) -> bool {
+        match (&self.level, &self.code, &self.location) {
+            (DiagnosticLevel::Warning, Some(code), Some(loc)) => allows.iter()
+                .any(|allow| allow.lines.contains(&loc.line) && allow.codes.contains(code)),
+            _ => false,
+        }
+    }
+    
+    /// The first line of the human format, e.g. `❌ Error[E0204]: message (line 3, col 5)`,
+    /// with `prefix` naming the level
+    pub fn headline(&self, prefix: &str) -> String {
+        let code = self.code.as_ref().map(|code| format!("[{}]", code)).unwrap_or_default();
+        match &self.location {
+            Some(loc) => format!("{}{}: {} (line {}, col {})", prefix, code, self.message, loc.line, loc.col),
+            None => format!("{}{}: {}", prefix, code, self.message),
         }
     }
     
+    /// Fills in the file of the locations that do not name one
+    pub fn in_file(mut self, file: &str) -> Self {
+        let labels = self.labels.iter_mut().map(|label| &mut label.location);
+        for loc in self.location.iter_mut().chain(labels) {
+            loc.file.get_or_insert_with(|| file.to_string());
+        }
+        self
+    }
+    
+    /// One JSON object for `--message-format=json`
+    pub fn to_json(&self) -> Value {
+        let loc = self.location.as_ref();
+        json!({
+            "level": match self.level {
+                DiagnosticLevel::Error => "error",
+                DiagnosticLevel::Warning => "warning",
+                DiagnosticLevel::Info => "info",
+            },
+            "message": self.message,
+            "file": loc.and_then(|loc| loc.file.as_ref()),
+            "line": loc.map(|loc| loc.line),
+            "col": loc.map(|loc| loc.col),
+            "notes": self.notes,
+            "code": self.code,
+            "labels": self.labels.iter().map(|label| json!({
+                "message": label.message,
+                "file": label.location.file,
+                "line": label.location.line,
+                "col": label.location.col,
+            })).collect::<Vec<_>>(),
+        })
+    }
+    
     pub fn format(&self, source_lines: &[String]) -> String {
         let mut output = String::new();
         
@@ -68,24 +269,26 @@ impl Diagnostic {
             DiagnosticLevel::Info => "ℹ️  Info",
         };
         
+        // Line numbers of the primary location and the labels share one gutter
+        let gutter = self.location.iter()
+            .chain(self.labels.iter().map(|label| &label.location))
+            .map(|loc| loc.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        
+        output.push_str(&self.headline(prefix));
+        output.push('\n');
+        
+        // Show source line with caret
         if let Some(ref loc) = self.location {
-            output.push_str(&format!("{}: {} (line {}, col {})\n", 
-                prefix, self.message, loc.line, loc.col));
-            
-            // Show source line with caret
-            if loc.line > 0 && loc.line <= source_lines.len() {
-                let line_idx = loc.line - 1;
-                let line = &source_lines[line_idx];
-                output.push_str(&format!("  {} | {}\n", loc.line, line));
-                
-                // Show caret pointing to the error
-                if loc.col > 0 {
-                    let spaces = " ".repeat(format!("{}", loc.line).len() + 3 + (loc.col - 1).min(line.len()));
-                    output.push_str(&format!("  {} | {}^\n", " ".repeat(format!("{}", loc.line).len()), spaces));
-                }
-            }
-        } else {
-            output.push_str(&format!("{}: {}\n", prefix, self.message));
+            output.push_str(&Self::snippet(loc, "^", source_lines, gutter, true));
+        }
+        
+        for label in &self.labels {
+            // A label on the primary line goes under the line already shown
+            let same_line = self.location.as_ref().is_some_and(|loc| loc.line == label.location.line);
+            let marker = format!("- {}", label.message);
+            output.push_str(&Self::snippet(&label.location, &marker, source_lines, gutter, !same_line));
         }
         
         for note in &self.notes {
@@ -94,11 +297,33 @@ impl Diagnostic {
         
         output
     }
+    
+    /// The source line at `loc`, unless `show_line` is false, with `marker` under its column
+    fn snippet(loc: &SourceLocation, marker: &str, source_lines: &[String], gutter: usize, show_line: bool) -> String {
+        let mut output = String::new();
+        if loc.line > 0 && loc.line <= source_lines.len() {
+            let line = &source_lines[loc.line - 1];
+            if show_line {
+                output.push_str(&format!("  {:>width$} | {}\n", loc.line, line, width = gutter));
+            }
+            
+            // Show the marker under the reported column
+            if loc.col > 0 {
+                let spaces = " ".repeat((loc.col - 1).min(line.len()));
+                output.push_str(&format!("  {} | {}{}\n", " ".repeat(gutter), spaces, marker));
+            }
+        }
+        output
+    }
 }
 
 pub struct DiagnosticReporter {
     diagnostics: Vec<Diagnostic>,
     source_lines: Vec<String>,
+    /// File the source was read from, as shown in JSON and SARIF output
+    file: Option<String>,
+    /// `#[allow]` comments of the source, whose warnings are not reported
+    allows: Vec<Allow>,
 }
 
 impl DiagnosticReporter {
@@ -106,51 +331,95 @@ impl DiagnosticReporter {
         Self {
             diagnostics: Vec::new(),
             source_lines: source.lines().map(|s| s.to_string()).collect(),
+            file: None,
+            allows: Vec::new(),
         }
     }
     
-    pub fn error(&mut self, message: String, location: Option<SourceLocation>) {
-        self.diagnostics.push(Diagnostic::error(message, location));
+    /// Names the file the source was read from, relative to the working directory
+    /// when it lies inside it
+    pub fn with_file(mut self, path: &Path) -> Self {
+        let relative = std::env::current_dir().ok()
+            .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+        self.file = Some(relative.as_deref().unwrap_or(path).display().to_string());
+        self
     }
     
-    pub fn warning(&mut self, message: String, location: Option<SourceLocation>) {
-        self.diagnostics.push(Diagnostic::warning(message, location));
+    /// Silences the warnings named by the `#[allow]` comments of the source
+    pub fn allow(&mut self, allows: &[Allow]) {
+        self.allows = allows.to_vec();
     }
     
-    pub fn error_from_token(&mut self, message: String, token: &Token) {
-        self.diagnostics.push(Diagnostic::from_token(
-            DiagnosticLevel::Error,
-            message,
-            token,
-        ));
+    pub fn error(&mut self, code: &str, message: String, location: Option<SourceLocation>) {
+        self.diagnostics.push(Diagnostic::error(message, location).with_code(code));
     }
     
-    pub fn warning_from_token(&mut self, message: String, token: &Token) {
-        self.diagnostics.push(Diagnostic::from_token(
-            DiagnosticLevel::Warning,
-            message,
-            token,
-        ));
+    pub fn warning(&mut self, code: &str, message: String, location: Option<SourceLocation>) {
+        self.diagnostics.push(Diagnostic::warning(message, location).with_code(code));
+    }
+    
+    /// Adds a diagnostic built by the caller, e.g. one with labels
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+    
+    /// Adds diagnostics collected by another phase, e.g. the lexer or parser
+    pub fn extend(&mut self, diagnostics: &[Diagnostic]) {
+        self.diagnostics.extend_from_slice(diagnostics);
     }
     
     pub fn has_errors(&self) -> bool {
         self.diagnostics.iter().any(|d| d.level == DiagnosticLevel::Error)
     }
     
+    /// Whether anything would be printed, once `#[allow]`ed warnings are left out
+    pub fn is_empty(&self) -> bool {
+        self.reported().next().is_none()
+    }
+    
+    /// Prints every diagnostic in the chosen message format, keeping them for the
+    /// SARIF log if one was requested
     pub fn print_all(&self) {
-        for diag in &self.diagnostics {
-            print!("{}", diag.format(&self.source_lines));
+        let diagnostics: Vec<Diagnostic> = self.reported()
+            .map(|diag| match &self.file {
+                Some(file) => diag.clone().in_file(file),
+                None => diag.clone(),
+            })
+            .collect();
+        
+        for diag in &diagnostics {
+            match message_format() {
+                MessageFormat::Human => print!("{}", diag.format(&self.source_lines)),
+                MessageFormat::Json => println!("{}", diag.to_json()),
+            }
+        }
+        
+        // Point at `joel explain` once, for the first error that has a code
+        let first_code = diagnostics.iter()
+            .find(|diag| diag.level == DiagnosticLevel::Error)
+            .and_then(|diag| diag.code.as_ref());
+        if let (MessageFormat::Human, Some(code)) = (message_format(), first_code) {
+            println!("💡 For more information about an error, try 'joel explain {}'", code);
+        }
+        
+        if let Some(results) = SARIF_RESULTS.lock().unwrap().as_mut() {
+            results.extend(diagnostics);
         }
     }
     
+    /// Diagnostics that are not silenced by an `#[allow]`
+    fn reported(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diag| !diag.is_allowed(&self.allows))
+    }
+    
     pub fn get_errors(&self) -> Vec<&Diagnostic> {
-        self.diagnostics.iter()
+        self.reported()
             .filter(|d| d.level == DiagnosticLevel::Error)
             .collect()
     }
     
     pub fn get_warnings(&self) -> Vec<&Diagnostic> {
-        self.diagnostics.iter()
+        self.reported()
             .filter(|d| d.level == DiagnosticLevel::Warning)
             .collect()
     }
@@ -0,0 +1,793 @@
+// Stable codes for JOEL diagnostics
+//
+// Every kind of diagnostic the lexer, parser and checkers report carries one of
+// these codes. Codes never change meaning once published, so they can be grepped
+// in logs, named in `#[allow(...)]` comments and looked up with `joel explain`.
+// `E` codes are errors and `W` codes are warnings; the digits after the letter
+// name the phase: 00 lexer, 01 parser, 02 type checker, 03 borrow checker,
+// 04 exhaustiveness checker, 05 module loader.
+
+// Lexer
+pub const UNEXPECTED_CHARACTER: &str = "E0001";
+pub const UNTERMINATED_STRING: &str = "E0002";
+pub const INVALID_ALLOW: &str = "W0001";
+
+// Parser
+pub const SYNTAX_ERROR: &str = "E0100";
+pub const DUPLICATE_MEMBER: &str = "E0101";
+pub const JUMP_OUTSIDE_LOOP: &str = "E0102";
+pub const YIELD_OUTSIDE_FUNCTION: &str = "E0103";
+pub const INVALID_ACTOR_OPERAND: &str = "E0104";
+pub const INVALID_ASSIGNMENT_TARGET: &str = "E0105";
+pub const SHADOWED_ACCUMULATOR: &str = "E0106";
+pub const UNSUPPORTED_SYNTAX: &str = "W0101";
+
+// Type checker
+pub const TYPE_MISMATCH: &str = "E0200";
+pub const RETURN_TYPE_MISMATCH: &str = "E0201";
+pub const ARGUMENT_TYPE_MISMATCH: &str = "E0202";
+pub const ARGUMENT_COUNT: &str = "E0203";
+pub const UNDEFINED_VARIABLE: &str = "E0204";
+pub const UNDEFINED_FUNCTION: &str = "E0205";
+pub const UNKNOWN_TYPE: &str = "E0206";
+pub const UNKNOWN_DECLARATION: &str = "E0207";
+pub const NO_SUCH_MEMBER: &str = "E0208";
+pub const MISSING_FIELD: &str = "E0209";
+pub const NON_BOOL_CONDITION: &str = "E0210";
+pub const INVALID_OPERANDS: &str = "E0211";
+pub const NOT_CALLABLE: &str = "E0212";
+pub const INVALID_INDEX: &str = "E0213";
+pub const NOT_ITERABLE: &str = "E0214";
+pub const MISSING_ANNOTATION: &str = "E0215";
+pub const PATTERN_MISMATCH: &str = "E0216";
+pub const INFERRED_ANY: &str = "W0201";
+pub const BRANCH_TYPES_DIFFER: &str = "W0202";
+pub const MIXED_LIST: &str = "W0203";
+
+// Borrow checker
+pub const USE_AFTER_MOVE: &str = "E0301";
+pub const ASSIGN_WHILE_BORROWED: &str = "E0302";
+
+// Exhaustiveness checker
+pub const NON_EXHAUSTIVE_MATCH: &str = "E0401";
+
+// Module loader
+pub const MODULE_NOT_FOUND: &str = "E0501";
+pub const IMPORT_CYCLE: &str = "E0502";
+pub const UNREADABLE_MODULE: &str = "E0503";
+pub const INVALID_MODULE: &str = "E0504";
+pub const UNRESOLVED_IMPORT: &str = "W0501";
+
+/// A diagnostic code with the text `joel explain` prints for it
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+/// Looks up a code, ignoring case: `e0204` finds `E0204`
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    CODES.iter().find(|entry| entry.code.eq_ignore_ascii_case(code))
+}
+
+/// Whether `#[allow]` may name `code`: warnings, plus non-exhaustive matches,
+/// which `[Interpreted]` files report as warnings
+pub fn can_allow(code: &str) -> bool {
+    code.starts_with('W') || code == NON_EXHAUSTIVE_MATCH
+}
+
+/// Prints the long-form explanation of `code` for `joel explain`
+pub fn explain(code: &str) -> Result<(), String> {
+    let entry = lookup(code)
+        .ok_or_else(|| format!("Unknown error code '{}'", code))?;
+    println!("{}: {}\n", entry.code, entry.title);
+    println!("{}", entry.explanation.trim());
+    Ok(())
+}
+
+pub static CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: UNEXPECTED_CHARACTER,
+        title: "Unexpected character",
+        explanation: r#"
+The source contains a character that does not start any JOEL token.
+
+Erroneous code example:
+
+    let total = 3 @ 4
+
+A single `|` is also rejected, because JOEL only has the logical operator `||`:
+
+    if ready | done { print("go") }
+
+Remove the character, or use the operator that was meant:
+
+    if ready || done { print("go") }
+"#,
+    },
+    ErrorCode {
+        code: UNTERMINATED_STRING,
+        title: "Unterminated string literal",
+        explanation: r#"
+A string literal was opened with `"` but the file ended before the closing quote.
+
+Erroneous code example:
+
+    print("hello)
+
+Close the string on the same line it was opened:
+
+    print("hello")
+"#,
+    },
+    ErrorCode {
+        code: INVALID_ALLOW,
+        title: "Invalid #[allow] comment",
+        explanation: r#"
+An `#[allow(...)]` comment is malformed, names a code that does not exist, or
+names an error. The comment, or that code in it, has no effect.
+
+Erroneous code examples:
+
+    #[allow(W9999)]
+    #[allow W0201]
+    #[allow(E0200)]
+
+List existing codes, separated by commas, inside the parentheses:
+
+    #[allow(W0201, W0203)]
+    let items = [1, "two"]
+
+Only warnings can be allowed, and E0401 where `[Interpreted]` files report it
+as a warning. `#![allow(...)]` with a `!` silences the codes in the whole file
+instead of the item that follows.
+"#,
+    },
+    ErrorCode {
+        code: SYNTAX_ERROR,
+        title: "Syntax error",
+        explanation: r#"
+The parser found a token where the grammar does not allow it, such as a missing
+closing bracket or an operator with no right-hand side. The message names what was
+expected and what was found.
+
+Erroneous code examples:
+
+    let x = (1 + 2
+    fn add(a: i32 b: i32) -> i32 { return a + b }
+
+Add or remove the tokens the message points at:
+
+    let x = (1 + 2)
+    fn add(a: i32, b: i32) -> i32 { return a + b }
+"#,
+    },
+    ErrorCode {
+        code: DUPLICATE_MEMBER,
+        title: "Duplicate struct field or enum variant",
+        explanation: r#"
+A struct declares the same field twice, or an enum the same variant twice.
+
+Erroneous code example:
+
+    struct Point { x: f64, x: f64 }
+
+Give every field and variant a distinct name:
+
+    struct Point { x: f64, y: f64 }
+"#,
+    },
+    ErrorCode {
+        code: JUMP_OUTSIDE_LOOP,
+        title: "`break` or `continue` outside of a loop",
+        explanation: r#"
+`break` and `continue` only make sense inside the body of a `while` or `for` loop.
+
+Erroneous code example:
+
+    fn stop() {
+        break
+    }
+
+Use `return` to leave a function early, or move the statement into a loop:
+
+    for item in items {
+        if item == 0 { break }
+    }
+"#,
+    },
+    ErrorCode {
+        code: YIELD_OUTSIDE_FUNCTION,
+        title: "`yield` outside of a function",
+        explanation: r#"
+`yield` turns the function it appears in into a generator, so it cannot be used
+at the top level of a file.
+
+Erroneous code example:
+
+    yield 1
+
+Put the `yield` inside a function and iterate over what it returns:
+
+    fn numbers() {
+        yield 1
+        yield 2
+    }
+
+    for n in numbers() { print(n) }
+"#,
+    },
+    ErrorCode {
+        code: INVALID_ACTOR_OPERAND,
+        title: "Invalid operand of `spawn` or `send`",
+        explanation: r#"
+`spawn` must be followed by an actor literal, and `send` by a method call on an
+actor.
+
+Erroneous code examples:
+
+    let c = spawn Counter
+    send c
+
+Write the literal with braces and name the method to call:
+
+    let c = spawn Counter{}
+    send c.inc()
+"#,
+    },
+    ErrorCode {
+        code: INVALID_ASSIGNMENT_TARGET,
+        title: "Invalid assignment target",
+        explanation: r#"
+Only variables, fields and indexed elements can appear on the left of `=` or a
+compound assignment such as `+=`.
+
+Erroneous code example:
+
+    1 + 2 = x
+    get_count() += 1
+
+Assign to a variable, a field or an element instead:
+
+    count += 1
+    point.x = 2.0
+    items[0] = 5
+"#,
+    },
+    ErrorCode {
+        code: SHADOWED_ACCUMULATOR,
+        title: "Loop variable shadows the accumulator of `parallel reduce`",
+        explanation: r#"
+In `parallel reduce acc = initial, item in items { ... }` the body combines `acc`
+with `item`. Giving both the same name would hide the accumulator.
+
+Erroneous code example:
+
+    let total = parallel reduce sum = 0, sum in numbers { sum + sum }
+
+Use different names:
+
+    let total = parallel reduce sum = 0, n in numbers { sum + n }
+"#,
+    },
+    ErrorCode {
+        code: UNSUPPORTED_SYNTAX,
+        title: "Unsupported syntax skipped in a block body",
+        explanation: r#"
+A line inside a `component`, `flow`, `deployment` or `cluster` body is not a
+statement JOEL can parse yet. The line is skipped and the rest of the body is
+still used.
+
+Example:
+
+    deployment "inference-api" {
+      image "ghcr.io/you/serve:1.2.3"
+      ports 8080
+    }
+
+Rewrite the line as a statement, or allow the warning on a body whose lines are
+meant for another tool:
+
+    #[allow(W0101)]
+    deployment "inference-api" {
+      image "ghcr.io/you/serve:1.2.3"
+    }
+"#,
+    },
+    ErrorCode {
+        code: TYPE_MISMATCH,
+        title: "Mismatched types",
+        explanation: r#"
+A value does not have the type its destination requires: the annotation of a
+`let` or `const`, the declared type of an assigned variable, a struct field or
+actor state field, an enum variant's payload, or a map's key type.
+
+Erroneous code examples:
+
+    let total: i64 = "many"
+    let count = 1
+    count = "two"
+
+Convert the value, or change the annotation to match it:
+
+    let total: i64 = 100
+    let label: str = "many"
+"#,
+    },
+    ErrorCode {
+        code: RETURN_TYPE_MISMATCH,
+        title: "Returned value does not match the return type",
+        explanation: r#"
+A `return` statement produces a value of a different type than the function's
+declared return type.
+
+Erroneous code example:
+
+    fn name() -> str {
+        return 42
+    }
+
+Return a value of the declared type, or change the declaration:
+
+    fn name() -> str {
+        return "answer"
+    }
+"#,
+    },
+    ErrorCode {
+        code: ARGUMENT_TYPE_MISMATCH,
+        title: "Argument has the wrong type",
+        explanation: r#"
+An argument passed to a function or built-in does not have the parameter's type.
+
+Erroneous code example:
+
+    fn add(a: i64, b: i64) -> i64 {
+        return a + b
+    }
+
+    print(add(1, "2"))
+
+Pass a value of the parameter's type:
+
+    print(add(1, 2))
+"#,
+    },
+    ErrorCode {
+        code: ARGUMENT_COUNT,
+        title: "Wrong number of arguments or values",
+        explanation: r#"
+A function, built-in or enum variant was given more or fewer values than it takes.
+
+Erroneous code examples:
+
+    fn add(a: i64, b: i64) -> i64 { return a + b }
+
+    add(1)
+    sleep()
+
+Pass exactly the values the declaration lists:
+
+    add(1, 2)
+    sleep(100)
+"#,
+    },
+    ErrorCode {
+        code: UNDEFINED_VARIABLE,
+        title: "Undefined variable",
+        explanation: r#"
+A name is read or assigned before any `let`, `const`, parameter or pattern
+binding introduces it in a visible scope.
+
+Erroneous code example:
+
+    print(undefined_thing)
+    total = 5
+
+Declare the variable first:
+
+    let total = 0
+    total = 5
+"#,
+    },
+    ErrorCode {
+        code: UNDEFINED_FUNCTION,
+        title: "Unknown function",
+        explanation: r#"
+A call names a function that is neither declared in the file, imported, nor a
+built-in.
+
+Erroneous code example:
+
+    let n = lenght([1, 2, 3])
+
+Check the spelling, or declare or import the function:
+
+    let n = len([1, 2, 3])
+"#,
+    },
+    ErrorCode {
+        code: UNKNOWN_TYPE,
+        title: "Unknown type",
+        explanation: r#"
+A type annotation names a type that is neither built in (`i32`, `i64`, `f64`,
+`str`, `bool`, `list[T]`, `map[K, V]`, ...) nor a declared struct or enum.
+
+Erroneous code example:
+
+    let x: integer = 5
+
+Use a known type name:
+
+    let x: i32 = 5
+"#,
+    },
+    ErrorCode {
+        code: UNKNOWN_DECLARATION,
+        title: "Unknown struct, enum, variant or actor",
+        explanation: r#"
+A literal, pattern or `spawn` refers to a struct, enum, enum variant or actor that
+is not declared.
+
+Erroneous code example:
+
+    enum Shape { Circle(f64), Square(f64) }
+
+    let s = Shape::Triangle(1.0)
+
+Declare the missing item, or use one that exists:
+
+    let s = Shape::Square(1.0)
+"#,
+    },
+    ErrorCode {
+        code: NO_SUCH_MEMBER,
+        title: "No such field or member",
+        explanation: r#"
+A field access or literal names a field the struct or actor does not declare, a
+member is read from a value that has no members, or an imported module does not
+export the name.
+
+Erroneous code example:
+
+    struct Point { x: f64, y: f64 }
+
+    let p = Point { x: 1.0, y: 2.0 }
+    print(p.z)
+
+Use a declared field, or add it to the declaration:
+
+    print(p.x)
+"#,
+    },
+    ErrorCode {
+        code: MISSING_FIELD,
+        title: "Missing field in struct literal",
+        explanation: r#"
+A struct literal must give a value for every field of the struct.
+
+Erroneous code example:
+
+    struct Point { x: f64, y: f64 }
+
+    let p = Point { x: 1.0 }
+
+Provide all fields:
+
+    let p = Point { x: 1.0, y: 0.0 }
+"#,
+    },
+    ErrorCode {
+        code: NON_BOOL_CONDITION,
+        title: "Condition is not a bool",
+        explanation: r#"
+The condition of `if` and `while` must be a `bool`. Numbers and strings are not
+converted implicitly.
+
+Erroneous code example:
+
+    let count = 3
+    if count { print("some") }
+
+Compare explicitly:
+
+    if count > 0 { print("some") }
+"#,
+    },
+    ErrorCode {
+        code: INVALID_OPERANDS,
+        title: "Operator cannot be applied to these types",
+        explanation: r#"
+An arithmetic, comparison, logical or unary operator was used with operand types
+it does not support, such as subtracting a bool from a string or negating a string.
+`as` only converts between numeric types.
+
+Erroneous code examples:
+
+    let s = "a" - true
+    let ok = !5
+    let n = "7" as i32
+
+Use operands of matching types:
+
+    let s = "a" + "b"
+    let ok = !(5 > 3)
+    let n = 7.5 as i32
+"#,
+    },
+    ErrorCode {
+        code: NOT_CALLABLE,
+        title: "Value is not callable",
+        explanation: r#"
+Something that is not a function or lambda was called.
+
+Erroneous code example:
+
+    let count = 3
+    count()
+
+Call a function, or drop the parentheses to read the value:
+
+    print(count)
+"#,
+    },
+    ErrorCode {
+        code: INVALID_INDEX,
+        title: "Invalid indexing",
+        explanation: r#"
+Indexing with `[]` needs a list indexed by an integer, or a map indexed by its key
+type.
+
+Erroneous code examples:
+
+    let items = [1, 2, 3]
+    print(items["first"])
+
+    let n = 5
+    print(n[0])
+
+Index lists with integers:
+
+    print(items[0])
+"#,
+    },
+    ErrorCode {
+        code: NOT_ITERABLE,
+        title: "Value cannot be iterated",
+        explanation: r#"
+`for` loops need a list or an iterator, and `parallel for`, `parallel map` and
+`parallel reduce` need a list.
+
+Erroneous code example:
+
+    for c in 42 { print(c) }
+
+Iterate over a list or a range:
+
+    for i in range(0, 42) { print(i) }
+"#,
+    },
+    ErrorCode {
+        code: MISSING_ANNOTATION,
+        title: "Constant needs a type annotation",
+        explanation: r#"
+In `[Compiled]` files a `const` must state its type.
+
+Erroneous code example:
+
+    const LIMIT = 10
+
+Annotate the constant:
+
+    const LIMIT: i32 = 10
+"#,
+    },
+    ErrorCode {
+        code: PATTERN_MISMATCH,
+        title: "Pattern does not fit the matched value",
+        explanation: r#"
+A `match` arm's pattern cannot match the type of the value being matched, or binds
+a different number of payload values than the variant carries.
+
+Erroneous code example:
+
+    enum Shape { Circle(f64), Rect(f64, f64) }
+
+    match shape {
+        Shape::Circle(r) => print(r),
+        Shape::Rect(w) => print(w)
+    }
+
+Bind every payload value:
+
+    match shape {
+        Shape::Circle(r) => print(r),
+        Shape::Rect(w, h) => print(w * h)
+    }
+"#,
+    },
+    ErrorCode {
+        code: INFERRED_ANY,
+        title: "Variable type could not be inferred",
+        explanation: r#"
+The type of a `let` could not be inferred from its value, so the variable is
+treated as `Any` and later uses of it are not checked.
+
+Example:
+
+    let handler = lookup_handler()
+
+Annotate the variable to keep it checked:
+
+    let handler: str = lookup_handler()
+"#,
+    },
+    ErrorCode {
+        code: BRANCH_TYPES_DIFFER,
+        title: "If/else branches return different types",
+        explanation: r#"
+The `if` and `else` branches end in values of different types, so the type of the
+whole expression is unclear.
+
+Example:
+
+    if ready { 1 } else { "no" }
+
+Make both branches produce the same type:
+
+    if ready { 1 } else { 0 }
+"#,
+    },
+    ErrorCode {
+        code: MIXED_LIST,
+        title: "List contains mixed types",
+        explanation: r#"
+A list literal holds elements of different types. Its element type is taken from
+the first element, so later elements are not checked against it.
+
+Example:
+
+    let items = [1, "two", 3]
+
+Keep elements of one type, or allow the warning where mixing is intended:
+
+    #[allow(W0203)]
+    let row = [1, "two", 3]
+"#,
+    },
+    ErrorCode {
+        code: USE_AFTER_MOVE,
+        title: "Use of moved value",
+        explanation: r#"
+Lists, maps and other owned values move when they are passed to a function or
+assigned to another variable. The old variable cannot be used afterwards.
+
+Erroneous code example:
+
+    let xs = [1, 2, 3]
+    consume(xs)
+    let ys = xs
+
+Use the value before moving it, or give the variable a new value first:
+
+    let xs = [1, 2, 3]
+    let ys = xs
+    consume(ys)
+"#,
+    },
+    ErrorCode {
+        code: ASSIGN_WHILE_BORROWED,
+        title: "Assignment to a borrowed value",
+        explanation: r#"
+A variable was assigned while a borrow of it is still live, which would change
+the value under the reference.
+
+JOEL has no syntax that creates borrows yet, so this error is not reported today.
+The code is reserved so that it stays stable once borrows are added.
+"#,
+    },
+    ErrorCode {
+        code: NON_EXHAUSTIVE_MATCH,
+        title: "Non-exhaustive match",
+        explanation: r#"
+A `match` does not cover every possible value. The message lists example values
+that no arm matches. In `[Compiled]` files this is an error; in `[Interpreted]`
+files it is a warning printed before the program runs, which `#[allow(E0401)]`
+silences.
+
+Erroneous code example:
+
+    enum Light { Red, Yellow, Green }
+
+    match light {
+        Light::Red => print("stop"),
+        Light::Green => print("go")
+    }
+
+Add the missing arms, or a catch-all `_` arm:
+
+    match light {
+        Light::Red => print("stop"),
+        Light::Green => print("go"),
+        _ => print("wait")
+    }
+"#,
+    },
+    ErrorCode {
+        code: MODULE_NOT_FOUND,
+        title: "Imported file not found",
+        explanation: r#"
+An `import` names a file path that does not exist next to the importing file or
+in any directory of the `JOEL_PATH` search path.
+
+Erroneous code example:
+
+    import "lib/utlis.joel"
+
+Fix the path, or add the directory that contains the file to `JOEL_PATH`:
+
+    import "lib/utils.joel"
+
+A bare module name that matches no file is only a warning (W0501), because it
+may name a platform module.
+"#,
+    },
+    ErrorCode {
+        code: IMPORT_CYCLE,
+        title: "Import cycle",
+        explanation: r#"
+A file imports a module that, directly or through other modules, imports the
+file back. Modules run once, before the files that import them, so a cycle has
+no order to run in. The message lists the whole chain.
+
+Erroneous code example:
+
+    # a.joel
+    import b
+
+    # b.joel
+    import a
+
+Move the declarations both files need into a third module that imports neither.
+"#,
+    },
+    ErrorCode {
+        code: UNREADABLE_MODULE,
+        title: "Imported file cannot be read",
+        explanation: r#"
+The file an `import` resolves to exists but could not be read, for example
+because of its permissions or because it is not valid UTF-8. The message
+includes the operating system's reason.
+
+Make the file readable, or save it as UTF-8 text.
+"#,
+    },
+    ErrorCode {
+        code: INVALID_MODULE,
+        title: "Imported module has syntax errors",
+        explanation: r#"
+The file an `import` resolves to could not be parsed. Its own syntax errors are
+reported first, against the imported file; this error points at the import that
+pulled it in.
+
+Fix the syntax errors in the imported file.
+"#,
+    },
+    ErrorCode {
+        code: UNRESOLVED_IMPORT,
+        title: "Module not found",
+        explanation: r#"
+An `import` names a bare module that is neither a standard library module nor a
+`.joel` file next to the importing file or on the `JOEL_PATH` search path. The
+import has no effect, so none of the module's names are defined.
+
+Erroneous code example:
+
+    import utlis
+
+Fix the name, or add the directory that contains `utils.joel` to `JOEL_PATH`.
+Imports of platform modules such as `ui` are expected to warn until those
+modules exist; `#[allow(W0501)]` on the import silences the warning.
+"#,
+    },
+];
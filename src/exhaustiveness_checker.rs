@@ -1,29 +1,209 @@
 use crate::ast::*;
+use crate::diagnostics::{Diagnostic, SourceLocation};
+use crate::error_codes;
+use std::collections::HashMap;
+
+/// Variants of each declared enum, with the number of payload values they carry
+pub type EnumVariants = HashMap<String, Vec<(String, usize)>>;
+
+/// Upper bound on the missing patterns reported for one match
+const MAX_WITNESSES: usize = 8;
+
+/// Stands in for sub-patterns a catch-all arm leaves unconstrained
+static WILDCARD: Pattern = Pattern::Wildcard;
 
 /// Exhaustiveness checker for pattern matching
 pub struct ExhaustivenessChecker;
 
+/// A way of building a value that a pattern column is split on
+enum Constructor {
+    Variant { enum_name: String, variant: String, arity: usize },
+    Boolean(bool),
+    Tuple(usize),
+    Struct { name: String, fields: Vec<String> },
+}
+
+impl Constructor {
+    fn arity(&self) -> usize {
+        match self {
+            Constructor::Variant { arity, .. } => *arity,
+            Constructor::Boolean(_) => 0,
+            Constructor::Tuple(arity) => *arity,
+            Constructor::Struct { fields, .. } => fields.len(),
+        }
+    }
+    
+    /// Renders the constructor applied to already rendered sub-patterns
+    fn render(&self, args: &[String]) -> String {
+        match self {
+            Constructor::Variant { enum_name, variant, .. } if args.is_empty() => format!("{}::{}", enum_name, variant),
+            Constructor::Variant { enum_name, variant, .. } => format!("{}::{}({})", enum_name, variant, args.join(", ")),
+            Constructor::Boolean(b) => b.to_string(),
+            Constructor::Tuple(_) => format!("({})", args.join(", ")),
+            Constructor::Struct { name, fields } => {
+                let pairs: Vec<String> = fields.iter().zip(args)
+                    .map(|(field, arg)| format!("{}: {}", field, arg))
+                    .collect();
+                format!("{} {{ {} }}", name, pairs.join(", "))
+            },
+        }
+    }
+}
+
 impl ExhaustivenessChecker {
     /// Check if a match expression is exhaustive
-    pub fn check_exhaustive(_expr: &Expr, arms: &[MatchArm]) -> Result<(), String> {
-        // Simplified exhaustiveness checking
-        // In a full implementation, this would:
-        // 1. Analyze the type of the matched expression
-        // 2. Check if all possible values are covered
-        // 3. Handle nested patterns
-        // 4. Handle wildcards and guards
-        
-        // For now, just check if there's a wildcard pattern
-        let has_wildcard = arms.iter().any(|arm| {
-            matches!(arm.pattern, Pattern::Wildcard)
-        });
-        
-        if !has_wildcard {
-            // Check if all enum variants are covered (simplified)
-            // This would need type information in a full implementation
+    pub fn check_exhaustive(expr: &Expr, arms: &[MatchArm], enums: &EnumVariants) -> Result<(), String> {
+        let missing = Self::get_uncovered_patterns(expr, arms, enums);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Non-exhaustive match, missing: {}", missing.join(", ")))
         }
+    }
+    
+    /// Checks every match in a program against the enums it declares, returning
+    /// a warning for each match that is not exhaustive and not `#[allow]`ed
+    pub fn check_program(program: &Program) -> Vec<Diagnostic> {
+        let mut enums = EnumVariants::new();
+        Self::collect_enums(&program.statements, &mut enums);
         
-        Ok(())
+        let mut messages = Vec::new();
+        Self::check_statements(&program.statements, &enums, &mut messages);
+        messages.retain(|warning| !warning.is_allowed(&program.allows));
+        messages
+    }
+    
+    /// Records the enums declared in `statements`, including nested declarations
+    pub fn collect_enums(statements: &[Stmt], enums: &mut EnumVariants) {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Enum { name, variants } => {
+                    let variants = variants.iter()
+                        .map(|(variant, payload)| (variant.clone(), payload.len()))
+                        .collect();
+                    enums.insert(name.clone(), variants);
+                },
+                StmtKind::If { then_branch, else_branch, .. } => {
+                    Self::collect_enums(then_branch, enums);
+                    if let Some(else_branch) = else_branch {
+                        Self::collect_enums(else_branch, enums);
+                    }
+                },
+                StmtKind::Block(body) | StmtKind::While { body, .. } | StmtKind::For { body, .. } |
+                StmtKind::Fn { body, .. } | StmtKind::AsyncFn { body, .. } | StmtKind::CoroutineFn { body, .. } => {
+                    Self::collect_enums(body, enums);
+                },
+                StmtKind::Export(declaration) => Self::collect_enums(std::slice::from_ref(declaration.as_ref()), enums),
+                _ => {},
+            }
+        }
+    }
+    
+    fn check_statements(statements: &[Stmt], enums: &EnumVariants, messages: &mut Vec<Diagnostic>) {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Let { value, .. } | StmtKind::Const { value, .. } |
+                StmtKind::Expr(value) | StmtKind::Print(value) | StmtKind::Return(Some(value)) => {
+                    Self::check_expression(value, enums, messages);
+                },
+                StmtKind::If { condition, then_branch, else_branch } => {
+                    Self::check_expression(condition, enums, messages);
+                    Self::check_statements(then_branch, enums, messages);
+                    if let Some(else_branch) = else_branch {
+                        Self::check_statements(else_branch, enums, messages);
+                    }
+                },
+                StmtKind::While { condition: iterable, body } | StmtKind::For { iterable, body, .. } |
+                StmtKind::ParallelFor { iterable, body, .. } => {
+                    Self::check_expression(iterable, enums, messages);
+                    Self::check_statements(body, enums, messages);
+                },
+                StmtKind::Block(body) | StmtKind::Fn { body, .. } | StmtKind::AsyncFn { body, .. } |
+                StmtKind::CoroutineFn { body, .. } | StmtKind::Component { body, .. } => {
+                    Self::check_statements(body, enums, messages);
+                },
+                StmtKind::Actor { fields, methods, .. } | StmtKind::Contract { fields, methods, .. } => {
+                    for (_, _, value) in fields {
+                        Self::check_expression(value, enums, messages);
+                    }
+                    Self::check_statements(methods, enums, messages);
+                },
+                StmtKind::MatchStmt { expr, arms } => Self::check_match(expr, arms, enums, messages),
+                StmtKind::Export(declaration) => {
+                    Self::check_statements(std::slice::from_ref(declaration.as_ref()), enums, messages);
+                },
+                _ => {},
+            }
+        }
+    }
+    
+    fn check_match(expr: &Expr, arms: &[MatchArm], enums: &EnumVariants, messages: &mut Vec<Diagnostic>) {
+        Self::check_expression(expr, enums, messages);
+        if let Err(e) = Self::check_exhaustive(expr, arms, enums) {
+            messages.push(Diagnostic::warning(e, SourceLocation::at(expr.span)).with_code(error_codes::NON_EXHAUSTIVE_MATCH));
+        }
+        for arm in arms {
+            if let Some(guard) = &arm.guard {
+                Self::check_expression(guard, enums, messages);
+            }
+            Self::check_statements(&arm.body, enums, messages);
+        }
+    }
+    
+    fn check_expression(expr: &Expr, enums: &EnumVariants, messages: &mut Vec<Diagnostic>) {
+        match &expr.kind {
+            ExprKind::Binary { left, right, .. } => {
+                Self::check_expression(left, enums, messages);
+                Self::check_expression(right, enums, messages);
+            },
+            ExprKind::Index { object: left, index: right } | ExprKind::Assign { target: left, value: right, .. } => {
+                Self::check_expression(left, enums, messages);
+                Self::check_expression(right, enums, messages);
+            },
+            ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } | ExprKind::Member { object: expr, .. } |
+            ExprKind::Await { expr } | ExprKind::Resume { coroutine: expr } | ExprKind::Destructure { value: expr, .. } => {
+                Self::check_expression(expr, enums, messages);
+            },
+            ExprKind::Yield(Some(expr)) | ExprKind::Suspend(Some(expr)) => Self::check_expression(expr, enums, messages),
+            ExprKind::Call { callee, args } => {
+                Self::check_expression(callee, enums, messages);
+                for arg in args {
+                    Self::check_expression(arg, enums, messages);
+                }
+            },
+            ExprKind::List(items) | ExprKind::EnumVariant { args: items, .. } => {
+                for item in items {
+                    Self::check_expression(item, enums, messages);
+                }
+            },
+            ExprKind::Map(pairs) => {
+                for (_, value) in pairs {
+                    Self::check_expression(value, enums, messages);
+                }
+            },
+            ExprKind::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    Self::check_expression(value, enums, messages);
+                }
+            },
+            ExprKind::Lambda { body, .. } | ExprKind::Generator { body } | ExprKind::Coroutine { body } | ExprKind::Async { body } => {
+                Self::check_statements(body, enums, messages);
+            },
+            ExprKind::ParallelMap { iterable, body, .. } => {
+                Self::check_expression(iterable, enums, messages);
+                Self::check_statements(body, enums, messages);
+            },
+            ExprKind::ParallelReduce { initial, iterable, body, combine, .. } => {
+                Self::check_expression(initial, enums, messages);
+                Self::check_expression(iterable, enums, messages);
+                Self::check_statements(body, enums, messages);
+                if let Some(combine) = combine {
+                    Self::check_statements(&combine.body, enums, messages);
+                }
+            },
+            ExprKind::Match { expr, arms } => Self::check_match(expr, arms, enums, messages),
+            _ => {},
+        }
     }
     
     /// Check if patterns overlap (should be an error)
@@ -61,6 +241,11 @@ impl ExhaustivenessChecker {
                     false
                 }
             },
+            (Pattern::Variant { enum_name: e1, variant: v1, fields: f1 },
+             Pattern::Variant { enum_name: e2, variant: v2, fields: f2 }) => {
+                e1 == e2 && v1 == v2 && f1.len() == f2.len()
+                    && f1.iter().zip(f2.iter()).all(|(p1, p2)| Self::patterns_overlap(p1, p2))
+            },
             (Pattern::Or(pats1), Pattern::Or(pats2)) => {
                 pats1.iter().any(|p1| {
                     pats2.iter().any(|p2| Self::patterns_overlap(p1, p2))
@@ -70,10 +255,167 @@ impl ExhaustivenessChecker {
         }
     }
     
-    /// Get uncovered patterns (for exhaustiveness warnings)
-    pub fn get_uncovered_patterns(_expr: &Expr, _arms: &[MatchArm]) -> Vec<String> {
-        // Simplified - would need type information
-        vec![]
+    /// Get uncovered patterns (for exhaustiveness warnings): example values that no
+    /// arm matches. Arms with guards are left out, since the guard may fail.
+    pub fn get_uncovered_patterns(_expr: &Expr, arms: &[MatchArm], enums: &EnumVariants) -> Vec<String> {
+        let rows: Vec<Vec<&Pattern>> = arms.iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| vec![&arm.pattern])
+            .collect();
+        
+        Self::witnesses(&rows, 1, enums).into_iter()
+            .map(|mut witness| witness.remove(0))
+            .collect()
+    }
+    
+    /// Finds value shapes of `width` columns that none of the pattern rows match
+    fn witnesses(rows: &[Vec<&Pattern>], width: usize, enums: &EnumVariants) -> Vec<Vec<String>> {
+        if width == 0 {
+            // An empty row matches everything that is left
+            return if rows.is_empty() { vec![Vec::new()] } else { Vec::new() };
+        }
+        
+        let rows = Self::expand_heads(rows);
+        let heads: Vec<&Pattern> = rows.iter()
+            .map(|row| row[0])
+            .filter(|head| !Self::is_catch_all(head))
+            .collect();
+        
+        match Self::signature(&heads, enums) {
+            // The first column can only hold these constructors: check each one
+            Some(constructors) => {
+                let mut result = Vec::new();
+                for constructor in constructors {
+                    let arity = constructor.arity();
+                    let specialized = Self::specialize(&rows, &constructor);
+                    for witness in Self::witnesses(&specialized, arity + width - 1, enums) {
+                        let (args, rest) = witness.split_at(arity);
+                        let mut shape = vec![constructor.render(args)];
+                        shape.extend_from_slice(rest);
+                        result.push(shape);
+                        if result.len() >= MAX_WITNESSES {
+                            return result;
+                        }
+                    }
+                }
+                result
+            },
+            // Infinitely many values (numbers, strings, lists): only catch-all rows cover the rest
+            None => {
+                let defaults: Vec<Vec<&Pattern>> = rows.iter()
+                    .filter(|row| Self::is_catch_all(row[0]))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                Self::witnesses(&defaults, width - 1, enums).into_iter()
+                    .map(|witness| {
+                        let mut shape = vec!["_".to_string()];
+                        shape.extend(witness);
+                        shape
+                    })
+                    .collect()
+            },
+        }
+    }
+    
+    /// Flattens or-patterns and bindings in the first column; guarded patterns are dropped
+    fn expand_heads<'a>(rows: &[Vec<&'a Pattern>]) -> Vec<Vec<&'a Pattern>> {
+        let mut expanded = Vec::new();
+        for row in rows {
+            match row[0] {
+                Pattern::Or(alternatives) => {
+                    let alternatives: Vec<Vec<&Pattern>> = alternatives.iter()
+                        .map(|alternative| {
+                            let mut alt_row = vec![alternative];
+                            alt_row.extend_from_slice(&row[1..]);
+                            alt_row
+                        })
+                        .collect();
+                    expanded.extend(Self::expand_heads(&alternatives));
+                },
+                Pattern::Binding { pattern, .. } => {
+                    let mut inner = vec![pattern.as_ref()];
+                    inner.extend_from_slice(&row[1..]);
+                    expanded.extend(Self::expand_heads(&[inner]));
+                },
+                Pattern::Guard { .. } => {},
+                _ => expanded.push(row.clone()),
+            }
+        }
+        expanded
+    }
+    
+    fn is_catch_all(pattern: &Pattern) -> bool {
+        matches!(pattern, Pattern::Identifier(_) | Pattern::Wildcard)
+    }
+    
+    /// All constructors of the first column's type, if there are finitely many
+    fn signature(heads: &[&Pattern], enums: &EnumVariants) -> Option<Vec<Constructor>> {
+        match heads.first()? {
+            Pattern::Variant { enum_name, .. } => {
+                let variants = enums.get(enum_name)?;
+                Some(variants.iter()
+                    .map(|(variant, arity)| Constructor::Variant {
+                        enum_name: enum_name.clone(),
+                        variant: variant.clone(),
+                        arity: *arity,
+                    })
+                    .collect())
+            },
+            Pattern::Boolean(_) => Some(vec![Constructor::Boolean(true), Constructor::Boolean(false)]),
+            Pattern::Tuple(patterns) => Some(vec![Constructor::Tuple(patterns.len())]),
+            Pattern::Struct { name, .. } => {
+                let mut fields: Vec<String> = Vec::new();
+                for head in heads {
+                    if let Pattern::Struct { name: head_name, fields: head_fields } = head {
+                        if head_name == name {
+                            for (field, _) in head_fields {
+                                if !fields.contains(field) {
+                                    fields.push(field.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+                Some(vec![Constructor::Struct { name: name.clone(), fields }])
+            },
+            _ => None,
+        }
+    }
+    
+    /// Rows that can match a value built by `constructor`, with the first column
+    /// replaced by the constructor's sub-patterns
+    fn specialize<'a>(rows: &[Vec<&'a Pattern>], constructor: &Constructor) -> Vec<Vec<&'a Pattern>> {
+        let mut specialized = Vec::new();
+        for row in rows {
+            let head = row[0];
+            let args: Vec<&Pattern> = match (head, constructor) {
+                _ if Self::is_catch_all(head) => vec![&WILDCARD; constructor.arity()],
+                (Pattern::Variant { enum_name, variant, fields },
+                 Constructor::Variant { enum_name: ctor_enum, variant: ctor_variant, arity })
+                    if enum_name == ctor_enum && variant == ctor_variant && fields.len() == *arity => {
+                    fields.iter().collect()
+                },
+                (Pattern::Boolean(b), Constructor::Boolean(ctor_b)) if b == ctor_b => Vec::new(),
+                (Pattern::Tuple(patterns), Constructor::Tuple(arity)) if patterns.len() == *arity => {
+                    patterns.iter().collect()
+                },
+                (Pattern::Struct { name, fields }, Constructor::Struct { name: ctor_name, fields: ctor_fields })
+                    if name == ctor_name => {
+                    ctor_fields.iter()
+                        .map(|field| {
+                            fields.iter()
+                                .find(|(name, _)| name == field)
+                                .map_or(&WILDCARD, |(_, pattern)| pattern)
+                        })
+                        .collect()
+                },
+                _ => continue,
+            };
+            
+            let mut new_row = args;
+            new_row.extend_from_slice(&row[1..]);
+            specialized.push(new_row);
+        }
+        specialized
     }
 }
-
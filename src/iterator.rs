@@ -1,10 +1,18 @@
-use crate::vm::Value;
+use crate::coroutine::{Coroutine, Resumed, Suspender};
+use crate::value::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex};
 
 /// Iterator trait for lazy evaluation
 /// Note: This is a custom iterator trait, not std::iter::Iterator
 pub trait JoelIterator {
     fn next(&mut self) -> Option<Value>;
     fn has_next(&self) -> bool;
+    
+    /// Error that ended the iteration early, if any; taking it clears it
+    fn take_error(&mut self) -> Option<String> {
+        None
+    }
 }
 
 /// Range iterator
@@ -71,34 +79,103 @@ impl JoelIterator for ListIterator {
     }
 }
 
-/// Generator iterator (simplified - would need full generator runtime)
+/// Iterator over a generator body, which runs as a coroutine suspended at each
+/// `yield`: `next` resumes it until the next value, so values are produced on demand
 pub struct GeneratorIterator {
-    values: Vec<Value>,
-    index: usize,
+    coroutine: Coroutine,
+    error: Option<String>,
 }
 
 impl GeneratorIterator {
-    pub fn new(values: Vec<Value>) -> Self {
-        Self {
-            values,
-            index: 0,
-        }
+    /// Starts `body` suspended; it first runs when `next` is called
+    pub fn spawn<F>(body: F) -> Result<Self, String>
+    where
+        F: FnOnce(Suspender) -> Result<(), String> + Send + 'static,
+    {
+        let coroutine = Coroutine::spawn(move |suspender| body(suspender).map(|()| Value::None))?;
+        Ok(Self {
+            coroutine,
+            error: None,
+        })
     }
 }
 
 impl JoelIterator for GeneratorIterator {
     fn next(&mut self) -> Option<Value> {
-        if self.index < self.values.len() {
-            let value = self.values[self.index].clone();
-            self.index += 1;
-            Some(value)
-        } else {
-            None
+        if self.coroutine.is_completed() {
+            return None;
+        }
+        
+        match self.coroutine.resume() {
+            Ok(Resumed::Suspended(value)) => Some(value),
+            Ok(Resumed::Completed(_)) => None,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
+        }
+    }
+    
+    /// Whether the generator may still yield; it is only known to be exhausted
+    /// once `next` has returned `None`
+    fn has_next(&self) -> bool {
+        !self.coroutine.is_completed()
+    }
+    
+    fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+}
+
+/// A lazy iterator as a JOEL value. Clones share the same position.
+#[derive(Clone)]
+pub struct LazyIterator {
+    name: String,
+    inner: Arc<Mutex<Box<dyn JoelIterator + Send>>>,
+}
+
+impl LazyIterator {
+    pub fn new(name: &str, iter: impl JoelIterator + Send + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            inner: Arc::new(Mutex::new(Box::new(iter))),
+        }
+    }
+    
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// Next value, or an error if the iterator failed or is already being advanced
+    /// further up the call stack (a generator iterating itself)
+    pub fn advance(&self) -> Result<Option<Value>, String> {
+        let mut inner = self.inner.try_lock()
+            .map_err(|_| format!("Iterator '{}' is already running", self.name))?;
+        let item = inner.next();
+        match inner.take_error() {
+            Some(e) => Err(e),
+            None => Ok(item),
         }
     }
+}
+
+impl JoelIterator for LazyIterator {
+    fn next(&mut self) -> Option<Value> {
+        self.inner.lock().unwrap().next()
+    }
     
     fn has_next(&self) -> bool {
-        self.index < self.values.len()
+        self.inner.lock().unwrap().has_next()
+    }
+    
+    fn take_error(&mut self) -> Option<String> {
+        self.inner.lock().unwrap().take_error()
+    }
+}
+
+impl fmt::Debug for LazyIterator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<iterator {}>", self.name)
     }
 }
 
@@ -133,7 +210,7 @@ impl IteratorUtils {
     }
 }
 
-struct MapIterator<I, F> {
+pub struct MapIterator<I, F> {
     iter: I,
     f: F,
 }
@@ -150,9 +227,13 @@ where
     fn has_next(&self) -> bool {
         self.iter.has_next()
     }
+    
+    fn take_error(&mut self) -> Option<String> {
+        self.iter.take_error()
+    }
 }
 
-struct FilterIterator<I, F> {
+pub struct FilterIterator<I, F> {
     iter: I,
     f: F,
 }
@@ -174,9 +255,13 @@ where
     fn has_next(&self) -> bool {
         self.iter.has_next()
     }
+    
+    fn take_error(&mut self) -> Option<String> {
+        self.iter.take_error()
+    }
 }
 
-struct TakeIterator<I> {
+pub struct TakeIterator<I> {
     iter: I,
     count: usize,
     limit: usize,
@@ -198,5 +283,9 @@ where
     fn has_next(&self) -> bool {
         self.count < self.limit && self.iter.has_next()
     }
+    
+    fn take_error(&mut self) -> Option<String> {
+        self.iter.take_error()
+    }
 }
 
@@ -1,3 +1,6 @@
+use crate::diagnostics::{Diagnostic, SourceLocation};
+use crate::error_codes;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     // Keywords
@@ -12,10 +15,14 @@ pub enum TokenKind {
     While,
     For,
     Return,
+    Break,
+    Continue,
     Import,
     Module,
     Actor,
     Contract,
+    Struct,
+    Enum,
     Component,
     Flow,
     Deployment,
@@ -26,6 +33,10 @@ pub enum TokenKind {
     Export,
     Async,
     Await,
+    Yield,
+    Coroutine,
+    Suspend,
+    Resume,
     Spawn,
     Match,
     Move,
@@ -47,6 +58,11 @@ pub enum TokenKind {
     Slash,
     Percent,
     Equal,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
     EqualEqual,
     NotEqual,
     LessThan,
@@ -56,7 +72,8 @@ pub enum TokenKind {
     And,
     Or,
     Not,
-    Arrow, // =>
+    Arrow, // ->
+    FatArrow, // =>
     
     // Delimiters
     LBrace,    // {
@@ -68,6 +85,7 @@ pub enum TokenKind {
     Comma,
     Semicolon,
     Colon,
+    ColonColon,
     Dot,
     Question,  // ?
     Exclamation, // !
@@ -77,6 +95,103 @@ pub enum TokenKind {
     Newline,
 }
 
+impl TokenKind {
+    /// Human-readable description used in syntax diagnostics
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Identifier(name) => format!("identifier '{}'", name),
+            TokenKind::Number(n) => format!("number {}", n),
+            TokenKind::String(s) => format!("string \"{}\"", s),
+            TokenKind::Boolean(b) => format!("'{}'", b),
+            TokenKind::Newline => "end of line".to_string(),
+            TokenKind::EOF => "end of file".to_string(),
+            other => format!("'{}'", other.lexeme()),
+        }
+    }
+    
+    fn lexeme(&self) -> &'static str {
+        match self {
+            TokenKind::Compiled => "[Compiled]",
+            TokenKind::Interpreted => "[Interpreted]",
+            TokenKind::Target => "[target",
+            TokenKind::Let => "let",
+            TokenKind::Fn => "fn",
+            TokenKind::If => "if",
+            TokenKind::Else => "else",
+            TokenKind::Elif => "elif",
+            TokenKind::While => "while",
+            TokenKind::For => "for",
+            TokenKind::Return => "return",
+            TokenKind::Break => "break",
+            TokenKind::Continue => "continue",
+            TokenKind::Import => "import",
+            TokenKind::Module => "module",
+            TokenKind::Actor => "actor",
+            TokenKind::Contract => "contract",
+            TokenKind::Struct => "struct",
+            TokenKind::Enum => "enum",
+            TokenKind::Component => "component",
+            TokenKind::Flow => "flow",
+            TokenKind::Deployment => "deployment",
+            TokenKind::Cluster => "cluster",
+            TokenKind::State => "state",
+            TokenKind::Signal => "signal",
+            TokenKind::View => "view",
+            TokenKind::Export => "export",
+            TokenKind::Async => "async",
+            TokenKind::Await => "await",
+            TokenKind::Yield => "yield",
+            TokenKind::Coroutine => "coroutine",
+            TokenKind::Suspend => "suspend",
+            TokenKind::Resume => "resume",
+            TokenKind::Spawn => "spawn",
+            TokenKind::Match => "match",
+            TokenKind::Move => "move",
+            TokenKind::Borrow => "borrow",
+            TokenKind::Defer => "defer",
+            TokenKind::Require => "require",
+            TokenKind::Send => "send",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::Equal => "=",
+            TokenKind::EqualEqual => "==",
+            TokenKind::NotEqual => "!=",
+            TokenKind::LessThan => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::GreaterThan => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::And => "&&",
+            TokenKind::Or => "||",
+            TokenKind::Not => "!",
+            TokenKind::PlusEqual => "+=",
+            TokenKind::MinusEqual => "-=",
+            TokenKind::StarEqual => "*=",
+            TokenKind::SlashEqual => "/=",
+            TokenKind::PercentEqual => "%=",
+            TokenKind::Arrow => "->",
+            TokenKind::FatArrow => "=>",
+            TokenKind::LBrace => "{",
+            TokenKind::RBrace => "}",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::Colon => ":",
+            TokenKind::ColonColon => "::",
+            TokenKind::Dot => ".",
+            TokenKind::Question => "?",
+            TokenKind::Exclamation => "!",
+            TokenKind::Identifier(_) | TokenKind::Number(_) | TokenKind::String(_) |
+            TokenKind::Boolean(_) | TokenKind::EOF | TokenKind::Newline => "",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
@@ -84,11 +199,22 @@ pub struct Token {
     pub col: usize,
 }
 
+/// An `#[allow(code, ...)]` comment found while tokenizing
+#[derive(Debug, Clone)]
+pub struct AllowComment {
+    pub line: usize,
+    pub codes: Vec<String>,
+    /// Written `#![allow(...)]`, which covers the whole file
+    pub whole_file: bool,
+}
+
 pub struct Lexer {
     source: Vec<char>,
     current: usize,
     line: usize,
     col: usize,
+    diagnostics: Vec<Diagnostic>,
+    allow_comments: Vec<AllowComment>,
 }
 
 impl Lexer {
@@ -98,7 +224,70 @@ impl Lexer {
             current: 0,
             line: 1,
             col: 1,
+            diagnostics: Vec::new(),
+            allow_comments: Vec::new(),
+        }
+    }
+    
+    /// Diagnostics collected while tokenizing (unknown characters, unterminated strings)
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+    
+    /// `#[allow]` comments in source order, for the parser to attach to items
+    pub fn allow_comments(&self) -> &[AllowComment] {
+        &self.allow_comments
+    }
+    
+    fn error(&mut self, code: &str, message: String, line: usize, col: usize) {
+        self.diagnostics.push(Diagnostic::error(message, Some(SourceLocation {
+            line,
+            col,
+            file: None,
+        })).with_code(code));
+    }
+    
+    /// Records a `#` comment that reads `[allow(...)]` or `![allow(...)]`
+    fn allow_comment(&mut self, text: &str, line: usize, col: usize) {
+        let (text, whole_file) = match text.strip_prefix('!') {
+            Some(rest) => (rest, true),
+            None => (text, false),
+        };
+        if !text.starts_with("[allow") {
+            return;
         }
+        
+        let location = Some(SourceLocation { line, col, file: None });
+        let list = text.trim_end()
+            .strip_prefix("[allow(")
+            .and_then(|rest| rest.strip_suffix(")]"))
+            .filter(|list| !list.trim().is_empty());
+        let list = match list {
+            Some(list) => list,
+            None => {
+                self.diagnostics.push(Diagnostic::warning(
+                    "Malformed allow comment, expected #[allow(CODE, ...)]".to_string(),
+                    location,
+                ).with_code(error_codes::INVALID_ALLOW));
+                return;
+            },
+        };
+        
+        let mut codes = Vec::new();
+        for code in list.split(',').map(str::trim) {
+            match error_codes::lookup(code) {
+                Some(entry) if !error_codes::can_allow(entry.code) => self.diagnostics.push(Diagnostic::warning(
+                    format!("'{}' is an error; only warnings can be allowed", entry.code),
+                    location.clone(),
+                ).with_code(error_codes::INVALID_ALLOW)),
+                Some(entry) => codes.push(entry.code.to_string()),
+                None => self.diagnostics.push(Diagnostic::warning(
+                    format!("Unknown diagnostic code '{}' in allow comment", code),
+                    location.clone(),
+                ).with_code(error_codes::INVALID_ALLOW)),
+            }
+        }
+        self.allow_comments.push(AllowComment { line, codes, whole_file });
     }
     
     pub fn tokenize(&mut self) -> Vec<Token> {
@@ -128,7 +317,6 @@ impl Lexer {
                 line: self.line,
                 col: self.col,
             });
-            self.advance_n(7); // skip "[target"
             self.skip_whitespace();
             let target = self.read_identifier();
             if !target.is_empty() {
@@ -154,7 +342,7 @@ impl Lexer {
             let start_line = self.line;
             let start_col = self.col;
             
-            let token = self.scan_token();
+            let token = self.scan_token(start_line, start_col);
             if let Some(t) = token {
                 tokens.push(Token {
                     kind: t,
@@ -200,7 +388,7 @@ impl Lexer {
         }
     }
     
-    fn scan_token(&mut self) -> Option<TokenKind> {
+    fn scan_token(&mut self, start_line: usize, start_col: usize) -> Option<TokenKind> {
         let ch = self.advance();
         
         match ch {
@@ -212,20 +400,43 @@ impl Lexer {
             ']' => Some(TokenKind::RBracket),
             ',' => Some(TokenKind::Comma),
             ';' => Some(TokenKind::Semicolon),
-            ':' => Some(TokenKind::Colon),
+            ':' => {
+                if self.peek() == ':' {
+                    self.advance();
+                    Some(TokenKind::ColonColon)
+                } else {
+                    Some(TokenKind::Colon)
+                }
+            },
             '.' => Some(TokenKind::Dot),
             '?' => Some(TokenKind::Question),
-            '!' => Some(TokenKind::Exclamation),
-            '+' => Some(TokenKind::Plus),
+            '+' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    Some(TokenKind::PlusEqual)
+                } else {
+                    Some(TokenKind::Plus)
+                }
+            },
             '-' => {
                 if self.peek() == '>' {
                     self.advance();
                     Some(TokenKind::Arrow)
+                } else if self.peek() == '=' {
+                    self.advance();
+                    Some(TokenKind::MinusEqual)
                 } else {
                     Some(TokenKind::Minus)
                 }
             },
-            '*' => Some(TokenKind::Star),
+            '*' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    Some(TokenKind::StarEqual)
+                } else {
+                    Some(TokenKind::Star)
+                }
+            },
             '/' => {
                 if self.peek() == '/' {
                     // Line comment
@@ -239,15 +450,37 @@ impl Lexer {
                         self.advance();
                     }
                     None
+                } else if self.peek() == '=' {
+                    self.advance();
+                    Some(TokenKind::SlashEqual)
                 } else {
                     Some(TokenKind::Slash)
                 }
             },
-            '%' => Some(TokenKind::Percent),
+            '#' => {
+                // Line comment, or an `#[allow(...)]` directive
+                let mut text = String::new();
+                while !self.is_at_end() && self.peek() != '\n' {
+                    text.push(self.advance());
+                }
+                self.allow_comment(&text, start_line, start_col);
+                None
+            },
+            '%' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    Some(TokenKind::PercentEqual)
+                } else {
+                    Some(TokenKind::Percent)
+                }
+            },
             '=' => {
                 if self.peek() == '=' {
                     self.advance();
                     Some(TokenKind::EqualEqual)
+                } else if self.peek() == '>' {
+                    self.advance();
+                    Some(TokenKind::FatArrow)
                 } else {
                     Some(TokenKind::Equal)
                 }
@@ -289,11 +522,12 @@ impl Lexer {
                     self.advance();
                     Some(TokenKind::Or)
                 } else {
-                    None // Invalid
+                    self.error(error_codes::UNEXPECTED_CHARACTER, "Unexpected character '|' (did you mean '||'?)".to_string(), start_line, start_col);
+                    None
                 }
             },
-            '"' => Some(self.string()),
-            '\'' => Some(self.string()),
+            '"' => Some(self.string(start_line, start_col)),
+            '\'' => Some(self.string(start_line, start_col)),
             '0'..='9' => {
                 self.current -= 1;
                 self.col -= 1;
@@ -304,16 +538,15 @@ impl Lexer {
                 self.col -= 1;
                 Some(self.identifier_or_keyword())
             },
-            '\n' => {
-                self.line += 1;
-                self.col = 1;
-                Some(TokenKind::Newline)
+            '\n' => Some(TokenKind::Newline), // advance() already moved to the next line
+            _ => {
+                self.error(error_codes::UNEXPECTED_CHARACTER, format!("Unexpected character '{}'", ch), start_line, start_col);
+                None
             },
-            _ => None, // Skip unknown characters
         }
     }
     
-    fn string(&mut self) -> TokenKind {
+    fn string(&mut self, start_line: usize, start_col: usize) -> TokenKind {
         let quote = self.source[self.current - 1];
         let mut value = String::new();
         
@@ -348,6 +581,8 @@ impl Lexer {
         
         if !self.is_at_end() {
             self.advance(); // Consume closing quote
+        } else {
+            self.error(error_codes::UNTERMINATED_STRING, "Unterminated string literal".to_string(), start_line, start_col);
         }
         
         TokenKind::String(value)
@@ -383,10 +618,14 @@ impl Lexer {
             "for" => TokenKind::For,
             "in" => TokenKind::Identifier("in".to_string()), // Special: keep as identifier for for loops
             "return" => TokenKind::Return,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
             "import" => TokenKind::Import,
             "module" => TokenKind::Module,
             "actor" => TokenKind::Actor,
             "contract" => TokenKind::Contract,
+            "struct" => TokenKind::Struct,
+            "enum" => TokenKind::Enum,
             "component" => TokenKind::Component,
             "flow" => TokenKind::Flow,
             "deployment" => TokenKind::Deployment,
@@ -397,6 +636,10 @@ impl Lexer {
             "export" => TokenKind::Export,
             "async" => TokenKind::Async,
             "await" => TokenKind::Await,
+            "yield" => TokenKind::Yield,
+            "coroutine" => TokenKind::Coroutine,
+            "suspend" => TokenKind::Suspend,
+            "resume" => TokenKind::Resume,
             "spawn" => TokenKind::Spawn,
             "match" => TokenKind::Match,
             "move" => TokenKind::Move,
@@ -441,12 +684,6 @@ impl Lexer {
         ch
     }
     
-    fn advance_n(&mut self, n: usize) {
-        for _ in 0..n {
-            self.advance();
-        }
-    }
-    
     fn peek(&self) -> char {
         if self.is_at_end() {
             '\0'
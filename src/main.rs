@@ -2,8 +2,10 @@ mod lexer;
 mod parser;
 mod ast;
 mod vm;
+mod value;
 mod types;
 mod diagnostics;
+mod error_codes;
 mod type_checker;
 mod compiler;
 mod ownership;
@@ -15,10 +17,17 @@ mod iterator;
 mod parallel;
 mod exhaustiveness_checker;
 mod coroutine;
+mod bytecode;
+mod repl;
+mod modules;
 
 use clap::{Parser as ClapParser, Subcommand};
+use diagnostics::status;
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 
 #[derive(ClapParser)]
 #[command(name = "joel")]
@@ -35,6 +44,24 @@ enum Commands {
     Run {
         /// Path to the JOEL source file
         file: PathBuf,
+        /// Execution engine (tree, bytecode)
+        #[arg(long, default_value = "tree", value_parser = ["tree", "bytecode"])]
+        vm: String,
+        /// Worker threads for parallel loops, async tasks and actors (defaults to one per CPU)
+        #[arg(long, env = "JOEL_THREADS", value_parser = parse_threads)]
+        threads: Option<usize>,
+        /// Messages an actor's mailbox holds before senders wait (defaults to 1024)
+        #[arg(long, env = "JOEL_MAILBOX_CAPACITY", value_parser = parse_mailbox_capacity)]
+        mailbox_capacity: Option<usize>,
+        /// Run async tasks and actor messages one at a time on the main thread, in the same order on every run
+        #[arg(long)]
+        deterministic: bool,
+        /// Diagnostic output format (human, json)
+        #[arg(long, default_value = "human", value_parser = ["human", "json"])]
+        message_format: String,
+        /// Also write the diagnostics to a SARIF log at this path
+        #[arg(long)]
+        sarif: Option<PathBuf>,
     },
     /// Build a JOEL file for a specific target
     Build {
@@ -52,126 +79,375 @@ enum Commands {
         /// Target architecture (x86_64, arm64, riscv64)
         #[arg(long)]
         arch: Option<String>,
+        /// Diagnostic output format (human, json)
+        #[arg(long, default_value = "human", value_parser = ["human", "json"])]
+        message_format: String,
+        /// Also write the diagnostics to a SARIF log at this path
+        #[arg(long)]
+        sarif: Option<PathBuf>,
+    },
+    /// Start an interactive session
+    Repl,
+    /// Explain a diagnostic code such as E0204
+    Explain {
+        /// The code shown in brackets after "Error" or "Warning"
+        code: String,
     },
     /// Show version information
     Version,
 }
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
     
     match cli.command {
-        Commands::Run { file } => {
-            run_file(&file);
+        Commands::Run { file, vm, threads, mailbox_capacity, deterministic, message_format, sarif } => {
+            start_diagnostics(&message_format, sarif.as_deref());
+            let options = RunOptions {
+                threads,
+                mailbox_capacity,
+                deterministic,
+            };
+            let status = run_file(&file, &vm, &options);
+            finish_diagnostics(sarif.as_deref());
+            status
+        },
+        Commands::Build { file, target, optimize, debug, arch, message_format, sarif } => {
+            start_diagnostics(&message_format, sarif.as_deref());
+            let status = build_file(&file, &target, optimize, debug, arch);
+            finish_diagnostics(sarif.as_deref());
+            status
+        },
+        Commands::Repl => {
+            repl::Repl::new().run();
+            ExitCode::SUCCESS
         },
-        Commands::Build { file, target, optimize, debug, arch } => {
-            build_file(&file, &target, optimize, debug, arch);
+        Commands::Explain { code } => {
+            if let Err(e) = error_codes::explain(&code) {
+                eprintln!("❌ Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
         },
         Commands::Version => {
             println!("JOEL Language v0.1.0");
             println!("A polymodal programming language");
+            ExitCode::SUCCESS
         },
     }
 }
 
-fn run_file(file: &PathBuf) {
+/// Applies `--message-format` and starts collecting diagnostics for `--sarif`
+fn start_diagnostics(message_format: &str, sarif: Option<&Path>) {
+    diagnostics::set_message_format(match message_format {
+        "json" => diagnostics::MessageFormat::Json,
+        _ => diagnostics::MessageFormat::Human,
+    });
+    if sarif.is_some() {
+        diagnostics::collect_sarif();
+    }
+}
+
+/// Writes the SARIF log requested with `--sarif`, if any
+fn finish_diagnostics(sarif: Option<&Path>) {
+    if let Some(path) = sarif {
+        if let Err(e) = diagnostics::write_sarif(path) {
+            eprintln!("❌ Error: {}", e);
+        }
+    }
+}
+
+/// Runtime settings for `joel run`
+struct RunOptions {
+    threads: Option<usize>,
+    mailbox_capacity: Option<usize>,
+    deterministic: bool,
+}
+
+impl RunOptions {
+    fn apply(&self, mut vm: vm::VM) -> vm::VM {
+        if let Some(threads) = self.threads {
+            vm = vm.with_threads(threads);
+        }
+        if self.deterministic {
+            vm = vm.with_deterministic_scheduling();
+        }
+        if let Some(capacity) = self.mailbox_capacity {
+            vm = vm.with_mailbox_capacity(capacity);
+        }
+        vm
+    }
+}
+
+/// Parses a worker thread count, which must be at least 1
+fn parse_threads(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(threads) => Ok(threads),
+        Err(_) => Err(format!("'{}' is not a number of threads", value)),
+    }
+}
+
+/// Parses a mailbox capacity, which must be at least 1
+fn parse_mailbox_capacity(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(capacity) => Ok(capacity),
+        Err(_) => Err(format!("'{}' is not a number of messages", value)),
+    }
+}
+
+fn run_file(file: &PathBuf, engine: &str, options: &RunOptions) -> ExitCode {
     let source = match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
             eprintln!("❌ Error reading file: {}", e);
-            return;
+            return ExitCode::FAILURE;
         },
     };
     
     // Check for header
     if source.trim_start().starts_with("[Interpreted]") {
-        // Tokenize
-        let mut lexer = lexer::Lexer::new(&source);
-        let tokens = lexer.tokenize();
+        // Tokenize and parse
+        let program = match parse_source(&source, Some(file)) {
+            Some(program) => program,
+            None => {
+                eprintln!("\n❌ Parsing failed. Execution aborted.");
+                return ExitCode::FAILURE;
+            },
+        };
         
-        // Parse
-        let mut parser = parser::Parser::new(tokens);
-        let program = parser.parse();
+        let graph = match load_modules(file, source, program) {
+            Some(graph) => graph,
+            None => {
+                eprintln!("\n❌ Import failed. Execution aborted.");
+                return ExitCode::FAILURE;
+            },
+        };
         
         // Interpret
-        let mut vm = vm::VM::new();
-        if let Err(e) = vm.interpret(&program) {
-            eprintln!("❌ Runtime error: {}", e);
-        }
+        execute_program(graph, engine, options)
     } else if source.trim_start().starts_with("[Compiled]") {
-        println!("⚙️  Compiling (AOT) ...");
+        status("⚙️  Compiling (AOT) ...");
         
-        // Tokenize
-        let mut lexer = lexer::Lexer::new(&source);
-        let tokens = lexer.tokenize();
+        // Tokenize and parse
+        let program = match parse_source(&source, Some(file)) {
+            Some(program) => program,
+            None => {
+                eprintln!("\n❌ Parsing failed. Execution aborted.");
+                return ExitCode::FAILURE;
+            },
+        };
         
-        // Parse
-        let mut parser = parser::Parser::new(tokens);
-        let program = parser.parse();
+        let graph = match load_modules(file, source, program) {
+            Some(graph) => graph,
+            None => {
+                eprintln!("\n❌ Import failed. Execution aborted.");
+                return ExitCode::FAILURE;
+            },
+        };
         
         // Type check
-        println!("🔍 Type checking...");
-        let mut checker = type_checker::TypeChecker::new(&source);
-        if !checker.check(&program) {
-            println!("\n");
-            checker.print_diagnostics();
+        status("🔍 Type checking...");
+        if type_check(&graph).is_none() {
             eprintln!("\n❌ Type checking failed. Execution aborted.");
-            return;
+            return ExitCode::FAILURE;
         }
-        println!("✅ Type checking passed\n");
+        status("✅ Type checking passed\n");
         
         // For now, still use VM for compiled mode (until LLVM backend is ready)
-        let mut vm = vm::VM::new();
-        if let Err(e) = vm.interpret(&program) {
-            eprintln!("❌ Runtime error: {}", e);
-        }
+        execute_program(graph, engine, options)
     } else {
         eprintln!("❌ Error: missing [Compiled] or [Interpreted] header");
         eprintln!("   Add [Interpreted] or [Compiled] at the top of your file");
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs the entry program of `graph` on the tree-walking interpreter or the bytecode VM
+fn execute_program(graph: modules::ModuleGraph, engine: &str, options: &RunOptions) -> ExitCode {
+    exhaustiveness_check(&graph);
+    if engine == "bytecode" {
+        if graph.modules.len() > 1 {
+            eprintln!("❌ Bytecode compilation failed: file imports not supported by the bytecode VM yet; run with --vm=tree");
+            return ExitCode::FAILURE;
+        }
+        let compiled = match bytecode::BytecodeCompiler::compile(&graph.entry().program) {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                eprintln!("❌ Bytecode compilation failed: {}", e);
+                return ExitCode::FAILURE;
+            },
+        };
+        let mut vm = bytecode::BytecodeVM::new();
+        if let Err(e) = vm.run(&compiled) {
+            eprintln!("❌ Runtime error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    } else {
+        let graph = Arc::new(graph);
+        let mut vm = options.apply(vm::VM::new().with_modules(Arc::clone(&graph)));
+        if let Err(e) = vm.interpret(&graph.entry().program) {
+            eprintln!("❌ Runtime error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Loads the file modules imported by `program`, reporting any import problems.
+/// Returns `None` if an import cannot be resolved or parsed.
+fn load_modules(file: &Path, source: String, program: ast::Program) -> Option<modules::ModuleGraph> {
+    modules::ModuleLoader::new().load(file, source, program)
+}
+
+/// Type checks every module, imported ones first, so each sees the exports of
+/// the modules it imports. Prints each module's warnings, and returns the
+/// modules' interfaces, or `None` after printing the diagnostics of the first
+/// module that fails.
+fn type_check(graph: &modules::ModuleGraph) -> Option<Vec<type_checker::ModuleInterface>> {
+    let mut interfaces = Vec::new();
+    for module in &graph.modules {
+        let imports = module_imports(module, &interfaces);
+        let mut checker = type_checker::TypeChecker::new(&module.source).with_file(&module.path).with_imports(imports);
+        if !checker.check(&module.program) {
+            status("\n");
+            print_module_header(graph, module);
+            checker.print_diagnostics();
+            return None;
+        }
+        if checker.has_diagnostics() {
+            print_module_header(graph, module);
+            checker.print_diagnostics();
+        }
+        interfaces.push(checker.interface(&module.name, &module.exports));
+    }
+    Some(interfaces)
+}
+
+fn ownership_check(graph: &modules::ModuleGraph, interfaces: &[type_checker::ModuleInterface]) -> bool {
+    for module in &graph.modules {
+        let imports = module_imports(module, interfaces);
+        let mut borrow_checker = ownership::BorrowChecker::new(&module.source).with_file(&module.path).with_imports(imports);
+        if !borrow_checker.check(&module.program) {
+            status("\n");
+            print_module_header(graph, module);
+            borrow_checker.print_diagnostics();
+            return false;
+        }
+        if borrow_checker.has_diagnostics() {
+            print_module_header(graph, module);
+            borrow_checker.print_diagnostics();
+        }
+    }
+    true
+}
+
+/// Reports non-exhaustive matches in every module as warnings before the program runs
+fn exhaustiveness_check(graph: &modules::ModuleGraph) {
+    for module in &graph.modules {
+        let warnings = exhaustiveness_checker::ExhaustivenessChecker::check_program(&module.program);
+        if warnings.is_empty() {
+            continue;
+        }
+        print_module_header(graph, module);
+        let mut reporter = diagnostics::DiagnosticReporter::new(&module.source).with_file(&module.path);
+        reporter.extend(&warnings);
+        reporter.print_all();
+    }
+}
+
+/// Interfaces of the modules that `module` imports, keyed by import spec
+fn module_imports(module: &modules::Module, interfaces: &[type_checker::ModuleInterface]) -> HashMap<String, type_checker::ModuleInterface> {
+    module.imports.iter()
+        .map(|(spec, &index)| (spec.clone(), interfaces[index].clone()))
+        .collect()
+}
+
+/// Names the module that diagnostics belong to, unless it is the entry file
+fn print_module_header(graph: &modules::ModuleGraph, module: &modules::Module) {
+    if !std::ptr::eq(module, graph.entry()) {
+        status(&format!("📦 In module '{}' ({}):", module.name, module.path.display()));
+    }
+}
+
+/// Lexes and parses `source`, printing any syntax diagnostics against `file`
+/// when the source was read from one. Returns `None` if the source contains
+/// syntax errors.
+fn parse_source(source: &str, file: Option<&Path>) -> Option<ast::Program> {
+    let mut lexer = lexer::Lexer::new(source);
+    let tokens = lexer.tokenize();
+    
+    let mut parser = parser::Parser::new(tokens).with_allow_comments(lexer.allow_comments());
+    let program = parser.parse();
+    
+    // Report lexer and parser diagnostics together, in source order
+    let mut syntax_diagnostics = lexer.diagnostics().to_vec();
+    syntax_diagnostics.extend_from_slice(parser.diagnostics());
+    syntax_diagnostics.sort_by_key(|d| d.location.as_ref().map(|loc| (loc.line, loc.col)));
+    
+    let mut reporter = diagnostics::DiagnosticReporter::new(source);
+    if let Some(file) = file {
+        reporter = reporter.with_file(file);
+    }
+    reporter.allow(&program.allows);
+    reporter.extend(&syntax_diagnostics);
+    reporter.print_all();
+    
+    if reporter.has_errors() {
+        None
+    } else {
+        Some(program)
     }
 }
 
-fn build_file(file: &PathBuf, target: &str, optimize: bool, debug: bool, arch: Option<String>) {
-    println!("🔨 Building: {} for target: {}\n", file.display(), target);
+fn build_file(file: &PathBuf, target: &str, optimize: bool, debug: bool, arch: Option<String>) -> ExitCode {
+    status(&format!("🔨 Building: {} for target: {}\n", file.display(), target));
     
     let source = match fs::read_to_string(file) {
         Ok(content) => content,
         Err(e) => {
             eprintln!("❌ Error reading file: {}", e);
-            return;
+            return ExitCode::FAILURE;
         },
     };
     
-    // Tokenize
-    let mut lexer = lexer::Lexer::new(&source);
-    let tokens = lexer.tokenize();
-    
-    // Parse
-    let mut parser = parser::Parser::new(tokens);
-    let program = parser.parse();
+    // Tokenize and parse
+    let program = match parse_source(&source, Some(file)) {
+        Some(program) => program,
+        None => {
+            eprintln!("\n❌ Parsing failed. Build aborted.");
+            return ExitCode::FAILURE;
+        },
+    };
+    let graph = match load_modules(file, source, program) {
+        Some(graph) => graph,
+        None => {
+            eprintln!("\n❌ Import failed. Build aborted.");
+            return ExitCode::FAILURE;
+        },
+    };
+    let program = &graph.entry().program;
     
     // Type check (for compiled mode)
     if program.mode == ast::ExecutionMode::Compiled {
-        println!("🔍 Type checking...");
-        let mut checker = type_checker::TypeChecker::new(&source);
-        if !checker.check(&program) {
-            println!("\n");
-            checker.print_diagnostics();
-            eprintln!("\n❌ Type checking failed. Build aborted.");
-            return;
-        }
-        println!("✅ Type checking passed");
+        status("🔍 Type checking...");
+        let interfaces = match type_check(&graph) {
+            Some(interfaces) => interfaces,
+            None => {
+                eprintln!("\n❌ Type checking failed. Build aborted.");
+                return ExitCode::FAILURE;
+            },
+        };
+        status("✅ Type checking passed");
         
         // Ownership checking
-        println!("🔒 Ownership checking...");
-        let mut borrow_checker = ownership::BorrowChecker::new(&source);
-        if !borrow_checker.check(&program) {
-            println!("\n");
-            borrow_checker.print_diagnostics();
+        status("🔒 Ownership checking...");
+        if !ownership_check(&graph, &interfaces) {
             eprintln!("\n❌ Ownership checking failed. Build aborted.");
-            return;
+            return ExitCode::FAILURE;
         }
-        println!("✅ Ownership checking passed\n");
+        status("✅ Ownership checking passed\n");
     }
     
     // Determine output path
@@ -186,7 +462,7 @@ fn build_file(file: &PathBuf, target: &str, optimize: bool, debug: bool, arch: O
         "polkadot" => "wasm",
         _ => {
             eprintln!("❌ Unknown target: {}", target);
-            return;
+            return ExitCode::FAILURE;
         },
     });
     
@@ -210,13 +486,15 @@ fn build_file(file: &PathBuf, target: &str, optimize: bool, debug: bool, arch: O
         arch,
     };
     let comp = compiler::Compiler::with_options(compilation_target, options);
-    match comp.compile(&program, &output_path) {
+    match comp.compile(program, &output_path) {
         Ok(_) => {
-            println!("✅ Compilation successful!");
-            println!("   Output: {}", output_path.display());
+            status("✅ Compilation successful!");
+            status(&format!("   Output: {}", output_path.display()));
+            ExitCode::SUCCESS
         },
         Err(e) => {
             eprintln!("❌ Compilation failed: {}", e);
+            ExitCode::FAILURE
         },
     }
 }
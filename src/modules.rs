@@ -0,0 +1,230 @@
+// Module loading for `import`
+//
+// Imports are resolved before a program runs. `import utils` looks for
+// `utils.joel` and `import "lib/utils.joel"` for that path, first next to the
+// importing file and then in each directory of the JOEL_PATH search path.
+// Every file is parsed once; importing a module that is still being loaded
+// further up the chain is reported as a cycle. Import problems are reported
+// as diagnostics against the importing file, at the import statement.
+
+use crate::ast::{Program, Stmt, StmtKind};
+use crate::diagnostics::{Diagnostic, DiagnosticReporter, SourceLocation};
+use crate::error_codes::{IMPORT_CYCLE, INVALID_MODULE, MODULE_NOT_FOUND, UNREADABLE_MODULE, UNRESOLVED_IMPORT};
+use crate::stdlib;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SEARCH_PATH_VAR: &str = "JOEL_PATH";
+
+pub struct Module {
+    /// Name from the file's `module` declaration, or else its file stem
+    pub name: String,
+    pub path: PathBuf,
+    pub source: String,
+    pub program: Program,
+    /// Names declared with `export` or named by `export default`
+    pub exports: Vec<String>,
+    /// Import spec -> index of the module it resolves to
+    pub imports: HashMap<String, usize>,
+}
+
+/// The entry file and every module it imports, directly or indirectly
+pub struct ModuleGraph {
+    /// Each module comes after the modules it imports, so the entry file is last
+    pub modules: Vec<Module>,
+}
+
+impl ModuleGraph {
+    pub fn entry(&self) -> &Module {
+        self.modules.last().expect("graph contains the entry module")
+    }
+
+    pub fn entry_index(&self) -> usize {
+        self.modules.len() - 1
+    }
+
+    /// Index of the module that `spec` names when imported from module `from`
+    pub fn resolve(&self, from: usize, spec: &str) -> Option<usize> {
+        self.modules[from].imports.get(spec).copied()
+    }
+}
+
+/// The name an import binds: the alias if given, otherwise the file stem of the spec
+pub fn import_name(spec: &str, alias: Option<&String>) -> String {
+    match alias {
+        Some(alias) => alias.clone(),
+        None => Path::new(spec).file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| spec.to_string()),
+    }
+}
+
+/// Whether an import spec is written as a file path rather than a bare module name
+fn is_path(spec: &str) -> bool {
+    spec.contains('/') || spec.contains('\\') || spec.ends_with(".joel")
+}
+
+/// Why an imported file could not be loaded
+enum LoadError {
+    /// A problem with the file itself, reported at the import that names it
+    Import { code: &'static str, message: String },
+    /// Already reported further down the chain, e.g. in a module the file imports
+    Reported,
+}
+
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    modules: Vec<Module>,
+    /// Canonical file path -> module index
+    cache: HashMap<PathBuf, usize>,
+    /// Files currently being loaded, outermost first
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        let search_path = std::env::var_os(SEARCH_PATH_VAR)
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        Self {
+            search_path,
+            modules: Vec::new(),
+            cache: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    /// Loads every module imported by the already parsed entry file at `path`,
+    /// reporting import problems. Returns `None` if any of them is an error.
+    pub fn load(mut self, path: &Path, source: String, program: Program) -> Option<ModuleGraph> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.link(path, source, program).ok()?;
+        Some(ModuleGraph { modules: self.modules })
+    }
+
+    fn load_file(&mut self, path: PathBuf) -> Result<usize, LoadError> {
+        if let Some(&index) = self.cache.get(&path) {
+            return Ok(index);
+        }
+        if let Some(start) = self.loading.iter().position(|loading| *loading == path) {
+            let mut chain: Vec<String> = self.loading[start..].iter()
+                .map(|file| file.display().to_string())
+                .collect();
+            chain.push(path.display().to_string());
+            return Err(LoadError::Import {
+                code: IMPORT_CYCLE,
+                message: format!("Import cycle: {}", chain.join(" -> ")),
+            });
+        }
+
+        let source = fs::read_to_string(&path).map_err(|e| LoadError::Import {
+            code: UNREADABLE_MODULE,
+            message: format!("Cannot read module {}: {}", path.display(), e),
+        })?;
+        let program = crate::parse_source(&source, Some(&path)).ok_or_else(|| LoadError::Import {
+            code: INVALID_MODULE,
+            message: format!("Module {} has syntax errors", path.display()),
+        })?;
+        self.link(path, source, program)
+    }
+
+    /// Loads the imports of a parsed file, then adds the file itself to the graph.
+    /// Stops at the first import that fails to load.
+    fn link(&mut self, path: PathBuf, source: String, program: Program) -> Result<usize, LoadError> {
+        self.loading.push(path.clone());
+        let mut imports = HashMap::new();
+        let mut diagnostics = Vec::new();
+        let mut failed = false;
+        for stmt in &program.statements {
+            if let StmtKind::Import { module, .. } = &stmt.kind {
+                if stdlib::module(module).is_some() || imports.contains_key(module) {
+                    continue;
+                }
+                let location = SourceLocation::at(stmt.span);
+                match self.find(module, &path).map(|file| self.load_file(file)) {
+                    Some(Ok(index)) => {
+                        imports.insert(module.clone(), index);
+                    },
+                    Some(Err(LoadError::Import { code, message })) => {
+                        diagnostics.push(Diagnostic::error(message, location).with_code(code));
+                        failed = true;
+                    },
+                    Some(Err(LoadError::Reported)) => failed = true,
+                    None if is_path(module) => {
+                        let message = format!("Module '{}' not found", module);
+                        diagnostics.push(Diagnostic::error(message, location).with_code(MODULE_NOT_FOUND));
+                        failed = true;
+                    },
+                    None => {
+                        let message = format!("Module '{}' not found; import has no effect", module);
+                        diagnostics.push(Diagnostic::warning(message, location).with_code(UNRESOLVED_IMPORT));
+                    },
+                }
+                if failed {
+                    break;
+                }
+            }
+        }
+        self.loading.pop();
+
+        if !diagnostics.is_empty() {
+            let mut reporter = DiagnosticReporter::new(&source).with_file(&path);
+            reporter.allow(&program.allows);
+            reporter.extend(&diagnostics);
+            reporter.print_all();
+        }
+        if failed {
+            return Err(LoadError::Reported);
+        }
+
+        let declared_name = program.statements.iter().find_map(|stmt| match &stmt.kind {
+            StmtKind::Module { name } => Some(name.clone()),
+            _ => None,
+        });
+        let name = declared_name.unwrap_or_else(|| import_name(&path.to_string_lossy(), None));
+        let exports = program.statements.iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Export(declaration) => declared_name_of(declaration),
+                StmtKind::ExportDefault(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        self.modules.push(Module {
+            name,
+            path: path.clone(),
+            source,
+            program,
+            exports,
+            imports,
+        });
+        let index = self.modules.len() - 1;
+        self.cache.insert(path, index);
+        Ok(index)
+    }
+
+    /// Locates the file for `spec`, next to the importing file first, then on the search path
+    fn find(&self, spec: &str, importer: &Path) -> Option<PathBuf> {
+        let mut relative = PathBuf::from(spec);
+        if relative.extension().is_none() {
+            relative.set_extension("joel");
+        }
+
+        let importer_dir = importer.parent().map(Path::to_path_buf).unwrap_or_default();
+        std::iter::once(importer_dir)
+            .chain(self.search_path.iter().cloned())
+            .map(|dir| dir.join(&relative))
+            .find(|candidate| candidate.is_file())
+            .map(|file| file.canonicalize().unwrap_or(file))
+    }
+}
+
+fn declared_name_of(stmt: &Stmt) -> Option<String> {
+    match &stmt.kind {
+        StmtKind::Fn { name, .. } | StmtKind::AsyncFn { name, .. } | StmtKind::CoroutineFn { name, .. } |
+        StmtKind::Let { name, .. } | StmtKind::Const { name, .. } |
+        StmtKind::Struct { name, .. } | StmtKind::Enum { name, .. } => Some(name.clone()),
+        _ => None,
+    }
+}
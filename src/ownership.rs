@@ -2,8 +2,14 @@
 // Inspired by Rust's ownership system
 
 use crate::ast::*;
-use crate::diagnostics::DiagnosticReporter;
+use crate::diagnostics::{Diagnostic, DiagnosticReporter, SourceLocation};
+use crate::error_codes;
+use crate::modules;
+use crate::stdlib;
+use crate::type_checker::ModuleInterface;
+use crate::types::Type;
 use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ownership {
@@ -22,8 +28,14 @@ pub struct VariableState {
 pub struct BorrowChecker {
     reporter: DiagnosticReporter,
     variables: HashMap<String, VariableState>,
+    /// Where each moved variable was last moved, for "moved here" labels
+    moves: HashMap<String, Span>,
     scope_level: usize,
     in_function: bool,
+    /// Parameter types of known functions, including imported ones as `module.name`
+    functions: HashMap<String, Vec<Type>>,
+    /// Import spec -> interface of the file module it names
+    imports: HashMap<String, ModuleInterface>,
 }
 
 impl BorrowChecker {
@@ -31,15 +43,33 @@ impl BorrowChecker {
         Self {
             reporter: DiagnosticReporter::new(source),
             variables: HashMap::new(),
+            moves: HashMap::new(),
             scope_level: 0,
             in_function: false,
+            functions: HashMap::new(),
+            imports: HashMap::new(),
         }
     }
     
+    /// Names the checked file in machine-readable diagnostics
+    pub fn with_file(mut self, path: &Path) -> Self {
+        self.reporter = self.reporter.with_file(path);
+        self
+    }
+    
+    /// Makes the signatures of imported file modules visible to `import`
+    pub fn with_imports(mut self, imports: HashMap<String, ModuleInterface>) -> Self {
+        self.imports = imports;
+        self
+    }
+    
+    /// Checks a program. Like the type checker, this covers Compiled programs and
+    /// the modules they import; callers decide which programs that is.
     pub fn check(&mut self, program: &Program) -> bool {
-        // Only check ownership in Compiled mode
-        if program.mode != ExecutionMode::Compiled {
-            return true;
+        self.reporter.allow(&program.allows);
+        
+        for stmt in &program.statements {
+            self.collect_signature(stmt);
         }
         
         for stmt in &program.statements {
@@ -49,9 +79,41 @@ impl BorrowChecker {
         !self.reporter.has_errors()
     }
     
+    /// Records parameter types of top-level and imported functions, so that
+    /// arguments of copy types are not treated as moved
+    fn collect_signature(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Fn { name, params, .. } | StmtKind::AsyncFn { name, params, .. } | StmtKind::CoroutineFn { name, params, .. } => {
+                let param_types = params.iter()
+                    .map(|(_, type_str)| type_str.as_deref().and_then(Type::from_string).unwrap_or(Type::Unknown))
+                    .collect();
+                self.functions.insert(name.clone(), param_types);
+            },
+            StmtKind::Import { module, alias } => {
+                let import_name = modules::import_name(module, alias.as_ref());
+                if let Some(natives) = stdlib::module(module) {
+                    for native in natives {
+                        let param_types = native.params.iter()
+                            .map(|type_str| Type::from_string(type_str).unwrap_or(Type::Any))
+                            .collect();
+                        self.functions.insert(format!("{}.{}", import_name, native.short_name()), param_types);
+                    }
+                } else if let Some(interface) = self.imports.get(module) {
+                    for (name, ty) in &interface.values {
+                        if let Type::Function { params, .. } = ty {
+                            self.functions.insert(format!("{}.{}", import_name, name), params.clone());
+                        }
+                    }
+                }
+            },
+            StmtKind::Export(declaration) => self.collect_signature(declaration),
+            _ => {},
+        }
+    }
+    
     fn check_statement(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::Let { name, value, .. } => {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
                 self.check_expression(value);
                 // New variable is owned
                 self.variables.insert(name.clone(), VariableState {
@@ -59,7 +121,7 @@ impl BorrowChecker {
                     scope_level: self.scope_level,
                 });
             },
-            Stmt::Const { name, value, .. } => {
+            StmtKind::Const { name, value, .. } => {
                 self.check_expression(value);
                 // Const is owned and immutable
                 self.variables.insert(name.clone(), VariableState {
@@ -67,15 +129,15 @@ impl BorrowChecker {
                     scope_level: self.scope_level,
                 });
             },
-            Stmt::Expr(expr) => {
+            StmtKind::Expr(expr) => {
                 self.check_expression(expr);
             },
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) => {
                 if let Some(expr) = expr {
                     self.check_expression(expr);
                 }
             },
-            Stmt::If { condition, then_branch, else_branch } => {
+            StmtKind::If { condition, then_branch, else_branch } => {
                 self.check_expression(condition);
                 self.begin_scope();
                 for stmt in then_branch {
@@ -91,7 +153,7 @@ impl BorrowChecker {
                     self.end_scope();
                 }
             },
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 self.check_expression(condition);
                 self.begin_scope();
                 for stmt in body {
@@ -99,7 +161,7 @@ impl BorrowChecker {
                 }
                 self.end_scope();
             },
-            Stmt::For { var, iterable, body } => {
+            StmtKind::For { var, iterable, body } | StmtKind::ParallelFor { var, iterable, body } => {
                 self.check_expression(iterable);
                 self.begin_scope();
                 // Loop variable is owned in the loop scope
@@ -112,14 +174,16 @@ impl BorrowChecker {
                 }
                 self.end_scope();
             },
-            Stmt::Block(statements) => {
+            StmtKind::Export(declaration) => self.check_statement(declaration),
+            StmtKind::Block(statements) => {
                 self.begin_scope();
                 for stmt in statements {
                     self.check_statement(stmt);
                 }
                 self.end_scope();
             },
-            Stmt::Fn { name: _, params, body, .. } => {
+            StmtKind::Fn { name: _, params, body, .. } | StmtKind::AsyncFn { name: _, params, body, .. } |
+            StmtKind::CoroutineFn { name: _, params, body, .. } => {
                 self.in_function = true;
                 self.begin_scope();
                 
@@ -143,64 +207,195 @@ impl BorrowChecker {
     }
     
     fn check_expression(&mut self, expr: &Expr) {
-        match expr {
-            Expr::Identifier(name) => {
+        match &expr.kind {
+            ExprKind::Identifier(name) => {
                 if let Some(var_state) = self.variables.get(name) {
                     match var_state.ownership {
-                        Ownership::Moved => {
-                            self.reporter.error(
-                                format!("Use of moved value: {}", name),
-                                None,
-                            );
-                        },
+                        Ownership::Moved => self.use_of_moved(name, expr.span),
                         _ => {
                             // Reading is fine for owned/borrowed values
                         }
                     }
                 }
             },
-            Expr::Binary { left, op: _, right } => {
+            ExprKind::Binary { left, op: _, right } => {
                 self.check_expression(left);
                 self.check_expression(right);
             },
-            Expr::Unary { expr, .. } => {
+            ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } => {
                 self.check_expression(expr);
             },
-            Expr::Call { callee: _, args } => {
-                for arg in args {
+            ExprKind::Call { callee, args } => {
+                if !matches!(&callee.kind, ExprKind::Identifier(_)) {
+                    self.check_expression(callee);
+                }
+                let callee_name = match &callee.kind {
+                    ExprKind::Identifier(name) => Some(name.clone()),
+                    ExprKind::Member { object, member } => match &object.kind {
+                        ExprKind::Identifier(module) => Some(format!("{}.{}", module, member)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let param_types = callee_name.and_then(|name| self.functions.get(&name).cloned()).unwrap_or_default();
+                for (i, arg) in args.iter().enumerate() {
                     self.check_expression(arg);
+                    if param_types.get(i).is_some_and(Type::is_copy) {
+                        continue;
+                    }
                     // Function calls may move values (simplified for now)
-                    if let Expr::Identifier(name) = arg {
+                    if let ExprKind::Identifier(name) = &arg.kind {
                         if let Some(var_state) = self.variables.get_mut(name) {
                             if var_state.ownership == Ownership::Owned {
                                 var_state.ownership = Ownership::Moved;
+                                self.moves.insert(name.clone(), arg.span);
                             }
                         }
                     }
                 }
             },
-            Expr::Member { object, .. } => {
+            ExprKind::Member { object, .. } => {
                 self.check_expression(object);
             },
-            Expr::Index { object, index } => {
+            ExprKind::Index { object, index } => {
                 self.check_expression(object);
                 self.check_expression(index);
             },
-            Expr::List(elements) => {
+            ExprKind::Lambda { params, body } => {
+                // The body runs when the closure is called, so it is checked like
+                // a nested function
+                let enclosing_function = self.in_function;
+                self.in_function = true;
+                self.begin_scope();
+                for (param_name, _) in params {
+                    self.variables.insert(param_name.clone(), VariableState {
+                        ownership: Ownership::Owned,
+                        scope_level: self.scope_level,
+                    });
+                }
+                for stmt in body {
+                    self.check_statement(stmt);
+                }
+                self.end_scope();
+                self.in_function = enclosing_function;
+            },
+            ExprKind::Generator { body } | ExprKind::Coroutine { body } | ExprKind::Async { body } => {
+                // Like a lambda, the body runs later against its environment
+                self.begin_scope();
+                for stmt in body {
+                    self.check_statement(stmt);
+                }
+                self.end_scope();
+            },
+            ExprKind::ParallelMap { var, iterable, body } => {
+                // Each item runs against the enclosing locals, which it cannot assign
+                self.check_expression(iterable);
+                self.begin_scope();
+                self.variables.insert(var.clone(), VariableState {
+                    ownership: Ownership::Owned,
+                    scope_level: self.scope_level,
+                });
+                for stmt in body {
+                    self.check_statement(stmt);
+                }
+                self.end_scope();
+            },
+            ExprKind::ParallelReduce { acc, initial, var, iterable, body, combine } => {
+                self.check_expression(initial);
+                self.check_expression(iterable);
+                self.begin_scope();
+                for name in [acc, var] {
+                    self.variables.insert(name.clone(), VariableState {
+                        ownership: Ownership::Owned,
+                        scope_level: self.scope_level,
+                    });
+                }
+                for stmt in body {
+                    self.check_statement(stmt);
+                }
+                self.end_scope();
+                if let Some(combine) = combine {
+                    self.begin_scope();
+                    for name in [&combine.left, &combine.right] {
+                        self.variables.insert(name.clone(), VariableState {
+                            ownership: Ownership::Owned,
+                            scope_level: self.scope_level,
+                        });
+                    }
+                    for stmt in &combine.body {
+                        self.check_statement(stmt);
+                    }
+                    self.end_scope();
+                }
+            },
+            ExprKind::Yield(Some(value)) | ExprKind::Suspend(Some(value)) => {
+                self.check_expression(value);
+            },
+            ExprKind::Resume { coroutine: expr } | ExprKind::Await { expr } => {
+                self.check_expression(expr);
+            },
+            ExprKind::Assign { target, op, value } => {
+                self.check_expression(value);
+                match &target.kind {
+                    ExprKind::Identifier(name) => {
+                        let ownership = self.variables.get(name).map(|state| state.ownership.clone());
+                        match ownership {
+                            Some(Ownership::Borrowed) | Some(Ownership::Mutable) => {
+                                self.reporter.error(
+                                    error_codes::ASSIGN_WHILE_BORROWED,
+                                    format!("Cannot assign to '{}' while it is borrowed", name),
+                                    SourceLocation::at(target.span),
+                                );
+                            },
+                            // Compound assignment reads the old value first
+                            Some(Ownership::Moved) if op.is_some() => self.use_of_moved(name, target.span),
+                            // Plain assignment gives a moved variable a fresh value
+                            Some(Ownership::Moved) => {
+                                if let Some(state) = self.variables.get_mut(name) {
+                                    state.ownership = Ownership::Owned;
+                                }
+                            },
+                            _ => {},
+                        }
+                    },
+                    // Writing through a member or index path requires the root to still be valid
+                    _ => self.check_expression(target),
+                }
+            },
+            ExprKind::List(elements) => {
                 for elem in elements {
                     self.check_expression(elem);
                 }
             },
-            Expr::Map(pairs) => {
-                for (key, value) in pairs {
-                    self.check_expression(key);
+            ExprKind::Map(pairs) => {
+                // Keys are field names, not variable uses
+                for (_, value) in pairs {
                     self.check_expression(value);
                 }
             },
+            ExprKind::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.check_expression(value);
+                }
+            },
+            ExprKind::EnumVariant { args, .. } => {
+                for arg in args {
+                    self.check_expression(arg);
+                }
+            },
             _ => {}
         }
     }
     
+    fn use_of_moved(&mut self, name: &str, span: Span) {
+        let moved_at = self.moves.get(name).copied().unwrap_or_default();
+        self.reporter.report(
+            Diagnostic::error(format!("Use of moved value: {}", name), SourceLocation::at(span))
+                .with_code(error_codes::USE_AFTER_MOVE)
+                .with_label(moved_at, "value moved here".to_string()),
+        );
+    }
+    
     fn begin_scope(&mut self) {
         self.scope_level += 1;
     }
@@ -218,5 +413,9 @@ impl BorrowChecker {
     pub fn has_errors(&self) -> bool {
         self.reporter.has_errors()
     }
+    
+    pub fn has_diagnostics(&self) -> bool {
+        !self.reporter.is_empty()
+    }
 }
 
@@ -1,149 +1,383 @@
-use crate::vm::Value;
-use std::sync::{Arc, Mutex};
-use std::thread;
+use crate::value::Value;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// Stack size for worker threads, so a loop body recurses as deep as it would
+/// on the main thread
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
 
 /// Parallel execution runtime
+///
+/// Runs loop bodies as jobs on a work-stealing scheduler whose workers start
+/// the first time something runs in parallel.
+#[derive(Clone)]
 pub struct ParallelRuntime {
     thread_pool_size: usize,
+    /// Shared by clones, so forked contexts use the same workers
+    scheduler: Arc<OnceLock<WorkStealingScheduler>>,
 }
 
 impl ParallelRuntime {
     pub fn new(pool_size: usize) -> Self {
         Self {
-            thread_pool_size: pool_size,
+            thread_pool_size: pool_size.max(1),
+            scheduler: Arc::new(OnceLock::new()),
         }
     }
     
-    /// Execute tasks in parallel
-    pub fn parallel_map<F>(&self, items: Vec<Value>, f: F) -> Vec<Value>
+    /// One worker per available CPU
+    pub fn per_cpu() -> Self {
+        Self::new(thread::available_parallelism().map_or(4, |n| n.get()))
+    }
+    
+    /// Number of worker threads
+    pub fn threads(&self) -> usize {
+        self.thread_pool_size
+    }
+    
+    fn scheduler(&self) -> &WorkStealingScheduler {
+        self.scheduler.get_or_init(|| WorkStealingScheduler::new(self.thread_pool_size))
+    }
+    
+    /// Queues `job` on the workers, returning a handle to wait for its result
+    pub fn spawn<T>(&self, job: impl FnOnce() -> T + Send + 'static) -> JoinHandle<T>
     where
-        F: Fn(Value) -> Value + Send + Sync + 'static,
+        T: Send + 'static,
     {
-        use std::sync::Arc;
-        let results = Arc::new(Mutex::new(Vec::new()));
-        let mut handles = Vec::new();
-        let f_arc = Arc::new(f);
-        
-        for item in items {
-            let results_clone = results.clone();
-            let f_clone = f_arc.clone();
-            let handle = thread::spawn(move || {
-                let result = f_clone(item);
-                results_clone.lock().unwrap().push(result);
-            });
-            handles.push(handle);
-        }
-        
-        // Wait for all threads
-        for handle in handles {
-            handle.join().unwrap();
+        self.scheduler().spawn(job)
+    }
+    
+    /// Queues `job` on the workers without waiting for it; a panic in the job
+    /// is contained to it
+    pub fn spawn_detached(&self, job: impl FnOnce() + Send + 'static) {
+        self.scheduler().shared.push(Box::new(move || {
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+        }));
+    }
+    
+    /// Whether the current thread is one of this runtime's workers
+    pub fn on_worker(&self) -> bool {
+        self.scheduler.get().is_some_and(|scheduler| scheduler.shared.current_worker().is_some())
+    }
+    
+    /// On a worker thread, runs one queued job and returns true. A worker
+    /// that must wait for something calls this meanwhile, so the job it
+    /// waits for cannot be stuck behind it.
+    pub fn help(&self) -> bool {
+        let shared = match self.scheduler.get() {
+            Some(scheduler) => &scheduler.shared,
+            None => return false,
+        };
+        match shared.current_worker().and_then(|index| shared.find_job(index)) {
+            Some(job) => {
+                job();
+                true
+            },
+            None => false,
         }
-        
-        Arc::try_unwrap(results).unwrap().into_inner().unwrap()
     }
     
-    /// Parallel reduce operation
-    pub fn parallel_reduce<F, R>(&self, items: Vec<Value>, initial: R, f: F) -> R
+    /// Runs `f` on every item as a separate job, each with its own context
+    /// built by `context`, and returns the results in input order. Once an
+    /// item fails, later items that have not started yet are skipped, and the
+    /// error of the earliest failing item is returned.
+    pub fn parallel_map<T, R, C, F>(&self, items: Vec<T>, mut context: impl FnMut() -> C, f: F) -> Result<Vec<R>, String>
     where
-        F: Fn(R, Value) -> R + Send + Sync + 'static,
-        R: Send + Sync + Clone + 'static,
+        T: Send + 'static,
+        R: Send + 'static,
+        C: Send + 'static,
+        F: Fn(&mut C, T) -> Result<R, String> + Send + Sync + 'static,
     {
-        use std::sync::Arc;
-        let chunk_size = (items.len() + self.thread_pool_size - 1) / self.thread_pool_size;
-        let mut handles = Vec::new();
-        let f_arc = Arc::new(f);
+        let scheduler = self.scheduler();
+        let f = Arc::new(f);
+        // Index of the earliest item known to have failed; items before it always run
+        let first_failure = Arc::new(AtomicUsize::new(usize::MAX));
         
-        for chunk in items.chunks(chunk_size) {
-            let chunk = chunk.to_vec();
-            let initial_clone = initial.clone();
-            let f_clone = f_arc.clone();
-            let handle = thread::spawn(move || {
-                let mut acc = initial_clone;
-                for item in chunk {
-                    acc = f_clone(acc, item);
+        let handles: Vec<_> = items.into_iter().enumerate().map(|(index, item)| {
+            let (f, first_failure) = (Arc::clone(&f), Arc::clone(&first_failure));
+            let mut context = context();
+            scheduler.spawn(move || {
+                if index > first_failure.load(Ordering::SeqCst) {
+                    return None;
                 }
-                acc
-            });
-            handles.push(handle);
-        }
+                let result = f(&mut context, item);
+                if result.is_err() {
+                    first_failure.fetch_min(index, Ordering::SeqCst);
+                }
+                Some(result)
+            })
+        }).collect();
         
-        // Combine results
-        let result = initial;
+        let mut results = Vec::with_capacity(handles.len());
+        let mut first_error = None;
         for handle in handles {
-            let _partial = handle.join().unwrap();
-            // Simplified - would need proper reduction combining partial results
-            // In a full implementation, we would combine partial results here
-            // result = combine(result, partial);
+            match handle.join()? {
+                Some(Ok(value)) => results.push(value),
+                Some(Err(e)) => {
+                    first_error.get_or_insert(e);
+                },
+                None => {}, // Skipped after an earlier item failed
+            }
         }
         
-        result
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(results),
+        }
     }
     
-    /// Execute function in parallel for each item
-    pub fn parallel_for<F>(&self, items: Vec<Value>, f: F)
+    /// Reduces `items` to one value. The list is split into a contiguous chunk
+    /// per worker; each chunk is folded from `initial` with `fold` in parallel,
+    /// then the chunks' results are merged in input order with `combine`.
+    /// `initial` starts every chunk, so it should be an identity for `combine`,
+    /// and `combine` should be associative. An empty list reduces to `initial`.
+    pub fn parallel_reduce<R, C, F, G>(
+        &self,
+        items: Vec<Value>,
+        initial: R,
+        context: impl FnMut() -> C,
+        fold: F,
+        mut combine: G,
+    ) -> Result<R, String>
     where
-        F: Fn(Value) + Send + Sync + 'static,
+        R: Clone + Send + Sync + 'static,
+        C: Send + 'static,
+        F: Fn(&mut C, R, Value) -> Result<R, String> + Send + Sync + 'static,
+        G: FnMut(R, R) -> Result<R, String>,
     {
-        use std::sync::Arc;
-        let mut handles = Vec::new();
-        let f_arc = Arc::new(f);
-        
-        for item in items {
-            let f_clone = f_arc.clone();
-            let handle = thread::spawn(move || {
-                f_clone(item);
-            });
-            handles.push(handle);
+        if items.is_empty() {
+            return Ok(initial);
         }
         
-        // Wait for all threads
-        for handle in handles {
-            handle.join().unwrap();
+        let chunk_size = items.len().div_ceil(self.thread_pool_size);
+        let mut chunks = Vec::new();
+        let mut items = items.into_iter();
+        loop {
+            let chunk: Vec<Value> = items.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            chunks.push(chunk);
         }
+        
+        let partials = self.parallel_map(chunks, context, move |context, chunk: Vec<Value>| {
+            chunk.into_iter().try_fold(initial.clone(), |acc, item| fold(context, acc, item))
+        })?;
+        
+        let mut partials = partials.into_iter();
+        let first = partials.next().expect("a non-empty list has a chunk");
+        partials.try_fold(first, &mut combine)
     }
 }
 
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    /// The scheduler (by address of its shared state) and queue index of the
+    /// worker running on this thread
+    static WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
 /// Work-stealing scheduler for parallel tasks
+///
+/// Every worker thread owns a deque. A job spawned by a worker goes on the
+/// back of its own deque, and the worker takes its newest job first (LIFO);
+/// an idle worker steals the oldest job from the front of another's (FIFO).
+/// Jobs spawned from other threads go through a shared injector queue. Idle
+/// workers park until a job is queued. Dropping the scheduler shuts it down
+/// gracefully: jobs already queued still run before the workers exit.
 pub struct WorkStealingScheduler {
-    queues: Vec<Arc<Mutex<Vec<Box<dyn FnOnce() + Send>>>>>,
-    num_threads: usize,
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+struct Shared {
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    injector: Mutex<VecDeque<Job>>,
+    /// Parked workers, one of which is woken for each queued job
+    idle: Mutex<Vec<Thread>>,
+    shutdown: AtomicBool,
+}
+
+/// The result of a job spawned on a `WorkStealingScheduler`
+pub struct JoinHandle<T> {
+    slot: Arc<JobSlot<T>>,
+    shared: Arc<Shared>,
+}
+
+struct JobSlot<T> {
+    result: Mutex<Option<Result<T, String>>>,
+    done: Condvar,
 }
 
 impl WorkStealingScheduler {
     pub fn new(num_threads: usize) -> Self {
-        let mut queues = Vec::new();
-        for _ in 0..num_threads {
-            queues.push(Arc::new(Mutex::new(Vec::new())));
-        }
+        let num_threads = num_threads.max(1);
+        let shared = Arc::new(Shared {
+            queues: (0..num_threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            injector: Mutex::new(VecDeque::new()),
+            idle: Mutex::new(Vec::new()),
+            shutdown: AtomicBool::new(false),
+        });
+        
+        let workers = (0..num_threads).map(|index| {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name(format!("joel-worker-{}", index))
+                .stack_size(WORKER_STACK_SIZE)
+                .spawn(move || shared.run_worker(index))
+                .expect("cannot start worker thread")
+        }).collect();
         
         Self {
-            queues,
-            num_threads,
+            shared,
+            workers,
         }
     }
     
-    /// Spawn a task
-    pub fn spawn<F>(&self, task: F)
+    /// Spawn a task, returning a handle to wait for its result
+    pub fn spawn<F, T>(&self, task: F) -> JoinHandle<T>
     where
-        F: FnOnce() + Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
     {
-        // Simple round-robin scheduling
-        let queue_idx = 0; // Would use thread-local index in full implementation
-        self.queues[queue_idx].lock().unwrap().push(Box::new(task));
+        let slot = Arc::new(JobSlot {
+            result: Mutex::new(None),
+            done: Condvar::new(),
+        });
+        
+        let job_slot = Arc::clone(&slot);
+        self.shared.push(Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(task))
+                .map_err(|_| "Parallel task panicked".to_string());
+            *job_slot.result.lock().unwrap() = Some(result);
+            job_slot.done.notify_all();
+        }));
+        
+        JoinHandle {
+            slot,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for WorkStealingScheduler {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        for worker in &self.workers {
+            worker.thread().unpark();
+        }
+        for worker in self.workers.drain(..) {
+            // A job may hold the last reference; its own worker exits once it returns
+            if worker.thread().id() != thread::current().id() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn id(&self) -> usize {
+        self as *const Shared as usize
+    }
+    
+    /// Queue index of this scheduler's worker on the current thread, if any
+    fn current_worker(&self) -> Option<usize> {
+        WORKER.with(|worker| worker.get())
+            .filter(|(scheduler, _)| *scheduler == self.id())
+            .map(|(_, index)| index)
     }
     
-    /// Steal work from another queue
-    pub fn steal_work(&self, from_idx: usize) -> Option<Box<dyn FnOnce() + Send>> {
-        if from_idx < self.queues.len() {
-            self.queues[from_idx].lock().unwrap().pop()
-        } else {
-            None
+    fn push(&self, job: Job) {
+        match self.current_worker() {
+            Some(index) => self.queues[index].lock().unwrap().push_back(job),
+            None => self.injector.lock().unwrap().push_back(job),
+        }
+        if let Some(worker) = self.idle.lock().unwrap().pop() {
+            worker.unpark();
         }
     }
     
-    /// Get number of threads
-    pub fn num_threads(&self) -> usize {
-        self.num_threads
+    /// Next job for worker `index`: its own newest job, else the oldest
+    /// injected one, else the oldest job of another worker
+    fn find_job(&self, index: usize) -> Option<Job> {
+        if let Some(job) = self.queues[index].lock().unwrap().pop_back() {
+            return Some(job);
+        }
+        if let Some(job) = self.injector.lock().unwrap().pop_front() {
+            return Some(job);
+        }
+        let count = self.queues.len();
+        (1..count)
+            .map(|offset| (index + offset) % count)
+            .find_map(|victim| self.queues[victim].lock().unwrap().pop_front())
+    }
+    
+    fn run_worker(&self, index: usize) {
+        WORKER.with(|worker| worker.set(Some((self.id(), index))));
+        loop {
+            if let Some(job) = self.find_job(index) {
+                job();
+                continue;
+            }
+            
+            // Registered as idle before looking again, so a job queued in
+            // between either is found now or unparks this worker
+            self.idle.lock().unwrap().push(thread::current());
+            if let Some(job) = self.find_job(index) {
+                self.leave_idle();
+                job();
+                continue;
+            }
+            if self.shutdown.load(Ordering::SeqCst) {
+                self.leave_idle();
+                return;
+            }
+            thread::park();
+            self.leave_idle();
+        }
+    }
+    
+    fn leave_idle(&self) {
+        let current = thread::current().id();
+        self.idle.lock().unwrap().retain(|worker| worker.id() != current);
+    }
+}
+
+impl<T> JoinHandle<T> {
+    /// Waits for the task and returns its result, or an error if it panicked.
+    /// A worker waiting on a task runs other queued jobs meanwhile, so tasks
+    /// can wait on tasks they spawn without exhausting the pool.
+    pub fn join(self) -> Result<T, String> {
+        let worker = self.shared.current_worker();
+        loop {
+            let result = self.slot.result.lock().unwrap();
+            let mut result = match worker {
+                Some(index) => {
+                    if result.is_none() {
+                        drop(result);
+                        if let Some(job) = self.shared.find_job(index) {
+                            job();
+                            continue;
+                        }
+                        // The task is running elsewhere; check back for new jobs now and then
+                        let result = self.slot.result.lock().unwrap();
+                        self.slot.done.wait_timeout(result, Duration::from_millis(1)).unwrap().0
+                    } else {
+                        result
+                    }
+                },
+                None => self.slot.done.wait_while(result, |result| result.is_none()).unwrap(),
+            };
+            if let Some(result) = result.take() {
+                return result;
+            }
+        }
     }
 }
 
@@ -1,14 +1,53 @@
 use crate::ast::*;
-use crate::lexer::{Token, TokenKind};
+use crate::diagnostics::{Diagnostic, DiagnosticLevel};
+use crate::error_codes;
+use crate::lexer::{AllowComment, Token, TokenKind};
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    diagnostics: Vec<Diagnostic>,
+    /// Number of enclosing loops, used to reject `break`/`continue` elsewhere
+    loop_depth: usize,
+    /// Whether the innermost enclosing function uses `yield`; None outside functions
+    function_yields: Option<bool>,
+    /// `#[allow]` comments not yet attached to the item after them, in source order
+    allow_comments: Vec<AllowComment>,
+    /// Index of the first comment in `allow_comments` still waiting for its item
+    next_allow: usize,
+    allows: Vec<Allow>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            diagnostics: Vec::new(),
+            loop_depth: 0,
+            function_yields: None,
+            allow_comments: Vec::new(),
+            next_allow: 0,
+            allows: Vec::new(),
+        }
+    }
+    
+    /// Gives the parser the `#[allow]` comments the lexer found. `#![allow]` ones
+    /// cover the whole file; the others cover the item that follows them.
+    pub fn with_allow_comments(mut self, comments: &[AllowComment]) -> Self {
+        for comment in comments {
+            if comment.whole_file {
+                self.allows.push(Allow { codes: comment.codes.clone(), lines: 1..=usize::MAX });
+            } else {
+                self.allow_comments.push(comment.clone());
+            }
+        }
+        self
+    }
+    
+    /// Syntax errors and warnings collected during `parse`
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
     
     pub fn parse(&mut self) -> Program {
@@ -37,11 +76,22 @@ impl Parser {
         }
         
         let mut statements = Vec::new();
-        while !self.is_at_end() {
-            if let Some(stmt) = self.declaration() {
-                statements.push(stmt);
-            } else {
-                self.advance(); // Skip error token
+        loop {
+            self.skip_separators();
+            if self.is_at_end() {
+                break;
+            }
+            
+            if self.peek().kind == TokenKind::RBrace {
+                self.error("Unexpected '}' without a matching '{'".to_string());
+                self.advance();
+                continue;
+            }
+            
+            let errors_before = self.diagnostics.len();
+            match self.declaration() {
+                Some(stmt) => statements.push(stmt),
+                None => self.recover(errors_before),
             }
         }
         
@@ -49,14 +99,43 @@ impl Parser {
             mode,
             target,
             statements,
+            allows: std::mem::take(&mut self.allows),
         }
     }
     
     fn declaration(&mut self) -> Option<Stmt> {
+        let span = self.span();
+        let allow_comments = self.take_allow_comments();
+        let kind = self.declaration_kind()?;
+        self.apply_allow_comments(allow_comments, span.line);
+        Some(Stmt::new(kind, span))
+    }
+    
+    /// Takes the codes of the `#[allow]` comments written above the current token
+    fn take_allow_comments(&mut self) -> Vec<Vec<String>> {
+        let line = self.peek().line;
+        let mut codes = Vec::new();
+        while let Some(comment) = self.allow_comments.get(self.next_allow).filter(|comment| comment.line < line) {
+            codes.push(comment.codes.clone());
+            self.next_allow += 1;
+        }
+        codes
+    }
+    
+    /// Makes the comments taken before an item cover its lines, from `start` to its last token
+    fn apply_allow_comments(&mut self, comments: Vec<Vec<String>>, start: usize) {
+        let end = self.tokens[self.current.saturating_sub(1)].line.max(start);
+        for codes in comments {
+            self.allows.push(Allow { codes, lines: start..=end });
+        }
+    }
+    
+    fn declaration_kind(&mut self) -> Option<StmtKind> {
         match self.peek().kind {
             TokenKind::Let => {
                 self.advance();
-                self.let_declaration()
+                let stmt = self.let_declaration()?;
+                self.end_statement(stmt)
             },
             TokenKind::Fn => {
                 self.advance();
@@ -64,11 +143,17 @@ impl Parser {
             },
             TokenKind::Module => {
                 self.advance();
-                self.module()
+                let stmt = self.module()?;
+                self.end_statement(stmt)
             },
             TokenKind::Import => {
                 self.advance();
-                self.import()
+                let stmt = self.import()?;
+                self.end_statement(stmt)
+            },
+            TokenKind::Export => {
+                self.advance();
+                self.export()
             },
             TokenKind::Actor => {
                 self.advance();
@@ -78,6 +163,14 @@ impl Parser {
                 self.advance();
                 self.contract()
             },
+            TokenKind::Struct => {
+                self.advance();
+                self.struct_declaration()
+            },
+            TokenKind::Enum => {
+                self.advance();
+                self.enum_declaration()
+            },
             TokenKind::Component => {
                 self.advance();
                 self.component()
@@ -98,138 +191,173 @@ impl Parser {
                 self.advance();
                 self.match_statement()
             },
-            TokenKind::Async => {
+            TokenKind::Async if self.peek_next().kind == TokenKind::Fn => {
                 self.advance();
                 self.async_function()
             },
-            _ => self.statement(),
+            TokenKind::Coroutine if self.peek_next().kind == TokenKind::Fn => {
+                self.advance();
+                self.coroutine_function()
+            },
+            _ => self.statement_kind(),
         }
     }
     
-    fn let_declaration(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
-            self.advance();
-            n
-        } else {
-            return None;
-        };
+    fn let_declaration(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("variable name after 'let'")?;
         
+        let mut annot_span = Span::default();
         let type_annot = if self.peek().kind == TokenKind::Colon {
             self.advance();
-            if let TokenKind::Identifier(t) = &self.peek().kind {
-                let t = t.clone();
-                self.advance();
-                Some(t)
-            } else {
-                None
-            }
+            annot_span = self.span();
+            Some(self.type_annotation()?)
         } else {
             None
         };
         
-        if self.peek().kind != TokenKind::Equal {
-            return None;
-        }
-        self.advance();
+        self.consume(TokenKind::Equal, &format!("'=' after variable '{}'", name))?;
         
         let value = self.expression()?;
         
-        Some(Stmt::Let {
+        Some(StmtKind::Let {
             name,
             type_annot,
+            annot_span,
             value,
         })
     }
     
-    fn function(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
-            self.advance();
-            n
-        } else {
-            return None;
-        };
-        
-        if self.peek().kind != TokenKind::LParen {
-            return None;
-        }
-        self.advance();
-        
-        let mut params = Vec::new();
-        if self.peek().kind != TokenKind::RParen {
-            loop {
-                let param_name = if let TokenKind::Identifier(n) = &self.peek().kind {
-                    let n = n.clone();
-                    self.advance();
-                    n
-                } else {
-                    break;
-                };
-                
-                let param_type = if self.peek().kind == TokenKind::Colon {
-                    self.advance();
-                    if let TokenKind::Identifier(t) = &self.peek().kind {
-                        let t = t.clone();
-                        self.advance();
-                        Some(t)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-                
-                params.push((param_name, param_type));
-                
-                if self.peek().kind != TokenKind::Comma {
-                    break;
-                }
-                self.advance();
-            }
-        }
+    fn function(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("function name after 'fn'")?;
         
-        if self.peek().kind != TokenKind::RParen {
-            return None;
-        }
-        self.advance();
+        self.consume(TokenKind::LParen, &format!("'(' after function name '{}'", name))?;
+        let params = self.parameters()?;
         
+        let mut return_span = Span::default();
         let return_type = if self.peek().kind == TokenKind::Arrow {
             self.advance();
-            if let TokenKind::Identifier(t) = &self.peek().kind {
-                let t = t.clone();
-                self.advance();
-                Some(t)
-            } else {
-                None
-            }
+            return_span = self.span();
+            Some(self.type_annotation()?)
         } else {
             None
         };
         
-        let body = self.block()?;
+        // Loops outside the function body cannot be exited from inside it
+        let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_yields = self.function_yields.replace(false);
+        let body = self.block();
+        self.loop_depth = enclosing_loops;
+        let yields = std::mem::replace(&mut self.function_yields, enclosing_yields);
+        let body = Self::function_body(body?, yields);
         
-        Some(Stmt::Fn {
+        Some(StmtKind::Fn {
             name,
             params,
             return_type,
+            return_span,
             body,
+            generator: yields == Some(true),
         })
     }
     
-    fn module(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
+    /// Parses a parameter list; the opening '(' has already been consumed
+    fn parameters(&mut self) -> Option<Vec<(String, Option<String>)>> {
+        let mut params = Vec::new();
+        self.skip_newlines();
+        while self.peek().kind != TokenKind::RParen {
+            let param_name = self.expect_identifier("parameter name")?;
+            
+            let param_type = if self.peek().kind == TokenKind::Colon {
+                self.advance();
+                Some(self.type_annotation()?)
+            } else {
+                None
+            };
+            
+            params.push((param_name, param_type));
+            
+            self.skip_newlines();
+            if self.peek().kind != TokenKind::Comma {
+                break;
+            }
             self.advance();
-            n
-        } else {
-            return None;
-        };
+            self.skip_newlines();
+        }
+        
+        self.consume(TokenKind::RParen, "')' after parameters")?;
+        Some(params)
+    }
+    
+    /// Parses a type annotation such as `i32`, `list[i32]`, `map[str, i32]`, `(i32, str)` or `fn(i32) -> i32`
+    /// into the string form understood by `Type::from_string`
+    fn type_annotation(&mut self) -> Option<String> {
+        match self.peek().kind.clone() {
+            TokenKind::Identifier(name) => {
+                self.advance();
+                if self.peek().kind == TokenKind::LBracket {
+                    self.advance();
+                    let mut args = Vec::new();
+                    loop {
+                        args.push(self.type_annotation()?);
+                        if self.peek().kind != TokenKind::Comma {
+                            break;
+                        }
+                        self.advance();
+                    }
+                    self.consume(TokenKind::RBracket, "']' to close type arguments")?;
+                    Some(format!("{}[{}]", name, args.join(", ")))
+                } else {
+                    Some(name)
+                }
+            },
+            TokenKind::Fn => {
+                // Function type: fn(i32, str) -> bool
+                self.advance();
+                self.consume(TokenKind::LParen, "'(' after 'fn' in function type")?;
+                let mut params = Vec::new();
+                while self.peek().kind != TokenKind::RParen {
+                    params.push(self.type_annotation()?);
+                    if self.peek().kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.advance();
+                }
+                self.consume(TokenKind::RParen, "')' after function type parameters")?;
+                let return_type = if self.peek().kind == TokenKind::Arrow {
+                    self.advance();
+                    self.type_annotation()?
+                } else {
+                    "None".to_string()
+                };
+                Some(format!("fn({}) -> {}", params.join(", "), return_type))
+            },
+            TokenKind::LParen => {
+                self.advance();
+                let mut elements = Vec::new();
+                while self.peek().kind != TokenKind::RParen {
+                    elements.push(self.type_annotation()?);
+                    if self.peek().kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.advance();
+                }
+                self.consume(TokenKind::RParen, "')' to close tuple type")?;
+                Some(format!("({})", elements.join(", ")))
+            },
+            _ => {
+                self.error(format!("Expected type, found {}", self.peek().kind.describe()));
+                None
+            },
+        }
+    }
+    
+    fn module(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("module name after 'module'")?;
         
-        Some(Stmt::Module { name })
+        Some(StmtKind::Module { name })
     }
     
-    fn import(&mut self) -> Option<Stmt> {
+    fn import(&mut self) -> Option<StmtKind> {
         let module = if let TokenKind::Identifier(n) = &self.peek().kind {
             let n = n.clone();
             self.advance();
@@ -239,270 +367,355 @@ impl Parser {
             self.advance();
             s
         } else {
+            self.error(format!("Expected module name after 'import', found {}", self.peek().kind.describe()));
             return None;
         };
         
-        let alias = if matches!(self.peek().kind, TokenKind::Identifier(_)) {
-            if let TokenKind::Identifier(a) = &self.peek().kind {
+        let alias = if let TokenKind::Identifier(a) = &self.peek().kind {
+            if a == "as" {
+                self.advance();
+                Some(self.expect_identifier("alias after 'as'")?)
+            } else {
                 let a = a.clone();
                 self.advance();
                 Some(a)
-            } else {
-                None
             }
         } else {
             None
         };
         
-        Some(Stmt::Import { module, alias })
+        Some(StmtKind::Import { module, alias })
     }
     
-    fn actor(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
+    fn export(&mut self) -> Option<StmtKind> {
+        if matches!(&self.peek().kind, TokenKind::Identifier(word) if word == "default") {
             self.advance();
-            n
-        } else {
-            return None;
-        };
-        
-        if self.peek().kind != TokenKind::LBrace {
+            let name = self.expect_identifier("name after 'export default'")?;
+            return self.end_statement(StmtKind::ExportDefault(name));
+        }
+        if !matches!(self.peek().kind, TokenKind::Fn | TokenKind::Async | TokenKind::Let | TokenKind::Struct | TokenKind::Enum) {
+            self.error(format!("Expected fn, let, struct, enum or default after 'export', found {}", self.peek().kind.describe()));
             return None;
         }
-        self.advance();
+        let stmt = self.declaration()?;
+        Some(StmtKind::Export(Box::new(stmt)))
+    }
+    
+    fn actor(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("actor name after 'actor'")?;
+        let (fields, methods) = self.state_and_methods("actor")?;
         
-        let mut fields = Vec::new();
-        let mut methods = Vec::new();
+        Some(StmtKind::Actor {
+            name,
+            fields,
+            methods,
+        })
+    }
+    
+    fn contract(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("contract name after 'contract'")?;
+        let (fields, methods) = self.state_and_methods("contract")?;
         
-        while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-            if self.peek().kind == TokenKind::State {
+        Some(StmtKind::Contract {
+            name,
+            fields,
+            methods,
+        })
+    }
+    
+    fn struct_declaration(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("struct name after 'struct'")?;
+        self.skip_newlines();
+        self.consume(TokenKind::LBrace, &format!("'{{' to open struct '{}'", name))?;
+        
+        let mut fields: Vec<(String, String)> = Vec::new();
+        self.skip_separators();
+        while self.peek().kind != TokenKind::RBrace {
+            let field = self.expect_identifier("field name")?;
+            self.consume(TokenKind::Colon, &format!("':' and a type after field '{}'", field))?;
+            let type_annot = self.type_annotation()?;
+            if fields.iter().any(|(existing, _)| *existing == field) {
+                self.error_with_code(error_codes::DUPLICATE_MEMBER, format!("Duplicate field '{}' in struct '{}'", field, name));
+                return None;
+            }
+            fields.push((field, type_annot));
+            
+            // Fields are separated by commas or newlines
+            let on_new_line = matches!(self.peek().kind, TokenKind::Newline | TokenKind::Semicolon);
+            self.skip_separators();
+            if self.peek().kind == TokenKind::Comma {
                 self.advance();
-                if self.peek().kind == TokenKind::Let {
-                    self.advance();
-                    if let Some(Stmt::Let { name, type_annot, value }) = self.let_declaration() {
-                        fields.push((name, type_annot, value));
-                    }
-                }
-            } else if self.peek().kind == TokenKind::Fn {
+                self.skip_separators();
+            } else if !on_new_line && self.peek().kind != TokenKind::RBrace {
+                self.error(format!("Expected ',' or '}}' after struct field, found {}", self.peek().kind.describe()));
+                return None;
+            }
+        }
+        self.consume(TokenKind::RBrace, &format!("'}}' to close struct '{}'", name))?;
+        
+        Some(StmtKind::Struct { name, fields })
+    }
+    
+    /// Parses `enum Name { Variant, Variant(type, ...), ... }`
+    fn enum_declaration(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("enum name after 'enum'")?;
+        self.skip_newlines();
+        self.consume(TokenKind::LBrace, &format!("'{{' to open enum '{}'", name))?;
+        
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        self.skip_separators();
+        while self.peek().kind != TokenKind::RBrace {
+            let variant = self.expect_identifier("variant name")?;
+            let mut payload = Vec::new();
+            if self.peek().kind == TokenKind::LParen {
                 self.advance();
-                if let Some(method) = self.function() {
-                    methods.push(method);
+                self.skip_newlines();
+                while self.peek().kind != TokenKind::RParen {
+                    payload.push(self.type_annotation()?);
+                    self.skip_newlines();
+                    if self.peek().kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.advance();
+                    self.skip_newlines();
                 }
-            } else {
+                self.consume(TokenKind::RParen, &format!("')' after payload of variant '{}'", variant))?;
+            }
+            if variants.iter().any(|(existing, _)| *existing == variant) {
+                self.error_with_code(error_codes::DUPLICATE_MEMBER, format!("Duplicate variant '{}' in enum '{}'", variant, name));
+                return None;
+            }
+            variants.push((variant, payload));
+            
+            // Variants are separated by commas or newlines
+            let on_new_line = matches!(self.peek().kind, TokenKind::Newline | TokenKind::Semicolon);
+            self.skip_separators();
+            if self.peek().kind == TokenKind::Comma {
                 self.advance();
+                self.skip_separators();
+            } else if !on_new_line && self.peek().kind != TokenKind::RBrace {
+                self.error(format!("Expected ',' or '}}' after enum variant, found {}", self.peek().kind.describe()));
+                return None;
             }
         }
+        self.consume(TokenKind::RBrace, &format!("'}}' to close enum '{}'", name))?;
         
-        if self.peek().kind == TokenKind::RBrace {
-            self.advance();
-        }
-        
-        Some(Stmt::Actor {
-            name,
-            fields,
-            methods,
-        })
+        Some(StmtKind::Enum { name, variants })
     }
     
-    fn contract(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
-            self.advance();
-            n
-        } else {
-            return None;
-        };
-        
-        if self.peek().kind != TokenKind::LBrace {
-            return None;
-        }
-        self.advance();
+    /// Parses the `{ state let ...  fn ... }` body shared by actors and contracts
+    #[allow(clippy::type_complexity)]
+    fn state_and_methods(&mut self, construct: &str) -> Option<(Vec<(String, Option<String>, Expr)>, Vec<Stmt>)> {
+        self.skip_newlines();
+        self.consume(TokenKind::LBrace, &format!("'{{' to open {} body", construct))?;
         
         let mut fields = Vec::new();
         let mut methods = Vec::new();
         
-        while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-            if self.peek().kind == TokenKind::State {
-                self.advance();
-                if self.peek().kind == TokenKind::Let {
+        loop {
+            self.skip_separators();
+            if self.peek().kind == TokenKind::RBrace || self.is_at_end() {
+                break;
+            }
+            
+            let errors_before = self.diagnostics.len();
+            match self.peek().kind {
+                TokenKind::State => {
                     self.advance();
-                    if let Some(Stmt::Let { name, type_annot, value }) = self.let_declaration() {
-                        fields.push((name, type_annot, value));
+                    match self.state_field() {
+                        Some(field) => fields.push(field),
+                        None => self.recover(errors_before),
                     }
-                }
-            } else if self.peek().kind == TokenKind::Fn {
-                self.advance();
-                if let Some(method) = self.function() {
-                    methods.push(method);
-                }
-            } else {
-                self.advance();
+                },
+                TokenKind::Fn => {
+                    let span = self.span();
+                    let allow_comments = self.take_allow_comments();
+                    self.advance();
+                    match self.function() {
+                        Some(method) => {
+                            self.apply_allow_comments(allow_comments, span.line);
+                            methods.push(Stmt::new(method, span));
+                        },
+                        None => self.recover(errors_before),
+                    }
+                },
+                _ => {
+                    self.error(format!("Expected 'state' or 'fn' in {} body, found {}",
+                        construct, self.peek().kind.describe()));
+                    self.synchronize();
+                },
             }
         }
         
-        if self.peek().kind == TokenKind::RBrace {
-            self.advance();
-        }
+        self.consume(TokenKind::RBrace, &format!("'}}' to close {} body", construct))?;
         
-        Some(Stmt::Contract {
-            name,
-            fields,
-            methods,
-        })
+        Some((fields, methods))
     }
     
-    fn component(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
+    /// Parses `state let name[: type] [= value]`; fields without an initializer start as `None`
+    fn state_field(&mut self) -> Option<(String, Option<String>, Expr)> {
+        self.consume(TokenKind::Let, "'let' after 'state'")?;
+        let name = self.expect_identifier("field name after 'state let'")?;
+        
+        let type_annot = if self.peek().kind == TokenKind::Colon {
             self.advance();
-            n
+            Some(self.type_annotation()?)
         } else {
-            return None;
+            None
         };
         
-        if self.peek().kind != TokenKind::LParen {
-            return None;
-        }
-        self.advance();
+        let value = if self.peek().kind == TokenKind::Equal {
+            self.advance();
+            self.expression()?
+        } else {
+            Expr::new(ExprKind::None, self.span())
+        };
         
-        if self.peek().kind != TokenKind::RParen {
+        if !self.at_statement_end() {
+            self.error(format!("Expected end of field declaration, found {}", self.peek().kind.describe()));
             return None;
         }
-        self.advance();
         
-        if self.peek().kind != TokenKind::LBrace {
-            return None;
-        }
-        self.advance();
+        Some((name, type_annot, value))
+    }
+    
+    fn component(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("component name after 'component'")?;
         
-        let mut body = Vec::new();
-        while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-            if let Some(stmt) = self.declaration() {
-                body.push(stmt);
-            } else {
-                self.advance();
-            }
-        }
+        self.consume(TokenKind::LParen, &format!("'(' after component name '{}'", name))?;
+        self.consume(TokenKind::RParen, "')' after component parameters")?;
         
-        if self.peek().kind == TokenKind::RBrace {
-            self.advance();
-        }
+        let body = self.dsl_body("component")?;
         
-        Some(Stmt::Component { name, body })
+        Some(StmtKind::Component { name, body })
     }
     
-    fn flow(&mut self) -> Option<Stmt> {
-        let name = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
-            self.advance();
-            n
-        } else {
-            return None;
-        };
+    fn flow(&mut self) -> Option<StmtKind> {
+        let name = self.expect_identifier("flow name after 'flow'")?;
         
-        if self.peek().kind != TokenKind::LBrace {
-            return None;
-        }
-        self.advance();
-        
-        let mut nodes = Vec::new();
-        while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-            if let Some(stmt) = self.declaration() {
-                nodes.push(stmt);
-            } else {
-                self.advance();
-            }
-        }
+        let nodes = self.dsl_body("flow")?;
         
-        if self.peek().kind == TokenKind::RBrace {
-            self.advance();
-        }
-        
-        Some(Stmt::Flow { name, nodes })
+        Some(StmtKind::Flow { name, nodes })
     }
     
-    fn deployment(&mut self) -> Option<Stmt> {
+    fn deployment(&mut self) -> Option<StmtKind> {
         if let TokenKind::String(name) = &self.peek().kind {
             let name = name.clone();
             self.advance();
             
-            if self.peek().kind != TokenKind::LBrace {
-                return None;
-            }
-            self.advance();
-            
-            let mut config = Vec::new();
-            while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-                if let Some(stmt) = self.declaration() {
-                    config.push(stmt);
-                } else {
-                    self.advance();
-                }
-            }
-            
-            if self.peek().kind == TokenKind::RBrace {
-                self.advance();
-            }
+            let config = self.dsl_body("deployment")?;
             
-            Some(Stmt::Deployment { name, config })
+            Some(StmtKind::Deployment { name, config })
         } else {
+            self.error(format!("Expected deployment name string, found {}", self.peek().kind.describe()));
             None
         }
     }
     
-    fn cluster(&mut self) -> Option<Stmt> {
+    fn cluster(&mut self) -> Option<StmtKind> {
         if let TokenKind::String(name) = &self.peek().kind {
             let name = name.clone();
             self.advance();
             
-            if self.peek().kind != TokenKind::LBrace {
-                return None;
-            }
-            self.advance();
+            let config = self.dsl_body("cluster")?;
             
-            let mut config = Vec::new();
-            while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-                if let Some(stmt) = self.declaration() {
-                    config.push(stmt);
-                } else {
-                    self.advance();
-                }
+            Some(StmtKind::Cluster { name, config })
+        } else {
+            self.error(format!("Expected cluster name string, found {}", self.peek().kind.describe()));
+            None
+        }
+    }
+    
+    /// Parses the body of a component/flow/deployment/cluster block.
+    /// These blocks host configuration DSLs that the core grammar does not cover yet,
+    /// so lines that fail to parse are skipped with a warning instead of an error.
+    fn dsl_body(&mut self, construct: &str) -> Option<Vec<Stmt>> {
+        self.skip_newlines();
+        self.consume(TokenKind::LBrace, &format!("'{{' to open {} body", construct))?;
+        
+        let mut items = Vec::new();
+        loop {
+            self.skip_separators();
+            if self.peek().kind == TokenKind::RBrace || self.is_at_end() {
+                break;
             }
             
-            if self.peek().kind == TokenKind::RBrace {
-                self.advance();
+            let start = self.peek().clone();
+            let errors_before = self.diagnostics.len();
+            match self.declaration() {
+                Some(stmt) => items.push(stmt),
+                None => {
+                    self.diagnostics.truncate(errors_before);
+                    self.diagnostics.push(Diagnostic::from_token(
+                        DiagnosticLevel::Warning,
+                        format!("Unsupported syntax in {} body, line skipped", construct),
+                        &start,
+                    ).with_code(error_codes::UNSUPPORTED_SYNTAX));
+                    self.synchronize();
+                },
             }
-            
-            Some(Stmt::Cluster { name, config })
-        } else {
-            None
         }
+        
+        self.consume(TokenKind::RBrace, &format!("'}}' to close {} body", construct))?;
+        
+        Some(items)
     }
     
     fn statement(&mut self) -> Option<Stmt> {
+        let span = self.span();
+        let allow_comments = self.take_allow_comments();
+        let kind = self.statement_kind()?;
+        self.apply_allow_comments(allow_comments, span.line);
+        Some(Stmt::new(kind, span))
+    }
+    
+    fn statement_kind(&mut self) -> Option<StmtKind> {
         match self.peek().kind {
             TokenKind::Identifier(ref s) if s == "print" => {
+                let span = self.span();
                 self.advance();
-                if self.peek().kind == TokenKind::LParen {
+                let stmt = if self.peek().kind == TokenKind::LParen {
                     self.advance();
-                    let expr = self.expression()?;
-                    if self.peek().kind == TokenKind::RParen {
-                        self.advance();
+                    let mut args = self.arguments()?;
+                    if args.len() == 1 {
+                        StmtKind::Print(args.remove(0))
+                    } else {
+                        // print(a, b, ...) is dispatched to the variadic builtin
+                        StmtKind::Expr(Expr::new(ExprKind::Call {
+                            callee: Box::new(Expr::new(ExprKind::Identifier("print".to_string()), span)),
+                            args,
+                        }, span))
                     }
-                    Some(Stmt::Print(expr))
                 } else {
-                    let expr = self.expression()?;
-                    Some(Stmt::Print(expr))
-                }
+                    StmtKind::Print(self.expression()?)
+                };
+                self.end_statement(stmt)
             },
             TokenKind::Return => {
                 self.advance();
-                let value = if self.peek().kind != TokenKind::Semicolon && self.peek().kind != TokenKind::Newline {
+                let value = if self.at_statement_end() {
+                    None
+                } else {
                     Some(self.expression()?)
+                };
+                self.end_statement(StmtKind::Return(value))
+            },
+            TokenKind::Break | TokenKind::Continue => {
+                let keyword = self.advance().clone();
+                if self.loop_depth == 0 {
+                    self.diagnostics.push(Diagnostic::from_token(
+                        DiagnosticLevel::Error,
+                        format!("{} outside of a loop", keyword.kind.describe()),
+                        &keyword,
+                    ).with_code(error_codes::JUMP_OUTSIDE_LOOP));
+                    return None;
+                }
+                let stmt = if keyword.kind == TokenKind::Break {
+                    StmtKind::Break
                 } else {
-                    None
+                    StmtKind::Continue
                 };
-                Some(Stmt::Return(value))
+                self.end_statement(stmt)
             },
             TokenKind::If => {
                 self.advance();
@@ -516,108 +729,293 @@ impl Parser {
                 self.advance();
                 self.for_statement()
             },
-            TokenKind::LBrace => {
+            TokenKind::Identifier(ref name) if name == "parallel" && self.peek_next().kind == TokenKind::For => {
+                self.advance();
+                self.advance();
+                let (var, iterable, body) = self.parallel_loop("for")?;
+                Some(StmtKind::ParallelFor { var, iterable, body })
+            },
+            TokenKind::LBrace if !self.at_map_literal() => {
                 self.advance();
-                Some(Stmt::Block(self.block()?))
+                Some(StmtKind::Block(self.block_body()?))
             },
             _ => {
                 let expr = self.expression()?;
-                Some(Stmt::Expr(expr))
+                self.end_statement(StmtKind::Expr(expr))
             },
         }
     }
     
-    fn if_statement(&mut self) -> Option<Stmt> {
+    fn if_statement(&mut self) -> Option<StmtKind> {
         let condition = self.expression()?;
         let then_branch = self.block()?;
         
+        // Allow `else`/`elif` on the line after the closing brace
+        if matches!(self.peek_past_newlines(), TokenKind::Else | TokenKind::Elif) {
+            self.skip_newlines();
+        }
+        
         let else_branch = if self.peek().kind == TokenKind::Else {
             self.advance();
             Some(self.block()?)
         } else if self.peek().kind == TokenKind::Elif {
+            let span = self.span();
             self.advance();
             // For simplicity, treat elif as nested if
-            Some(vec![self.if_statement()?])
+            Some(vec![Stmt::new(self.if_statement()?, span)])
         } else {
             None
         };
         
-        Some(Stmt::If {
+        Some(StmtKind::If {
             condition,
             then_branch,
             else_branch,
         })
     }
     
-    fn while_statement(&mut self) -> Option<Stmt> {
+    fn while_statement(&mut self) -> Option<StmtKind> {
         let condition = self.expression()?;
-        let body = self.block()?;
-        Some(Stmt::While { condition, body })
+        let body = self.loop_body()?;
+        Some(StmtKind::While { condition, body })
     }
     
-    fn for_statement(&mut self) -> Option<Stmt> {
-        let var = if let TokenKind::Identifier(n) = &self.peek().kind {
-            let n = n.clone();
-            self.advance();
-            n
-        } else {
-            return None;
-        };
+    fn for_statement(&mut self) -> Option<StmtKind> {
+        let (var, iterable) = self.loop_header("for")?;
+        let body = self.loop_body()?;
+        
+        Some(StmtKind::For {
+            var,
+            iterable,
+            body,
+        })
+    }
+    
+    /// Parses `var in iterable` after `keyword`
+    fn loop_header(&mut self, keyword: &str) -> Option<(String, Expr)> {
+        let var = self.expect_identifier(&format!("loop variable after '{}'", keyword))?;
         
         // Check for "in" keyword
-        if let TokenKind::Identifier(ref s) = self.peek().kind {
-            if s == "in" {
-                self.advance();
-            } else {
-                return None;
-            }
-        } else {
+        if !matches!(self.peek().kind, TokenKind::Identifier(ref s) if s == "in") {
+            self.error(format!("Expected 'in' after loop variable '{}', found {}", var, self.peek().kind.describe()));
             return None;
         }
+        self.advance();
         
         let iterable = self.expression()?;
-        let body = self.block()?;
+        Some((var, iterable))
+    }
+    
+    /// Parses `var in iterable { ... }` after `parallel for` or `parallel map`.
+    /// Each item runs on its own like a function call, so enclosing loops are
+    /// out of reach and `yield` does not apply.
+    fn parallel_loop(&mut self, keyword: &str) -> Option<(String, Expr, Vec<Stmt>)> {
+        let (var, iterable) = self.loop_header(&format!("parallel {}", keyword))?;
+        
+        let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_yields = self.function_yields.take();
+        let body = self.block();
+        self.loop_depth = enclosing_loops;
+        self.function_yields = enclosing_yields;
+        
+        Some((var, iterable, body?))
+    }
+    
+    /// Parses `acc = initial, var in iterable { ... }` after `parallel reduce`, with an
+    /// optional `combine (left, right) { ... }` after the body
+    fn parallel_reduce(&mut self, span: Span) -> Option<Expr> {
+        let acc = self.expect_identifier("accumulator name after 'parallel reduce'")?;
+        self.consume(TokenKind::Equal, &format!("'=' after accumulator '{}'", acc))?;
+        let initial = self.expression()?;
+        self.consume(TokenKind::Comma, "',' after the initial value")?;
+        let (var, iterable, body) = self.parallel_loop("reduce")?;
+        if var == acc {
+            self.error_with_code(error_codes::SHADOWED_ACCUMULATOR, format!("Loop variable '{}' shadows the accumulator of 'parallel reduce'", var));
+            return None;
+        }
+        
+        let combine = if matches!(self.peek().kind, TokenKind::Identifier(ref s) if s == "combine") {
+            self.advance();
+            Some(self.reduce_combine()?)
+        } else {
+            None
+        };
         
-        Some(Stmt::For {
+        Some(Expr::new(ExprKind::ParallelReduce {
+            acc,
+            initial: Box::new(initial),
             var,
-            iterable,
+            iterable: Box::new(iterable),
             body,
-        })
+            combine,
+        }, span))
     }
     
-    fn block(&mut self) -> Option<Vec<Stmt>> {
-        let mut statements = Vec::new();
+    /// Parses `(left, right) { ... }` after `combine`. Like the fold body, it runs
+    /// on its own, out of reach of enclosing loops.
+    fn reduce_combine(&mut self) -> Option<ReduceCombine> {
+        self.consume(TokenKind::LParen, "'(' after 'combine'")?;
+        let left = self.expect_identifier("first parameter of 'combine'")?;
+        self.consume(TokenKind::Comma, "',' between the parameters of 'combine'")?;
+        let right = self.expect_identifier("second parameter of 'combine'")?;
+        self.consume(TokenKind::RParen, "')' after the parameters of 'combine'")?;
+        if left == right {
+            self.error_with_code(error_codes::DUPLICATE_MEMBER, format!("Both parameters of 'combine' are named '{}'", left));
+            return None;
+        }
         
+        let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_yields = self.function_yields.take();
+        let body = self.block();
+        self.loop_depth = enclosing_loops;
+        self.function_yields = enclosing_yields;
+        
+        Some(ReduceCombine { left, right, body: body? })
+    }
+    
+    fn loop_body(&mut self) -> Option<Vec<Stmt>> {
+        self.loop_depth += 1;
+        let body = self.block();
+        self.loop_depth -= 1;
+        body
+    }
+    
+    fn block(&mut self) -> Option<Vec<Stmt>> {
+        self.skip_newlines();
         if self.peek().kind == TokenKind::LBrace {
             self.advance();
-            while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-                if let Some(stmt) = self.declaration() {
-                    statements.push(stmt);
-                } else {
-                    self.advance();
-                }
-            }
-            if self.peek().kind == TokenKind::RBrace {
-                self.advance();
-            }
+            self.block_body()
         } else {
             // Single statement
-            if let Some(stmt) = self.statement() {
-                statements.push(stmt);
+            Some(vec![self.statement()?])
+        }
+    }
+    
+    /// Parses statements up to and including the closing '}'; the opening '{' has already been consumed
+    fn block_body(&mut self) -> Option<Vec<Stmt>> {
+        let mut statements = Vec::new();
+        
+        loop {
+            self.skip_separators();
+            if self.peek().kind == TokenKind::RBrace || self.is_at_end() {
+                break;
+            }
+            
+            let errors_before = self.diagnostics.len();
+            match self.declaration() {
+                Some(stmt) => statements.push(stmt),
+                None => self.recover(errors_before),
             }
         }
         
+        self.consume(TokenKind::RBrace, "'}' to close block")?;
+        
         Some(statements)
     }
     
     fn expression(&mut self) -> Option<Expr> {
-        self.assignment()
+        match self.peek().kind {
+            TokenKind::Yield => self.yield_expression(),
+            TokenKind::Suspend => {
+                let span = self.span();
+                self.advance();
+                let value = if self.at_expression_end() {
+                    None
+                } else {
+                    Some(Box::new(self.expression()?))
+                };
+                Some(Expr::new(ExprKind::Suspend(value), span))
+            },
+            _ => self.assignment(),
+        }
+    }
+    
+    /// Parses `yield` or `yield value`, marking the enclosing function as a generator
+    fn yield_expression(&mut self) -> Option<Expr> {
+        let keyword = self.advance().clone();
+        match self.function_yields.as_mut() {
+            Some(yields) => *yields = true,
+            None => {
+                self.diagnostics.push(Diagnostic::from_token(
+                    DiagnosticLevel::Error,
+                    "'yield' outside of a function".to_string(),
+                    &keyword,
+                ).with_code(error_codes::YIELD_OUTSIDE_FUNCTION));
+                return None;
+            },
+        }
+        
+        let value = if self.at_expression_end() {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        Some(Expr::new(ExprKind::Yield(value), Self::span_of(&keyword)))
+    }
+    
+    /// Parses `spawn Name{ field: value, ... }`
+    fn spawn_expression(&mut self) -> Option<Expr> {
+        let keyword = self.advance().clone();
+        match self.call()?.kind {
+            ExprKind::StructLiteral { name, fields } => Some(Expr::new(ExprKind::Spawn { actor: name, fields }, Self::span_of(&keyword))),
+            _ => {
+                self.diagnostics.push(Diagnostic::from_token(
+                    DiagnosticLevel::Error,
+                    "Expected an actor literal such as 'Name{}' after 'spawn'".to_string(),
+                    &keyword,
+                ).with_code(error_codes::INVALID_ACTOR_OPERAND));
+                None
+            },
+        }
+    }
+    
+    /// Parses `send actor.method(args)`
+    fn send_expression(&mut self) -> Option<Expr> {
+        let keyword = self.advance().clone();
+        if let ExprKind::Call { callee, args } = self.call()?.kind {
+            if let ExprKind::Member { object, member } = callee.kind {
+                return Some(Expr::new(ExprKind::Send { actor: object, method: member, args }, Self::span_of(&keyword)));
+            }
+        }
+        self.diagnostics.push(Diagnostic::from_token(
+            DiagnosticLevel::Error,
+            "Expected a method call such as 'actor.method()' after 'send'".to_string(),
+            &keyword,
+        ).with_code(error_codes::INVALID_ACTOR_OPERAND));
+        None
     }
     
     fn assignment(&mut self) -> Option<Expr> {
         let expr = self.or()?;
-        // Assignment parsing can be added here later
-        Some(expr)
+        
+        let op = match self.peek().kind {
+            TokenKind::Equal => None,
+            TokenKind::PlusEqual => Some(BinaryOp::Add),
+            TokenKind::MinusEqual => Some(BinaryOp::Subtract),
+            TokenKind::StarEqual => Some(BinaryOp::Multiply),
+            TokenKind::SlashEqual => Some(BinaryOp::Divide),
+            TokenKind::PercentEqual => Some(BinaryOp::Modulo),
+            _ => return Some(expr),
+        };
+        let operator = self.advance().clone();
+        
+        if !matches!(expr.kind, ExprKind::Identifier(_) | ExprKind::Member { .. } | ExprKind::Index { .. }) {
+            self.diagnostics.push(Diagnostic::from_token(
+                DiagnosticLevel::Error,
+                format!("Invalid assignment target before {}", operator.kind.describe()),
+                &operator,
+            ).with_code(error_codes::INVALID_ASSIGNMENT_TARGET));
+            return None;
+        }
+        
+        // Assignment is right-associative: a = b = c
+        let value = self.assignment()?;
+        
+        Some(Expr::new(ExprKind::Assign {
+            target: Box::new(expr),
+            op,
+            value: Box::new(value),
+        }, Self::span_of(&operator)))
     }
     
     fn or(&mut self) -> Option<Expr> {
@@ -625,13 +1023,14 @@ impl Parser {
         
         while self.peek().kind == TokenKind::Or {
             let op = BinaryOp::Or;
+            let span = self.span();
             self.advance();
             let right = self.and()?;
-            expr = Expr::Binary {
+            expr = Expr::new(ExprKind::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
-            };
+            }, span);
         }
         
         Some(expr)
@@ -642,13 +1041,14 @@ impl Parser {
         
         while self.peek().kind == TokenKind::And {
             let op = BinaryOp::And;
+            let span = self.span();
             self.advance();
             let right = self.equality()?;
-            expr = Expr::Binary {
+            expr = Expr::new(ExprKind::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
-            };
+            }, span);
         }
         
         Some(expr)
@@ -663,13 +1063,14 @@ impl Parser {
                 TokenKind::NotEqual => BinaryOp::NotEqual,
                 _ => break,
             };
+            let span = self.span();
             self.advance();
             let right = self.comparison()?;
-            expr = Expr::Binary {
+            expr = Expr::new(ExprKind::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
-            };
+            }, span);
         }
         
         Some(expr)
@@ -692,13 +1093,14 @@ impl Parser {
                 TokenKind::LessEqual => BinaryOp::LessEqual,
                 _ => break,
             };
+            let span = self.span();
             self.advance();
             let right = self.term()?;
-            expr = Expr::Binary {
+            expr = Expr::new(ExprKind::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
-            };
+            }, span);
         }
         
         Some(expr)
@@ -713,20 +1115,21 @@ impl Parser {
                 TokenKind::Minus => BinaryOp::Subtract,
                 _ => break,
             };
+            let span = self.span();
             self.advance();
             let right = self.factor()?;
-            expr = Expr::Binary {
+            expr = Expr::new(ExprKind::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
-            };
+            }, span);
         }
         
         Some(expr)
     }
     
     fn factor(&mut self) -> Option<Expr> {
-        let mut expr = self.unary()?;
+        let mut expr = self.cast()?;
         
         while matches!(self.peek().kind, TokenKind::Star | TokenKind::Slash | TokenKind::Percent) {
             let op = match self.peek().kind {
@@ -735,31 +1138,73 @@ impl Parser {
                 TokenKind::Percent => BinaryOp::Modulo,
                 _ => break,
             };
+            let span = self.span();
             self.advance();
-            let right = self.unary()?;
-            expr = Expr::Binary {
+            let right = self.cast()?;
+            expr = Expr::new(ExprKind::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
-            };
+            }, span);
+        }
+        
+        Some(expr)
+    }
+    
+    /// `as` binds tighter than `*` and looser than unary operators, so
+    /// `-a as f64 / b as f64` divides two converted operands
+    fn cast(&mut self) -> Option<Expr> {
+        let mut expr = self.unary()?;
+        
+        while matches!(&self.peek().kind, TokenKind::Identifier(word) if word == "as") {
+            let span = self.span();
+            self.advance();
+            let target = self.type_annotation()?;
+            expr = Expr::new(ExprKind::Cast {
+                expr: Box::new(expr),
+                target,
+            }, span);
         }
         
         Some(expr)
     }
     
     fn unary(&mut self) -> Option<Expr> {
+        match self.peek().kind {
+            TokenKind::Resume => {
+                let span = self.span();
+                self.advance();
+                let coroutine = self.unary()?;
+                return Some(Expr::new(ExprKind::Resume {
+                    coroutine: Box::new(coroutine),
+                }, span));
+            },
+            TokenKind::Await => {
+                let span = self.span();
+                self.advance();
+                let expr = self.unary()?;
+                return Some(Expr::new(ExprKind::Await {
+                    expr: Box::new(expr),
+                }, span));
+            },
+            TokenKind::Spawn => return self.spawn_expression(),
+            // `send(to, amount)` in contracts is a call, not an actor message
+            TokenKind::Send if self.peek_next().kind != TokenKind::LParen => return self.send_expression(),
+            _ => {},
+        }
         if matches!(self.peek().kind, TokenKind::Not | TokenKind::Minus) {
             let op = match self.peek().kind {
                 TokenKind::Not => UnaryOp::Not,
                 TokenKind::Minus => UnaryOp::Negate,
                 _ => return self.call(),
             };
+            let span = self.span();
             self.advance();
             let expr = self.unary()?;
-            Some(Expr::Unary {
+            Some(Expr::new(ExprKind::Unary {
                 op,
                 expr: Box::new(expr),
-            })
+            }, span))
         } else {
             self.call()
         }
@@ -771,48 +1216,29 @@ impl Parser {
         loop {
             if self.peek().kind == TokenKind::LParen {
                 self.advance();
-                let mut args = Vec::new();
-                if self.peek().kind != TokenKind::RParen {
-                    loop {
-                        args.push(self.expression()?);
-                        if self.peek().kind != TokenKind::Comma {
-                            break;
-                        }
-                        self.advance();
-                    }
-                }
-                if self.peek().kind == TokenKind::RParen {
-                    self.advance();
-                }
-                
-                if let Expr::Identifier(callee) = expr {
-                    expr = Expr::Call {
-                        callee,
-                        args,
-                    };
-                }
+                let args = self.arguments()?;
+                let span = expr.span;
+                expr = Expr::new(ExprKind::Call {
+                    callee: Box::new(expr),
+                    args,
+                }, span);
             } else if self.peek().kind == TokenKind::Dot {
                 self.advance();
-                if let TokenKind::Identifier(member) = &self.peek().kind {
-                    let member = member.clone();
-                    self.advance();
-                    expr = Expr::Member {
-                        object: Box::new(expr),
-                        member,
-                    };
-                } else {
-                    break;
-                }
+                let span = self.span();
+                let member = self.expect_identifier("member name after '.'")?;
+                expr = Expr::new(ExprKind::Member {
+                    object: Box::new(expr),
+                    member,
+                }, span);
             } else if self.peek().kind == TokenKind::LBracket {
+                let span = self.span();
                 self.advance();
                 let index = self.expression()?;
-                if self.peek().kind == TokenKind::RBracket {
-                    self.advance();
-                }
-                expr = Expr::Index {
+                self.consume(TokenKind::RBracket, "']' after index")?;
+                expr = Expr::new(ExprKind::Index {
                     object: Box::new(expr),
                     index: Box::new(index),
-                };
+                }, span);
             } else {
                 break;
             }
@@ -821,61 +1247,315 @@ impl Parser {
         Some(expr)
     }
     
+    /// Parses a comma-separated argument list; the opening '(' has already been consumed
+    fn arguments(&mut self) -> Option<Vec<Expr>> {
+        let mut args = Vec::new();
+        self.skip_newlines();
+        while self.peek().kind != TokenKind::RParen {
+            args.push(self.expression()?);
+            self.skip_newlines();
+            if self.peek().kind != TokenKind::Comma {
+                break;
+            }
+            self.advance();
+            self.skip_newlines();
+        }
+        self.consume(TokenKind::RParen, "')' after arguments")?;
+        Some(args)
+    }
+    
     fn primary(&mut self) -> Option<Expr> {
+        let span = self.span();
         match &self.peek().kind {
             TokenKind::Number(n) => {
                 let n = *n;
                 self.advance();
-                Some(Expr::Number(n))
+                Some(Expr::new(ExprKind::Number(n), span))
             },
             TokenKind::String(s) => {
                 let s = s.clone();
                 self.advance();
-                Some(Expr::String(s))
+                Some(Expr::new(ExprKind::String(s), span))
             },
             TokenKind::Boolean(b) => {
                 let b = *b;
                 self.advance();
-                Some(Expr::Boolean(b))
+                Some(Expr::new(ExprKind::Boolean(b), span))
             },
             TokenKind::Identifier(name) => {
                 let name = name.clone();
                 self.advance();
-                Some(Expr::Identifier(name))
+                if name == "parallel" && matches!(self.peek().kind, TokenKind::Identifier(ref next) if next == "map") {
+                    self.advance();
+                    let (var, iterable, body) = self.parallel_loop("map")?;
+                    return Some(Expr::new(ExprKind::ParallelMap {
+                        var,
+                        iterable: Box::new(iterable),
+                        body,
+                    }, span));
+                }
+                if name == "parallel" && matches!(self.peek().kind, TokenKind::Identifier(ref next) if next == "reduce") {
+                    self.advance();
+                    return self.parallel_reduce(span);
+                }
+                if self.peek().kind == TokenKind::LBrace && self.at_struct_literal(&name) {
+                    self.advance();
+                    return self.struct_literal(name, span);
+                }
+                if self.peek().kind == TokenKind::ColonColon {
+                    self.advance();
+                    let variant = self.expect_identifier(&format!("variant name after '{}::'", name))?;
+                    let args = if self.peek().kind == TokenKind::LParen {
+                        self.advance();
+                        self.arguments()?
+                    } else {
+                        Vec::new()
+                    };
+                    return Some(Expr::new(ExprKind::EnumVariant {
+                        enum_name: name,
+                        variant,
+                        args,
+                    }, span));
+                }
+                Some(Expr::new(ExprKind::Identifier(name), span))
+            },
+            TokenKind::LParen if self.at_lambda() => {
+                self.advance();
+                self.lambda(span)
             },
             TokenKind::LParen => {
                 self.advance();
+                self.skip_newlines();
                 let expr = self.expression()?;
-                if self.peek().kind == TokenKind::RParen {
+                self.skip_newlines();
+                if self.peek().kind != TokenKind::Comma {
+                    self.consume(TokenKind::RParen, "')' after expression")?;
+                    return Some(expr);
+                }
+                
+                // Tuple literal: a fixed-size list, matched by tuple patterns
+                let mut elements = vec![expr];
+                while self.peek().kind == TokenKind::Comma {
                     self.advance();
+                    self.skip_newlines();
+                    if self.peek().kind == TokenKind::RParen {
+                        break;
+                    }
+                    elements.push(self.expression()?);
+                    self.skip_newlines();
                 }
-                Some(expr)
+                self.consume(TokenKind::RParen, "')' after tuple elements")?;
+                Some(Expr::new(ExprKind::List(elements), span))
+            },
+            // Contract builtins are keywords, but called like functions
+            TokenKind::Require | TokenKind::Send if self.peek_next().kind == TokenKind::LParen => {
+                let name = match self.advance().kind {
+                    TokenKind::Require => "require",
+                    _ => "send",
+                };
+                Some(Expr::new(ExprKind::Identifier(name.to_string()), span))
             },
             TokenKind::LBracket => {
                 self.advance();
                 let mut elements = Vec::new();
-                if self.peek().kind != TokenKind::RBracket {
-                    loop {
-                        elements.push(self.expression()?);
-                        if self.peek().kind != TokenKind::Comma {
-                            break;
-                        }
-                        self.advance();
+                self.skip_newlines();
+                while self.peek().kind != TokenKind::RBracket {
+                    elements.push(self.expression()?);
+                    self.skip_newlines();
+                    if self.peek().kind != TokenKind::Comma {
+                        break;
                     }
-                }
-                if self.peek().kind == TokenKind::RBracket {
                     self.advance();
+                    self.skip_newlines();
                 }
-                Some(Expr::List(elements))
+                self.consume(TokenKind::RBracket, "']' after list elements")?;
+                Some(Expr::new(ExprKind::List(elements), span))
+            },
+            TokenKind::LBrace => {
+                self.advance();
+                self.map_literal(span)
             },
-            _ => None,
+            TokenKind::Coroutine | TokenKind::Async => {
+                let keyword = self.advance().kind.clone();
+                self.consume(TokenKind::LBrace, &format!("'{{' after {}", keyword.describe()))?;
+                // Like a function body: enclosing loops are out of reach and `yield` does not apply
+                let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+                let enclosing_yields = self.function_yields.take();
+                let body = self.block_body();
+                self.loop_depth = enclosing_loops;
+                self.function_yields = enclosing_yields;
+                let body = body?;
+                let kind = if keyword == TokenKind::Async {
+                    ExprKind::Async { body }
+                } else {
+                    ExprKind::Coroutine { body }
+                };
+                Some(Expr::new(kind, span))
+            },
+            _ => {
+                self.error(format!("Expected expression, found {}", self.peek().kind.describe()));
+                None
+            },
+        }
+    }
+    
+    /// Parses a lambda after its opening '(': `(params) => expr` or `(params) => { ... }`
+    fn lambda(&mut self, span: Span) -> Option<Expr> {
+        let params = self.parameters()?;
+        self.consume(TokenKind::FatArrow, "'=>' after lambda parameters")?;
+        
+        // The lambda body is a new function: enclosing loops cannot be exited from it
+        let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_yields = self.function_yields.replace(false);
+        let body = if self.peek().kind == TokenKind::LBrace && !self.at_map_literal() {
+            self.advance();
+            self.block_body()
+        } else {
+            self.expression().map(|expr| {
+                let span = expr.span;
+                vec![Stmt::new(StmtKind::Return(Some(expr)), span)]
+            })
+        };
+        self.loop_depth = enclosing_loops;
+        let yields = std::mem::replace(&mut self.function_yields, enclosing_yields);
+        
+        Some(Expr::new(ExprKind::Lambda {
+            params,
+            body: Self::function_body(body?, yields),
+        }, span))
+    }
+    
+    /// A function whose body uses `yield` is a generator function: calling it
+    /// returns a generator over the body instead of running it
+    fn function_body(body: Vec<Stmt>, yields: Option<bool>) -> Vec<Stmt> {
+        if yields == Some(true) {
+            let generator = Expr::from(ExprKind::Generator { body });
+            vec![StmtKind::Return(Some(generator)).into()]
+        } else {
+            body
+        }
+    }
+    
+    /// Whether the '(' at the current token opens a lambda parameter list,
+    /// i.e. its matching ')' is followed by '=>'
+    fn at_lambda(&self) -> bool {
+        let mut depth = 0usize;
+        for (offset, token) in self.tokens[self.current..].iter().enumerate() {
+            match token.kind {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return matches!(
+                            self.tokens.get(self.current + offset + 1).map(|t| &t.kind),
+                            Some(TokenKind::FatArrow)
+                        );
+                    }
+                },
+                TokenKind::EOF => return false,
+                _ => {},
+            }
+        }
+        false
+    }
+    
+    /// Parses `{ key: value, ... }` entries; the opening '{' has already been consumed.
+    /// Keys are identifiers or strings.
+    fn map_literal(&mut self, span: Span) -> Option<Expr> {
+        let mut entries = Vec::new();
+        self.skip_newlines();
+        while self.peek().kind != TokenKind::RBrace {
+            let key_span = self.span();
+            let key = match &self.peek().kind {
+                TokenKind::Identifier(name) => Expr::new(ExprKind::Identifier(name.clone()), key_span),
+                TokenKind::String(s) => Expr::new(ExprKind::String(s.clone()), key_span),
+                _ => {
+                    self.error(format!("Expected map key (identifier or string), found {}", self.peek().kind.describe()));
+                    return None;
+                },
+            };
+            self.advance();
+            
+            self.consume(TokenKind::Colon, "':' after map key")?;
+            self.skip_newlines();
+            let value = self.expression()?;
+            entries.push((key, value));
+            
+            self.skip_newlines();
+            if self.peek().kind != TokenKind::Comma {
+                break;
+            }
+            self.advance();
+            self.skip_newlines();
+        }
+        self.consume(TokenKind::RBrace, "'}' after map entries")?;
+        Some(Expr::new(ExprKind::Map(entries), span))
+    }
+    
+    /// Parses `field: value, ...` after `Name {`
+    fn struct_literal(&mut self, name: String, span: Span) -> Option<Expr> {
+        let mut fields = Vec::new();
+        self.skip_newlines();
+        while self.peek().kind != TokenKind::RBrace {
+            let field = self.expect_identifier(&format!("field name in '{}' literal", name))?;
+            self.consume(TokenKind::Colon, &format!("':' after field '{}'", field))?;
+            self.skip_newlines();
+            fields.push((field, self.expression()?));
+            
+            self.skip_newlines();
+            if self.peek().kind != TokenKind::Comma {
+                break;
+            }
+            self.advance();
+            self.skip_newlines();
         }
+        self.consume(TokenKind::RBrace, &format!("'}}' to close '{}' literal", name))?;
+        Some(Expr::new(ExprKind::StructLiteral { name, fields }, span))
+    }
+    
+    /// Whether `Name {` at the current '{' starts a struct literal rather than a block,
+    /// as in `if ready { ... }`. Struct names are capitalized, and the brace must be
+    /// followed by `field:` or be empty.
+    fn at_struct_literal(&self, name: &str) -> bool {
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            return false;
+        }
+        let mut tokens = self.tokens[self.current + 1..].iter()
+            .filter(|t| t.kind != TokenKind::Newline);
+        matches!(
+            (tokens.next().map(|t| &t.kind), tokens.next().map(|t| &t.kind)),
+            (Some(TokenKind::Identifier(_)), Some(TokenKind::Colon)) | (Some(TokenKind::RBrace), _)
+        )
+    }
+    
+    /// At statement level `{` opens a block unless it is followed by `key:`
+    fn at_map_literal(&self) -> bool {
+        let mut tokens = self.tokens[self.current + 1..].iter()
+            .filter(|t| t.kind != TokenKind::Newline);
+        matches!(
+            (tokens.next().map(|t| &t.kind), tokens.next().map(|t| &t.kind)),
+            (Some(TokenKind::Identifier(_) | TokenKind::String(_)), Some(TokenKind::Colon))
+        )
+    }
+    
+    /// Span of the current token
+    fn span(&self) -> Span {
+        Self::span_of(self.peek())
+    }
+    
+    fn span_of(token: &Token) -> Span {
+        Span::new(token.line, token.col)
     }
     
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
     
+    /// The token after the current one
+    fn peek_next(&self) -> &Token {
+        self.tokens.get(self.current + 1).unwrap_or(&self.tokens[self.current])
+    }
+    
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -887,17 +1567,126 @@ impl Parser {
         matches!(self.tokens[self.current].kind, TokenKind::EOF)
     }
     
-    fn match_statement(&mut self) -> Option<Stmt> {
+    /// Kind of the next token that is not a newline
+    fn peek_past_newlines(&self) -> &TokenKind {
+        self.tokens[self.current..].iter()
+            .map(|t| &t.kind)
+            .find(|kind| **kind != TokenKind::Newline)
+            .unwrap_or(&TokenKind::EOF)
+    }
+    
+    fn skip_newlines(&mut self) {
+        while self.peek().kind == TokenKind::Newline {
+            self.advance();
+        }
+    }
+    
+    fn skip_separators(&mut self) {
+        while matches!(self.peek().kind, TokenKind::Newline | TokenKind::Semicolon) {
+            self.advance();
+        }
+    }
+    
+    /// Consumes a token of the given kind or reports `Expected <what>, found <token>`
+    fn consume(&mut self, kind: TokenKind, what: &str) -> Option<()> {
+        if self.peek().kind == kind {
+            self.advance();
+            Some(())
+        } else {
+            self.error(format!("Expected {}, found {}", what, self.peek().kind.describe()));
+            None
+        }
+    }
+    
+    fn expect_identifier(&mut self, what: &str) -> Option<String> {
+        if let TokenKind::Identifier(name) = &self.peek().kind {
+            let name = name.clone();
+            self.advance();
+            Some(name)
+        } else {
+            self.error(format!("Expected {}, found {}", what, self.peek().kind.describe()));
+            None
+        }
+    }
+    
+    fn at_statement_end(&self) -> bool {
+        matches!(
+            self.peek().kind,
+            TokenKind::Newline | TokenKind::Semicolon | TokenKind::RBrace | TokenKind::EOF
+        )
+    }
+    
+    /// Whether an optional operand (of `yield` or `suspend`) is absent
+    fn at_expression_end(&self) -> bool {
+        self.at_statement_end() || matches!(self.peek().kind, TokenKind::RParen | TokenKind::Comma)
+    }
+    
+    /// Simple statements must be followed by a newline, ';', '}' or the end of the file
+    fn end_statement(&mut self, stmt: StmtKind) -> Option<StmtKind> {
+        if self.at_statement_end() {
+            Some(stmt)
+        } else {
+            self.error(format!("Expected end of statement, found {}", self.peek().kind.describe()));
+            None
+        }
+    }
+    
+    /// Reports a syntax error at the current token
+    fn error(&mut self, message: String) {
+        self.error_with_code(error_codes::SYNTAX_ERROR, message);
+    }
+    
+    /// Reports an error of another kind than a syntax error at the current token
+    fn error_with_code(&mut self, code: &str, message: String) {
+        let diagnostic = Diagnostic::from_token(DiagnosticLevel::Error, message, self.peek()).with_code(code);
+        self.diagnostics.push(diagnostic);
+    }
+    
+    /// Called after a declaration failed to parse: makes sure the failure was reported,
+    /// then skips to the next statement boundary
+    fn recover(&mut self, errors_before: usize) {
+        if self.diagnostics.len() == errors_before {
+            self.error(format!("Unexpected {}", self.peek().kind.describe()));
+        }
+        self.synchronize();
+    }
+    
+    /// Skips tokens up to the end of the current line, stepping over nested brackets so that
+    /// a multi-line construct is skipped as a whole. Stops before a '}' that closes the
+    /// enclosing block.
+    fn synchronize(&mut self) {
+        let mut depth = 0usize;
+        while !self.is_at_end() {
+            match self.peek().kind {
+                TokenKind::Newline | TokenKind::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                },
+                TokenKind::RBrace if depth == 0 => return,
+                TokenKind::LBrace | TokenKind::LParen | TokenKind::LBracket => depth += 1,
+                TokenKind::RBrace | TokenKind::RParen | TokenKind::RBracket => {
+                    depth = depth.saturating_sub(1);
+                },
+                _ => {},
+            }
+            self.advance();
+        }
+    }
+    
+    fn match_statement(&mut self) -> Option<StmtKind> {
         let expr = self.expression()?;
         
-        if self.peek().kind != TokenKind::LBrace {
-            return None;
-        }
-        self.advance();
+        self.skip_newlines();
+        self.consume(TokenKind::LBrace, "'{' after match expression")?;
         
         let mut arms = Vec::new();
         
-        while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
+        loop {
+            self.skip_newlines();
+            if self.peek().kind == TokenKind::RBrace || self.is_at_end() {
+                break;
+            }
+            
             let pattern = self.pattern()?;
             
             let guard = if self.peek().kind == TokenKind::If {
@@ -907,28 +1696,21 @@ impl Parser {
                 None
             };
             
-            if self.peek().kind != TokenKind::Arrow {
+            if !matches!(self.peek().kind, TokenKind::FatArrow | TokenKind::Arrow) {
+                self.error(format!("Expected '=>' after match pattern, found {}", self.peek().kind.describe()));
                 return None;
             }
             self.advance();
+            self.skip_newlines();
             
             let body = if self.peek().kind == TokenKind::LBrace {
                 self.advance();
-                let mut stmts = Vec::new();
-                while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-                    if let Some(stmt) = self.declaration() {
-                        stmts.push(stmt);
-                    } else {
-                        self.advance();
-                    }
-                }
-                if self.peek().kind == TokenKind::RBrace {
-                    self.advance();
-                }
-                stmts
+                self.block_body()?
             } else {
                 // Single expression
-                vec![Stmt::Expr(self.expression()?)]
+                let expr = self.expression()?;
+                let span = expr.span;
+                vec![Stmt::new(StmtKind::Expr(expr), span)]
             };
             
             arms.push(MatchArm {
@@ -937,16 +1719,18 @@ impl Parser {
                 body,
             });
             
+            self.skip_newlines();
             if self.peek().kind == TokenKind::Comma {
                 self.advance();
+            } else if self.peek().kind != TokenKind::RBrace {
+                self.error(format!("Expected ',' or '}}' after match arm, found {}", self.peek().kind.describe()));
+                return None;
             }
         }
         
-        if self.peek().kind == TokenKind::RBrace {
-            self.advance();
-        }
+        self.consume(TokenKind::RBrace, "'}' to close match")?;
         
-        Some(Stmt::MatchStmt {
+        Some(StmtKind::MatchStmt {
             expr,
             arms,
         })
@@ -957,6 +1741,27 @@ impl Parser {
             TokenKind::Identifier(ref name) => {
                 let name = name.clone();
                 self.advance();
+                if self.peek().kind == TokenKind::LBrace {
+                    self.advance();
+                    return self.struct_pattern(name);
+                }
+                if self.peek().kind == TokenKind::ColonColon {
+                    self.advance();
+                    let variant = self.expect_identifier(&format!("variant name after '{}::'", name))?;
+                    let fields = if self.peek().kind == TokenKind::LParen {
+                        self.advance();
+                        let fields = self.pattern_list(TokenKind::RParen)?;
+                        self.consume(TokenKind::RParen, "')' to close variant pattern")?;
+                        fields
+                    } else {
+                        Vec::new()
+                    };
+                    return Some(Pattern::Variant {
+                        enum_name: name,
+                        variant,
+                        fields,
+                    });
+                }
                 Some(Pattern::Identifier(name))
             },
             TokenKind::Number(n) => {
@@ -974,128 +1779,94 @@ impl Parser {
             },
             TokenKind::LParen => {
                 self.advance();
-                let mut patterns = Vec::new();
-                while self.peek().kind != TokenKind::RParen && !self.is_at_end() {
-                    if let Some(pattern) = self.pattern() {
-                        patterns.push(pattern);
-                    }
-                    if self.peek().kind == TokenKind::Comma {
-                        self.advance();
-                    }
-                }
-                if self.peek().kind == TokenKind::RParen {
-                    self.advance();
-                }
+                let patterns = self.pattern_list(TokenKind::RParen)?;
+                self.consume(TokenKind::RParen, "')' to close tuple pattern")?;
                 Some(Pattern::Tuple(patterns))
             },
             TokenKind::LBracket => {
                 self.advance();
-                let mut patterns = Vec::new();
-                while self.peek().kind != TokenKind::RBracket && !self.is_at_end() {
-                    if let Some(pattern) = self.pattern() {
-                        patterns.push(pattern);
-                    }
-                    if self.peek().kind == TokenKind::Comma {
-                        self.advance();
-                    }
-                }
-                if self.peek().kind == TokenKind::RBracket {
-                    self.advance();
-                }
+                let patterns = self.pattern_list(TokenKind::RBracket)?;
+                self.consume(TokenKind::RBracket, "']' to close list pattern")?;
                 Some(Pattern::List(patterns))
             },
             TokenKind::Question => {
                 self.advance();
                 Some(Pattern::Wildcard)
             },
-            _ => None,
+            _ => {
+                self.error(format!("Expected pattern, found {}", self.peek().kind.describe()));
+                None
+            },
         }
     }
     
-    fn async_function(&mut self) -> Option<Stmt> {
-        if self.peek().kind != TokenKind::Fn {
-            return None;
-        }
-        self.advance();
-        
-        let name = if let TokenKind::Identifier(ref n) = self.peek().kind {
-            let n = n.clone();
-            self.advance();
-            n
-        } else {
-            return None;
-        };
-        
-        let params = if self.peek().kind == TokenKind::LParen {
-            self.advance();
-            let mut params = Vec::new();
-            while self.peek().kind != TokenKind::RParen && !self.is_at_end() {
-                if let TokenKind::Identifier(ref name) = self.peek().kind {
-                    let name = name.clone();
-                    self.advance();
-                    let type_annot = if self.peek().kind == TokenKind::Colon {
-                        self.advance();
-                        if let TokenKind::Identifier(ref t) = self.peek().kind {
-                            let t = t.clone();
-                            self.advance();
-                            Some(t)
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    };
-                    params.push((name, type_annot));
-                }
-                if self.peek().kind == TokenKind::Comma {
-                    self.advance();
-                }
-            }
-            if self.peek().kind == TokenKind::RParen {
-                self.advance();
-            }
-            params
-        } else {
-            Vec::new()
-        };
-        
-        let return_type = if self.peek().kind == TokenKind::Arrow {
-            self.advance();
-            if let TokenKind::Identifier(ref t) = self.peek().kind {
-                let t = t.clone();
+    /// Parses `field: pattern` or shorthand `field` entries after `Name {`
+    fn struct_pattern(&mut self, name: String) -> Option<Pattern> {
+        let mut fields = Vec::new();
+        self.skip_newlines();
+        while self.peek().kind != TokenKind::RBrace {
+            let field = self.expect_identifier(&format!("field name in '{}' pattern", name))?;
+            let pattern = if self.peek().kind == TokenKind::Colon {
                 self.advance();
-                Some(t)
+                self.pattern()?
             } else {
-                None
+                Pattern::Identifier(field.clone())
+            };
+            fields.push((field, pattern));
+            
+            self.skip_newlines();
+            if self.peek().kind != TokenKind::Comma {
+                break;
             }
-        } else {
-            None
-        };
-        
-        let body = if self.peek().kind == TokenKind::LBrace {
             self.advance();
-            let mut stmts = Vec::new();
-            while self.peek().kind != TokenKind::RBrace && !self.is_at_end() {
-                if let Some(stmt) = self.declaration() {
-                    stmts.push(stmt);
-                } else {
-                    self.advance();
-                }
-            }
-            if self.peek().kind == TokenKind::RBrace {
-                self.advance();
+            self.skip_newlines();
+        }
+        self.consume(TokenKind::RBrace, &format!("'}}' to close '{}' pattern", name))?;
+        Some(Pattern::Struct { name, fields })
+    }
+    
+    fn pattern_list(&mut self, close: TokenKind) -> Option<Vec<Pattern>> {
+        let mut patterns = Vec::new();
+        self.skip_newlines();
+        while self.peek().kind != close {
+            patterns.push(self.pattern()?);
+            self.skip_newlines();
+            if self.peek().kind != TokenKind::Comma {
+                break;
             }
-            stmts
-        } else {
-            Vec::new()
-        };
-        
-        Some(Stmt::AsyncFn {
-            name,
-            params,
-            return_type,
-            body,
-        })
+            self.advance();
+            self.skip_newlines();
+        }
+        Some(patterns)
+    }
+    
+    fn coroutine_function(&mut self) -> Option<StmtKind> {
+        self.consume(TokenKind::Fn, "'fn' after 'coroutine'")?;
+        
+        match self.function()? {
+            StmtKind::Fn { name, params, return_type, return_span, body, .. } => Some(StmtKind::CoroutineFn {
+                name,
+                params,
+                return_type,
+                return_span,
+                body,
+            }),
+            _ => unreachable!("function() only produces StmtKind::Fn"),
+        }
+    }
+    
+    fn async_function(&mut self) -> Option<StmtKind> {
+        self.consume(TokenKind::Fn, "'fn' after 'async'")?;
+        
+        match self.function()? {
+            StmtKind::Fn { name, params, return_type, return_span, body, .. } => Some(StmtKind::AsyncFn {
+                name,
+                params,
+                return_type,
+                return_span,
+                body,
+            }),
+            _ => unreachable!("function() only produces StmtKind::Fn"),
+        }
     }
 }
-
@@ -1,5 +1,5 @@
 use crate::ast::*;
-use crate::vm::Value;
+use crate::value::Value;
 
 /// Pattern matching engine
 pub struct PatternMatcher;
@@ -37,9 +37,12 @@ impl PatternMatcher {
                 patterns.iter().zip(values.iter())
                     .all(|(p, v)| Self::matches_nested_recursive(p, v, depth + 1))
             },
-            (Pattern::Struct { fields, .. }, Value::Map(map)) => {
+            (Pattern::Struct { name, fields }, Value::Struct { name: struct_name, fields: values }) => {
+                if name != struct_name {
+                    return false;
+                }
                 for (field_name, field_pattern) in fields {
-                    if let Some(field_value) = map.get(field_name) {
+                    if let Some(field_value) = values.get(field_name) {
                         if !Self::matches_nested_recursive(field_pattern, field_value, depth + 1) {
                             return false;
                         }
@@ -49,6 +52,12 @@ impl PatternMatcher {
                 }
                 true
             },
+            (Pattern::Variant { enum_name, variant, fields },
+             Value::Enum { enum_name: value_enum, variant: value_variant, values }) => {
+                enum_name == value_enum && variant == value_variant && fields.len() == values.len()
+                    && fields.iter().zip(values.iter())
+                        .all(|(p, v)| Self::matches_nested_recursive(p, v, depth + 1))
+            },
             (Pattern::Or(patterns), value) => {
                 patterns.iter().any(|p| Self::matches_nested_recursive(p, value, depth + 1))
             },
@@ -87,6 +96,11 @@ impl PatternMatcher {
                 patterns.iter().zip(values.iter())
                     .all(|(p, v)| Self::matches(p, v))
             },
+            (Pattern::Variant { enum_name, variant, fields },
+             Value::Enum { enum_name: value_enum, variant: value_variant, values }) => {
+                enum_name == value_enum && variant == value_variant && fields.len() == values.len()
+                    && fields.iter().zip(values.iter()).all(|(p, v)| Self::matches(p, v))
+            },
             (Pattern::Or(patterns), value) => {
                 patterns.iter().any(|p| Self::matches(p, value))
             },
@@ -97,10 +111,13 @@ impl PatternMatcher {
                 // Guard evaluation would need VM context
                 Self::matches(pattern, value)
             },
-            (Pattern::Struct { fields, .. }, Value::Map(map)) => {
-                // Match struct pattern against map
+            (Pattern::Struct { name, fields }, Value::Struct { name: struct_name, fields: values }) => {
+                // The struct name must match as well as every listed field
+                if name != struct_name {
+                    return false;
+                }
                 for (field_name, field_pattern) in fields {
-                    if let Some(field_value) = map.get(field_name) {
+                    if let Some(field_value) = values.get(field_name) {
                         if !Self::matches(field_pattern, field_value) {
                             return false;
                         }
@@ -148,9 +165,9 @@ impl PatternMatcher {
                 }
             },
             Pattern::Struct { fields, .. } => {
-                if let Value::Map(map) = value {
+                if let Value::Struct { fields: values, .. } = value {
                     for (field_name, field_pattern) in fields {
-                        if let Some(field_value) = map.get(field_name) {
+                        if let Some(field_value) = values.get(field_name) {
                             Self::extract_bindings_recursive(field_pattern, field_value, bindings);
                         }
                     }
@@ -160,6 +177,13 @@ impl PatternMatcher {
                 bindings.push((name.clone(), value.clone()));
                 Self::extract_bindings_recursive(pattern, value, bindings);
             },
+            Pattern::Variant { fields, .. } => {
+                if let Value::Enum { values, .. } = value {
+                    for (p, v) in fields.iter().zip(values.iter()) {
+                        Self::extract_bindings_recursive(p, v, bindings);
+                    }
+                }
+            },
             Pattern::Guard { pattern, .. } => {
                 Self::extract_bindings_recursive(pattern, value, bindings);
             },
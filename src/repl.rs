@@ -0,0 +1,319 @@
+// Interactive JOEL session (`joel repl`)
+//
+// One VM stays alive across inputs, so variables and functions persist between
+// lines. An input continues over several lines while a bracket or string is
+// still open; a blank line submits it as is. On a terminal, lines are read
+// with a line editor, so Up/Down recall earlier inputs and Ctrl-R searches them.
+
+use crate::ast::{Expr, ExprKind, Stmt, StmtKind};
+use crate::lexer::{Lexer, TokenKind};
+use crate::type_checker::TypeChecker;
+use crate::value::Value;
+use crate::vm::VM;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+const HISTORY_FILE: &str = ".joel_history";
+const MAX_HISTORY: usize = 1000;
+
+pub struct Repl {
+    vm: VM,
+    /// Statements executed so far; `:type` checks expressions against them
+    session: Vec<Stmt>,
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    input: Input,
+}
+
+/// Where the REPL reads its lines from
+enum Input {
+    /// A terminal, through a line editor holding the history
+    Editor(Box<DefaultEditor>),
+    /// Piped input, read as plain lines
+    Lines(io::Lines<io::StdinLock<'static>>),
+}
+
+/// What reading a line gave
+enum Read {
+    Line(String),
+    /// Ctrl-C: the input typed so far is dropped
+    Interrupted,
+    End,
+}
+
+impl Input {
+    fn new(history: &[String]) -> Self {
+        if io::stdin().is_terminal() {
+            if let Ok(mut editor) = DefaultEditor::new() {
+                for entry in history {
+                    let _ = editor.add_history_entry(entry.as_str());
+                }
+                return Input::Editor(Box::new(editor));
+            }
+        }
+        Input::Lines(io::stdin().lock().lines())
+    }
+
+    fn read(&mut self, prompt: &str) -> Read {
+        let line = match self {
+            Input::Editor(editor) => match editor.readline(prompt) {
+                Ok(line) => Ok(Some(line)),
+                Err(ReadlineError::Interrupted) => return Read::Interrupted,
+                Err(ReadlineError::Eof) => Ok(None),
+                Err(e) => Err(e.to_string()),
+            },
+            Input::Lines(lines) => {
+                print!("{}", prompt);
+                let _ = io::stdout().flush();
+                lines.next().transpose().map_err(|e| e.to_string())
+            },
+        };
+        match line {
+            Ok(Some(line)) => Read::Line(line),
+            // End of input (Ctrl-D)
+            Ok(None) => {
+                println!();
+                Read::End
+            },
+            Err(e) => {
+                eprintln!("❌ Error reading input: {}", e);
+                Read::End
+            },
+        }
+    }
+
+    /// Makes an entry recallable with Up/Down
+    fn remember(&mut self, entry: &str) {
+        if let Input::Editor(editor) = self {
+            let _ = editor.add_history_entry(entry);
+        }
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let history_path = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let mut history: Vec<String> = history_path.as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().map(unescape_history).collect())
+            .unwrap_or_default();
+        if history.len() > MAX_HISTORY {
+            history.drain(..history.len() - MAX_HISTORY);
+        }
+
+        Self {
+            vm: VM::new(),
+            session: Vec::new(),
+            input: Input::new(&history),
+            history,
+            history_path,
+        }
+    }
+
+    pub fn run(&mut self) {
+        println!("JOEL Language v0.1.0 - interactive mode");
+        println!("Type :help for commands, :quit to exit\n");
+
+        let mut buffer = String::new();
+
+        loop {
+            let line = match self.input.read(if buffer.is_empty() { "joel> " } else { "  ... " }) {
+                Read::Line(line) => line,
+                Read::Interrupted => {
+                    buffer.clear();
+                    continue;
+                },
+                Read::End => break,
+            };
+
+            if buffer.is_empty() {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                if trimmed.starts_with(':') {
+                    self.record(trimmed);
+                    if !self.command(trimmed) {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            if !buffer.is_empty() && line.trim().is_empty() {
+                // A blank line submits an unfinished input so its errors get reported
+            } else {
+                buffer.push_str(&line);
+                buffer.push('\n');
+                if is_incomplete(&buffer) {
+                    continue;
+                }
+            }
+
+            let input = std::mem::take(&mut buffer);
+            self.record(input.trim_end());
+            self.evaluate(&input);
+        }
+    }
+
+    /// Runs a `:` command. Returns false when the session should end.
+    fn command(&mut self, input: &str) -> bool {
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (input, ""),
+        };
+
+        match name {
+            ":quit" | ":q" | ":exit" => return false,
+            ":help" | ":h" => {
+                println!("Commands:");
+                println!("  :type <expr>   Show the type of an expression");
+                println!("  :load <file>   Run a JOEL file in this session");
+                println!("  :history       Show previous inputs (Up/Down recall them, Ctrl-R searches)");
+                println!("  :help          Show this message");
+                println!("  :quit          Exit the REPL");
+            },
+            ":type" | ":t" => self.show_type(arg),
+            ":load" | ":l" => self.load(arg),
+            ":history" => {
+                for (i, entry) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", i + 1, entry);
+                }
+            },
+            _ => eprintln!("❌ Unknown command: {} (try :help)", name),
+        }
+        true
+    }
+
+    fn evaluate(&mut self, input: &str) {
+        let program = match crate::parse_source(input, None) {
+            Some(program) => program,
+            None => return,
+        };
+
+        match self.vm.run_statements(&program.statements) {
+            Ok(value) => {
+                // Echo the value of a trailing expression, but not of assignments
+                let echo = matches!(program.statements.last().map(|stmt| &stmt.kind), Some(StmtKind::Expr(expr)) if !matches!(expr.kind, ExprKind::Assign { .. }));
+                if echo && !matches!(value, Value::None) {
+                    println!("{}", value);
+                }
+                self.session.extend(program.statements);
+            },
+            Err(e) => eprintln!("❌ Runtime error: {}", e),
+        }
+    }
+
+    fn show_type(&mut self, source: &str) {
+        if source.is_empty() {
+            eprintln!("❌ Usage: :type <expr>");
+            return;
+        }
+
+        let program = match crate::parse_source(source, None) {
+            Some(program) => program,
+            None => return,
+        };
+        let expr = match program.statements.as_slice() {
+            [Stmt { kind: StmtKind::Expr(expr), .. }] => expr.clone(),
+            // At statement level `{}` is an empty block, but here it can only be a map
+            [Stmt { kind: StmtKind::Block(body), span }] if body.is_empty() => Expr::new(ExprKind::Map(Vec::new()), *span),
+            _ => {
+                eprintln!("❌ :type expects a single expression");
+                return;
+            },
+        };
+        let mut checker = TypeChecker::new(source);
+        let ty = checker.infer_type(&self.session, &expr);
+        println!("{} : {}", source, ty.to_string());
+    }
+
+    fn load(&mut self, path: &str) {
+        if path.is_empty() {
+            eprintln!("❌ Usage: :load <file>");
+            return;
+        }
+
+        let source = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("❌ Error reading file: {}", e);
+                return;
+            },
+        };
+        let program = match crate::parse_source(&source, Some(Path::new(path))) {
+            Some(program) => program,
+            None => {
+                eprintln!("\n❌ Parsing failed. {} not loaded.", path);
+                return;
+            },
+        };
+
+        match self.vm.run_statements(&program.statements) {
+            Ok(_) => {
+                println!("✅ Loaded {}", path);
+                self.session.extend(program.statements);
+            },
+            Err(e) => eprintln!("❌ Runtime error: {}", e),
+        }
+    }
+
+    /// Adds an input to the history, persisting it to ~/.joel_history
+    fn record(&mut self, entry: &str) {
+        if self.history.last().map(String::as_str) == Some(entry) {
+            return;
+        }
+        self.history.push(entry.to_string());
+        self.input.remember(entry);
+
+        if let Some(path) = &self.history_path {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", escape_history(entry));
+            }
+        }
+    }
+}
+
+/// Encodes a history entry on a single line of the history file
+fn escape_history(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_history(line: &str) -> String {
+    let mut entry = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => entry.push('\n'),
+                Some(other) => entry.push(other),
+                None => entry.push('\\'),
+            }
+        } else {
+            entry.push(c);
+        }
+    }
+    entry
+}
+
+/// Whether the input still has an open bracket or an unterminated string
+fn is_incomplete(source: &str) -> bool {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize();
+    if lexer.diagnostics().iter().any(|d| d.message.starts_with("Unterminated string")) {
+        return true;
+    }
+
+    let mut depth = 0i32;
+    for token in &tokens {
+        match token.kind {
+            TokenKind::LBrace | TokenKind::LParen | TokenKind::LBracket => depth += 1,
+            TokenKind::RBrace | TokenKind::RParen | TokenKind::RBracket => depth -= 1,
+            _ => {},
+        }
+    }
+    depth > 0
+}
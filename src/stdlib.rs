@@ -1,35 +1,139 @@
 // Standard Library Core Modules for JOEL
+//
+// `import math` binds `math` to a map of native functions, so calls read as
+// `math.sqrt(2)`. Each native carries its JOEL signature: the VMs check
+// arguments against it at call time and the type checker uses it statically.
+
+use crate::value::Value;
+
+/// A Rust function callable from JOEL
+#[derive(Debug)]
+pub struct NativeFunction {
+    /// Qualified name, e.g. `math.sqrt`
+    pub name: &'static str,
+    /// Parameter types, written as in JOEL type annotations (`any` accepts every value)
+    pub params: &'static [&'static str],
+    pub return_type: &'static str,
+    /// Called with arguments already checked against `params`
+    pub func: fn(&[Value]) -> Result<Value, String>,
+}
+
+impl NativeFunction {
+    /// Name without the module prefix
+    pub fn short_name(&self) -> &'static str {
+        self.name.rsplit('.').next().unwrap_or(self.name)
+    }
+    
+    pub fn call(&self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != self.params.len() {
+            return Err(format!("Function '{}' expects {} arguments, got {}", self.name, self.params.len(), args.len()));
+        }
+        for (i, (arg, param)) in args.iter().zip(self.params).enumerate() {
+            if !value_has_type(arg, param) {
+                return Err(format!("Argument {} to '{}': expected {}, got {}",
+                    i + 1, self.name, param, arg.type_name()));
+            }
+        }
+        (self.func)(args)
+    }
+}
+
+/// Natives of a standard library module, if `name` is one
+pub fn module(name: &str) -> Option<&'static [NativeFunction]> {
+    match name {
+        "math" => Some(math::FUNCTIONS),
+        "string" => Some(string::FUNCTIONS),
+        "collections" => Some(collections::FUNCTIONS),
+        _ => None,
+    }
+}
+
+/// The value an `import` of a standard library module binds: a map from each
+/// function's short name to the function
+pub fn module_value(name: &str) -> Option<Value> {
+    let functions = module(name)?;
+    Some(Value::Map(functions.iter()
+        .map(|function| (function.short_name().to_string(), Value::NativeFunction(function)))
+        .collect()))
+}
+
+fn value_has_type(value: &Value, ty: &str) -> bool {
+    match (ty, value) {
+        ("any", _) => true,
+        ("i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "f32" | "f64", Value::Number(_)) => true,
+        ("str", Value::String(_)) | ("bool", Value::Boolean(_)) => true,
+        (_, Value::List(items)) => match ty.strip_prefix("list[").and_then(|inner| inner.strip_suffix(']')) {
+            Some(element) => items.iter().all(|item| value_has_type(item, element)),
+            None => false,
+        },
+        (_, Value::Map(_)) => ty.starts_with("map["),
+        _ => false,
+    }
+}
+
+// Argument accessors for natives; `NativeFunction::call` has already checked the types
+
+fn number(value: &Value) -> f64 {
+    match value {
+        Value::Number(n) => *n,
+        _ => unreachable!("argument checked as a number"),
+    }
+}
+
+fn text(value: &Value) -> &str {
+    match value {
+        Value::String(s) => s,
+        _ => unreachable!("argument checked as a string"),
+    }
+}
+
+fn list(value: &Value) -> &[Value] {
+    match value {
+        Value::List(items) => items,
+        _ => unreachable!("argument checked as a list"),
+    }
+}
+
+fn map(value: &Value) -> &std::collections::HashMap<String, Value> {
+    match value {
+        Value::Map(map) => map,
+        _ => unreachable!("argument checked as a map"),
+    }
+}
 
 pub mod core {
     // Core utilities and functions
     
-    pub fn print(args: &[crate::vm::Value]) -> crate::vm::Value {
+    use crate::iterator::{IteratorUtils, LazyIterator};
+    use crate::value::Value;
+    
+    pub fn print(args: &[crate::value::Value]) -> crate::value::Value {
         let mut parts = Vec::new();
         for arg in args {
-            parts.push(value_to_string(arg));
+            parts.push(arg.to_string());
         }
         println!("{}", parts.join(" "));
-        crate::vm::Value::None
+        crate::value::Value::None
     }
     
-    pub fn range(args: &[crate::vm::Value]) -> Result<crate::vm::Value, String> {
+    pub fn range(args: &[crate::value::Value]) -> Result<crate::value::Value, String> {
         if args.len() == 2 {
-            if let (crate::vm::Value::Number(s), crate::vm::Value::Number(e)) = (&args[0], &args[1]) {
+            if let (crate::value::Value::Number(s), crate::value::Value::Number(e)) = (&args[0], &args[1]) {
                 let mut list = Vec::new();
                 for i in (*s as i64)..(*e as i64) {
-                    list.push(crate::vm::Value::Number(i as f64));
+                    list.push(crate::value::Value::Number(i as f64));
                 }
-                Ok(crate::vm::Value::List(list))
+                Ok(crate::value::Value::List(list))
             } else {
                 Err("range() expects two numbers".to_string())
             }
         } else if args.len() == 1 {
-            if let crate::vm::Value::Number(e) = &args[0] {
+            if let crate::value::Value::Number(e) = &args[0] {
                 let mut list = Vec::new();
                 for i in 0..(*e as i64) {
-                    list.push(crate::vm::Value::Number(i as f64));
+                    list.push(crate::value::Value::Number(i as f64));
                 }
-                Ok(crate::vm::Value::List(list))
+                Ok(crate::value::Value::List(list))
             } else {
                 Err("range() expects a number".to_string())
             }
@@ -38,29 +142,44 @@ pub mod core {
         }
     }
     
-    fn value_to_string(value: &crate::vm::Value) -> String {
-        match value {
-            crate::vm::Value::Number(n) => {
-                if n.fract() == 0.0 {
-                    format!("{}", *n as i64)
-                } else {
-                    format!("{}", n)
-                }
+    /// First `n` items of a list, or an iterator that stops after `n` items
+    /// without running the rest of the underlying one
+    pub fn take(args: &[Value]) -> Result<Value, String> {
+        match args {
+            [Value::List(list), Value::Number(n)] => {
+                Ok(Value::List(list.iter().take(*n as usize).cloned().collect()))
             },
-            crate::vm::Value::String(s) => s.clone(),
-            crate::vm::Value::Boolean(b) => format!("{}", b),
-            crate::vm::Value::List(list) => {
-                let items: Vec<String> = list.iter().map(|v| value_to_string(v)).collect();
-                format!("[{}]", items.join(", "))
+            [Value::Iterator(iter), Value::Number(n)] => {
+                let taken = IteratorUtils::take(iter.clone(), *n as usize);
+                Ok(Value::Iterator(LazyIterator::new("take", taken)))
             },
-            crate::vm::Value::Map(map) => {
-                let pairs: Vec<String> = map.iter()
-                    .map(|(k, v)| format!("{}: {}", k, value_to_string(v)))
-                    .collect();
-                format!("{{{}}}", pairs.join(", "))
+            [_, _] => Err("take() expects a list or iterator and a number".to_string()),
+            _ => Err(format!("take() expects 2 arguments, got {}", args.len())),
+        }
+    }
+    
+    /// Advances an iterator, giving None once it is exhausted
+    pub fn next(args: &[Value]) -> Result<Value, String> {
+        match args {
+            [Value::Iterator(iter)] => Ok(iter.advance()?.unwrap_or(Value::None)),
+            [_] => Err("next() expects an iterator".to_string()),
+            _ => Err(format!("next() expects 1 argument, got {}", args.len())),
+        }
+    }
+    
+    /// Runs an iterator to the end and returns its items as a list
+    pub fn collect(args: &[Value]) -> Result<Value, String> {
+        match args {
+            [Value::List(list)] => Ok(Value::List(list.clone())),
+            [Value::Iterator(iter)] => {
+                let mut items = Vec::new();
+                while let Some(item) = iter.advance()? {
+                    items.push(item);
+                }
+                Ok(Value::List(items))
             },
-            crate::vm::Value::Function { name, .. } => format!("<function {}>", name),
-            crate::vm::Value::None => "None".to_string(),
+            [_] => Err("collect() expects a list or iterator".to_string()),
+            _ => Err(format!("collect() expects 1 argument, got {}", args.len())),
         }
     }
 }
@@ -68,6 +187,28 @@ pub mod core {
 pub mod math {
     // Mathematical functions
     
+    use super::{number, NativeFunction};
+    use crate::value::Value;
+    
+    pub static FUNCTIONS: &[NativeFunction] = &[
+        NativeFunction { name: "math.abs", params: &["f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(abs(number(&args[0])))) },
+        NativeFunction { name: "math.min", params: &["f64", "f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(min(number(&args[0]), number(&args[1])))) },
+        NativeFunction { name: "math.max", params: &["f64", "f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(max(number(&args[0]), number(&args[1])))) },
+        NativeFunction { name: "math.sqrt", params: &["f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(sqrt(number(&args[0])))) },
+        NativeFunction { name: "math.pow", params: &["f64", "f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(pow(number(&args[0]), number(&args[1])))) },
+        NativeFunction { name: "math.sin", params: &["f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(sin(number(&args[0])))) },
+        NativeFunction { name: "math.cos", params: &["f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(cos(number(&args[0])))) },
+        NativeFunction { name: "math.tan", params: &["f64"], return_type: "f64",
+            func: |args| Ok(Value::Number(tan(number(&args[0])))) },
+    ];
+    
     pub fn abs(x: f64) -> f64 {
         x.abs()
     }
@@ -104,6 +245,29 @@ pub mod math {
 pub mod string {
     // String manipulation functions
     
+    use super::{list, text, NativeFunction};
+    use crate::value::Value;
+    
+    pub static FUNCTIONS: &[NativeFunction] = &[
+        NativeFunction { name: "string.len", params: &["str"], return_type: "i64",
+            func: |args| Ok(Value::Number(len(text(&args[0])) as f64)) },
+        NativeFunction { name: "string.contains", params: &["str", "str"], return_type: "bool",
+            func: |args| Ok(Value::Boolean(contains(text(&args[0]), text(&args[1])))) },
+        NativeFunction { name: "string.split", params: &["str", "str"], return_type: "list[str]",
+            func: |args| Ok(Value::List(split(text(&args[0]), text(&args[1])).into_iter().map(Value::String).collect())) },
+        NativeFunction { name: "string.join", params: &["list[str]", "str"], return_type: "str",
+            func: |args| {
+                let parts: Vec<String> = list(&args[0]).iter().map(|part| text(part).to_string()).collect();
+                Ok(Value::String(join(&parts, text(&args[1]))))
+            } },
+        NativeFunction { name: "string.trim", params: &["str"], return_type: "str",
+            func: |args| Ok(Value::String(trim(text(&args[0])))) },
+        NativeFunction { name: "string.to_upper", params: &["str"], return_type: "str",
+            func: |args| Ok(Value::String(to_upper(text(&args[0])))) },
+        NativeFunction { name: "string.to_lower", params: &["str"], return_type: "str",
+            func: |args| Ok(Value::String(to_lower(text(&args[0])))) },
+    ];
+    
     pub fn len(s: &str) -> usize {
         s.len()
     }
@@ -137,7 +301,32 @@ pub mod collections {
     // Collection utilities
     
     use std::collections::HashMap;
-    use crate::vm::Value;
+    use super::{list, map, text, NativeFunction};
+    use crate::value::Value;
+    
+    // JOEL values are immutable, so the updating functions return a new collection
+    pub static FUNCTIONS: &[NativeFunction] = &[
+        NativeFunction { name: "collections.len", params: &["list[any]"], return_type: "i64",
+            func: |args| Ok(Value::Number(len(list(&args[0])) as f64)) },
+        NativeFunction { name: "collections.push", params: &["list[any]", "any"], return_type: "list[any]",
+            func: |args| {
+                let mut items = list(&args[0]).to_vec();
+                push(&mut items, args[1].clone());
+                Ok(Value::List(items))
+            } },
+        NativeFunction { name: "collections.pop", params: &["list[any]"], return_type: "any",
+            func: |args| Ok(pop(&mut list(&args[0]).to_vec()).unwrap_or(Value::None)) },
+        NativeFunction { name: "collections.map_len", params: &["map[str, any]"], return_type: "i64",
+            func: |args| Ok(Value::Number(map_len(map(&args[0])) as f64)) },
+        NativeFunction { name: "collections.map_get", params: &["map[str, any]", "str"], return_type: "any",
+            func: |args| Ok(map_get(map(&args[0]), text(&args[1])).cloned().unwrap_or(Value::None)) },
+        NativeFunction { name: "collections.map_insert", params: &["map[str, any]", "str", "any"], return_type: "map[str, any]",
+            func: |args| {
+                let mut entries = map(&args[0]).clone();
+                map_insert(&mut entries, text(&args[1]).to_string(), args[2].clone());
+                Ok(Value::Map(entries))
+            } },
+    ];
     
     pub fn len(list: &[Value]) -> usize {
         list.len()
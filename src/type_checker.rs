@@ -1,14 +1,38 @@
 use crate::ast::*;
 use crate::types::Type;
-use crate::diagnostics::{DiagnosticReporter, SourceLocation};
+use crate::diagnostics::{Diagnostic, DiagnosticReporter, SourceLocation};
+use crate::error_codes;
 use crate::lexer::Token;
+use crate::exhaustiveness_checker::{EnumVariants, ExhaustivenessChecker};
+use crate::stdlib;
 use std::collections::HashMap;
+use std::path::Path;
+
+/// What a checked module exports, as seen by the modules importing it
+#[derive(Debug, Clone)]
+pub struct ModuleInterface {
+    pub name: String,
+    pub values: HashMap<String, Type>,
+    pub structs: HashMap<String, Vec<(String, Type)>>,
+    pub enums: HashMap<String, Vec<(String, Vec<Type>)>>,
+}
 
 pub struct TypeChecker {
     reporter: DiagnosticReporter,
-    scopes: Vec<HashMap<String, Type>>,
+    scopes: Vec<HashMap<String, (Type, Span)>>, // name -> (type, where it was declared)
     functions: HashMap<String, (Vec<(String, Type)>, Type)>, // name -> (params, return_type)
+    modules: HashMap<String, String>, // import name -> module name
+    imports: HashMap<String, ModuleInterface>, // import spec -> interface of the file module it names
+    module_values: HashMap<String, Type>, // qualified name -> type of an imported non-function export
+    structs: HashMap<String, Vec<(String, Type)>>, // name -> fields
+    enums: HashMap<String, Vec<(String, Vec<Type>)>>, // name -> variants with payload types
+    actors: HashMap<String, Vec<(String, Type)>>, // name -> state fields
+    declarations: HashMap<String, Span>, // name -> where a function, struct, enum, actor or component of this file is declared
     current_function_return: Option<Type>,
+    current_function_annotation: Option<Span>, // where the function being checked annotates its return type, if it does
+    yield_types: Option<Vec<Type>>, // types yielded so far by the generator body being checked
+    infer_returns: bool, // whether functions without a return annotation take their return type from their body
+    return_types: Option<Vec<Type>>, // types returned so far by the function whose return type is being inferred
 }
 
 impl TypeChecker {
@@ -17,15 +41,37 @@ impl TypeChecker {
             reporter: DiagnosticReporter::new(source),
             scopes: vec![HashMap::new()],
             functions: HashMap::new(),
+            modules: HashMap::new(),
+            imports: HashMap::new(),
+            module_values: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            actors: HashMap::new(),
+            declarations: HashMap::new(),
             current_function_return: None,
+            current_function_annotation: None,
+            yield_types: None,
+            infer_returns: false,
+            return_types: None,
         }
     }
     
+    /// Names the checked file in machine-readable diagnostics
+    pub fn with_file(mut self, path: &Path) -> Self {
+        self.reporter = self.reporter.with_file(path);
+        self
+    }
+    
+    /// Makes the interfaces of already checked file modules visible to `import`
+    pub fn with_imports(mut self, imports: HashMap<String, ModuleInterface>) -> Self {
+        self.imports = imports;
+        self
+    }
+    
+    /// Type checks a program. Only Compiled programs and the modules they import
+    /// are checked; callers decide which programs that covers.
     pub fn check(&mut self, program: &Program) -> bool {
-        // Only type check in Compiled mode
-        if program.mode != ExecutionMode::Compiled {
-            return true; // Skip type checking for interpreted mode
-        }
+        self.reporter.allow(&program.allows);
         
         // First pass: collect function signatures
         for stmt in &program.statements {
@@ -41,8 +87,8 @@ impl TypeChecker {
     }
     
     fn collect_declarations(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::Fn { name, params, return_type, .. } => {
+        match &stmt.kind {
+            StmtKind::Fn { name, params, return_type, generator, .. } => {
                 let param_types: Vec<(String, Type)> = params.iter()
                     .map(|(name, type_str)| {
                         let ty = type_str.as_ref()
@@ -52,129 +98,237 @@ impl TypeChecker {
                     })
                     .collect();
                 
-                let ret_type = return_type.as_ref()
-                    .and_then(|s| Type::from_string(s))
-                    .unwrap_or(Type::None);
+                let ret_type = Self::declared_return_type(return_type, *generator);
                 
                 self.functions.insert(name.clone(), (param_types, ret_type));
+                self.declarations.insert(name.clone(), stmt.span);
             },
-            Stmt::Let { name, type_annot, .. } => {
+            StmtKind::Import { module, alias } => {
+                // Imported functions are registered under their qualified name, e.g. `math.sqrt`
+                let import_name = crate::modules::import_name(module, alias.as_ref());
+                if let Some(natives) = stdlib::module(module) {
+                    for native in natives {
+                        let param_types = native.params.iter().enumerate()
+                            .map(|(i, type_str)| (format!("arg{}", i + 1), Type::from_string(type_str).unwrap_or(Type::Any)))
+                            .collect();
+                        let ret_type = Type::from_string(native.return_type).unwrap_or(Type::Any);
+                        let qualified = format!("{}.{}", import_name, native.short_name());
+                        self.functions.insert(qualified, (param_types, ret_type));
+                    }
+                    self.modules.insert(import_name, module.clone());
+                } else if let Some(interface) = self.imports.get(module).cloned() {
+                    for (name, ty) in interface.values {
+                        let qualified = format!("{}.{}", import_name, name);
+                        match ty {
+                            Type::Function { params, return_type } => {
+                                let params = params.into_iter().enumerate()
+                                    .map(|(i, ty)| (format!("arg{}", i + 1), ty))
+                                    .collect();
+                                self.functions.insert(qualified, (params, *return_type));
+                            },
+                            ty => {
+                                self.module_values.insert(qualified, ty);
+                            },
+                        }
+                    }
+                    // Struct and enum names are global at runtime, so imported ones are too
+                    self.structs.extend(interface.structs);
+                    self.enums.extend(interface.enums);
+                    self.modules.insert(import_name, interface.name);
+                }
+            },
+            StmtKind::CoroutineFn { name, params, .. } | StmtKind::AsyncFn { name, params, .. } => {
+                let param_types = params.iter()
+                    .map(|(name, type_str)| {
+                        let ty = type_str.as_ref()
+                            .and_then(|s| Type::from_string(s))
+                            .unwrap_or(Type::Unknown);
+                        (name.clone(), ty)
+                    })
+                    .collect();
+                // Calls return the coroutine or task handle
+                self.functions.insert(name.clone(), (param_types, Type::Any));
+                self.declarations.insert(name.clone(), stmt.span);
+            },
+            StmtKind::Export(declaration) => self.collect_declarations(declaration),
+            StmtKind::Let { name, type_annot, .. } => {
                 if let Some(type_str) = type_annot {
                     if let Some(ty) = Type::from_string(type_str) {
-                        self.define_variable(name.clone(), ty);
+                        self.declare_variable(name.clone(), ty, stmt.span);
                     }
                 }
             },
+            StmtKind::Struct { name, fields } => {
+                let field_types = fields.iter()
+                    .map(|(field, type_str)| {
+                        (field.clone(), Type::from_string(type_str).unwrap_or(Type::Unknown))
+                    })
+                    .collect();
+                self.structs.insert(name.clone(), field_types);
+                self.declarations.insert(name.clone(), stmt.span);
+            },
+            StmtKind::Enum { name, variants } => {
+                let variant_types = variants.iter()
+                    .map(|(variant, payload)| {
+                        let types = payload.iter()
+                            .map(|type_str| Type::from_string(type_str).unwrap_or(Type::Unknown))
+                            .collect();
+                        (variant.clone(), types)
+                    })
+                    .collect();
+                self.enums.insert(name.clone(), variant_types);
+                self.declarations.insert(name.clone(), stmt.span);
+            },
+            StmtKind::Actor { name, fields, .. } => {
+                let field_types = fields.iter()
+                    .map(|(field, type_annot, _)| {
+                        let ty = type_annot.as_ref()
+                            .and_then(|s| Type::from_string(s))
+                            .unwrap_or(Type::Unknown);
+                        (field.clone(), ty)
+                    })
+                    .collect();
+                self.actors.insert(name.clone(), field_types);
+                self.declarations.insert(name.clone(), stmt.span);
+            },
+            StmtKind::Component { name, .. } => {
+                self.declarations.insert(name.clone(), stmt.span);
+            },
             _ => {}
         }
     }
     
     fn check_statement(&mut self, stmt: &Stmt) -> Type {
-        match stmt {
-            Stmt::Let { name, type_annot, value } => {
-                let value_type = self.check_expression(value);
+        match &stmt.kind {
+            StmtKind::Let { name, type_annot, annot_span, value } => {
+                let value_type = self.check_initializer(value, type_annot);
                 
                 if let Some(type_str) = type_annot {
                     if let Some(expected_type) = Type::from_string(type_str) {
                         if !value_type.can_coerce_to(&expected_type) && value_type != Type::Unknown {
-                            self.reporter.error(
+                            self.error_with_label(
+                                error_codes::TYPE_MISMATCH,
                                 format!("Type mismatch: expected {}, got {}", 
                                     expected_type.to_string(), value_type.to_string()),
-                                None,
+                                value.span,
+                                *annot_span,
+                                format!("'{}' declared as {} here", name, expected_type.to_string()),
                             );
                         }
-                        self.define_variable(name.clone(), expected_type.clone());
+                        self.declare_variable(name.clone(), expected_type.clone(), *annot_span);
                         expected_type
                     } else {
                         self.reporter.error(
+                            error_codes::UNKNOWN_TYPE,
                             format!("Unknown type: {}", type_str),
-                            None,
+                            SourceLocation::at(stmt.span),
                         );
-                        self.define_variable(name.clone(), value_type.clone());
+                        self.declare_variable(name.clone(), value_type.clone(), stmt.span);
                         value_type
                     }
                 } else {
                     // Type inference
                     if value_type == Type::Unknown {
                         self.reporter.warning(
+                            error_codes::INFERRED_ANY,
                             format!("Cannot infer type for variable '{}', defaulting to Any", name),
-                            None,
+                            SourceLocation::at(stmt.span),
                         );
-                        self.define_variable(name.clone(), Type::Any);
+                        self.declare_variable(name.clone(), Type::Any, stmt.span);
                         Type::Any
                     } else {
-                        self.define_variable(name.clone(), value_type.clone());
+                        self.declare_variable(name.clone(), value_type.clone(), stmt.span);
                         value_type
                     }
                 }
             },
-            Stmt::Const { name, type_annot, value } => {
-                let value_type = self.check_expression(value);
+            StmtKind::Const { name, type_annot, annot_span, value } => {
+                let value_type = self.check_initializer(value, type_annot);
                 
                 if let Some(type_str) = type_annot {
                     if let Some(expected_type) = Type::from_string(type_str) {
                         if !value_type.can_coerce_to(&expected_type) && value_type != Type::Unknown {
-                            self.reporter.error(
+                            self.error_with_label(
+                                error_codes::TYPE_MISMATCH,
                                 format!("Type mismatch: expected {}, got {}", 
                                     expected_type.to_string(), value_type.to_string()),
-                                None,
+                                value.span,
+                                *annot_span,
+                                format!("'{}' declared as {} here", name, expected_type.to_string()),
                             );
                         }
-                        self.define_variable(name.clone(), expected_type.clone());
+                        self.declare_variable(name.clone(), expected_type.clone(), *annot_span);
                         expected_type
                     } else {
                         self.reporter.error(
+                            error_codes::UNKNOWN_TYPE,
                             format!("Unknown type: {}", type_str),
-                            None,
+                            SourceLocation::at(stmt.span),
                         );
-                        self.define_variable(name.clone(), value_type.clone());
+                        self.declare_variable(name.clone(), value_type.clone(), stmt.span);
                         value_type
                     }
                 } else {
                     if value_type == Type::Unknown {
                         self.reporter.error(
+                            error_codes::MISSING_ANNOTATION,
                             format!("Const '{}' requires explicit type annotation", name),
-                            None,
+                            SourceLocation::at(stmt.span),
                         );
-                        self.define_variable(name.clone(), Type::Any);
+                        self.declare_variable(name.clone(), Type::Any, stmt.span);
                         Type::Any
                     } else {
-                        self.define_variable(name.clone(), value_type.clone());
+                        self.declare_variable(name.clone(), value_type.clone(), stmt.span);
                         value_type
                     }
                 }
             },
-            Stmt::Expr(expr) => {
+            StmtKind::Expr(expr) => {
                 self.check_expression(expr)
             },
-            Stmt::Print(expr) => {
+            StmtKind::Print(expr) => {
                 self.check_expression(expr);
                 Type::None
             },
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) => {
                 let return_type = if let Some(expr) = expr {
                     self.check_expression(expr)
                 } else {
                     Type::None
                 };
                 
-                if let Some(expected_return) = &self.current_function_return {
-                    if !return_type.can_coerce_to(expected_return) && return_type != Type::Unknown {
-                        self.reporter.error(
-                            format!("Return type mismatch: expected {}, got {}", 
-                                expected_return.to_string(), return_type.to_string()),
-                            None,
-                        );
+                // Blocks that `return` does not leave, such as lambdas, clear the expected type
+                if let (Some(_), Some(return_types)) = (&self.current_function_return, self.return_types.as_mut()) {
+                    return_types.push(return_type.clone());
+                }
+                if let Some(expected_return) = self.current_function_return.clone() {
+                    if !return_type.can_coerce_to(&expected_return) && return_type != Type::Unknown {
+                        let message = format!("Return type mismatch: expected {}, got {}", 
+                            expected_return.to_string(), return_type.to_string());
+                        let span = expr.as_ref().map_or(stmt.span, |expr| expr.span);
+                        match self.current_function_annotation {
+                            Some(declared_at) => self.error_with_label(
+                                error_codes::RETURN_TYPE_MISMATCH,
+                                message,
+                                span,
+                                declared_at,
+                                format!("declared to return {} here", expected_return.to_string()),
+                            ),
+                            None => self.reporter.report(Diagnostic::error(message, SourceLocation::at(span))
+                                .with_code(error_codes::RETURN_TYPE_MISMATCH)
+                                .with_note("the function has no return annotation, so it returns None; add '-> <type>' to return a value".to_string())),
+                        }
                     }
                 }
                 return_type
             },
-            Stmt::If { condition, then_branch, else_branch } => {
+            StmtKind::If { condition, then_branch, else_branch } => {
                 let cond_type = self.check_expression(condition);
                 if cond_type != Type::Bool && cond_type != Type::Unknown && cond_type != Type::Any {
                     self.reporter.error(
+                        error_codes::NON_BOOL_CONDITION,
                         format!("If condition must be bool, got {}", cond_type.to_string()),
-                        None,
+                        SourceLocation::at(condition.span),
                     );
                 }
                 
@@ -191,21 +345,23 @@ impl TypeChecker {
                     if then_type != else_type && then_type != Type::Unknown && else_type != Type::Unknown {
                         // This is a warning, not an error
                         self.reporter.warning(
+                            error_codes::BRANCH_TYPES_DIFFER,
                             format!("If/else branches return different types: {} and {}", 
                                 then_type.to_string(), else_type.to_string()),
-                            None,
+                            SourceLocation::at(stmt.span),
                         );
                     }
                 }
                 
                 Type::None
             },
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 let cond_type = self.check_expression(condition);
                 if cond_type != Type::Bool && cond_type != Type::Unknown && cond_type != Type::Any {
                     self.reporter.error(
+                        error_codes::NON_BOOL_CONDITION,
                         format!("While condition must be bool, got {}", cond_type.to_string()),
-                        None,
+                        SourceLocation::at(condition.span),
                     );
                 }
                 
@@ -215,39 +371,45 @@ impl TypeChecker {
                 
                 Type::None
             },
-            Stmt::For { var, iterable, body } => {
+            StmtKind::For { var, iterable, body } => {
                 let iter_type = self.check_expression(iterable);
                 match &iter_type {
-                    Type::List(_) | Type::Any | Type::Unknown => {
+                    Type::List(_) | Type::Iterator(_) | Type::Any | Type::Unknown => {
                         // OK
                     },
                     _ => {
                         self.reporter.error(
-                            format!("For loop iterable must be a list, got {}", iter_type.to_string()),
-                            None,
+                            error_codes::NOT_ITERABLE,
+                            format!("For loop iterable must be a list or iterator, got {}", iter_type.to_string()),
+                            SourceLocation::at(iterable.span),
                         );
                     }
                 }
                 
                 self.begin_scope();
-                // Infer loop variable type from list element type
-                if let Type::List(elem_type) = &iter_type {
-                    self.define_variable(var.clone(), *elem_type.clone());
+                // Infer loop variable type from the element type
+                if let Type::List(elem_type) | Type::Iterator(elem_type) = &iter_type {
+                    self.declare_variable(var.clone(), *elem_type.clone(), stmt.span);
                 } else {
-                    self.define_variable(var.clone(), Type::Any);
+                    self.declare_variable(var.clone(), Type::Any, stmt.span);
                 }
                 self.check_block(body);
                 self.end_scope();
                 
                 Type::None
             },
-            Stmt::Block(statements) => {
+            StmtKind::ParallelFor { var, iterable, body } => {
+                self.check_parallel("for", var, iterable, None, body);
+                Type::None
+            },
+            StmtKind::Block(statements) => {
                 self.begin_scope();
                 let result = self.check_block(statements);
                 self.end_scope();
                 result
             },
-            Stmt::Fn { name, params, return_type, body } => {
+            StmtKind::Fn { name, params, return_type, return_span, body, .. } | StmtKind::AsyncFn { name, params, return_type, return_span, body } |
+            StmtKind::CoroutineFn { name, params, return_type, return_span, body } => {
                 let param_types: Vec<(String, Type)> = params.iter()
                     .map(|(name, type_str)| {
                         let ty = type_str.as_ref()
@@ -257,28 +419,175 @@ impl TypeChecker {
                     })
                     .collect();
                 
-                let ret_type = return_type.as_ref()
-                    .and_then(|s| Type::from_string(s))
-                    .unwrap_or(Type::None);
+                // Calling a coroutine or async function returns a handle to it. An async
+                // body still returns the declared type; a coroutine's `return` only ends it.
+                let generator = matches!(&stmt.kind, StmtKind::Fn { generator: true, .. });
+                let declared = Self::declared_return_type(return_type, generator);
+                let inferring = self.infer_returns && return_type.is_none() && matches!(&stmt.kind, StmtKind::Fn { generator: false, .. });
+                let (ret_type, body_return) = match &stmt.kind {
+                    StmtKind::CoroutineFn { .. } => (Type::Any, None),
+                    StmtKind::AsyncFn { .. } => (Type::Any, Some(declared)),
+                    _ if inferring => (Type::Any, Some(Type::Any)),
+                    _ => (declared.clone(), Some(declared)),
+                };
+                
+                // Visible as a value in the enclosing scope, including to itself
+                self.declare_variable(name.clone(), Type::Function {
+                    params: param_types.iter().map(|(_, ty)| ty.clone()).collect(),
+                    return_type: Box::new(ret_type.clone()),
+                }, stmt.span);
                 
                 self.begin_scope();
-                self.current_function_return = Some(ret_type.clone());
+                let enclosing_return = std::mem::replace(&mut self.current_function_return, body_return);
+                let annotation = return_type.as_ref().map(|_| *return_span);
+                let enclosing_annotation = std::mem::replace(&mut self.current_function_annotation, annotation);
+                let enclosing_returns = std::mem::replace(&mut self.return_types, inferring.then(Vec::new));
                 
                 // Define parameters in scope
                 for (param_name, param_type) in &param_types {
-                    self.define_variable(param_name.clone(), param_type.clone());
+                    self.declare_variable(param_name.clone(), param_type.clone(), stmt.span);
                 }
                 
                 self.check_block(body);
-                self.current_function_return = None;
+                self.current_function_return = enclosing_return;
+                self.current_function_annotation = enclosing_annotation;
+                let returned = std::mem::replace(&mut self.return_types, enclosing_returns);
                 self.end_scope();
                 
+                if let Some(returned) = returned {
+                    let ret_type = if returned.is_empty() {
+                        Type::None
+                    } else {
+                        Self::agreed_type(returned).unwrap_or(Type::Any)
+                    };
+                    self.functions.insert(name.clone(), (param_types.clone(), ret_type.clone()));
+                    self.declare_variable(name.clone(), Type::Function {
+                        params: param_types.into_iter().map(|(_, ty)| ty).collect(),
+                        return_type: Box::new(ret_type),
+                    }, stmt.span);
+                }
+                
+                Type::None
+            },
+            StmtKind::Export(declaration) => self.check_statement(declaration),
+            StmtKind::ExportDefault(name) => {
+                if !self.declarations.contains_key(name) && self.get_variable_type(name).is_none() {
+                    self.reporter.error(
+                        error_codes::UNDEFINED_VARIABLE,
+                        format!("Cannot export '{}': nothing by that name is declared in this file", name),
+                        SourceLocation::at(stmt.span),
+                    );
+                }
+                Type::None
+            },
+            StmtKind::Actor { name, fields, methods } => {
+                let declared = self.actors.get(name).cloned().unwrap_or_default();
+                for ((field, _, value), (_, field_type)) in fields.iter().zip(&declared) {
+                    let value_type = self.check_expression(value);
+                    self.check_actor_field(name, field, field_type, &value_type, value.span);
+                }
+                
+                // Methods see the actor's state as `self`
+                self.begin_scope();
+                self.declare_variable("self".to_string(), Type::Named(name.clone()), stmt.span);
+                for method in methods {
+                    self.check_statement(method);
+                }
+                self.end_scope();
+                Type::None
+            },
+            StmtKind::Struct { name, fields } => {
+                for (field, type_str) in fields {
+                    if !self.is_known_type(type_str) {
+                        self.reporter.error(
+                            error_codes::UNKNOWN_TYPE,
+                            format!("Unknown type '{}' for field '{}' in struct '{}'", type_str, field, name),
+                            SourceLocation::at(stmt.span),
+                        );
+                    }
+                }
+                Type::None
+            },
+            StmtKind::Enum { name, variants } => {
+                for (variant, payload) in variants {
+                    for type_str in payload {
+                        if !self.is_known_type(type_str) {
+                            self.reporter.error(
+                                error_codes::UNKNOWN_TYPE,
+                                format!("Unknown type '{}' in variant '{}::{}'", type_str, name, variant),
+                                SourceLocation::at(stmt.span),
+                            );
+                        }
+                    }
+                }
+                Type::None
+            },
+            StmtKind::MatchStmt { expr, arms } => {
+                self.check_match(expr, arms);
                 Type::None
             },
             _ => Type::None,
         }
     }
     
+    /// Type of a `let` or `const` initializer. An empty list or map literal has
+    /// nothing to infer its element type from, so it takes the annotated type.
+    fn check_initializer(&mut self, value: &Expr, type_annot: &Option<String>) -> Type {
+        let annotated = type_annot.as_ref().and_then(|s| Type::from_string(s));
+        match (&value.kind, annotated) {
+            (ExprKind::List(items), Some(ty @ Type::List(_))) if items.is_empty() => ty,
+            (ExprKind::Map(pairs), Some(ty @ Type::Map(..))) if pairs.is_empty() => ty,
+            _ => self.check_expression(value),
+        }
+    }
+    
+    /// Annotated return type of a function; without one, generator functions
+    /// return an iterator and others return None
+    /// The type every known one of `types` agrees on, if any
+    fn agreed_type(types: Vec<Type>) -> Option<Type> {
+        let mut known = types.into_iter().filter(|ty| *ty != Type::Unknown);
+        let first = known.next()?;
+        known.all(|ty| ty == first).then_some(first)
+    }
+    
+    fn declared_return_type(return_type: &Option<String>, generator: bool) -> Type {
+        match return_type.as_ref().and_then(|s| Type::from_string(s)) {
+            Some(ty) => ty,
+            None if generator => Type::Iterator(Box::new(Type::Any)),
+            None => Type::None,
+        }
+    }
+    
+    /// Checks the body of a parallel loop, with the accumulator of a `parallel reduce`
+    /// in scope, returning the type of its value
+    fn check_parallel(&mut self, keyword: &str, var: &str, iterable: &Expr, acc: Option<(&String, Type)>, body: &[Stmt]) -> Type {
+        let iter_type = self.check_expression(iterable);
+        let item_type = match &iter_type {
+            Type::List(elem_type) => *elem_type.clone(),
+            Type::Any | Type::Unknown => Type::Any,
+            _ => {
+                self.reporter.error(
+                    error_codes::NOT_ITERABLE,
+                    format!("Parallel {} expects a list, got {}", keyword, iter_type.to_string()),
+                    SourceLocation::at(iterable.span),
+                );
+                Type::Any
+            }
+        };
+        
+        // Each item runs on its own, so `return` only finishes that item
+        let enclosing_return = self.current_function_return.take();
+        self.begin_scope();
+        if let Some((acc, acc_type)) = acc {
+            self.define_variable(acc.clone(), acc_type);
+        }
+        self.define_variable(var.to_string(), item_type);
+        let body_type = self.check_block(body);
+        self.end_scope();
+        self.current_function_return = enclosing_return;
+        body_type
+    }
+    
     fn check_block(&mut self, statements: &[Stmt]) -> Type {
         let mut last_type = Type::None;
         for stmt in statements {
@@ -288,134 +597,132 @@ impl TypeChecker {
     }
     
     fn check_expression(&mut self, expr: &Expr) -> Type {
-        match expr {
-            Expr::Number(n) => {
+        match &expr.kind {
+            ExprKind::Number(n) => {
                 if n.fract() == 0.0 {
                     Type::I32
                 } else {
                     Type::F64
                 }
             },
-            Expr::String(_) => Type::Str,
-            Expr::Boolean(_) => Type::Bool,
-            Expr::Identifier(name) => {
-                self.get_variable_type(name).unwrap_or_else(|| {
+            ExprKind::String(_) => Type::Str,
+            ExprKind::Boolean(_) => Type::Bool,
+            ExprKind::Identifier(name) => {
+                if let Some(ty) = self.get_variable_type(name) {
+                    ty
+                } else if let Some((params, return_type)) = self.functions.get(name) {
+                    // Named functions can be used as values
+                    Type::Function {
+                        params: params.iter().map(|(_, ty)| ty.clone()).collect(),
+                        return_type: Box::new(return_type.clone()),
+                    }
+                } else if self.modules.contains_key(name) {
+                    // An imported module is a map of its functions
+                    Type::Map(Box::new(Type::Str), Box::new(Type::Any))
+                } else {
                     self.reporter.error(
+                        error_codes::UNDEFINED_VARIABLE,
                         format!("Undefined variable: {}", name),
-                        None,
+                        SourceLocation::at(expr.span),
                     );
                     Type::Unknown
-                })
-            },
-            Expr::Match { expr, arms } => {
-                let _expr_type = self.check_expression(expr);
-                // Check each arm's pattern and body
-                for arm in arms {
-                    self.begin_scope();
-                    // Pattern matching type checking (simplified)
-                    self.check_block(&arm.body);
-                    self.end_scope();
                 }
-                // Return type of first arm (simplified)
+            },
+            ExprKind::Match { expr, arms } => {
+                self.check_match(expr, arms);
                 Type::Any
             },
-            Expr::Destructure { pattern: _, value } => {
+            ExprKind::Destructure { pattern: _, value } => {
                 self.check_expression(value);
                 Type::None
             },
-            Expr::Async { body } => {
-                self.check_expression(body)
+            ExprKind::Async { body } => {
+                // `return` inside the block only finishes the task
+                let enclosing_return = self.current_function_return.take();
+                self.begin_scope();
+                self.check_block(body);
+                self.end_scope();
+                self.current_function_return = enclosing_return;
+                Type::Any // Task handle
             },
-            Expr::Await { expr } => {
+            ExprKind::Await { expr } => {
                 self.check_expression(expr)
             },
-            Expr::Yield(expr) => {
-                if let Some(e) = expr {
+            ExprKind::Yield(expr) => {
+                let yielded = if let Some(e) = expr {
                     self.check_expression(e)
                 } else {
                     Type::None
+                };
+                if let Some(yield_types) = self.yield_types.as_mut() {
+                    yield_types.push(yielded);
                 }
+                Type::None
             },
-            Expr::Generator { body } => {
+            ExprKind::Generator { body } => {
+                // `return` inside a generator only ends it
+                let enclosing_return = self.current_function_return.take();
+                let enclosing_yields = self.yield_types.replace(Vec::new());
+                self.begin_scope();
                 self.check_block(body);
-                Type::Any // Generator type
+                self.end_scope();
+                let yielded = std::mem::replace(&mut self.yield_types, enclosing_yields).unwrap_or_default();
+                self.current_function_return = enclosing_return;
+                
+                // The item type is known when every yield agrees on it
+                Type::Iterator(Box::new(Self::agreed_type(yielded).unwrap_or(Type::Any)))
+            },
+            ExprKind::ParallelMap { var, iterable, body } => {
+                Type::List(Box::new(self.check_parallel("map", var, iterable, None, body)))
+            },
+            ExprKind::ParallelReduce { acc, initial, var, iterable, body, combine } => {
+                let acc_type = self.check_expression(initial);
+                let body_type = self.check_parallel("reduce", var, iterable, Some((acc, acc_type.clone())), body);
+                if !body_type.can_coerce_to(&acc_type) && body_type != Type::Unknown {
+                    self.reporter.error(
+                        error_codes::TYPE_MISMATCH,
+                        format!("Parallel reduce body must produce {}, got {}", acc_type.to_string(), body_type.to_string()),
+                        SourceLocation::at(expr.span),
+                    );
+                }
+                if let Some(combine) = combine {
+                    let enclosing_return = self.current_function_return.take();
+                    self.begin_scope();
+                    self.define_variable(combine.left.clone(), acc_type.clone());
+                    self.define_variable(combine.right.clone(), acc_type.clone());
+                    let combine_type = self.check_block(&combine.body);
+                    self.end_scope();
+                    self.current_function_return = enclosing_return;
+                    if !combine_type.can_coerce_to(&acc_type) && combine_type != Type::Unknown {
+                        self.reporter.error(
+                            error_codes::TYPE_MISMATCH,
+                            format!("Parallel reduce combine must produce {}, got {}", acc_type.to_string(), combine_type.to_string()),
+                            SourceLocation::at(expr.span),
+                        );
+                    }
+                }
+                acc_type
             },
-            Expr::Coroutine { body } => {
+            ExprKind::Coroutine { body } => {
                 self.check_block(body);
                 Type::Any // Coroutine type
             },
-            Expr::Suspend => Type::None,
-            Expr::Resume { coroutine } => {
+            ExprKind::Suspend(expr) => {
+                if let Some(e) = expr {
+                    self.check_expression(e);
+                }
+                Type::Any // Whatever the next `resume` passes back
+            },
+            ExprKind::Resume { coroutine } => {
                 self.check_expression(coroutine);
                 Type::Any
             },
-            Expr::Binary { left, op, right } => {
+            ExprKind::Binary { left, op, right } => {
                 let left_type = self.check_expression(left);
                 let right_type = self.check_expression(right);
-                
-                match op {
-                    BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | 
-                    BinaryOp::Divide | BinaryOp::Modulo => {
-                        if left_type.is_numeric() && right_type.is_numeric() {
-                            // Promote to the larger type
-                            if left_type.is_float() || right_type.is_float() {
-                                Type::F64
-                            } else if left_type == Type::I64 || right_type == Type::I64 {
-                                Type::I64
-                            } else {
-                                Type::I32
-                            }
-                        } else if left_type == Type::Str || right_type == Type::Str {
-                            Type::Str // String concatenation
-                        } else {
-                            self.reporter.error(
-                                format!("Cannot apply {:?} to {} and {}", 
-                                    op, left_type.to_string(), right_type.to_string()),
-                                None,
-                            );
-                            Type::Unknown
-                        }
-                    },
-                    BinaryOp::Equal | BinaryOp::NotEqual => {
-                        if left_type.can_coerce_to(&right_type) || right_type.can_coerce_to(&left_type) {
-                            Type::Bool
-                        } else {
-                            self.reporter.error(
-                                format!("Cannot compare {} and {}", 
-                                    left_type.to_string(), right_type.to_string()),
-                                None,
-                            );
-                            Type::Unknown
-                        }
-                    },
-                    BinaryOp::LessThan | BinaryOp::LessEqual | 
-                    BinaryOp::GreaterThan | BinaryOp::GreaterEqual => {
-                        if left_type.is_numeric() && right_type.is_numeric() {
-                            Type::Bool
-                        } else {
-                            self.reporter.error(
-                                format!("Cannot compare {} and {} with {:?}", 
-                                    left_type.to_string(), right_type.to_string(), op),
-                                None,
-                            );
-                            Type::Unknown
-                        }
-                    },
-                    BinaryOp::And | BinaryOp::Or => {
-                        if left_type == Type::Bool && right_type == Type::Bool {
-                            Type::Bool
-                        } else {
-                            self.reporter.error(
-                                format!("Logical operators require bool, got {} and {}", 
-                                    left_type.to_string(), right_type.to_string()),
-                                None,
-                            );
-                            Type::Unknown
-                        }
-                    },
-                }
+                self.binary_result_type(&left_type, op, &right_type, expr.span)
             },
-            Expr::Unary { op, expr } => {
+            ExprKind::Unary { op, expr } => {
                 let expr_type = self.check_expression(expr);
                 match op {
                     UnaryOp::Not => {
@@ -423,8 +730,9 @@ impl TypeChecker {
                             Type::Bool
                         } else {
                             self.reporter.error(
+                                error_codes::INVALID_OPERANDS,
                                 format!("Not operator requires bool, got {}", expr_type.to_string()),
-                                None,
+                                SourceLocation::at(expr.span),
                             );
                             Type::Unknown
                         }
@@ -434,99 +742,186 @@ impl TypeChecker {
                             expr_type
                         } else {
                             self.reporter.error(
+                                error_codes::INVALID_OPERANDS,
                                 format!("Negation requires numeric type, got {}", expr_type.to_string()),
-                                None,
+                                SourceLocation::at(expr.span),
                             );
                             Type::Unknown
                         }
                     },
                 }
             },
-            Expr::Call { callee, args } => {
-                let (param_types, return_type) = if let Some(info) = self.functions.get(callee) {
-                    (info.0.clone(), info.1.clone())
-                } else {
-                    // Built-in function - check based on name
-                    match callee.as_str() {
-                        "print" => {
-                            for arg in args {
-                                self.check_expression(arg);
-                            }
-                            return Type::None;
-                        },
-                        "range" => {
-                            if args.len() == 1 || args.len() == 2 {
-                                for arg in args {
-                                    let arg_type = self.check_expression(arg);
-                                    if !arg_type.is_integer() && arg_type != Type::Unknown && arg_type != Type::Any {
-                                        self.reporter.error(
-                                            format!("range() expects integer arguments, got {}", arg_type.to_string()),
-                                            None,
-                                        );
-                                    }
-                                }
-                                return Type::List(Box::new(Type::I32));
-                            } else {
-                                self.reporter.error(
-                                    format!("range() expects 1 or 2 arguments, got {}", args.len()),
-                                    None,
-                                );
-                                return Type::Unknown;
-                            }
-                        },
-                        _ => {
-                            self.reporter.error(
-                                format!("Unknown function: {}", callee),
-                                None,
-                            );
-                            return Type::Unknown;
-                        }
+            ExprKind::Cast { expr: operand, target } => {
+                let operand_type = self.check_expression(operand);
+                match Type::from_string(target) {
+                    Some(target_type) if target_type.is_numeric()
+                        && (operand_type.is_numeric() || matches!(operand_type, Type::Any | Type::Unknown)) => target_type,
+                    _ => {
+                        self.reporter.error(
+                            error_codes::INVALID_OPERANDS,
+                            format!("Cannot cast {} to {}; 'as' converts between numeric types", operand_type.to_string(), target),
+                            SourceLocation::at(expr.span),
+                        );
+                        Type::Unknown
+                    },
+                }
+            },
+            ExprKind::Call { callee, args } => {
+                let callee_name = match &callee.kind {
+                    ExprKind::Identifier(name) => Some(name.clone()),
+                    _ => self.qualified_name(callee),
+                };
+                
+                // Names that are neither variables nor declared functions refer to builtins
+                if let Some(name) = &callee_name {
+                    if self.get_variable_type(name).is_none() && !self.functions.contains_key(name) {
+                        return self.check_builtin_call(name, args, expr.span);
                     }
+                }
+                
+                let (param_types, return_type) = match self.check_expression(callee) {
+                    Type::Function { params, return_type } => (params, *return_type),
+                    Type::Any | Type::Unknown => {
+                        for arg in args {
+                            self.check_expression(arg);
+                        }
+                        return Type::Any;
+                    },
+                    other => {
+                        self.reporter.error(
+                            error_codes::NOT_CALLABLE,
+                            format!("Cannot call value of type {}", other.to_string()),
+                            SourceLocation::at(callee.span),
+                        );
+                        return Type::Unknown;
+                    },
                 };
                 
+                let declared_at = callee_name.as_deref()
+                    .map(|name| self.declaration_span(name))
+                    .unwrap_or_default();
+                let described = callee_name
+                    .map(|name| format!("'{}'", name))
+                    .unwrap_or_else(|| "function value".to_string());
+                
                 if args.len() != param_types.len() {
                     self.reporter.error(
-                        format!("Function '{}' expects {} arguments, got {}", 
-                            callee, param_types.len(), args.len()),
-                        None,
+                        error_codes::ARGUMENT_COUNT,
+                        format!("Function {} expects {} arguments, got {}", 
+                            described, param_types.len(), args.len()),
+                        SourceLocation::at(expr.span),
                     );
                     return Type::Unknown;
                 }
                 
-                for (i, (arg, (_, param_type))) in args.iter().zip(param_types.iter()).enumerate() {
+                for (i, (arg, param_type)) in args.iter().zip(param_types.iter()).enumerate() {
                     let arg_type = self.check_expression(arg);
-                    if !arg_type.can_coerce_to(param_type) && arg_type != Type::Unknown {
-                        self.reporter.error(
-                            format!("Argument {} to '{}': expected {}, got {}", 
-                                i + 1, callee, param_type.to_string(), arg_type.to_string()),
-                            None,
+                    let dynamic = |ty: &Type| matches!(ty, Type::Any | Type::Unknown);
+                    if !arg_type.can_coerce_to(param_type) && !dynamic(&arg_type) && !dynamic(param_type) {
+                        self.error_with_label(
+                            error_codes::ARGUMENT_TYPE_MISMATCH,
+                            format!("Argument {} to {}: expected {}, got {}", 
+                                i + 1, described, param_type.to_string(), arg_type.to_string()),
+                            arg.span,
+                            declared_at,
+                            format!("{} declared here", described),
                         );
                     }
                 }
                 
                 return_type
             },
-            Expr::Member { object, member: _ } => {
+            ExprKind::Lambda { params, body } => {
+                let param_types: Vec<Type> = params.iter()
+                    .map(|(_, type_str)| type_str.as_ref()
+                        .and_then(|s| Type::from_string(s))
+                        .unwrap_or(Type::Unknown))
+                    .collect();
+                
+                self.begin_scope();
+                for ((param_name, _), param_type) in params.iter().zip(&param_types) {
+                    self.define_variable(param_name.clone(), param_type.clone());
+                }
+                let enclosing_return = self.current_function_return.take();
+                let body_type = self.check_block(body);
+                self.current_function_return = enclosing_return;
+                self.end_scope();
+                
+                // Expression lambdas return their expression; block lambdas are not inferred
+                let return_type = match body.as_slice() {
+                    [Stmt { kind: StmtKind::Return(Some(_)), .. }] => body_type,
+                    _ => Type::Any,
+                };
+                
+                Type::Function {
+                    params: param_types,
+                    return_type: Box::new(return_type),
+                }
+            },
+            ExprKind::Member { object, member } => {
+                if let Some(name) = self.qualified_name(expr) {
+                    return match self.functions.get(&name) {
+                        Some((params, return_type)) => Type::Function {
+                            params: params.iter().map(|(_, ty)| ty.clone()).collect(),
+                            return_type: Box::new(return_type.clone()),
+                        },
+                        None => self.module_values[&name].clone(),
+                    };
+                }
+                if let ExprKind::Identifier(import_name) = &object.kind {
+                    if let Some(module) = self.modules.get(import_name).filter(|_| self.get_variable_type(import_name).is_none()) {
+                        self.reporter.error(
+                            error_codes::NO_SUCH_MEMBER,
+                            format!("Module '{}' does not export '{}'", module, member),
+                            SourceLocation::at(expr.span),
+                        );
+                        return Type::Unknown;
+                    }
+                }
+                
                 let obj_type = self.check_expression(object);
-                // For now, we only support maps
                 if let Type::Map(_, value_type) = obj_type {
                     *value_type.clone()
+                } else if let Some(fields) = self.struct_fields(&obj_type) {
+                    match fields.iter().find(|(field, _)| field == member) {
+                        Some((_, field_type)) => field_type.clone(),
+                        None => {
+                            self.reporter.error(
+                                error_codes::NO_SUCH_MEMBER,
+                                format!("Struct '{}' has no field '{}'", obj_type.to_string(), member),
+                                SourceLocation::at(expr.span),
+                            );
+                            Type::Unknown
+                        },
+                    }
+                } else if matches!(obj_type, Type::Any | Type::Unknown) {
+                    Type::Any
                 } else {
                     self.reporter.error(
+                        error_codes::NO_SUCH_MEMBER,
                         format!("Cannot access member on {}", obj_type.to_string()),
-                        None,
+                        SourceLocation::at(expr.span),
                     );
                     Type::Unknown
                 }
             },
-            Expr::Index { object, index } => {
+            ExprKind::Index { object, index } => {
                 let obj_type = self.check_expression(object);
                 let idx_type = self.check_expression(index);
                 
-                if !idx_type.is_integer() && idx_type != Type::Unknown && idx_type != Type::Any {
+                if let Type::Map(key_type, _) = &obj_type {
+                    if !idx_type.can_coerce_to(key_type) && idx_type != Type::Unknown && idx_type != Type::Any {
+                        self.reporter.error(
+                            error_codes::INVALID_INDEX,
+                            format!("Map key must be {}, got {}", key_type.to_string(), idx_type.to_string()),
+                            SourceLocation::at(index.span),
+                        );
+                    }
+                } else if !idx_type.is_integer() && idx_type != Type::Unknown && idx_type != Type::Any {
                     self.reporter.error(
+                        error_codes::INVALID_INDEX,
                         format!("Index must be integer, got {}", idx_type.to_string()),
-                        None,
+                        SourceLocation::at(index.span),
                     );
                 }
                 
@@ -536,14 +931,15 @@ impl TypeChecker {
                     Type::Any | Type::Unknown => Type::Any,
                     _ => {
                         self.reporter.error(
+                            error_codes::INVALID_INDEX,
                             format!("Cannot index {}", obj_type.to_string()),
-                            None,
+                            SourceLocation::at(expr.span),
                         );
                         Type::Unknown
                     }
                 }
             },
-            Expr::List(elements) => {
+            ExprKind::List(elements) => {
                 if elements.is_empty() {
                     Type::List(Box::new(Type::Any))
                 } else {
@@ -552,54 +948,468 @@ impl TypeChecker {
                         let elem_type = self.check_expression(elem);
                         if elem_type != first_type && elem_type != Type::Unknown && first_type != Type::Unknown {
                             self.reporter.warning(
+                                error_codes::MIXED_LIST,
                                 format!("List contains mixed types: {} and {}", 
                                     first_type.to_string(), elem_type.to_string()),
-                                None,
+                                SourceLocation::at(elem.span),
                             );
                         }
                     }
                     Type::List(Box::new(first_type))
                 }
             },
-            Expr::Map(pairs) => {
-                let mut key_type = Type::Unknown;
+            ExprKind::Map(pairs) => {
+                // Keys are field names or string literals, so they are always str.
+                // Records mixing value types are inferred as map[str, any].
                 let mut value_type = Type::Unknown;
                 
-                for (key, value) in pairs {
-                    let kt = self.check_expression(key);
+                for (_, value) in pairs {
                     let vt = self.check_expression(value);
                     
-                    if key_type == Type::Unknown {
-                        key_type = kt.clone();
-                    } else if kt != key_type && kt != Type::Unknown {
-                        self.reporter.warning(
-                            format!("Map contains mixed key types: {} and {}", 
-                                key_type.to_string(), kt.to_string()),
-                            None,
-                        );
-                    }
-                    
                     if value_type == Type::Unknown {
-                        value_type = vt.clone();
+                        value_type = vt;
                     } else if vt != value_type && vt != Type::Unknown {
-                        self.reporter.warning(
-                            format!("Map contains mixed value types: {} and {}", 
-                                value_type.to_string(), vt.to_string()),
-                            None,
-                        );
+                        value_type = Type::Any;
                     }
                 }
                 
-                if key_type == Type::Unknown {
-                    key_type = Type::Str; // Default to string keys
-                }
                 if value_type == Type::Unknown {
                     value_type = Type::Any;
                 }
                 
-                Type::Map(Box::new(key_type), Box::new(value_type))
+                Type::Map(Box::new(Type::Str), Box::new(value_type))
+            },
+            ExprKind::StructLiteral { name, fields } if self.actors.contains_key(name) => {
+                self.check_spawn(name, fields)
+            },
+            ExprKind::Spawn { actor, fields } => {
+                if self.actors.contains_key(actor) {
+                    self.check_spawn(actor, fields)
+                } else {
+                    self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Unknown actor '{}'", actor), SourceLocation::at(expr.span));
+                    Type::Unknown
+                }
+            },
+            ExprKind::Send { actor, args, .. } => {
+                self.check_expression(actor);
+                for arg in args {
+                    self.check_expression(arg);
+                }
+                Type::None
+            },
+            ExprKind::StructLiteral { name, fields } => {
+                let declared = match self.structs.get(name) {
+                    Some(declared) => declared.clone(),
+                    None => {
+                        self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Unknown struct '{}'", name), SourceLocation::at(expr.span));
+                        for (_, value) in fields {
+                            self.check_expression(value);
+                        }
+                        return Type::Unknown;
+                    },
+                };
+                
+                for (field, value) in fields {
+                    let value_type = self.check_expression(value);
+                    match declared.iter().find(|(declared_field, _)| declared_field == field) {
+                        Some((_, field_type)) => {
+                            if !value_type.can_coerce_to(field_type)
+                                && !matches!(value_type, Type::Unknown | Type::Any)
+                                && *field_type != Type::Unknown {
+                                let declared_at = self.declaration_span(name);
+                                self.error_with_label(
+                                    error_codes::TYPE_MISMATCH,
+                                    format!("Field '{}' of '{}' expects {}, got {}",
+                                        field, name, field_type.to_string(), value_type.to_string()),
+                                    value.span,
+                                    declared_at,
+                                    format!("'{}' declared here", name),
+                                );
+                            }
+                        },
+                        None => {
+                            self.reporter.error(error_codes::NO_SUCH_MEMBER, format!("Struct '{}' has no field '{}'", name, field), SourceLocation::at(value.span));
+                        },
+                    }
+                }
+                
+                for (field, _) in &declared {
+                    if !fields.iter().any(|(given, _)| given == field) {
+                        self.reporter.error(error_codes::MISSING_FIELD, format!("Missing field '{}' in '{}' literal", field, name), SourceLocation::at(expr.span));
+                    }
+                }
+                
+                Type::Named(name.clone())
+            },
+            ExprKind::EnumVariant { enum_name, variant, args } => {
+                let arg_types: Vec<Type> = args.iter().map(|arg| self.check_expression(arg)).collect();
+                let payload = match self.enums.get(enum_name) {
+                    Some(variants) => variants.iter()
+                        .find(|(name, _)| name == variant)
+                        .map(|(_, payload)| payload.clone()),
+                    None => {
+                        self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Unknown enum '{}'", enum_name), SourceLocation::at(expr.span));
+                        return Type::Unknown;
+                    },
+                };
+                let payload = match payload {
+                    Some(payload) => payload,
+                    None => {
+                        self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Enum '{}' has no variant '{}'", enum_name, variant), SourceLocation::at(expr.span));
+                        return Type::Named(enum_name.clone());
+                    },
+                };
+                
+                if payload.len() != arg_types.len() {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("Variant '{}::{}' expects {} values, got {}",
+                            enum_name, variant, payload.len(), arg_types.len()),
+                        SourceLocation::at(expr.span),
+                    );
+                } else {
+                    for (i, (expected, actual)) in payload.iter().zip(&arg_types).enumerate() {
+                        if !actual.can_coerce_to(expected)
+                            && !matches!(actual, Type::Unknown | Type::Any)
+                            && *expected != Type::Unknown {
+                            self.reporter.error(
+                                error_codes::TYPE_MISMATCH,
+                                format!("Value {} of '{}::{}' expects {}, got {}",
+                                    i + 1, enum_name, variant, expected.to_string(), actual.to_string()),
+                                SourceLocation::at(args[i].span),
+                            );
+                        }
+                    }
+                }
+                
+                Type::Named(enum_name.clone())
+            },
+            ExprKind::None => Type::None,
+            ExprKind::Assign { target, op, value } => {
+                let target_type = match &target.kind {
+                    ExprKind::Identifier(name) => match self.get_variable_type(name) {
+                        Some(ty) => ty,
+                        None => {
+                            self.reporter.error(
+                                error_codes::UNDEFINED_VARIABLE,
+                                format!("Cannot assign to undefined variable: {}", name),
+                                SourceLocation::at(target.span),
+                            );
+                            self.check_expression(value);
+                            return Type::Unknown;
+                        },
+                    },
+                    ExprKind::Member { .. } | ExprKind::Index { .. } => self.check_expression(target),
+                    _ => {
+                        self.reporter.error(error_codes::INVALID_ASSIGNMENT_TARGET, "Invalid assignment target".to_string(), SourceLocation::at(target.span));
+                        return Type::Unknown;
+                    },
+                };
+                
+                let value_type = self.check_expression(value);
+                let result_type = match op {
+                    Some(op) => self.binary_result_type(&target_type, op, &value_type, expr.span),
+                    None => value_type,
+                };
+                
+                if !result_type.can_coerce_to(&target_type)
+                    && !matches!(result_type, Type::Unknown | Type::Any)
+                    && target_type != Type::Unknown {
+                    let message = format!("Type mismatch in assignment: expected {}, got {}", 
+                        target_type.to_string(), result_type.to_string());
+                    match &target.kind {
+                        ExprKind::Identifier(name) => {
+                            let declared_at = self.declaration_span(name);
+                            self.error_with_label(error_codes::TYPE_MISMATCH, message, value.span, declared_at,
+                                format!("'{}' declared as {} here", name, target_type.to_string()));
+                        },
+                        _ => self.reporter.error(error_codes::TYPE_MISMATCH, message, SourceLocation::at(value.span)),
+                    }
+                }
+                target_type
+            },
+        }
+    }
+    
+    /// Checks a call to a builtin function by name
+    fn check_builtin_call(&mut self, name: &str, args: &[Expr], span: Span) -> Type {
+        match name {
+            "print" => {
+                for arg in args {
+                    self.check_expression(arg);
+                }
+                Type::None
+            },
+            "range" => {
+                if args.len() == 1 || args.len() == 2 {
+                    for arg in args {
+                        let arg_type = self.check_expression(arg);
+                        if !arg_type.is_integer() && arg_type != Type::Unknown && arg_type != Type::Any {
+                            self.reporter.error(
+                                error_codes::ARGUMENT_TYPE_MISMATCH,
+                                format!("range() expects integer arguments, got {}", arg_type.to_string()),
+                                SourceLocation::at(arg.span),
+                            );
+                        }
+                    }
+                    Type::List(Box::new(Type::I32))
+                } else {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("range() expects 1 or 2 arguments, got {}", args.len()),
+                        SourceLocation::at(span),
+                    );
+                    Type::Unknown
+                }
+            },
+            "sleep" => {
+                if args.len() != 1 {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("sleep() expects 1 argument, got {}", args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                let millis_type = self.check_expression(&args[0]);
+                if !millis_type.is_numeric() && !matches!(millis_type, Type::Unknown | Type::Any) {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_TYPE_MISMATCH,
+                        format!("sleep() expects milliseconds as a number, got {}", millis_type.to_string()),
+                        SourceLocation::at(args[0].span),
+                    );
+                }
+                Type::Any // Task handle
+            },
+            "join_all" | "select" => {
+                if args.len() != 1 {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("{}() expects 1 argument, got {}", name, args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                let tasks_type = self.check_expression(&args[0]);
+                if !matches!(tasks_type, Type::List(_) | Type::Unknown | Type::Any) {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_TYPE_MISMATCH,
+                        format!("{}() expects a list of tasks, got {}", name, tasks_type.to_string()),
+                        SourceLocation::at(args[0].span),
+                    );
+                }
+                Type::List(Box::new(Type::Any))
+            },
+            "timeout" => {
+                if args.len() != 2 {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("timeout() expects 2 arguments, got {}", args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                self.check_expression(&args[0]);
+                let millis_type = self.check_expression(&args[1]);
+                if !millis_type.is_numeric() && !matches!(millis_type, Type::Unknown | Type::Any) {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_TYPE_MISMATCH,
+                        format!("timeout() expects milliseconds as a number, got {}", millis_type.to_string()),
+                        SourceLocation::at(args[1].span),
+                    );
+                }
+                Type::Any // Actor reply
+            },
+            "supervise" => {
+                if args.is_empty() || args.len() > 4 {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("supervise() expects 1 to 4 arguments, got {}", args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                let children_type = self.check_expression(&args[0]);
+                if !matches!(children_type, Type::List(_) | Type::Unknown | Type::Any) {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_TYPE_MISMATCH,
+                        format!("supervise() expects a list of actors and supervisors, got {}", children_type.to_string()),
+                        SourceLocation::at(args[0].span),
+                    );
+                }
+                if let Some(strategy) = args.get(1) {
+                    let strategy_type = self.check_expression(strategy);
+                    if !matches!(strategy_type, Type::Str | Type::Unknown | Type::Any) {
+                        self.reporter.error(
+                            error_codes::ARGUMENT_TYPE_MISMATCH,
+                            format!("supervise() expects a restart strategy name, got {}", strategy_type.to_string()),
+                            SourceLocation::at(strategy.span),
+                        );
+                    }
+                }
+                for limit in args.iter().skip(2) {
+                    let limit_type = self.check_expression(limit);
+                    if !limit_type.is_numeric() && !matches!(limit_type, Type::Unknown | Type::Any) {
+                        self.reporter.error(
+                            error_codes::ARGUMENT_TYPE_MISMATCH,
+                            format!("supervise() expects restart limits as numbers, got {}", limit_type.to_string()),
+                            SourceLocation::at(limit.span),
+                        );
+                    }
+                }
+                Type::Any // Supervisor handle
+            },
+            "stop" | "terminate" => {
+                if args.len() != 1 {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("{}() expects 1 argument, got {}", name, args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                self.check_expression(&args[0]);
+                Type::None
+            },
+            "is_completed" | "is_suspended" | "cancel" => {
+                if args.len() != 1 {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("{}() expects 1 argument, got {}", name, args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                self.check_expression(&args[0]);
+                if name == "cancel" { Type::None } else { Type::Bool }
+            },
+            "take" | "next" | "collect" => {
+                let arity = if name == "take" { 2 } else { 1 };
+                if args.len() != arity {
+                    self.reporter.error(
+                        error_codes::ARGUMENT_COUNT,
+                        format!("{}() expects {} argument{}, got {}", name, arity, if arity == 1 { "" } else { "s" }, args.len()),
+                        SourceLocation::at(span),
+                    );
+                    return Type::Unknown;
+                }
+                
+                let items_type = self.check_expression(&args[0]);
+                if name == "take" {
+                    let count_type = self.check_expression(&args[1]);
+                    if !count_type.is_integer() && !matches!(count_type, Type::Unknown | Type::Any) {
+                        self.reporter.error(
+                            error_codes::ARGUMENT_TYPE_MISMATCH,
+                            format!("take() expects an integer count, got {}", count_type.to_string()),
+                            SourceLocation::at(args[1].span),
+                        );
+                    }
+                }
+                
+                match (name, &items_type) {
+                    (_, Type::Unknown | Type::Any) => Type::Any,
+                    ("take", Type::List(_) | Type::Iterator(_)) => items_type.clone(),
+                    ("next", Type::Iterator(item)) => *item.clone(),
+                    ("collect", Type::List(_)) => items_type.clone(),
+                    ("collect", Type::Iterator(item)) => Type::List(item.clone()),
+                    _ => {
+                        let expected = if name == "next" { "an iterator" } else { "a list or iterator" };
+                        self.reporter.error(
+                            error_codes::ARGUMENT_TYPE_MISMATCH,
+                            format!("{}() expects {}, got {}", name, expected, items_type.to_string()),
+                            SourceLocation::at(args[0].span),
+                        );
+                        Type::Unknown
+                    },
+                }
+            },
+            _ => {
+                self.reporter.error(
+                    error_codes::UNDEFINED_FUNCTION,
+                    format!("Unknown function: {}", name),
+                    SourceLocation::at(span),
+                );
+                Type::Unknown
+            }
+        }
+    }
+    
+    /// Result type of applying a binary operator, reporting operands it cannot accept
+    fn binary_result_type(&mut self, left_type: &Type, op: &BinaryOp, right_type: &Type, span: Span) -> Type {
+        // Dynamically typed operands are checked at runtime
+        let dynamic = |ty: &Type| matches!(ty, Type::Any | Type::Unknown);
+        if dynamic(left_type) || dynamic(right_type) {
+            return match op {
+                BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply |
+                BinaryOp::Divide | BinaryOp::Modulo => Type::Any,
+                _ => Type::Bool,
+            };
+        }
+        
+        match op {
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | 
+            BinaryOp::Divide | BinaryOp::Modulo => {
+                if left_type.is_numeric() && right_type.is_numeric() {
+                    // Promote to the larger type
+                    if left_type.is_float() || right_type.is_float() {
+                        Type::F64
+                    } else if *left_type == Type::I64 || *right_type == Type::I64 {
+                        Type::I64
+                    } else {
+                        Type::I32
+                    }
+                } else if *left_type == Type::Str || *right_type == Type::Str {
+                    Type::Str // String concatenation
+                } else {
+                    self.reporter.error(
+                        error_codes::INVALID_OPERANDS,
+                        format!("Cannot apply {:?} to {} and {}", 
+                            op, left_type.to_string(), right_type.to_string()),
+                        SourceLocation::at(span),
+                    );
+                    Type::Unknown
+                }
+            },
+            BinaryOp::Equal | BinaryOp::NotEqual => {
+                if left_type.can_coerce_to(right_type) || right_type.can_coerce_to(left_type) {
+                    Type::Bool
+                } else {
+                    self.reporter.error(
+                        error_codes::INVALID_OPERANDS,
+                        format!("Cannot compare {} and {}", 
+                            left_type.to_string(), right_type.to_string()),
+                        SourceLocation::at(span),
+                    );
+                    Type::Unknown
+                }
+            },
+            BinaryOp::LessThan | BinaryOp::LessEqual | 
+            BinaryOp::GreaterThan | BinaryOp::GreaterEqual => {
+                if left_type.is_numeric() && right_type.is_numeric() {
+                    Type::Bool
+                } else {
+                    self.reporter.error(
+                        error_codes::INVALID_OPERANDS,
+                        format!("Cannot compare {} and {} with {:?}", 
+                            left_type.to_string(), right_type.to_string(), op),
+                        SourceLocation::at(span),
+                    );
+                    Type::Unknown
+                }
+            },
+            BinaryOp::And | BinaryOp::Or => {
+                if *left_type == Type::Bool && *right_type == Type::Bool {
+                    Type::Bool
+                } else {
+                    self.reporter.error(
+                        error_codes::INVALID_OPERANDS,
+                        format!("Logical operators require bool, got {} and {}", 
+                            left_type.to_string(), right_type.to_string()),
+                        SourceLocation::at(span),
+                    );
+                    Type::Unknown
+                }
             },
-            Expr::None => Type::None,
         }
     }
     
@@ -612,19 +1422,251 @@ impl TypeChecker {
     }
     
     fn define_variable(&mut self, name: String, ty: Type) {
+        self.declare_variable(name, ty, Span::default());
+    }
+    
+    /// Defines a variable, remembering where it was declared for diagnostics
+    fn declare_variable(&mut self, name: String, ty: Type, span: Span) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name, ty);
+            scope.insert(name, (ty, span));
         }
     }
     
     fn get_variable_type(&self, name: &str) -> Option<Type> {
+        self.lookup_variable(name).map(|(ty, _)| ty.clone())
+    }
+    
+    fn lookup_variable(&self, name: &str) -> Option<&(Type, Span)> {
         // Check scopes from innermost to outermost
-        for scope in self.scopes.iter().rev() {
-            if let Some(ty) = scope.get(name) {
-                return Some(ty.clone());
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+    
+    /// Where a variable or declaration of this file is declared; unknown for
+    /// builtins and imports
+    fn declaration_span(&self, name: &str) -> Span {
+        match self.lookup_variable(name) {
+            Some((_, span)) => *span,
+            None => self.declarations.get(name).copied().unwrap_or_default(),
+        }
+    }
+    
+    /// Reports an error at `span` with a secondary label pointing at a related declaration
+    fn error_with_label(&mut self, code: &str, message: String, span: Span, declared_at: Span, label: String) {
+        self.reporter.report(Diagnostic::error(message, SourceLocation::at(span)).with_code(code).with_label(declared_at, label));
+    }
+    
+    /// Checks each arm against the matched value and requires the arms to be exhaustive
+    fn check_match(&mut self, expr: &Expr, arms: &[MatchArm]) {
+        let subject_type = self.check_expression(expr);
+        for arm in arms {
+            self.begin_scope();
+            self.check_pattern(&arm.pattern, &subject_type, expr.span);
+            if let Some(guard) = &arm.guard {
+                self.check_expression(guard);
+            }
+            self.check_block(&arm.body);
+            self.end_scope();
+        }
+        
+        let enums: EnumVariants = self.enums.iter()
+            .map(|(name, variants)| {
+                let variants = variants.iter()
+                    .map(|(variant, payload)| (variant.clone(), payload.len()))
+                    .collect();
+                (name.clone(), variants)
+            })
+            .collect();
+        if let Err(e) = ExhaustivenessChecker::check_exhaustive(expr, arms, &enums) {
+            self.reporter.error(error_codes::NON_EXHAUSTIVE_MATCH, e, SourceLocation::at(expr.span));
+        }
+    }
+    
+    /// Checks a pattern against the type of the value it matches and defines the
+    /// variables it binds. Patterns have no spans, so errors point at the matched value.
+    fn check_pattern(&mut self, pattern: &Pattern, ty: &Type, span: Span) {
+        match pattern {
+            Pattern::Identifier(name) => self.define_variable(name.clone(), ty.clone()),
+            Pattern::Binding { name, pattern } => {
+                self.define_variable(name.clone(), ty.clone());
+                self.check_pattern(pattern, ty, span);
+            },
+            Pattern::Variant { enum_name, variant, fields } => {
+                if let Type::Named(type_name) = ty {
+                    if type_name != enum_name {
+                        self.reporter.error(
+                            error_codes::PATTERN_MISMATCH,
+                            format!("Pattern '{}::{}' cannot match a value of type {}", enum_name, variant, type_name),
+                            SourceLocation::at(span),
+                        );
+                    }
+                }
+                
+                let payload = match self.enums.get(enum_name) {
+                    Some(variants) => match variants.iter().find(|(name, _)| name == variant) {
+                        Some((_, payload)) => payload.clone(),
+                        None => {
+                            self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Enum '{}' has no variant '{}'", enum_name, variant), SourceLocation::at(span));
+                            vec![Type::Any; fields.len()]
+                        },
+                    },
+                    None => {
+                        self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Unknown enum '{}'", enum_name), SourceLocation::at(span));
+                        vec![Type::Any; fields.len()]
+                    },
+                };
+                if payload.len() != fields.len() {
+                    self.reporter.error(
+                        error_codes::PATTERN_MISMATCH,
+                        format!("Variant '{}::{}' has {} values, but the pattern has {}",
+                            enum_name, variant, payload.len(), fields.len()),
+                        SourceLocation::at(span),
+                    );
+                }
+                for (i, field) in fields.iter().enumerate() {
+                    let field_type = payload.get(i).cloned().unwrap_or(Type::Any);
+                    self.check_pattern(field, &field_type, span);
+                }
+            },
+            Pattern::Struct { name, fields } => {
+                let declared = self.structs.get(name).cloned();
+                if declared.is_none() {
+                    self.reporter.error(error_codes::UNKNOWN_DECLARATION, format!("Unknown struct '{}'", name), SourceLocation::at(span));
+                }
+                for (field, field_pattern) in fields {
+                    let field_type = match &declared {
+                        Some(declared) => match declared.iter().find(|(declared_field, _)| declared_field == field) {
+                            Some((_, field_type)) => field_type.clone(),
+                            None => {
+                                self.reporter.error(error_codes::NO_SUCH_MEMBER, format!("Struct '{}' has no field '{}'", name, field), SourceLocation::at(span));
+                                Type::Any
+                            },
+                        },
+                        None => Type::Any,
+                    };
+                    self.check_pattern(field_pattern, &field_type, span);
+                }
+            },
+            Pattern::Tuple(patterns) | Pattern::List(patterns) => {
+                let element_type = match ty {
+                    Type::List(element_type) => *element_type.clone(),
+                    _ => Type::Any,
+                };
+                for pattern in patterns {
+                    self.check_pattern(pattern, &element_type, span);
+                }
+            },
+            Pattern::Or(patterns) => {
+                for pattern in patterns {
+                    self.check_pattern(pattern, ty, span);
+                }
+            },
+            Pattern::Guard { pattern, condition } => {
+                self.check_pattern(pattern, ty, span);
+                self.check_expression(condition);
+            },
+            Pattern::Number(_) | Pattern::String(_) | Pattern::Boolean(_) | Pattern::Wildcard => {},
+        }
+    }
+    
+    /// Spawns the actor, with the given fields replacing their initializers
+    fn check_spawn(&mut self, actor: &str, fields: &[(String, Expr)]) -> Type {
+        let declared = self.actors[actor].clone();
+        for (field, value) in fields {
+            let value_type = self.check_expression(value);
+            match declared.iter().find(|(declared_field, _)| declared_field == field) {
+                Some((_, field_type)) => self.check_actor_field(actor, field, field_type, &value_type, value.span),
+                None => self.reporter.error(error_codes::NO_SUCH_MEMBER, format!("Actor '{}' has no state field '{}'", actor, field), SourceLocation::at(value.span)),
+            }
+        }
+        Type::Any // Actor reference
+    }
+    
+    fn check_actor_field(&mut self, actor: &str, field: &str, field_type: &Type, value_type: &Type, span: Span) {
+        if !value_type.can_coerce_to(field_type)
+            && !matches!(value_type, Type::Unknown | Type::Any)
+            && *field_type != Type::Unknown {
+            let declared_at = self.declaration_span(actor);
+            self.error_with_label(
+                error_codes::TYPE_MISMATCH,
+                format!("State field '{}' of actor '{}' expects {}, got {}",
+                    field, actor, field_type.to_string(), value_type.to_string()),
+                span,
+                declared_at,
+                format!("'{}' declared here", actor),
+            );
+        }
+    }
+    
+    /// Whether a field or payload type annotation names a known type
+    fn is_known_type(&self, type_str: &str) -> bool {
+        match Type::from_string(type_str) {
+            Some(Type::Named(name)) => self.structs.contains_key(&name) || self.enums.contains_key(&name),
+            Some(_) => true,
+            None => false,
+        }
+    }
+    
+    /// Fields of a declared struct or actor type
+    fn struct_fields(&self, ty: &Type) -> Option<&Vec<(String, Type)>> {
+        match ty {
+            // Inside an actor's methods, `self` is the actor's state
+            Type::Named(name) => self.structs.get(name).or_else(|| self.actors.get(name)),
+            _ => None,
+        }
+    }
+    
+    /// Qualified name of an imported function or value referenced as `module.name`
+    fn qualified_name(&self, expr: &Expr) -> Option<String> {
+        match &expr.kind {
+            ExprKind::Member { object, member } => match &object.kind {
+                ExprKind::Identifier(import_name) if self.get_variable_type(import_name).is_none() => {
+                    let name = format!("{}.{}", import_name, member);
+                    let known = self.functions.contains_key(&name) || self.module_values.contains_key(&name);
+                    known.then_some(name)
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    
+    /// The types of a checked module's exports
+    pub fn interface(&self, name: &str, exports: &[String]) -> ModuleInterface {
+        let mut interface = ModuleInterface {
+            name: name.to_string(),
+            values: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+        };
+        for export in exports {
+            if let Some((params, return_type)) = self.functions.get(export) {
+                interface.values.insert(export.clone(), Type::Function {
+                    params: params.iter().map(|(_, ty)| ty.clone()).collect(),
+                    return_type: Box::new(return_type.clone()),
+                });
+            } else if let Some(fields) = self.structs.get(export) {
+                interface.structs.insert(export.clone(), fields.clone());
+            } else if let Some(variants) = self.enums.get(export) {
+                interface.enums.insert(export.clone(), variants.clone());
+            } else if let Some(ty) = self.get_variable_type(export) {
+                interface.values.insert(export.clone(), ty);
             }
         }
-        None
+        interface
+    }
+    
+    /// Infers the type of `expr` after checking the statements that precede it,
+    /// as the REPL's `:type` does. Diagnostics are collected but not reported, and
+    /// functions without a return annotation return what their `return`s give.
+    pub fn infer_type(&mut self, context: &[Stmt], expr: &Expr) -> Type {
+        self.infer_returns = true;
+        for stmt in context {
+            self.collect_declarations(stmt);
+        }
+        for stmt in context {
+            self.check_statement(stmt);
+        }
+        self.check_expression(expr)
     }
     
     pub fn print_diagnostics(&self) {
@@ -634,5 +1676,9 @@ impl TypeChecker {
     pub fn has_errors(&self) -> bool {
         self.reporter.has_errors()
     }
+    
+    pub fn has_diagnostics(&self) -> bool {
+        !self.reporter.is_empty()
+    }
 }
 
@@ -18,6 +18,7 @@ pub enum Type {
     // Collection types
     List(Box<Type>),
     Map(Box<Type>, Box<Type>), // key type, value type
+    Iterator(Box<Type>), // lazy sequence, e.g. from a generator function
     
     // Special types
     Option(Box<Type>),
@@ -57,14 +58,18 @@ impl Type {
             "char" => Some(Type::Char),
             "Bytes" => Some(Type::Bytes),
             "None" => Some(Type::None),
+            "any" => Some(Type::Any),
             _ => {
                 // Check for generic types like list[i32], map[str, i32], Option[i32]
                 if s.starts_with("list[") && s.ends_with("]") {
                     let inner = &s[5..s.len()-1];
                     Type::from_string(inner).map(|t| Type::List(Box::new(t)))
+                } else if s.starts_with("iter[") && s.ends_with("]") {
+                    let inner = &s[5..s.len()-1];
+                    Type::from_string(inner).map(|t| Type::Iterator(Box::new(t)))
                 } else if s.starts_with("map[") && s.ends_with("]") {
                     let inner = &s[4..s.len()-1];
-                    let parts: Vec<&str> = inner.split(',').map(|s| s.trim()).collect();
+                    let parts = split_type_args(inner);
                     if parts.len() == 2 {
                         if let (Some(k), Some(v)) = (Type::from_string(parts[0]), Type::from_string(parts[1])) {
                             Some(Type::Map(Box::new(k), Box::new(v)))
@@ -79,7 +84,7 @@ impl Type {
                     Type::from_string(inner).map(|t| Type::Option(Box::new(t)))
                 } else if s.starts_with("Result[") && s.ends_with("]") {
                     let inner = &s[7..s.len()-1];
-                    let parts: Vec<&str> = inner.split(',').map(|s| s.trim()).collect();
+                    let parts = split_type_args(inner);
                     if parts.len() == 2 {
                         if let (Some(ok), Some(err)) = (Type::from_string(parts[0]), Type::from_string(parts[1])) {
                             Some(Type::Result(Box::new(ok), Box::new(err)))
@@ -89,6 +94,20 @@ impl Type {
                     } else {
                         None
                     }
+                } else if let Some(rest) = s.strip_prefix("fn(") {
+                    // fn(i32, str) -> bool
+                    let close = matching_paren(rest)?;
+                    let params = split_type_args(&rest[..close]).into_iter()
+                        .map(Type::from_string)
+                        .collect::<Option<Vec<_>>>()?;
+                    let return_type = match rest[close + 1..].trim().strip_prefix("->") {
+                        Some(ret) => Type::from_string(ret.trim())?,
+                        None => Type::None,
+                    };
+                    Some(Type::Function {
+                        params,
+                        return_type: Box::new(return_type),
+                    })
                 } else {
                     Some(Type::Named(s.to_string()))
                 }
@@ -115,6 +134,7 @@ impl Type {
             Type::None => "None".to_string(),
             Type::List(inner) => format!("list[{}]", inner.to_string()),
             Type::Map(k, v) => format!("map[{}, {}]", k.to_string(), v.to_string()),
+            Type::Iterator(inner) => format!("iter[{}]", inner.to_string()),
             Type::Option(inner) => format!("Option[{}]", inner.to_string()),
             Type::Result(ok, err) => format!("Result[{}, {}]", ok.to_string(), err.to_string()),
             Type::Function { params, return_type } => {
@@ -135,6 +155,11 @@ impl Type {
         )
     }
     
+    /// Values of these types are copied rather than moved when passed to a function
+    pub fn is_copy(&self) -> bool {
+        self.is_numeric() || matches!(self, Type::Bool | Type::Char | Type::None)
+    }
+    
     pub fn is_integer(&self) -> bool {
         matches!(self,
             Type::I8 | Type::I16 | Type::I32 | Type::I64 |
@@ -163,28 +188,85 @@ impl Type {
             (t, Type::F32) if t.is_integer() => true,
             (t, Type::F64) if t.is_integer() => true,
             (Type::F32, Type::F64) => true,
-            // Any type can be coerced to Any
-            (_, Type::Any) => true,
+            // Any type can be coerced to Any, and a value typed Any, such as the
+            // result of calling an unannotated function value, to any type
+            (_, Type::Any) | (Type::Any, _) => true,
+            (Type::List(_), Type::List(to)) if **to == Type::Any => true,
+            (Type::Iterator(_), Type::Iterator(to)) if **to == Type::Any => true,
+            (Type::Map(from_key, _), Type::Map(to_key, to)) if **to == Type::Any => from_key.can_coerce_to(to_key),
+            // Function values are compatible when their signatures agree; parameters
+            // and results without a known type match anything
+            (Type::Function { params: from_params, return_type: from_ret },
+             Type::Function { params: to_params, return_type: to_ret }) => {
+                let compatible = |from: &Type, to: &Type| {
+                    matches!(from, Type::Any | Type::Unknown)
+                        || matches!(to, Type::Any | Type::Unknown)
+                        || from.can_coerce_to(to)
+                };
+                from_params.len() == to_params.len()
+                    && from_params.iter().zip(to_params).all(|(from, to)| compatible(to, from))
+                    && compatible(from_ret, to_ret)
+            },
             _ => false,
         }
     }
     
     pub fn infer_from_literal(value: &crate::ast::Expr) -> Type {
-        match value {
-            crate::ast::Expr::Number(n) => {
+        match &value.kind {
+            crate::ast::ExprKind::Number(n) => {
                 if n.fract() == 0.0 {
                     Type::I32 // Default to i32 for integers
                 } else {
                     Type::F64 // Default to f64 for floats
                 }
             },
-            crate::ast::Expr::String(_) => Type::Str,
-            crate::ast::Expr::Boolean(_) => Type::Bool,
-            crate::ast::Expr::List(_) => Type::List(Box::new(Type::Any)),
-            crate::ast::Expr::Map(_) => Type::Map(Box::new(Type::Str), Box::new(Type::Any)),
-            crate::ast::Expr::None => Type::None,
+            crate::ast::ExprKind::String(_) => Type::Str,
+            crate::ast::ExprKind::Boolean(_) => Type::Bool,
+            crate::ast::ExprKind::List(_) => Type::List(Box::new(Type::Any)),
+            crate::ast::ExprKind::Map(_) => Type::Map(Box::new(Type::Str), Box::new(Type::Any)),
+            crate::ast::ExprKind::None => Type::None,
             _ => Type::Unknown,
         }
     }
 }
 
+/// Splits generic type arguments on top-level commas, so `str, list[i32]` or
+/// `fn(i32, i32) -> i32, str` keep their nested commas
+fn split_type_args(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        parts.push(s[start..].trim());
+    }
+    parts
+}
+
+/// Byte index of the ')' closing a '(' that precedes `s`
+fn matching_paren(s: &str) -> Option<usize> {
+    let mut depth = 1usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            },
+            _ => {},
+        }
+    }
+    None
+}
@@ -0,0 +1,209 @@
+use crate::actor_runtime::{ActorRef, Reply, SupervisorRef};
+use crate::ast::Stmt;
+use crate::bytecode::Closure;
+use crate::iterator::LazyIterator;
+use crate::stdlib::NativeFunction;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A runtime value, shared by both VMs, the standard library and the
+/// concurrency runtimes
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    List(Vec<Value>),
+    Map(HashMap<String, Value>),
+    Function {
+        name: String,
+        params: Vec<(String, Option<String>)>,
+        body: Vec<Stmt>,
+        /// Local variables captured by reference when the function was created
+        env: HashMap<String, Slot>,
+        /// Module whose globals the body sees
+        module: usize,
+    },
+    /// Function compiled by the bytecode VM
+    Closure(Arc<Closure>),
+    /// Standard library function implemented in Rust
+    NativeFunction(&'static NativeFunction),
+    /// Instance of a declared struct
+    Struct {
+        name: String,
+        fields: HashMap<String, Value>,
+    },
+    /// Variant of a declared enum with its payload
+    Enum {
+        enum_name: String,
+        variant: String,
+        values: Vec<Value>,
+    },
+    /// Lazy sequence, such as the result of calling a generator function
+    Iterator(LazyIterator),
+    /// Handle to a coroutine registered with the VM's `CoroutineRuntime`
+    Coroutine(usize),
+    /// Handle to a task or timer scheduled on the VM's `AsyncRuntime`
+    Task(usize),
+    /// Reference to a running actor
+    Actor(ActorRef),
+    /// Pending result of a method call on an actor, resolved by `await` like a task
+    Reply(Reply),
+    /// Supervisor restarting a group of actors when they fail
+    Supervisor(SupervisorRef),
+    None,
+}
+
+impl Value {
+    /// Name of the value's type, as used in messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "str",
+            Value::Boolean(_) => "bool",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Function { .. } | Value::Closure(_) | Value::NativeFunction(_) => "function",
+            Value::Struct { .. } => "struct",
+            Value::Enum { .. } => "enum",
+            Value::Iterator(_) => "iterator",
+            Value::Coroutine(_) => "coroutine",
+            Value::Task(_) => "task",
+            Value::Actor(_) => "actor",
+            Value::Reply(_) => "reply",
+            Value::Supervisor(_) => "supervisor",
+            Value::None => "None",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Value::Number(n) => {
+                if n.fract() == 0.0 {
+                    format!("{}", *n as i64)
+                } else {
+                    format!("{}", n)
+                }
+            },
+            Value::String(s) => s.clone(),
+            Value::Boolean(b) => format!("{}", b),
+            Value::List(list) => {
+                let items: Vec<String> = list.iter().map(Value::to_string).collect();
+                format!("[{}]", items.join(", "))
+            },
+            Value::Map(map) => {
+                // Sort keys so output does not depend on hash order
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                let pairs: Vec<String> = keys.into_iter()
+                    .map(|k| format!("{}: {}", k, map[k]))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            },
+            Value::Function { name, .. } => format!("<function {}>", name),
+            Value::Closure(closure) => format!("<function {}>", closure.proto.name),
+            Value::NativeFunction(native) => format!("<native function {}>", native.name),
+            Value::Struct { name, fields } => {
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                let pairs: Vec<String> = keys.into_iter()
+                    .map(|k| format!("{}: {}", k, fields[k]))
+                    .collect();
+                if pairs.is_empty() {
+                    format!("{} {{}}", name)
+                } else {
+                    format!("{} {{ {} }}", name, pairs.join(", "))
+                }
+            },
+            Value::Enum { enum_name, variant, values } => {
+                if values.is_empty() {
+                    format!("{}::{}", enum_name, variant)
+                } else {
+                    let items: Vec<String> = values.iter().map(Value::to_string).collect();
+                    format!("{}::{}({})", enum_name, variant, items.join(", "))
+                }
+            },
+            Value::Iterator(iter) => format!("<{}>", iter.name()),
+            Value::Coroutine(id) => format!("<coroutine {}>", id),
+            Value::Task(id) => format!("<task {}>", id),
+            Value::Actor(actor) => format!("<actor {} {}>", actor.name(), actor.id()),
+            Value::Reply(reply) => format!("<reply {}.{}>", reply.actor(), reply.method()),
+            Value::Supervisor(supervisor) => format!("<supervisor {} {}>", supervisor.strategy().name(), supervisor.id()),
+            Value::None => "None".to_string(),
+        };
+        f.write_str(&text)
+    }
+}
+
+/// Storage of a local variable, shared by the scope declaring it and every
+/// function capturing it, so an assignment on either side is seen by both
+#[derive(Clone)]
+pub struct Slot(Arc<Mutex<Value>>);
+
+impl Slot {
+    pub fn new(value: Value) -> Self {
+        Slot(Arc::new(Mutex::new(value)))
+    }
+    
+    pub fn get(&self) -> Value {
+        self.0.lock().unwrap().clone()
+    }
+    
+    /// Runs `update` on the value while holding its lock, so concurrent updates
+    /// through other functions sharing the variable are not lost
+    pub fn update<T>(&self, update: impl FnOnce(&mut Value) -> T) -> T {
+        update(&mut self.0.lock().unwrap())
+    }
+}
+
+// A function may capture itself, so the shared value is not printed
+impl fmt::Debug for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Slot(..)")
+    }
+}
+
+/// A value that can be handed to an actor: it shares no mutable state with
+/// the code that sent it. Iterators are advanced in place, coroutines and
+/// tasks belong to the context that drives them, and closures share the
+/// variables they capture, so those are refused, also when nested in a
+/// collection.
+#[derive(Debug, Clone)]
+pub struct Sendable(Value);
+
+impl Sendable {
+    pub fn new(value: Value) -> Result<Self, String> {
+        Self::check(&value)?;
+        Ok(Sendable(value))
+    }
+    
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+    
+    fn check(value: &Value) -> Result<(), String> {
+        match value {
+            Value::Iterator(_) | Value::Coroutine(_) | Value::Task(_) => {
+                Err(format!("{} values cannot be shared between actors", value.type_name()))
+            },
+            Value::List(items) | Value::Enum { values: items, .. } => items.iter().try_for_each(Self::check),
+            Value::Function { name, env, .. } if !env.is_empty() => Err(Self::closure_error(name)),
+            Value::Closure(closure) if !closure.captures.is_empty() => Err(Self::closure_error(&closure.proto.name)),
+            Value::Map(entries) | Value::Struct { fields: entries, .. } => entries.values().try_for_each(Self::check),
+            _ => Ok(()),
+        }
+    }
+    
+    fn closure_error(name: &str) -> String {
+        format!("function '{}' closes over local variables, so it cannot be shared between actors", name)
+    }
+}
+
+// Values move between threads: into actors, parallel jobs and coroutine bodies
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Value>();
+};
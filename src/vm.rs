@@ -1,48 +1,224 @@
 use crate::ast::*;
 use crate::pattern_matcher::PatternMatcher;
 use crate::async_runtime::AsyncRuntime;
-use crate::iterator::{JoelIterator, RangeIterator, ListIterator};
+use crate::iterator::{GeneratorIterator, JoelIterator, LazyIterator, ListIterator, RangeIterator};
 use crate::parallel::ParallelRuntime;
 use crate::exhaustiveness_checker::ExhaustivenessChecker;
-use crate::coroutine::CoroutineRuntime;
+use crate::coroutine::{Coroutine, CoroutineRuntime, Suspender};
+use crate::stdlib;
+use crate::modules::{self, ModuleGraph};
+use crate::actor_runtime::{ActorBehavior, ActorRef, ActorRuntime, Child, RestartStrategy, DEFAULT_MAILBOX_CAPACITY};
+use crate::value::{Sendable, Slot, Value};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+/// How a statement finished executing
 #[derive(Debug, Clone)]
-pub enum Value {
-    Number(f64),
-    String(String),
-    Boolean(bool),
-    List(Vec<Value>),
-    Map(HashMap<String, Value>),
-    Function {
-        name: String,
-        params: Vec<(String, Option<String>)>,
-        body: Vec<Stmt>,
-    },
-    None,
+pub enum ControlFlow {
+    /// Execution continues with the next statement; carries the statement's value
+    Normal(Value),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+/// Program state shared by the main VM and every context forked from it.
+/// The lock is only held while reading or writing, never while user code runs.
+#[derive(Default)]
+struct Globals {
+    /// Global scope of each loaded module, indexed like `ModuleGraph::modules`
+    scopes: Vec<HashMap<String, Value>>,
+    /// Exports of modules that have already run, so each runs once
+    module_exports: HashMap<usize, Value>,
+    /// Declared structs and their field names
+    structs: HashMap<String, Vec<String>>,
+    /// Declared enums and their variants with payload sizes
+    enums: HashMap<String, Vec<(String, usize)>>,
+    /// Declared actors, spawned with struct literal syntax
+    actors: HashMap<String, Arc<ActorType>>,
 }
 
 pub struct VM {
-    globals: HashMap<String, Value>,
-    stack: Vec<HashMap<String, Value>>,
+    globals: Arc<Mutex<Globals>>,
+    /// Module whose code is executing
+    module: usize,
+    modules: Option<Arc<ModuleGraph>>,
+    stack: Vec<HashMap<String, Slot>>,
     async_runtime: AsyncRuntime,
     parallel_runtime: ParallelRuntime,
     coroutine_runtime: CoroutineRuntime,
+    actor_runtime: ActorRuntime,
+    /// Set when this context runs a generator body, to hand values to its consumer
+    yielder: Option<Suspender>,
+    /// Set when this context runs a coroutine body, to pause it at `suspend`
+    suspender: Option<Suspender>,
+    /// Set when this context runs an async task, to park it while it awaits
+    task: Option<TaskContext>,
+    /// Set when this context runs an actor's methods, so `self.<method>(...)`
+    /// calls another method on the same state
+    actor: Option<Arc<ActorType>>,
+    /// Set when this context runs an item of a parallel body: how many frames at the
+    /// bottom of `stack` hold the outer locals. Items run at the same time, so
+    /// assigning to those frames or to globals is an error.
+    parallel_frames: Option<usize>,
+}
+
+/// The async task an execution context is running
+struct TaskContext {
+    id: usize,
+    suspender: Suspender,
+}
+
+/// An `actor` declaration: state fields with their initializers, and methods
+/// as functions over the environment the declaration ran in
+struct ActorType {
+    name: String,
+    fields: Vec<(String, Expr)>,
+    methods: HashMap<String, Value>,
+    env: HashMap<String, Slot>,
+    module: usize,
+}
+
+/// Runs an actor's initializers and methods in its own execution context
+struct ActorContext {
+    vm: VM,
+    actor: Arc<ActorType>,
+    /// Field values given when the actor was spawned, used instead of the initializers
+    overrides: HashMap<String, Value>,
+}
+
+impl ActorBehavior for ActorContext {
+    fn init(&mut self) -> Result<HashMap<String, Value>, String> {
+        let mut state = HashMap::new();
+        for (field, initializer) in &self.actor.fields {
+            let value = match self.overrides.get(field) {
+                Some(value) => value.clone(),
+                None => {
+                    self.vm.module = self.actor.module;
+                    self.vm.stack = vec![self.actor.env.clone()];
+                    self.vm.evaluate(initializer)?
+                },
+            };
+            state.insert(field.clone(), value);
+        }
+        Ok(state)
+    }
+    
+    fn handle(&mut self, state: &mut HashMap<String, Value>, method: &str, args: Vec<Value>) -> Result<Value, String> {
+        let func = self.actor.methods.get(method)
+            .ok_or_else(|| format!("Actor '{}' has no method '{}'", self.actor.name, method))?;
+        // A failing method leaves the state as it was before the message
+        let receiver = Value::Struct {
+            name: self.actor.name.clone(),
+            fields: state.clone(),
+        };
+        
+        let (result, receiver) = self.vm.invoke(func, args, Some(receiver))?;
+        match receiver {
+            Some(Value::Struct { name, fields }) if name == self.actor.name => {
+                *state = fields;
+                Ok(result)
+            },
+            _ => Err(format!("'self' in actor '{}' must stay the actor's state", self.actor.name)),
+        }
+    }
 }
 
 impl VM {
     pub fn new() -> Self {
+        let globals = Globals {
+            scopes: vec![HashMap::new()],
+            ..Globals::default()
+        };
+        let parallel_runtime = ParallelRuntime::per_cpu();
         let mut vm = Self {
-            globals: HashMap::new(),
+            globals: Arc::new(Mutex::new(globals)),
+            module: 0,
+            modules: None,
             stack: Vec::new(),
-            async_runtime: AsyncRuntime::new(),
-            parallel_runtime: ParallelRuntime::new(4), // 4 threads by default
+            async_runtime: AsyncRuntime::new(parallel_runtime.clone()),
+            actor_runtime: ActorRuntime::new(parallel_runtime.clone(), DEFAULT_MAILBOX_CAPACITY),
+            parallel_runtime,
             coroutine_runtime: CoroutineRuntime::new(),
+            yielder: None,
+            suspender: None,
+            task: None,
+            actor: None,
+            parallel_frames: None,
         };
         vm.init_builtins();
         vm
     }
     
+    /// Lets `import` statements run the file modules of `graph`; the entry
+    /// file's code executes as the graph's last module
+    pub fn with_modules(mut self, graph: Arc<ModuleGraph>) -> Self {
+        self.globals.lock().unwrap().scopes = vec![HashMap::new(); graph.modules.len()];
+        self.module = graph.entry_index();
+        self.modules = Some(graph);
+        self
+    }
+    
+    /// Runs parallel loops, async tasks and actors on `threads` worker threads
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.parallel_runtime = ParallelRuntime::new(threads);
+        if !self.async_runtime.is_deterministic() {
+            self.async_runtime = AsyncRuntime::new(self.parallel_runtime.clone());
+        }
+        if !self.actor_runtime.is_deterministic() {
+            self.actor_runtime = ActorRuntime::new(self.parallel_runtime.clone(), self.actor_runtime.mailbox_capacity());
+        }
+        self
+    }
+    
+    /// Lets each actor's mailbox hold `capacity` messages before senders wait
+    pub fn with_mailbox_capacity(mut self, capacity: usize) -> Self {
+        self.actor_runtime = if self.actor_runtime.is_deterministic() {
+            ActorRuntime::deterministic(capacity)
+        } else {
+            ActorRuntime::new(self.parallel_runtime.clone(), capacity)
+        };
+        self
+    }
+    
+    /// Runs async tasks and handles actor messages one at a time on the thread
+    /// that waits for them, in the same order on every run
+    pub fn with_deterministic_scheduling(mut self) -> Self {
+        self.async_runtime = AsyncRuntime::deterministic();
+        self.actor_runtime = ActorRuntime::deterministic(self.actor_runtime.mailbox_capacity());
+        self
+    }
+    
+    /// A separate execution context over the same globals, running in the
+    /// current module with `stack` as its local scopes
+    fn fork(&self, stack: Vec<HashMap<String, Slot>>) -> VM {
+        VM {
+            globals: Arc::clone(&self.globals),
+            module: self.module,
+            modules: self.modules.clone(),
+            stack,
+            async_runtime: self.async_runtime.clone(),
+            parallel_runtime: self.parallel_runtime.clone(),
+            coroutine_runtime: self.coroutine_runtime.clone(),
+            actor_runtime: self.actor_runtime.clone(),
+            yielder: None,
+            suspender: None,
+            task: None,
+            actor: None,
+            // Code reached from a parallel item still must not write globals
+            parallel_frames: self.parallel_frames.map(|_| 0),
+        }
+    }
+    
+    /// A context for the items of a parallel body, whose first frame will hold
+    /// the outer locals
+    fn fork_parallel(&self) -> VM {
+        let mut context = self.fork(Vec::new());
+        context.parallel_frames = Some(1);
+        context
+    }
+    
     fn init_builtins(&mut self) {
         // Built-in functions will be added here
     }
@@ -54,61 +230,83 @@ impl VM {
         }
         println!();
         
-        for stmt in &program.statements {
-            self.execute(stmt)?;
-        }
-        
+        self.run_statements(&program.statements)?;
+        // Messages already sent are handled before the program ends
+        self.actor_runtime.wait_idle();
         Ok(())
     }
     
-    fn execute(&mut self, stmt: &Stmt) -> Result<Value, String> {
-        match stmt {
-            Stmt::Let { name, value, .. } => {
+    /// Executes top-level statements in the persistent global scope and returns
+    /// the value of the last one. The REPL calls this once per input.
+    pub fn run_statements(&mut self, statements: &[Stmt]) -> Result<Value, String> {
+        let mut last = Value::None;
+        for stmt in statements {
+            match self.execute(stmt)? {
+                ControlFlow::Normal(value) => last = value,
+                // A top-level return ends the program
+                ControlFlow::Return(value) => return Ok(value),
+                ControlFlow::Break => return Err("'break' outside of a loop".to_string()),
+                ControlFlow::Continue => return Err("'continue' outside of a loop".to_string()),
+            }
+        }
+        Ok(last)
+    }
+    
+    fn execute(&mut self, stmt: &Stmt) -> Result<ControlFlow, String> {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
                 let val = self.evaluate(value)?;
                 self.define_variable(name.clone(), val.clone());
-                Ok(val)
+                Ok(ControlFlow::Normal(val))
             },
-            Stmt::Const { name, value, .. } => {
+            StmtKind::Const { name, value, .. } => {
                 let val = self.evaluate(value)?;
                 self.define_variable(name.clone(), val.clone());
-                Ok(val)
+                Ok(ControlFlow::Normal(val))
             },
-            Stmt::Expr(expr) => {
-                self.evaluate(expr)
+            StmtKind::Expr(expr) => {
+                Ok(ControlFlow::Normal(self.evaluate(expr)?))
             },
-            Stmt::Print(expr) => {
+            StmtKind::Print(expr) => {
                 let value = self.evaluate(expr)?;
-                println!("{}", self.value_to_string(&value));
-                Ok(Value::None)
+                println!("{}", value);
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Return(expr) => {
-                if let Some(expr) = expr {
-                    self.evaluate(expr)
+            StmtKind::Return(expr) => {
+                let value = if let Some(expr) = expr {
+                    self.evaluate(expr)?
                 } else {
-                    Ok(Value::None)
-                }
+                    Value::None
+                };
+                Ok(ControlFlow::Return(value))
             },
-            Stmt::If { condition, then_branch, else_branch } => {
+            StmtKind::Break => Ok(ControlFlow::Break),
+            StmtKind::Continue => Ok(ControlFlow::Continue),
+            StmtKind::If { condition, then_branch, else_branch } => {
                 let cond = self.evaluate(condition)?;
-                if self.is_truthy(&cond) {
+                if Self::is_truthy(&cond) {
                     self.execute_block(then_branch)
                 } else if let Some(else_branch) = else_branch {
                     self.execute_block(else_branch)
                 } else {
-                    Ok(Value::None)
+                    Ok(ControlFlow::Normal(Value::None))
                 }
             },
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 loop {
                     let cond_val = self.evaluate(condition)?;
-                    if !self.is_truthy(&cond_val) {
+                    if !Self::is_truthy(&cond_val) {
                         break;
                     }
-                    self.execute_block(body)?;
+                    match self.execute_block(body)? {
+                        ControlFlow::Break => break,
+                        ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                        ControlFlow::Normal(_) | ControlFlow::Continue => {},
+                    }
                 }
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::For { var, iterable, body } => {
+            StmtKind::For { var, iterable, body } => {
                 let iter_val = self.evaluate(iterable)?;
                 match iter_val {
                     Value::List(list) => {
@@ -116,7 +314,22 @@ impl VM {
                         while iter.has_next() {
                             if let Some(item) = iter.next() {
                                 self.define_variable(var.clone(), item);
-                                self.execute_block(body)?;
+                                match self.execute_block(body)? {
+                                    ControlFlow::Break => break,
+                                    ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                                    ControlFlow::Normal(_) | ControlFlow::Continue => {},
+                                }
+                            }
+                        }
+                    },
+                    Value::Iterator(iter) => {
+                        // Items are produced one at a time, as the loop asks for them
+                        while let Some(item) = iter.advance()? {
+                            self.define_variable(var.clone(), item);
+                            match self.execute_block(body)? {
+                                ControlFlow::Break => break,
+                                ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                                ControlFlow::Normal(_) | ControlFlow::Continue => {},
                             }
                         }
                     },
@@ -127,257 +340,374 @@ impl VM {
                         while iter.has_next() {
                             if let Some(item) = iter.next() {
                                 self.define_variable(var.clone(), item);
-                                self.execute_block(body)?;
+                                match self.execute_block(body)? {
+                                    ControlFlow::Break => break,
+                                    ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                                    ControlFlow::Normal(_) | ControlFlow::Continue => {},
+                                }
                             }
                         }
                     },
                     _ => {
-                        return Err(format!("For loop expects a list or range, got {:?}", iter_val));
+                        return Err(format!("For loop expects a list, range or iterator, got {:?}", iter_val));
                     },
                 }
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Block(statements) => {
+            StmtKind::Block(statements) => {
                 self.execute_block(statements)
             },
-            Stmt::Fn { name, params, body, .. } => {
+            StmtKind::Fn { name, params, body, .. } => {
                 let func = Value::Function {
                     name: name.clone(),
                     params: params.clone(),
                     body: body.clone(),
+                    env: self.capture_environment(),
+                    module: self.module,
                 };
-                self.define_variable(name.clone(), func);
-                Ok(Value::None)
+                self.define_function(name.clone(), func);
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Module { name } => {
+            StmtKind::Module { name } => {
                 println!("📦 Module: {}", name);
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Import { module, alias } => {
-                let import_name = alias.as_ref().unwrap_or(module);
+            StmtKind::Import { module, alias } => {
+                let import_name = modules::import_name(module, alias.as_ref());
                 println!("📥 Import: {} as {}", module, import_name);
-                Ok(Value::None)
-            },
-            Stmt::Actor { name, fields, methods } => {
-                println!("🎭 Actor: {} ({} fields, {} methods)", name, fields.len(), methods.len());
-                Ok(Value::None)
+                if let Some(natives) = stdlib::module_value(module) {
+                    self.define_variable(import_name, natives);
+                } else if let Some(index) = self.modules.as_ref().and_then(|graph| graph.resolve(self.module, module)) {
+                    let exports = self.import_module(index)?;
+                    self.define_variable(import_name, exports);
+                }
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::MatchStmt { expr, arms } => {
-                // Check exhaustiveness (warning only)
-                if let Err(e) = ExhaustivenessChecker::check_exhaustive(expr, arms) {
-                    eprintln!("⚠️  Exhaustiveness warning: {}", e);
+            StmtKind::Export(declaration) => self.execute(declaration),
+            // The module loader lists the name among the file's exports
+            StmtKind::ExportDefault(_) => Ok(ControlFlow::Normal(Value::None)),
+            StmtKind::Actor { name, fields, methods } => {
+                let env = self.capture_environment();
+                let mut actor_methods = HashMap::new();
+                for method in methods {
+                    if let StmtKind::Fn { name: method_name, params, body, .. } = &method.kind {
+                        actor_methods.insert(method_name.clone(), Value::Function {
+                            name: format!("{}.{}", name, method_name),
+                            params: params.clone(),
+                            body: body.clone(),
+                            env: env.clone(),
+                            module: self.module,
+                        });
+                    }
                 }
                 
+                let actor = ActorType {
+                    name: name.clone(),
+                    fields: fields.iter().map(|(field, _, value)| (field.clone(), value.clone())).collect(),
+                    methods: actor_methods,
+                    env,
+                    module: self.module,
+                };
+                self.globals.lock().unwrap().actors.insert(name.clone(), Arc::new(actor));
+                Ok(ControlFlow::Normal(Value::None))
+            },
+            StmtKind::MatchStmt { expr, arms } => {
+                // Exhaustiveness is checked once, before the program runs
+                
                 // Check for overlapping patterns
                 if let Err(e) = ExhaustivenessChecker::check_overlap(arms) {
                     return Err(format!("Pattern matching error: {}", e));
                 }
                 
                 let value = self.evaluate(expr)?;
-                for arm in arms {
-                    if PatternMatcher::matches_nested(&arm.pattern, &value) {
-                        // Check guard if present
-                        if let Some(ref guard) = arm.guard {
-                            let guard_result = self.evaluate(guard)?;
-                            if !self.is_truthy(&guard_result) {
-                                continue;
-                            }
-                        }
-                        
-                        // Extract bindings
-                        let bindings = PatternMatcher::extract_bindings(&arm.pattern, &value);
-                        for (name, val) in bindings {
-                            self.define_variable(name, val);
-                        }
-                        
-                        // Execute matched arm
-                        return self.execute_block(&arm.body);
-                    }
-                }
-                Err("No pattern matched".to_string())
+                self.execute_match(&value, arms)
             },
-            Stmt::AsyncFn { name, params, return_type: _, body } => {
-                // Store async function (simplified - would need proper async runtime)
-                self.define_variable(name.clone(), Value::Function {
+            StmtKind::AsyncFn { name, params, body, .. } => {
+                // Each call spawns a task running the body and returns its handle
+                self.define_function(name.clone(), Value::Function {
                     name: name.clone(),
                     params: params.clone(),
-                    body: body.clone(),
+                    body: vec![StmtKind::Return(Some(ExprKind::Async { body: body.clone() }.into())).into()],
+                    env: self.capture_environment(),
+                    module: self.module,
                 });
-                Ok(Value::None)
-            },
-            Stmt::ParallelFor { var, iterable, body } => {
-                let iter_val = self.evaluate(iterable)?;
-                match iter_val {
-                    Value::List(list) => {
-                        // Execute in parallel (simplified - would need VM context in closure)
-                        // For now, just execute sequentially
-                        for item in list {
-                            self.define_variable(var.clone(), item);
-                            self.execute_block(body)?;
-                        }
-                    },
-                    _ => {
-                        return Err(format!("Parallel for expects a list, got {:?}", iter_val));
-                    },
-                }
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::ParallelMap { var, iterable, body } => {
-                let iter_val = self.evaluate(iterable)?;
-                match iter_val {
+            StmtKind::ParallelFor { var, iterable, body } => {
+                match self.evaluate(iterable)? {
                     Value::List(list) => {
-                        // Map in parallel (simplified - would need VM context)
-                        let mut results = Vec::new();
-                        for item in list {
-                            self.define_variable(var.clone(), item.clone());
-                            let result = self.execute_block(body)?;
-                            results.push(result);
-                        }
-                        Ok(Value::List(results))
+                        self.parallel_map(var, list, body)?;
                     },
-                    _ => {
-                        return Err(format!("Parallel map expects a list, got {:?}", iter_val));
+                    other => {
+                        return Err(format!("Parallel for expects a list, got {:?}", other));
                     },
                 }
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::CoroutineFn { name, params, return_type: _, body } => {
-                // Store coroutine function (simplified - would need proper coroutine runtime)
-                self.define_variable(name.clone(), Value::Function {
+            StmtKind::CoroutineFn { name, params, body, .. } => {
+                // Each call starts a new coroutine over the body, suspended before its first statement
+                self.define_function(name.clone(), Value::Function {
                     name: name.clone(),
                     params: params.clone(),
-                    body: body.clone(),
+                    body: vec![StmtKind::Return(Some(ExprKind::Coroutine { body: body.clone() }.into())).into()],
+                    env: self.capture_environment(),
+                    module: self.module,
                 });
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Contract { name, fields, methods } => {
+            StmtKind::Contract { name, fields, methods } => {
                 println!("📜 Contract: {} ({} fields, {} methods)", name, fields.len(), methods.len());
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
+            },
+            StmtKind::Struct { name, fields } => {
+                let field_names = fields.iter().map(|(field, _)| field.clone()).collect();
+                self.globals.lock().unwrap().structs.insert(name.clone(), field_names);
+                Ok(ControlFlow::Normal(Value::None))
+            },
+            StmtKind::Enum { name, variants } => {
+                let variants = variants.iter()
+                    .map(|(variant, payload)| (variant.clone(), payload.len()))
+                    .collect();
+                self.globals.lock().unwrap().enums.insert(name.clone(), variants);
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Component { name, body } => {
+            StmtKind::Component { name, body } => {
                 println!("🎨 Component: {} ({} statements)", name, body.len());
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Flow { name, nodes } => {
+            StmtKind::Flow { name, nodes } => {
                 println!("🔄 Flow: {} ({} nodes)", name, nodes.len());
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Deployment { name, config } => {
+            StmtKind::Deployment { name, config } => {
                 println!("🐳 Deployment: {} ({} config items)", name, config.len());
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
-            Stmt::Cluster { name, config } => {
+            StmtKind::Cluster { name, config } => {
                 println!("☸️  Cluster: {} ({} config items)", name, config.len());
-                Ok(Value::None)
+                Ok(ControlFlow::Normal(Value::None))
             },
         }
     }
     
-    fn execute_block(&mut self, statements: &[Stmt]) -> Result<Value, String> {
+    /// Executes statements in a new scope, stopping early on return/break/continue.
+    /// A block that runs to completion yields the value of its last statement.
+    fn execute_block(&mut self, statements: &[Stmt]) -> Result<ControlFlow, String> {
         self.stack.push(HashMap::new());
-        
+        let result = self.execute_statements(statements);
+        self.stack.pop();
+        result
+    }
+    
+    fn execute_statements(&mut self, statements: &[Stmt]) -> Result<ControlFlow, String> {
+        // Functions of a local scope are declared before its statements run, so a
+        // function can capture one declared after it
+        if let Some(frame) = self.stack.last_mut() {
+            for stmt in statements {
+                if let StmtKind::Fn { name, .. } | StmtKind::AsyncFn { name, .. } | StmtKind::CoroutineFn { name, .. } = &stmt.kind {
+                    frame.entry(name.clone()).or_insert_with(|| Slot::new(Value::None));
+                }
+            }
+        }
+        let mut last = Value::None;
         for stmt in statements {
-            self.execute(stmt)?;
+            match self.execute(stmt)? {
+                ControlFlow::Normal(value) => last = value,
+                flow => return Ok(flow),
+            }
+        }
+        Ok(ControlFlow::Normal(last))
+    }
+    
+    /// Runs the first arm whose pattern matches and whose guard holds. The pattern's
+    /// bindings live in a scope of their own, visible to the guard and the arm body.
+    fn execute_match(&mut self, value: &Value, arms: &[MatchArm]) -> Result<ControlFlow, String> {
+        for arm in arms {
+            if !PatternMatcher::matches_nested(&arm.pattern, value) {
+                continue;
+            }
+            
+            self.stack.push(HashMap::new());
+            for (name, val) in PatternMatcher::extract_bindings(&arm.pattern, value) {
+                self.define_variable(name, val);
+            }
+            let guard_holds = match &arm.guard {
+                Some(guard) => self.evaluate(guard).map(|result| Self::is_truthy(&result)),
+                None => Ok(true),
+            };
+            let result = match guard_holds {
+                Ok(true) => self.execute_statements(&arm.body).map(Some),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            };
+            self.stack.pop();
+            
+            if let Some(flow) = result? {
+                return Ok(flow);
+            }
+        }
+        Err("No pattern matched".to_string())
+    }
+    
+    /// Runs a block whose result is used as a value (match arms, generator bodies)
+    fn evaluate_block(&mut self, statements: &[Stmt]) -> Result<Value, String> {
+        match self.execute_block(statements)? {
+            ControlFlow::Normal(value) | ControlFlow::Return(value) => Ok(value),
+            ControlFlow::Break => Err("'break' outside of a loop".to_string()),
+            ControlFlow::Continue => Err("'continue' outside of a loop".to_string()),
         }
-        
-        self.stack.pop();
-        Ok(Value::None)
     }
     
     fn evaluate(&mut self, expr: &Expr) -> Result<Value, String> {
-        match expr {
-            Expr::Number(n) => Ok(Value::Number(*n)),
-            Expr::String(s) => Ok(Value::String(s.clone())),
-            Expr::Boolean(b) => Ok(Value::Boolean(*b)),
-            Expr::Identifier(name) => {
+        match &expr.kind {
+            ExprKind::Number(n) => Ok(Value::Number(*n)),
+            ExprKind::String(s) => Ok(Value::String(s.clone())),
+            ExprKind::Boolean(b) => Ok(Value::Boolean(*b)),
+            ExprKind::Identifier(name) => {
                 self.get_variable(name)
             },
-            Expr::Binary { left, op, right } => {
+            ExprKind::Binary { left, op, right } => {
                 let left_val = self.evaluate(left)?;
                 let right_val = self.evaluate(right)?;
-                self.binary_op(&left_val, op, &right_val)
+                Self::binary_op(&left_val, op, &right_val)
             },
-            Expr::Unary { op, expr } => {
+            ExprKind::Unary { op, expr } => {
                 let val = self.evaluate(expr)?;
-                self.unary_op(op, &val)
+                Self::unary_op(op, &val)
             },
-            Expr::Call { callee, args } => {
-                let func = self.get_variable(callee)?;
-                match func {
-                    Value::Function { params, body, .. } => {
-                        self.call_function(&params, args, &body)
+            ExprKind::Cast { expr, target } => {
+                let val = self.evaluate(expr)?;
+                Self::cast(&val, target)
+            },
+            ExprKind::Call { callee, args } => {
+                // Built-in functions, unless shadowed by a variable
+                if let ExprKind::Identifier(name) = &callee.kind {
+                    if self.get_variable(name).is_err() {
+                        return self.call_builtin(name, args);
+                    }
+                }
+                
+                let func = match &callee.kind {
+                    ExprKind::Member { object, member } if self.is_own_method(object, member) => {
+                        return self.call_own_method(member, args);
                     },
-                    _ => {
-                        // Built-in functions
-                        self.call_builtin(callee, args)
+                    ExprKind::Member { object, member } => match self.evaluate(object)? {
+                        Value::Actor(actor) => {
+                            let arg_values = self.evaluate_arguments(args)?;
+                            self.check_actor_call(&actor, member, &arg_values)?;
+                            return actor.ask(member.clone(), arg_values).map(Value::Reply);
+                        },
+                        obj => Self::member(obj, member)?,
                     },
-                }
+                    _ => self.evaluate(callee)?,
+                };
+                let arg_values = self.evaluate_arguments(args)?;
+                self.call_function(&func, arg_values)
             },
-            Expr::Member { object, member } => {
+            ExprKind::Lambda { params, body } => {
+                Ok(Value::Function {
+                    name: "lambda".to_string(),
+                    params: params.clone(),
+                    body: body.clone(),
+                    env: self.capture_environment(),
+                    module: self.module,
+                })
+            },
+            ExprKind::Member { object, member } => {
                 let obj = self.evaluate(object)?;
-                match obj {
-                    Value::Map(map) => {
-                        map.get(member).cloned().ok_or_else(|| format!("No member '{}'", member))
-                    },
-                    _ => Err(format!("Cannot access member '{}' on non-object", member)),
-                }
+                Self::member(obj, member)
             },
-            Expr::Index { object, index } => {
+            ExprKind::Index { object, index } => {
                 let obj = self.evaluate(object)?;
                 let idx = self.evaluate(index)?;
                 match (obj, idx) {
                     (Value::List(list), Value::Number(n)) => {
-                        let i = n as usize;
+                        let i = Self::list_index(n)?;
                         if i < list.len() {
                             Ok(list[i].clone())
                         } else {
                             Err(format!("Index {} out of bounds", i))
                         }
                     },
+                    (Value::Map(map), Value::String(key)) => {
+                        map.get(&key).cloned().ok_or_else(|| format!("No member '{}'", key))
+                    },
                     _ => Err("Invalid index operation".to_string()),
                 }
             },
-            Expr::List(elements) => {
+            ExprKind::List(elements) => {
                 let mut list = Vec::new();
                 for elem in elements {
                     list.push(self.evaluate(elem)?);
                 }
                 Ok(Value::List(list))
             },
-            Expr::Map(pairs) => {
+            ExprKind::Map(pairs) => {
                 let mut map = HashMap::new();
                 for (key, value) in pairs {
-                    let key_str = match key {
-                        Expr::String(s) => s.clone(),
-                        Expr::Identifier(s) => s.clone(),
+                    let key_str = match &key.kind {
+                        ExprKind::String(s) => s.clone(),
+                        ExprKind::Identifier(s) => s.clone(),
                         _ => return Err("Map keys must be strings or identifiers".to_string()),
                     };
                     map.insert(key_str, self.evaluate(value)?);
                 }
                 Ok(Value::Map(map))
             },
-            Expr::None => Ok(Value::None),
-            Expr::Match { expr, arms } => {
+            ExprKind::StructLiteral { name, fields } => {
+                let mut values = Vec::new();
+                for (field, value) in fields {
+                    values.push((field.clone(), self.evaluate(value)?));
+                }
+                let actor = self.globals.lock().unwrap().actors.get(name).cloned();
+                if let Some(actor) = actor {
+                    return self.spawn_actor(actor, values);
+                }
+                let globals = self.globals.lock().unwrap();
+                let declared = globals.structs.get(name)
+                    .ok_or_else(|| format!("Unknown struct '{}'", name))?;
+                Self::construct_struct(name, declared, values)
+            },
+            ExprKind::EnumVariant { enum_name, variant, args } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.evaluate(arg)?);
+                }
+                let globals = self.globals.lock().unwrap();
+                let declared = globals.enums.get(enum_name)
+                    .ok_or_else(|| format!("Unknown enum '{}'", enum_name))?;
+                Self::construct_variant(enum_name, variant, declared, values)
+            },
+            ExprKind::None => Ok(Value::None),
+            ExprKind::Assign { target, op, value } => {
+                // The target's indices are evaluated once, and a compound assignment
+                // reads and writes through the same keys
+                let mut path = Vec::new();
+                let name = self.assignment_path(target, &mut path)?;
+                let rhs = self.evaluate(value)?;
+                match op {
+                    // Tasks and actors may update a variable at the same time, so it is
+                    // read and written under one lock, after the right-hand side
+                    Some(op) => self.update(name, |slot| {
+                        let new_value = Self::binary_op(&Self::load_at_path(slot.clone(), &path)?, op, &rhs)?;
+                        Self::store_at_path(slot, &path, new_value.clone())?;
+                        Ok(new_value)
+                    }),
+                    None => {
+                        self.assign(name, &path, rhs.clone())?;
+                        Ok(rhs)
+                    },
+                }
+            },
+            ExprKind::Match { expr, arms } => {
                 let value = self.evaluate(expr)?;
-                for arm in arms {
-                    if PatternMatcher::matches(&arm.pattern, &value) {
-                        if let Some(ref guard) = arm.guard {
-                            let guard_result = self.evaluate(guard)?;
-                            if !self.is_truthy(&guard_result) {
-                                continue;
-                            }
-                        }
-                        
-                        let bindings = PatternMatcher::extract_bindings(&arm.pattern, &value);
-                        for (name, val) in bindings {
-                            self.define_variable(name, val);
-                        }
-                        
-                        return self.execute_block(&arm.body);
-                    }
+                match self.execute_match(&value, arms)? {
+                    ControlFlow::Normal(value) | ControlFlow::Return(value) => Ok(value),
+                    ControlFlow::Break => Err("'break' outside of a loop".to_string()),
+                    ControlFlow::Continue => Err("'continue' outside of a loop".to_string()),
                 }
-                Err("No pattern matched".to_string())
             },
-            Expr::Destructure { pattern, value } => {
+            ExprKind::Destructure { pattern, value } => {
                 let val = self.evaluate(value)?;
                 let bindings = PatternMatcher::extract_bindings(&pattern, &val);
                 for (name, val) in bindings {
@@ -385,53 +715,113 @@ impl VM {
                 }
                 Ok(Value::None)
             },
-            Expr::Async { body } => {
-                // Async expression (simplified - would need async runtime)
-                self.evaluate(body)
+            ExprKind::Async { body } => {
+                // The body runs as a coroutine that the executor resumes
+                let mut context = self.fork(vec![self.capture_environment()]);
+                let body = body.clone();
+                let id = self.async_runtime.spawn(|id| Coroutine::spawn(move |suspender| {
+                    context.task = Some(TaskContext { id, suspender });
+                    context.evaluate_block(&body)
+                }))?;
+                Ok(Value::Task(id))
             },
-            Expr::Await { expr } => {
-                // Await expression (simplified - would need async runtime)
-                self.evaluate(expr)
+            ExprKind::Await { expr } => {
+                // Other values are already available
+                match self.evaluate(expr)? {
+                    Value::Task(id) => self.await_first(&[id]).map(|(_, value)| value),
+                    // The actor runs on its own thread, so blocking here cannot stall it
+                    Value::Reply(reply) => reply.wait(),
+                    value => Ok(value),
+                }
             },
-            Expr::Yield(expr) => {
-                // Yield expression (simplified - would need generator runtime)
-                if let Some(expr) = expr {
-                    self.evaluate(expr)
-                } else {
-                    Ok(Value::None)
+            ExprKind::Spawn { actor, fields } => {
+                let mut values = Vec::new();
+                for (field, value) in fields {
+                    values.push((field.clone(), self.evaluate(value)?));
                 }
+                let declared = self.globals.lock().unwrap().actors.get(actor).cloned()
+                    .ok_or_else(|| format!("Unknown actor '{}'", actor))?;
+                self.spawn_actor(declared, values)
             },
-            Expr::Generator { body } => {
-                // Create generator (simplified)
-                self.execute_block(body)?;
-                Ok(Value::None) // Generator would return iterator in full implementation
+            ExprKind::Send { actor, method, args } => {
+                let actor = match self.evaluate(actor)? {
+                    Value::Actor(actor) => actor,
+                    other => return Err(format!("'send' expects an actor, got {}", other)),
+                };
+                let arg_values = self.evaluate_arguments(args)?;
+                self.check_actor_call(&actor, method, &arg_values)?;
+                actor.send(method.clone(), arg_values)?;
+                Ok(Value::None)
+            },
+            ExprKind::Yield(expr) => {
+                let value = match expr {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::None,
+                };
+                // Blocks until the consumer asks for the next value
+                let yielder = self.yielder.as_ref()
+                    .ok_or_else(|| "'yield' outside of a generator".to_string())?;
+                yielder.suspend(value)?;
+                Ok(Value::None)
             },
-            Expr::Coroutine { body } => {
-                // Create coroutine (simplified)
-                let coroutine_id = self.coroutine_runtime.create();
-                // Execute coroutine body (simplified - would need separate execution context)
-                self.execute_block(body)?;
-                Ok(Value::Number(coroutine_id as f64)) // Return coroutine ID
+            ExprKind::Generator { body } => {
+                // The body runs in its own context, suspended at each yield
+                let mut context = self.fork(vec![self.capture_environment()]);
+                let body = body.clone();
+                let generator = GeneratorIterator::spawn(move |yielder| {
+                    context.yielder = Some(yielder);
+                    context.evaluate_block(&body).map(|_| ())
+                })?;
+                Ok(Value::Iterator(LazyIterator::new("generator", generator)))
+            },
+            ExprKind::ParallelMap { var, iterable, body } => {
+                match self.evaluate(iterable)? {
+                    Value::List(list) => Ok(Value::List(self.parallel_map(var, list, body)?)),
+                    other => Err(format!("Parallel map expects a list, got {:?}", other)),
+                }
+            },
+            ExprKind::ParallelReduce { acc, initial, var, iterable, body, combine } => {
+                let initial = self.evaluate(initial)?;
+                match self.evaluate(iterable)? {
+                    Value::List(list) => self.parallel_reduce(acc, initial, var, list, body, combine.as_ref()),
+                    other => Err(format!("Parallel reduce expects a list, got {:?}", other)),
+                }
             },
-            Expr::Suspend => {
-                // Suspend current coroutine (simplified)
+            ExprKind::Coroutine { body } => {
+                // The body runs in its own context, whose frames survive each suspend
+                let mut context = self.fork(vec![self.capture_environment()]);
+                let body = body.clone();
+                let coroutine = Coroutine::spawn(move |suspender| {
+                    context.suspender = Some(suspender);
+                    context.evaluate_block(&body)
+                })?;
+                Ok(Value::Coroutine(self.coroutine_runtime.create(coroutine)))
+            },
+            ExprKind::Suspend(expr) => {
+                let value = match expr {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::None,
+                };
+                // Blocks until the coroutine is resumed again
+                let suspender = self.suspender.as_ref()
+                    .ok_or_else(|| "'suspend' outside of a coroutine".to_string())?;
+                suspender.suspend(value)?;
                 Ok(Value::None)
             },
-            Expr::Resume { coroutine } => {
+            ExprKind::Resume { coroutine } => {
                 // Resume coroutine
                 let coroutine_val = self.evaluate(coroutine)?;
-                if let Value::Number(id) = coroutine_val {
-                    let coroutine_id = id as usize;
-                    self.coroutine_runtime.resume(coroutine_id)
+                if let Value::Coroutine(id) = coroutine_val {
+                    self.coroutine_runtime.resume(id)
                         .map_err(|e| format!("Failed to resume coroutine: {}", e))
                 } else {
-                    Err("Resume expects a coroutine ID".to_string())
+                    Err(format!("Resume expects a coroutine, got {}", coroutine_val))
                 }
             },
         }
     }
     
-    fn binary_op(&self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, String> {
+    pub(crate) fn binary_op(left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, String> {
         match (left, op, right) {
             (Value::Number(a), BinaryOp::Add, Value::Number(b)) => Ok(Value::Number(a + b)),
             (Value::Number(a), BinaryOp::Subtract, Value::Number(b)) => Ok(Value::Number(a - b)),
@@ -461,7 +851,7 @@ impl VM {
         }
     }
     
-    fn unary_op(&self, op: &UnaryOp, val: &Value) -> Result<Value, String> {
+    pub(crate) fn unary_op(op: &UnaryOp, val: &Value) -> Result<Value, String> {
         match (op, val) {
             (UnaryOp::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
             (UnaryOp::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
@@ -469,25 +859,154 @@ impl VM {
         }
     }
     
-    fn call_function(&mut self, params: &[(String, Option<String>)], args: &[Expr], body: &[Stmt]) -> Result<Value, String> {
+    /// Converts a number to a numeric type the way Rust's `as` does: integer
+    /// targets truncate toward zero and saturate at the type's bounds
+    pub(crate) fn cast(val: &Value, target: &str) -> Result<Value, String> {
+        let n = match val {
+            Value::Number(n) => *n,
+            other => return Err(format!("Cannot cast {} to {}", other.type_name(), target)),
+        };
+        let converted = match target {
+            "i8" => n as i8 as f64,
+            "i16" => n as i16 as f64,
+            "i32" => n as i32 as f64,
+            "i64" => n as i64 as f64,
+            "u8" => n as u8 as f64,
+            "u16" => n as u16 as f64,
+            "u32" => n as u32 as f64,
+            "u64" => n as u64 as f64,
+            "f32" => n as f32 as f64,
+            "f64" => n,
+            _ => return Err(format!("Cannot cast number to {}", target)),
+        };
+        Ok(Value::Number(converted))
+    }
+    
+    /// Calls a function value. The body runs against its captured environment rather
+    /// than the caller's locals; globals stay visible.
+    fn call_function(&mut self, func: &Value, args: Vec<Value>) -> Result<Value, String> {
+        self.invoke(func, args, None).map(|(value, _)| value)
+    }
+    
+    /// Calls a function value with `receiver`, if any, bound to `self`, and
+    /// returns `self` as the body left it along with the result
+    fn invoke(&mut self, func: &Value, args: Vec<Value>, receiver: Option<Value>) -> Result<(Value, Option<Value>), String> {
+        let (name, params, body, env, module) = match func {
+            Value::Function { name, params, body, env, module } => (name, params, body, env, module),
+            Value::NativeFunction(native) => return native.call(&args).map(|value| (value, receiver)),
+            other => return Err(format!("Cannot call {}", other)),
+        };
+        
         if args.len() != params.len() {
-            return Err(format!("Expected {} arguments, got {}", params.len(), args.len()));
+            return Err(format!("Function '{}' expects {} arguments, got {}", name, params.len(), args.len()));
         }
         
-        self.stack.push(HashMap::new());
+        let mut captured = env.clone();
+        // Let named functions defined in a local scope call themselves
+        if !captured.contains_key(name) && !self.globals.lock().unwrap().scopes[*module].contains_key(name) {
+            captured.insert(name.clone(), Slot::new(func.clone()));
+        }
         
-        for (i, (param_name, _)) in params.iter().enumerate() {
-            let arg_val = self.evaluate(&args[i])?;
-            self.stack.last_mut().unwrap().insert(param_name.clone(), arg_val);
+        let mut frame = HashMap::new();
+        let has_receiver = receiver.is_some();
+        if let Some(receiver) = receiver {
+            frame.insert("self".to_string(), Slot::new(receiver));
+        }
+        for ((param_name, _), arg_val) in params.iter().zip(args) {
+            frame.insert(param_name.clone(), Slot::new(arg_val));
         }
         
-        let mut result = Value::None;
-        for stmt in body {
-            result = self.execute(stmt)?;
+        let caller_stack = std::mem::replace(&mut self.stack, vec![captured, frame]);
+        let caller_module = std::mem::replace(&mut self.module, *module);
+        // The callee's frames are its own, even when called from a parallel item
+        let caller_parallel_frames = self.parallel_frames;
+        self.parallel_frames = caller_parallel_frames.map(|_| 0);
+        let result = self.execute_statements(body);
+        self.parallel_frames = caller_parallel_frames;
+        let receiver = if has_receiver {
+            self.stack.get_mut(1).and_then(|frame| frame.remove("self")).map(|slot| slot.get())
+        } else {
+            None
+        };
+        self.module = caller_module;
+        self.stack = caller_stack;
+        
+        // Without an explicit return, a function yields the value of its last statement
+        match result? {
+            ControlFlow::Normal(value) | ControlFlow::Return(value) => Ok((value, receiver)),
+            ControlFlow::Break => Err("'break' outside of a loop".to_string()),
+            ControlFlow::Continue => Err("'continue' outside of a loop".to_string()),
+        }
+    }
+    
+    /// Whether `object.member` names a method of the actor whose method is running
+    fn is_own_method(&self, object: &Expr, member: &str) -> bool {
+        matches!(&object.kind, ExprKind::Identifier(name) if name == "self")
+            && self.actor.as_ref().is_some_and(|actor| actor.methods.contains_key(member))
+    }
+    
+    /// Runs another method of the running actor right away, on the same state
+    /// rather than through the mailbox, and keeps the state it leaves behind
+    fn call_own_method(&mut self, method: &str, args: &[Expr]) -> Result<Value, String> {
+        let actor = Arc::clone(self.actor.as_ref().expect("only called inside an actor"));
+        let arg_values = self.evaluate_arguments(args)?;
+        let receiver = self.get_variable("self")?;
+        let (result, receiver) = self.invoke(&actor.methods[method], arg_values, Some(receiver))?;
+        match receiver {
+            Some(receiver @ Value::Struct { .. }) => {
+                self.assign("self", &[], receiver)?;
+                Ok(result)
+            },
+            _ => Err(format!("'self' in actor '{}' must stay the actor's state", actor.name)),
+        }
+    }
+    
+    fn evaluate_arguments(&mut self, args: &[Expr]) -> Result<Vec<Value>, String> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.evaluate(arg)?);
+        }
+        Ok(values)
+    }
+    
+    /// Starts an actor of type `actor`; given fields replace their initializers
+    fn spawn_actor(&self, actor: Arc<ActorType>, values: Vec<(String, Value)>) -> Result<Value, String> {
+        let mut overrides = HashMap::new();
+        for (field, value) in values {
+            // The actor's state must not share anything with the spawning code
+            let value = Sendable::new(value)
+                .map_err(|e| format!("Cannot spawn actor '{}' with field '{}': {}", actor.name, field, e))?
+                .into_value();
+            if !actor.fields.iter().any(|(declared, _)| *declared == field) {
+                return Err(format!("Actor '{}' has no state field '{}'", actor.name, field));
+            }
+            if overrides.insert(field.clone(), value).is_some() {
+                return Err(format!("Field '{}' is given twice in '{}' literal", field, actor.name));
+            }
         }
         
-        self.stack.pop();
-        Ok(result)
+        let mut vm = self.fork(Vec::new());
+        vm.actor = Some(Arc::clone(&actor));
+        let context = ActorContext {
+            vm,
+            actor: Arc::clone(&actor),
+            overrides,
+        };
+        let actor_ref = self.actor_runtime.spawn(&actor.name, Box::new(context))?;
+        Ok(Value::Actor(actor_ref))
+    }
+    
+    /// Checks that `actor` has a method `method` taking `args`
+    fn check_actor_call(&self, actor: &ActorRef, method: &str, args: &[Value]) -> Result<(), String> {
+        let actor_type = self.globals.lock().unwrap().actors.get(actor.name()).cloned();
+        if let Some(Value::Function { params, .. }) = actor_type.as_ref().and_then(|actor_type| actor_type.methods.get(method)) {
+            if params.len() != args.len() {
+                return Err(format!("Method '{}.{}' expects {} arguments, got {}", actor.name(), method, params.len(), args.len()));
+            }
+        } else if actor_type.is_some() {
+            return Err(format!("Actor '{}' has no method '{}'", actor.name(), method));
+        }
+        Ok(())
     }
     
     fn call_builtin(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
@@ -496,68 +1015,441 @@ impl VM {
                 let mut parts = Vec::new();
                 for arg in args {
                     let val = self.evaluate(arg)?;
-                    parts.push(self.value_to_string(&val));
+                    parts.push(val.to_string());
                 }
                 println!("{}", parts.join(" "));
                 Ok(Value::None)
             },
-            "range" => {
-                if args.len() == 2 {
-                    let start = self.evaluate(&args[0])?;
-                    let end = self.evaluate(&args[1])?;
-                    if let (Value::Number(s), Value::Number(e)) = (start, end) {
-                        let mut list = Vec::new();
-                        for i in (s as i64)..(e as i64) {
-                            list.push(Value::Number(i as f64));
-                        }
-                        Ok(Value::List(list))
-                    } else {
-                        Err("range() expects two numbers".to_string())
+            "range" | "take" | "next" | "collect" => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.evaluate(arg)?);
+                }
+                match name {
+                    "range" => stdlib::core::range(&values),
+                    "take" => stdlib::core::take(&values),
+                    "next" => stdlib::core::next(&values),
+                    _ => stdlib::core::collect(&values),
+                }
+            },
+            "sleep" => {
+                let millis = match args {
+                    [arg] => match self.evaluate(arg)? {
+                        Value::Number(n) if n >= 0.0 => n as u64,
+                        other => return Err(format!("sleep() expects a non-negative number of milliseconds, got {}", other)),
+                    },
+                    _ => return Err(format!("sleep() expects 1 argument, got {}", args.len())),
+                };
+                Ok(Value::Task(self.async_runtime.sleep(millis)))
+            },
+            "join_all" | "select" => {
+                let items = match args {
+                    [arg] => match self.evaluate(arg)? {
+                        Value::List(items) => items,
+                        other => return Err(format!("{}() expects a list of tasks, got {}", name, other)),
+                    },
+                    _ => return Err(format!("{}() expects 1 argument, got {}", name, args.len())),
+                };
+                let mut tasks = Vec::with_capacity(items.len());
+                for item in &items {
+                    match item {
+                        Value::Task(id) => tasks.push(*id),
+                        other => return Err(format!("{}() expects a list of tasks, got {}", name, other)),
                     }
-                } else if args.len() == 1 {
-                    let end = self.evaluate(&args[0])?;
-                    if let Value::Number(e) = end {
-                        let mut list = Vec::new();
-                        for i in 0..(e as i64) {
-                            list.push(Value::Number(i as f64));
-                        }
-                        Ok(Value::List(list))
-                    } else {
-                        Err("range() expects a number".to_string())
+                }
+                
+                if name == "select" {
+                    // The first task to finish, as [position, result]
+                    if tasks.is_empty() {
+                        return Err("select() needs at least one task".to_string());
+                    }
+                    let (position, value) = self.await_first(&tasks)?;
+                    Ok(Value::List(vec![Value::Number(position as f64), value]))
+                } else {
+                    // Results in list order; the tasks make progress together
+                    let mut results = Vec::with_capacity(tasks.len());
+                    for id in tasks {
+                        results.push(self.await_first(&[id])?.1);
                     }
+                    Ok(Value::List(results))
+                }
+            },
+            "timeout" => {
+                let (reply, millis) = match args {
+                    [reply, millis] => (self.evaluate(reply)?, self.evaluate(millis)?),
+                    _ => return Err(format!("timeout() expects 2 arguments, got {}", args.len())),
+                };
+                let reply = match reply {
+                    Value::Reply(reply) => reply,
+                    other => return Err(format!("timeout() expects an actor reply, got {}", other)),
+                };
+                match millis {
+                    Value::Number(n) if n >= 0.0 => Ok(Value::Reply(reply.with_timeout(Duration::from_millis(n as u64)))),
+                    other => Err(format!("timeout() expects a non-negative number of milliseconds, got {}", other)),
+                }
+            },
+            "supervise" => {
+                if args.is_empty() || args.len() > 4 {
+                    return Err(format!("supervise() expects 1 to 4 arguments, got {}", args.len()));
+                }
+                let values = self.evaluate_arguments(args)?;
+                let children = match &values[0] {
+                    Value::List(items) => items.iter().map(|item| match item {
+                        Value::Actor(actor) => Ok(Child::Actor(actor.clone())),
+                        Value::Supervisor(supervisor) => Ok(Child::Supervisor(supervisor.clone())),
+                        other => Err(format!("supervise() expects a list of actors and supervisors, got {}", other)),
+                    }).collect::<Result<Vec<_>, String>>()?,
+                    other => return Err(format!("supervise() expects a list of actors and supervisors, got {}", other)),
+                };
+                let strategy = match values.get(1) {
+                    None => RestartStrategy::OneForOne,
+                    Some(Value::String(name)) => RestartStrategy::from_name(name)
+                        .ok_or_else(|| format!("Unknown restart strategy '{}'; use one_for_one, one_for_all or rest_for_one", name))?,
+                    Some(other) => return Err(format!("supervise() expects a restart strategy name, got {}", other)),
+                };
+                // Default intensity: at most 3 restarts within 5 seconds
+                let max_restarts = match values.get(2) {
+                    None => 3,
+                    Some(Value::Number(n)) if *n >= 0.0 => *n as usize,
+                    Some(other) => return Err(format!("supervise() expects a non-negative number of restarts, got {}", other)),
+                };
+                let within = match values.get(3) {
+                    None => Duration::from_secs(5),
+                    Some(Value::Number(n)) if *n >= 0.0 => Duration::from_millis(*n as u64),
+                    Some(other) => return Err(format!("supervise() expects a non-negative number of milliseconds, got {}", other)),
+                };
+                let supervisor = self.actor_runtime.supervise(children, strategy, max_restarts, within)?;
+                Ok(Value::Supervisor(supervisor))
+            },
+            "stop" | "terminate" => {
+                let actor = match args {
+                    [arg] => match self.evaluate(arg)? {
+                        Value::Actor(actor) => actor,
+                        other => return Err(format!("{}() expects an actor, got {}", name, other)),
+                    },
+                    _ => return Err(format!("{}() expects 1 argument, got {}", name, args.len())),
+                };
+                if name == "stop" {
+                    actor.stop();
                 } else {
-                    Err("range() expects 1 or 2 arguments".to_string())
+                    actor.terminate();
+                }
+                Ok(Value::None)
+            },
+            "is_completed" | "is_suspended" | "cancel" => {
+                let id = match args {
+                    [arg] => match self.evaluate(arg)? {
+                        Value::Coroutine(id) => id,
+                        other => return Err(format!("{}() expects a coroutine, got {}", name, other)),
+                    },
+                    _ => return Err(format!("{}() expects 1 argument, got {}", name, args.len())),
+                };
+                match name {
+                    "is_completed" => Ok(Value::Boolean(self.coroutine_runtime.is_completed(id))),
+                    "is_suspended" => Ok(Value::Boolean(self.coroutine_runtime.is_suspended(id))),
+                    _ => self.coroutine_runtime.cancel(id).map(|()| Value::None),
                 }
             },
             _ => Err(format!("Unknown function: {}", name)),
         }
     }
     
+    /// Waits until one of `tasks` finishes and returns its position and result.
+    /// A task parks itself and lets the executor run others; any other context
+    /// drives the executor until then.
+    fn await_first(&mut self, tasks: &[usize]) -> Result<(usize, Value), String> {
+        loop {
+            if let Some(finished) = self.async_runtime.first_finished(tasks)? {
+                return Ok(finished);
+            }
+            match &self.task {
+                Some(task) => {
+                    self.async_runtime.wait_for(task.id, tasks);
+                    task.suspender.suspend(Value::None)?;
+                },
+                None => {
+                    if !self.async_runtime.run_once() {
+                        return Err("Deadlock: awaited task can never finish".to_string());
+                    }
+                },
+            }
+        }
+    }
+    
+    /// Runs a file module on first import and returns a map of its exported values
+    fn import_module(&mut self, index: usize) -> Result<Value, String> {
+        if let Some(exports) = self.globals.lock().unwrap().module_exports.get(&index) {
+            return Ok(exports.clone());
+        }
+        
+        let graph = Arc::clone(self.modules.as_ref().expect("file imports resolve through the module graph"));
+        let module = &graph.modules[index];
+        let caller_stack = std::mem::take(&mut self.stack);
+        let caller_module = std::mem::replace(&mut self.module, index);
+        let result = self.run_statements(&module.program.statements);
+        self.module = caller_module;
+        self.stack = caller_stack;
+        result.map_err(|e| format!("{} (in module '{}')", e, module.name))?;
+        
+        let mut globals = self.globals.lock().unwrap();
+        let exports = Value::Map(module.exports.iter()
+            .filter_map(|name| globals.scopes[index].get(name).map(|value| (name.clone(), value.clone())))
+            .collect());
+        globals.module_exports.insert(index, exports.clone());
+        Ok(exports)
+    }
+    
+    /// Evaluates `body` for every item on the worker pool. Each item runs in its
+    /// own context over the current locals, with `var` bound to the item in a
+    /// frame of its own, so items cannot see each other's local changes.
+    fn parallel_map(&self, var: &str, items: Vec<Value>, body: &[Stmt]) -> Result<Vec<Value>, String> {
+        let env = self.capture_environment();
+        let (var, body) = (var.to_string(), body.to_vec());
+        self.parallel_runtime.parallel_map(items, || self.fork_parallel(), move |context, item| {
+            context.stack = vec![env.clone(), HashMap::from([(var.clone(), Slot::new(item))])];
+            context.evaluate_block(&body)
+        })
+    }
+    
+    /// Reduces `items` with `body`, binding the running total to `acc` and the item
+    /// to `var`. With a `combine` clause, one contiguous chunk per worker is folded
+    /// from `initial` on the worker pool and the chunk results are merged in order
+    /// with the clause; without one, the items are folded one after another.
+    fn parallel_reduce(&self, acc: &str, initial: Value, var: &str, items: Vec<Value>, body: &[Stmt], combine: Option<&ReduceCombine>) -> Result<Value, String> {
+        let env = self.capture_environment();
+        let (acc, var, body) = (acc.to_string(), var.to_string(), body.to_vec());
+        let fold_env = env.clone();
+        let fold = move |context: &mut VM, total: Value, item: Value| {
+            let frame = HashMap::from([(acc.clone(), Slot::new(total)), (var.clone(), Slot::new(item))]);
+            context.stack = vec![fold_env.clone(), frame];
+            context.evaluate_block(&body)
+        };
+        
+        let combine = match combine {
+            Some(combine) => combine.clone(),
+            None => {
+                let mut context = self.fork_parallel();
+                return items.into_iter().try_fold(initial, |total, item| fold(&mut context, total, item));
+            },
+        };
+        
+        let mut combiner = self.fork_parallel();
+        self.parallel_runtime.parallel_reduce(
+            items,
+            initial,
+            || self.fork_parallel(),
+            fold,
+            |left, right| {
+                let frame = HashMap::from([(combine.left.clone(), Slot::new(left)), (combine.right.clone(), Slot::new(right))]);
+                combiner.stack = vec![env.clone(), frame];
+                combiner.evaluate_block(&combine.body)
+            },
+        )
+    }
+    
+    /// The local variables visible at this point, innermost bindings winning. They
+    /// are shared with the scopes declaring them, not copied.
+    fn capture_environment(&self) -> HashMap<String, Slot> {
+        let mut env = HashMap::new();
+        for frame in &self.stack {
+            for (name, slot) in frame {
+                env.insert(name.clone(), slot.clone());
+            }
+        }
+        env
+    }
+    
     fn define_variable(&mut self, name: String, value: Value) {
         if let Some(frame) = self.stack.last_mut() {
-            frame.insert(name, value);
+            frame.insert(name, Slot::new(value));
+        } else {
+            self.globals.lock().unwrap().scopes[self.module].insert(name, value);
+        }
+    }
+    
+    /// Stores a function declared in the current scope into the variable declared
+    /// for it when the scope started, which earlier functions may have captured
+    fn define_function(&mut self, name: String, func: Value) {
+        match self.stack.last().and_then(|frame| frame.get(&name)).cloned() {
+            Some(slot) => slot.update(|value| *value = func),
+            None => self.define_variable(name, func),
+        }
+    }
+    
+    /// Stores `value` into variable `name` or at `path` inside it, updating the
+    /// innermost scope frame that defines the variable
+    fn assign(&mut self, name: &str, path: &[Value], value: Value) -> Result<(), String> {
+        self.update(name, |slot| Self::store_at_path(slot, path, value))
+    }
+    
+    /// Runs `update` on variable `name` in the innermost scope frame that defines
+    /// it, or on the module global, while holding the variable's lock
+    fn update<T>(&mut self, name: &str, update: impl FnOnce(&mut Value) -> Result<T, String>) -> Result<T, String> {
+        let depth = self.stack.iter().rposition(|frame| frame.contains_key(name));
+        if let Some(outer_frames) = self.parallel_frames {
+            let outer = match depth {
+                Some(depth) => depth < outer_frames,
+                None => self.globals.lock().unwrap().scopes[self.module].contains_key(name),
+            };
+            if outer {
+                return Err(format!(
+                    "Cannot assign to outer variable '{}' inside a parallel body; items run at the same time, \
+                     so produce values with 'parallel map' or 'parallel reduce' instead", name));
+            }
+        }
+        if let Some(depth) = depth {
+            return self.stack[depth][name].update(update);
+        }
+        let mut globals = self.globals.lock().unwrap();
+        let slot = globals.scopes[self.module].get_mut(name)
+            .ok_or_else(|| format!("Undefined variable: {}", name))?;
+        update(slot)
+    }
+    
+    /// Resolves the root variable of an assignment target, collecting member names and
+    /// evaluated indices from the outermost access inwards
+    fn assignment_path<'a>(&mut self, target: &'a Expr, path: &mut Vec<Value>) -> Result<&'a str, String> {
+        match &target.kind {
+            ExprKind::Identifier(name) => Ok(name),
+            ExprKind::Member { object, member } => {
+                let root = self.assignment_path(object, path)?;
+                path.push(Value::String(member.clone()));
+                Ok(root)
+            },
+            ExprKind::Index { object, index } => {
+                let root = self.assignment_path(object, path)?;
+                path.push(self.evaluate(index)?);
+                Ok(root)
+            },
+            _ => Err("Invalid assignment target".to_string()),
+        }
+    }
+    
+    /// Builds a struct value, checking the given fields against the declaration
+    pub(crate) fn construct_struct(name: &str, declared: &[String], values: Vec<(String, Value)>) -> Result<Value, String> {
+        let mut fields = HashMap::new();
+        for (field, value) in values {
+            if !declared.contains(&field) {
+                return Err(format!("Struct '{}' has no field '{}'", name, field));
+            }
+            if fields.insert(field.clone(), value).is_some() {
+                return Err(format!("Field '{}' is given twice in '{}' literal", field, name));
+            }
+        }
+        if let Some(missing) = declared.iter().find(|field| !fields.contains_key(*field)) {
+            return Err(format!("Missing field '{}' in '{}' literal", missing, name));
+        }
+        Ok(Value::Struct {
+            name: name.to_string(),
+            fields,
+        })
+    }
+    
+    /// Builds an enum value, checking the variant and its payload size against the declaration
+    pub(crate) fn construct_variant(enum_name: &str, variant: &str, declared: &[(String, usize)], values: Vec<Value>) -> Result<Value, String> {
+        let arity = declared.iter()
+            .find(|(name, _)| name == variant)
+            .map(|(_, arity)| *arity)
+            .ok_or_else(|| format!("Enum '{}' has no variant '{}'", enum_name, variant))?;
+        if values.len() != arity {
+            return Err(format!("Variant '{}::{}' expects {} values, got {}", enum_name, variant, arity, values.len()));
+        }
+        Ok(Value::Enum {
+            enum_name: enum_name.to_string(),
+            variant: variant.to_string(),
+            values,
+        })
+    }
+    
+    pub(crate) fn member(object: Value, member: &str) -> Result<Value, String> {
+        match object {
+            Value::Map(mut map) => map.remove(member).ok_or_else(|| format!("No member '{}'", member)),
+            Value::Struct { name, mut fields } => fields.remove(member)
+                .ok_or_else(|| format!("Struct '{}' has no field '{}'", name, member)),
+            _ => Err(format!("Cannot access member '{}' on non-object", member)),
+        }
+    }
+    
+    /// The list position a number index refers to; negative and fractional
+    /// indices are errors rather than being truncated
+    pub(crate) fn list_index(n: f64) -> Result<usize, String> {
+        if n >= 0.0 && n.fract() == 0.0 {
+            Ok(n as usize)
         } else {
-            self.globals.insert(name, value);
+            Err(format!("List index must be a non-negative integer, got {}", n))
+        }
+    }
+    
+    /// Reads the value at `path` inside `value`, as `store_at_path` would write it
+    pub(crate) fn load_at_path(value: Value, path: &[Value]) -> Result<Value, String> {
+        path.iter().try_fold(value, |value, key| match (value, key) {
+            (Value::List(mut list), Value::Number(n)) => {
+                let i = Self::list_index(*n)?;
+                if i < list.len() {
+                    Ok(list.swap_remove(i))
+                } else {
+                    Err(format!("Index {} out of bounds", i))
+                }
+            },
+            (value @ (Value::Map(_) | Value::Struct { .. }), Value::String(member)) => Self::member(value, member),
+            (Value::Map(_), _) => Err("Map keys must be strings".to_string()),
+            _ => Err("Invalid index operation".to_string()),
+        })
+    }
+    
+    pub(crate) fn store_at_path(slot: &mut Value, path: &[Value], value: Value) -> Result<(), String> {
+        let (key, rest) = match path.split_first() {
+            Some(step) => step,
+            None => {
+                *slot = value;
+                return Ok(());
+            },
+        };
+        
+        match (slot, key) {
+            (Value::List(list), Value::Number(n)) => {
+                let i = Self::list_index(*n)?;
+                let element = list.get_mut(i)
+                    .ok_or_else(|| format!("Index {} out of bounds", i))?;
+                Self::store_at_path(element, rest, value)
+            },
+            (Value::Map(map), Value::String(member)) => {
+                if rest.is_empty() {
+                    map.insert(member.clone(), value);
+                    Ok(())
+                } else {
+                    let entry = map.get_mut(member)
+                        .ok_or_else(|| format!("No member '{}'", member))?;
+                    Self::store_at_path(entry, rest, value)
+                }
+            },
+            (Value::Struct { name, fields }, Value::String(member)) => {
+                let field = fields.get_mut(member)
+                    .ok_or_else(|| format!("Struct '{}' has no field '{}'", name, member))?;
+                Self::store_at_path(field, rest, value)
+            },
+            (Value::Map(_), _) => Err("Map keys must be strings".to_string()),
+            _ => Err("Invalid index assignment".to_string()),
         }
     }
     
     fn get_variable(&self, name: &str) -> Result<Value, String> {
         // Check stack frames (local scope)
         for frame in self.stack.iter().rev() {
-            if let Some(value) = frame.get(name) {
-                return Ok(value.clone());
+            if let Some(slot) = frame.get(name) {
+                return Ok(slot.get());
             }
         }
         
         // Check globals
-        if let Some(value) = self.globals.get(name) {
+        if let Some(value) = self.globals.lock().unwrap().scopes[self.module].get(name) {
             return Ok(value.clone());
         }
         
         Err(format!("Undefined variable: {}", name))
     }
     
-    fn is_truthy(&self, value: &Value) -> bool {
+    pub(crate) fn is_truthy(value: &Value) -> bool {
         match value {
             Value::Boolean(false) => false,
             Value::Number(n) => *n != 0.0,
@@ -566,31 +1458,5 @@ impl VM {
             _ => true,
         }
     }
-    
-    fn value_to_string(&self, value: &Value) -> String {
-        match value {
-            Value::Number(n) => {
-                if n.fract() == 0.0 {
-                    format!("{}", *n as i64)
-                } else {
-                    format!("{}", n)
-                }
-            },
-            Value::String(s) => s.clone(),
-            Value::Boolean(b) => format!("{}", b),
-            Value::List(list) => {
-                let items: Vec<String> = list.iter().map(|v| self.value_to_string(v)).collect();
-                format!("[{}]", items.join(", "))
-            },
-            Value::Map(map) => {
-                let pairs: Vec<String> = map.iter()
-                    .map(|(k, v)| format!("{}: {}", k, self.value_to_string(v)))
-                    .collect();
-                format!("{{{}}}", pairs.join(", "))
-            },
-            Value::Function { name, .. } => format!("<function {}>", name),
-            Value::None => "None".to_string(),
-        }
-    }
 }
 
@@ -32,6 +32,14 @@ EXAMPLES=(
     "examples/hello.joel"
     "examples/arithmetic.joel"
     "examples/control_flow.joel"
+    "examples/pattern_matching.joel"
+    "examples/contract.joel"
+    "examples/ui_component.joel"
+    "examples/generator_example.joel"
+    "examples/coroutine_example.joel"
+    "examples/async_example.joel"
+    "examples/parallel_example.joel"
+    "examples/actor.joel"
 )
 
 # Test each example
@@ -0,0 +1,20 @@
+//! Behaviour tests for asking actors for replies, run through the `joel`
+//! binary under `--deterministic`
+
+mod common;
+
+use common::{run, COUNTER};
+
+#[test]
+fn ask_waits_for_earlier_sends() {
+    let source = format!("{}{}", COUNTER, r#"
+send counter.bump()
+send counter.bump()
+print(await counter.count())
+send counter.bump()
+print(await counter.count())
+"#);
+    let run = run("actor-ask", &source, &["--deterministic"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["2", "3"]);
+}
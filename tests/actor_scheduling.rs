@@ -0,0 +1,96 @@
+//! Behaviour tests for scheduling actors on the worker pool: bounded mailboxes
+//! and stopping, run through the `joel` binary
+
+mod common;
+
+use common::{run, COUNTER};
+
+#[test]
+fn full_mailbox_makes_senders_wait_instead_of_dropping() {
+    let source = format!("{}{}", COUNTER, r#"
+for i in range(0, 100) {
+  send counter.bump()
+}
+print(await counter.count())
+"#);
+    for mode in ["--deterministic", "--threads=4"] {
+        let run = run("actor-backpressure", &source, &[mode, "--mailbox-capacity", "1"]);
+        assert!(run.success, "{}: {}", mode, run.stderr);
+        assert_eq!(run.lines(), ["100"], "{}", mode);
+    }
+}
+
+#[test]
+fn stopped_actor_refuses_messages() {
+    let source = format!("{}{}", COUNTER, r#"
+stop(counter)
+print(await counter.count())
+"#);
+    let run = run("actor-stop", &source, &["--deterministic"]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("Actor Counter is stopped"), "{}", run.stderr);
+}
+
+#[test]
+fn actors_filling_each_others_mailboxes_fail_instead_of_hanging() {
+    let source = r#"[Interpreted]
+actor Node {
+  state let hits = 0
+
+  fn start(other, me) {
+    send other.relay(me)
+    for i in range(3) { send other.hit() }
+  }
+
+  fn relay(other) {
+    for i in range(3) { send other.hit() }
+  }
+
+  fn hit() {
+    self.hits += 1
+  }
+}
+
+let a = spawn Node{}
+let b = spawn Node{}
+await a.start(b, a)
+"#;
+    for mode in ["--deterministic", "--threads=4"] {
+        let run = run("actor-mailbox-cycle", source, &[mode, "--mailbox-capacity", "1"]);
+        assert!(run.stderr.contains("Mailbox of actor Node is full and nothing can empty it"), "{}: {}", mode, run.stderr);
+    }
+}
+
+#[test]
+fn actors_awaiting_each_other_fail_instead_of_hanging() {
+    let source = r#"[Interpreted]
+actor Left {
+  state let n = 0
+
+  fn ask_right(right, left) {
+    return await right.ask_left(left)
+  }
+
+  fn value() {
+    return 1
+  }
+}
+
+actor Right {
+  state let n = 0
+
+  fn ask_left(left) {
+    return await left.value()
+  }
+}
+
+let l = spawn Left{}
+let r = spawn Right{}
+print(await l.ask_right(r, l))
+"#;
+    for mode in ["--deterministic", "--threads=4"] {
+        let run = run("actor-await-cycle", source, &[mode]);
+        assert!(!run.success, "{}", mode);
+        assert!(run.stderr.contains("Deadlock: actor Left can never reply to 'value'"), "{}: {}", mode, run.stderr);
+    }
+}
@@ -0,0 +1,138 @@
+//! Behaviour tests for actor methods, run through the `joel` binary, mostly
+//! under `--deterministic` so messages are handled in the same order on every run
+
+mod common;
+
+use common::{run, LOG};
+
+#[test]
+fn messages_are_handled_in_send_order() {
+    let source = format!("{}{}", LOG, r#"
+for i in range(0, 5) {
+  send log.add(i)
+}
+print(await log.get())
+"#);
+    // A mailbox keeps its order also when the actor runs on a worker thread
+    for mode in ["--deterministic", "--threads=4"] {
+        let run = run("actor-fifo", &source, &[mode]);
+        assert!(run.success, "{}: {}", mode, run.stderr);
+        assert_eq!(run.lines(), [" 0 1 2 3 4"], "{}", mode);
+    }
+}
+
+#[test]
+fn methods_can_call_other_methods_on_self() {
+    let source = r#"[Interpreted]
+actor Account {
+  state let balance: i64 = 0
+
+  fn credit(amount) {
+    self.balance += amount
+    return self.balance
+  }
+
+  fn credit_twice(amount) {
+    let first = self.credit(amount)
+    return first + self.credit(amount)
+  }
+
+  fn get() {
+    return self.balance
+  }
+}
+
+let account = spawn Account{}
+print(await account.credit_twice(5), await account.get())
+"#;
+    let run = run("actor-self-call", source, &["--deterministic"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["15 10"]);
+}
+
+#[test]
+fn actors_on_several_workers_do_not_lose_global_updates() {
+    let source = r#"[Interpreted]
+let total = 0
+actor Bumper {
+  state let runs = 0
+
+  fn bump(n) {
+    for i in range(n) {
+      total += 1
+    }
+  }
+}
+let a = spawn Bumper{}
+let b = spawn Bumper{}
+let c = spawn Bumper{}
+let d = spawn Bumper{}
+for reply in [a.bump(10000), b.bump(10000), c.bump(10000), d.bump(10000)] {
+  await reply
+}
+print(total)
+"#;
+    let run = run("actor-global-updates", source, &["--threads", "4"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["40000"]);
+}
+
+const HOLDER: &str = r#"[Interpreted]
+actor Holder {
+  state let item = 0
+
+  fn put(x) {
+    self.item = x
+  }
+
+  fn apply(f, x) {
+    return f(x)
+  }
+}
+
+let holder = spawn Holder{}
+"#;
+
+#[test]
+fn closures_over_locals_cannot_be_sent_to_actors() {
+    let source = format!("{}{}", HOLDER, r#"
+fn make_adder(k) {
+  fn add(x) {
+    return x + k
+  }
+  return add
+}
+holder.put(make_adder(5))
+"#);
+    let run = run("actor-closure", &source, &["--deterministic"]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("Cannot send 'put' to actor Holder: function 'add' closes over local variables, so it cannot be shared between actors"), "{}", run.stderr);
+}
+
+#[test]
+fn coroutine_handles_cannot_be_sent_to_actors_even_inside_a_list() {
+    let source = format!("{}{}", HOLDER, r#"
+coroutine fn ticks() {
+  suspend 1
+}
+send holder.put([ticks()])
+"#);
+    for mode in ["--deterministic", "--threads=4"] {
+        let run = run("actor-coroutine", &source, &[mode]);
+        assert!(!run.success, "{}", mode);
+        assert!(run.stderr.contains("Cannot send 'put' to actor Holder: coroutine values cannot be shared between actors"), "{}: {}", mode, run.stderr);
+    }
+}
+
+#[test]
+fn functions_without_captured_locals_can_be_sent_to_actors() {
+    let source = format!("{}{}", HOLDER, r#"
+fn double(x) {
+  return x * 2
+}
+print(await holder.apply(double, 21))
+"#);
+    let run = run("actor-function", &source, &["--deterministic"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["42"]);
+}
@@ -0,0 +1,44 @@
+//! Behaviour tests for assignment and compound assignment, run through the
+//! `joel` binary on both VMs
+
+mod common;
+
+use common::run;
+
+const VMS: [&str; 2] = ["--vm=tree", "--vm=bytecode"];
+
+#[test]
+fn compound_assignment_evaluates_the_target_once() {
+    let source = r#"[Interpreted]
+let calls = 0
+fn idx() {
+  calls += 1
+  return 1
+}
+let xs = [1, 2, 3]
+xs[idx()] += 10
+let m = { a: { b: 1 } }
+m.a.b += 5
+m["a"]["b"] *= 2
+print(xs, calls, m.a.b)
+"#;
+    for vm in VMS {
+        let run = run("compound-once", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["[1, 12, 3] 1 12"], "{}", vm);
+    }
+}
+
+#[test]
+fn negative_and_fractional_indices_are_rejected() {
+    for (index, value) in [("-1", "9"), ("1.7", "8")] {
+        let source = format!("[Interpreted]\nlet xs = [1, 2, 3]\nxs[{}] = {}\nprint(xs)\n", index, value);
+        for vm in VMS {
+            let run = run("bad-index", &source, &[vm]);
+            assert!(!run.success, "{} {}: {}", vm, index, run.stdout);
+            let expected = format!("List index must be a non-negative integer, got {}", index);
+            assert!(run.stderr.contains(&expected), "{}: {}", vm, run.stderr);
+            assert!(run.lines().is_empty(), "{}: {}", vm, run.stdout);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Behaviour tests for running async tasks on the work-stealing pool, run
+//! through the `joel` binary
+
+mod common;
+
+use common::run;
+
+const CHATTY: &str = r#"[Interpreted]
+async fn chatty(id) {
+  print("start", id)
+  await sleep(1)
+  print("end", id)
+  return id
+}
+print(join_all([chatty(1), chatty(2), chatty(3)]))
+"#;
+
+#[test]
+fn one_worker_runs_ready_tasks_in_fifo_order() {
+    for args in [["--threads", "1"].as_slice(), ["--deterministic"].as_slice()] {
+        let run = run("async-fifo", CHATTY, args);
+        assert!(run.success, "{:?}: {}", args, run.stderr);
+        assert_eq!(run.lines(), ["start 1", "start 2", "start 3", "end 1", "end 2", "end 3", "[1, 2, 3]"], "{:?}", args);
+    }
+}
+
+#[test]
+fn tasks_on_a_single_worker_can_run_parallel_loops() {
+    let source = r#"[Interpreted]
+async fn work(id) {
+  let squares = parallel map x in [1, 2, 3] { x * x + id }
+  await sleep(1)
+  return squares
+}
+print(join_all([work(1), work(2)]))
+"#;
+    let run = run("async-parallel", source, &["--threads", "1"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["[[2, 5, 10], [3, 6, 11]]"]);
+}
+
+#[test]
+fn ready_tasks_run_on_several_workers_at_once() {
+    // Each task waits, for a bounded time, until the other has started
+    let source = r#"[Interpreted]
+let started = [false, false]
+async fn meet(me, other) {
+  started[me] = true
+  let spins = 0
+  while !started[other] && spins < 500000 {
+    spins += 1
+  }
+  return started[other]
+}
+print(join_all([meet(0, 1), meet(1, 0)]))
+"#;
+    let run = run("async-overlap", source, &["--threads", "2"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["[true, true]"]);
+}
+
+#[test]
+fn tasks_on_several_workers_do_not_lose_global_updates() {
+    let source = r#"[Interpreted]
+let counter = 0
+async fn bump() {
+  for i in range(20000) {
+    counter += 1
+  }
+}
+join_all([bump(), bump(), bump(), bump()])
+print(counter)
+"#;
+    let run = run("async-global-updates", source, &["--threads", "4"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["80000"]);
+}
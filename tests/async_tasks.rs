@@ -0,0 +1,22 @@
+//! Behaviour tests for async tasks, run through the `joel` binary
+
+mod common;
+
+use common::run;
+
+#[test]
+fn join_all_keeps_task_order_and_select_returns_the_first() {
+    let source = r#"[Interpreted]
+async fn fetch(id, delay) {
+  await sleep(delay)
+  print("done", id)
+  return "item " + id
+}
+print(join_all([fetch(1, 30), fetch(2, 10)]))
+print(select([fetch(3, 50), sleep(5)]))
+"#;
+    let run = run("async-combinators", source, &[]);
+    assert!(run.success, "{}", run.stderr);
+    // The shorter sleep finishes first, but results keep the order of the tasks
+    assert_eq!(run.lines(), ["done 2", "done 1", "[item 1, item 2]", "[1, None]"]);
+}
@@ -0,0 +1,66 @@
+//! Behaviour tests for the bytecode VM, run through the `joel` binary and
+//! compared with the tree-walking interpreter's output
+
+mod common;
+
+use common::{run, run_file};
+use std::path::Path;
+
+const PARITY: &str = r#"[Interpreted]
+fn fib(n) {
+  if n < 2 {
+    return n
+  }
+  return fib(n - 1) + fib(n - 2)
+}
+
+fn make_adder(k) {
+  fn add(x) {
+    return x + k
+  }
+  return add
+}
+
+struct Point { x: f64, y: f64 }
+enum Shape { Circle(f64), Rect(f64, f64) }
+
+let add3 = make_adder(3)
+let total = 0
+for i in range(0, 10) {
+  total += add3(i)
+}
+print(fib(15), total)
+
+let p = Point { x: 1.0, y: 2.0 }
+p.x = 3.0
+print(p.x + p.y)
+
+for s in [Shape::Circle(1.0), Shape::Rect(2.0, 3.0)] {
+  match s {
+    Shape::Circle(r) => print("circle", r),
+    Shape::Rect(w, h) => print("rect", w * h)
+  }
+}
+"#;
+
+#[test]
+fn bytecode_vm_matches_tree_interpreter() {
+    let tree = run("parity-tree", PARITY, &[]);
+    let bytecode = run("parity-bytecode", PARITY, &["--vm=bytecode"]);
+    assert!(tree.success, "{}", tree.stderr);
+    assert!(bytecode.success, "{}", bytecode.stderr);
+    assert_eq!(tree.lines(), ["610 75", "5", "circle 1", "rect 6"]);
+    assert_eq!(bytecode.stdout, tree.stdout);
+}
+
+#[test]
+fn bytecode_vm_matches_tree_interpreter_on_examples() {
+    for example in ["hello", "control_flow", "iterator_example", "test_basic", "type_check"] {
+        let path = Path::new("examples").join(format!("{}.joel", example));
+        let tree = run_file(&path, &[]);
+        let bytecode = run_file(&path, &["--vm=bytecode"]);
+        assert!(tree.success, "{}: {}", example, tree.stderr);
+        assert!(bytecode.success, "{}: {}", example, bytecode.stderr);
+        assert_eq!(bytecode.stdout, tree.stdout, "{}", example);
+    }
+}
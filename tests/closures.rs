@@ -0,0 +1,89 @@
+//! Behaviour tests for closures, run through the `joel` binary on both VMs
+
+mod common;
+
+use common::run;
+
+const VMS: [&str; 2] = ["--vm=tree", "--vm=bytecode"];
+
+#[test]
+fn closures_capture_locals_and_assign_module_variables() {
+    let source = r#"[Interpreted]
+fn make_adder(n) {
+  return (x) => x + n
+}
+let calls = 0
+let tracked = (x) => {
+  calls += 1
+  let doubled = x
+  doubled = doubled * 2
+  return doubled
+}
+let add_two = make_adder(2)
+print(add_two(tracked(3)))
+print(tracked(1))
+print(calls)
+"#;
+    for vm in VMS {
+        let run = run("closure-capture", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["8", "2", "2"], "{}", vm);
+    }
+}
+
+#[test]
+fn closures_share_the_locals_they_capture() {
+    let source = r#"[Interpreted]
+fn make_counter() {
+  let count = 0
+  return () => {
+    count += 1
+    return count
+  }
+}
+let tick = make_counter()
+tick()
+print(tick())
+print(make_counter()())
+if true {
+  let seen = [0]
+  let mark = () => {
+    seen[0] = 1
+  }
+  mark()
+  print(seen[0])
+  seen = [5]
+  let read = () => seen[0]
+  print(read())
+}
+"#;
+    for vm in VMS {
+        let run = run("closure-shared", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["2", "1", "1", "5"], "{}", vm);
+    }
+}
+
+#[test]
+fn local_functions_can_call_siblings_declared_after_them() {
+    let source = r#"[Interpreted]
+fn mk() {
+  let k = 5
+  fn a() { return b() }
+  fn b() { return k }
+  return a()
+}
+print(mk())
+fn parity(n) {
+  fn is_even(k) { if k == 0 { return true } return is_odd(k - 1) }
+  fn is_odd(k) { if k == 0 { return false } return is_even(k - 1) }
+  return is_even(n)
+}
+print(parity(7))
+"#;
+    for vm in VMS {
+        let run = run("closure-siblings", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["5", "false"], "{}", vm);
+    }
+}
@@ -0,0 +1,158 @@
+//! Runs the `joel` binary on small programs for the behaviour tests
+
+// Each test file uses only some of these helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Program prefix spawning `counter`, an actor counting `bump` messages
+pub const COUNTER: &str = r#"[Interpreted]
+actor Counter {
+  state let n: i64 = 0
+
+  fn bump() {
+    self.n += 1
+  }
+
+  fn count() -> i64 {
+    return self.n
+  }
+}
+
+let counter = spawn Counter{}
+"#;
+
+/// Program prefix spawning `log`, an actor recording what it is sent, counting
+/// `bump` messages and failing on `fail`
+pub const LOG: &str = r#"[Interpreted]
+actor Log {
+  state let seen = ""
+  state let n: i64 = 0
+
+  fn add(x) {
+    self.seen = self.seen + " " + x
+  }
+
+  fn bump() {
+    self.n += 1
+  }
+
+  fn fail() {
+    return missing_name
+  }
+
+  fn get() {
+    return self.seen
+  }
+
+  fn count() -> i64 {
+    return self.n
+  }
+}
+
+let log = spawn Log{}
+"#;
+
+/// What a `joel run` printed, without the runtime banner
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+impl Run {
+    /// The program's output lines
+    pub fn lines(&self) -> Vec<&str> {
+        self.stdout.lines().collect()
+    }
+}
+
+/// Writes `source` to a temporary file named after `name` and runs it with `args`
+pub fn run(name: &str, source: &str, args: &[&str]) -> Run {
+    let path = source_file(name);
+    fs::write(&path, source).expect("write test program");
+    let run = run_file(&path, args);
+    let _ = fs::remove_file(&path);
+    run
+}
+
+/// Runs the program at `path`, relative to the crate root, with `args`
+pub fn run_file(path: &Path, args: &[&str]) -> Run {
+    run_file_with_env(path, args, &[])
+}
+
+/// Runs the program at `path` like `run_file`, with extra environment variables
+pub fn run_file_with_env(path: &Path, args: &[&str], env: &[(&str, &str)]) -> Run {
+    let mut run = joel_with_env(&[&["run", path.to_str().expect("UTF-8 path")], args].concat(), env);
+    if let Some((banner, rest)) = run.stdout.split_once("\n\n") {
+        if banner.starts_with("🚀 JOEL Runtime") {
+            run.stdout = rest.to_string();
+        }
+    }
+    run
+}
+
+/// Writes `source` to a temporary file named after `name` and runs the `joel`
+/// subcommand `command` on it with `args`, e.g. `build` or `run`
+pub fn command(command: &str, name: &str, source: &str, args: &[&str]) -> Run {
+    let path = source_file(name);
+    fs::write(&path, source).expect("write test program");
+    let run = joel(&[&[command, path.to_str().expect("UTF-8 path")], args].concat());
+    let _ = fs::remove_file(&path);
+    // What `joel build` wrote for the native target
+    let _ = fs::remove_file(path.with_extension("ll"));
+    run
+}
+
+/// Runs `joel` with `args` from the crate root
+pub fn joel(args: &[&str]) -> Run {
+    joel_with_env(args, &[])
+}
+
+/// Runs `joel` with `args` from the crate root, with extra environment variables
+pub fn joel_with_env(args: &[&str], env: &[(&str, &str)]) -> Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_joel"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .envs(env.iter().copied())
+        .output()
+        .expect("run joel");
+
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        success: output.status.success(),
+    }
+}
+
+/// Runs `joel repl` with `input` piped to it, keeping its history in a
+/// temporary home directory named after `name`
+pub fn repl(name: &str, input: &str) -> Run {
+    let home = std::env::temp_dir().join(format!("joel-test-{}-{}-home", std::process::id(), name));
+    fs::create_dir_all(&home).expect("create test home");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_joel"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg("repl")
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run joel repl");
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes()).expect("write REPL input");
+    let output = child.wait_with_output().expect("wait for joel repl");
+    let _ = fs::remove_dir_all(&home);
+
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        success: output.status.success(),
+    }
+}
+
+fn source_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("joel-test-{}-{}.joel", std::process::id(), name))
+}
@@ -0,0 +1,102 @@
+//! Behaviour tests for `return`, `break` and `continue`, run on both VMs
+
+mod common;
+
+use common::run;
+
+const VMS: [&str; 2] = ["--vm=tree", "--vm=bytecode"];
+
+#[test]
+fn break_leaves_only_the_innermost_loop() {
+    let source = r#"[Interpreted]
+for i in range(0, 3) {
+  for j in range(0, 10) {
+    if j == 2 {
+      break
+    }
+    print(i, j)
+  }
+}
+let n = 0
+while true {
+  n += 1
+  if n == 4 {
+    break
+  }
+}
+print(n)
+"#;
+    for vm in VMS {
+        let run = run("break", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["0 0", "0 1", "1 0", "1 1", "2 0", "2 1", "4"], "{}", vm);
+    }
+}
+
+#[test]
+fn continue_skips_to_the_next_iteration() {
+    let source = r#"[Interpreted]
+let odd = 0
+for i in range(0, 7) {
+  if i % 2 == 0 {
+    continue
+  }
+  odd += i
+}
+print(odd)
+let n = 0
+let skipped = 0
+while n < 5 {
+  n += 1
+  if n == 3 {
+    skipped += 1
+    continue
+  }
+  print(n)
+}
+print(skipped)
+"#;
+    for vm in VMS {
+        let run = run("continue", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["9", "1", "2", "4", "5", "1"], "{}", vm);
+    }
+}
+
+#[test]
+fn return_from_a_nested_loop_leaves_the_function() {
+    let source = r#"[Interpreted]
+fn find(grid, target) {
+  let row = 0
+  for cells in grid {
+    for cell in cells {
+      if cell == target {
+        return row
+      }
+    }
+    row += 1
+  }
+  return -1
+}
+fn first_over(limit) {
+  let i = 0
+  while true {
+    while true {
+      i += 1
+      if i > limit {
+        return i
+      }
+    }
+  }
+}
+let grid = [[1, 2], [3, 4], [5, 6]]
+print(find(grid, 4))
+print(find(grid, 9))
+print(first_over(7))
+"#;
+    for vm in VMS {
+        let run = run("nested-return", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert_eq!(run.lines(), ["1", "-1", "8"], "{}", vm);
+    }
+}
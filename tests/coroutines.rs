@@ -0,0 +1,42 @@
+//! Behaviour tests for coroutines, run through the `joel` binary
+
+mod common;
+
+use common::run;
+
+const TICKER: &str = r#"[Interpreted]
+coroutine fn ticker(n) {
+  while true {
+    suspend n
+    n += 1
+  }
+}
+let t = ticker(1)
+print(resume t, resume t, resume t)
+print(is_completed(t))
+cancel(t)
+print(is_completed(t))
+"#;
+
+#[test]
+fn coroutine_keeps_state_between_resumes() {
+    let run = run("coroutine-resume", TICKER, &[]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["1 2 3", "false", "true"]);
+}
+
+#[test]
+fn cancelled_coroutine_cannot_resume() {
+    let source = format!("{}resume t\n", TICKER);
+    let run = run("coroutine-cancel", &source, &[]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("was cancelled"), "{}", run.stderr);
+}
+
+#[test]
+fn finished_coroutine_cannot_resume() {
+    let source = "[Interpreted]\ncoroutine fn once() {\n  suspend 1\n}\nlet c = once()\nresume c\nresume c\nresume c\n";
+    let run = run("coroutine-finished", source, &[]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("already completed"), "{}", run.stderr);
+}
@@ -0,0 +1,110 @@
+//! Behaviour tests for `--message-format=json` and `--sarif`, run through the
+//! `joel` binary
+
+mod common;
+
+use common::{command, joel};
+use serde_json::Value;
+use std::fs;
+
+/// Each stdout line as JSON, failing the test on any line that is not
+fn json_lines(stdout: &str) -> Vec<Value> {
+    stdout.lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("not JSON ({}): {}", e, line)))
+        .collect()
+}
+
+#[test]
+fn json_build_prints_only_diagnostics_on_stdout() {
+    let source = "[Compiled]\n#[allow(W0999)]\nlet x = 1\nprint(x)\n";
+    let build = command("build", "json-build", source, &["--message-format=json"]);
+    assert!(build.success, "{}", build.stderr);
+    let diagnostics = json_lines(&build.stdout);
+    assert_eq!(diagnostics.len(), 1, "{}", build.stdout);
+    assert_eq!(diagnostics[0]["code"], "W0001");
+    assert_eq!(diagnostics[0]["line"], 2);
+    assert!(build.stderr.contains("Compilation successful"), "{}", build.stderr);
+}
+
+const TYPE_ERRORS: &str = r#"[Compiled]
+fn name(x) -> str {
+  return 1
+}
+let y: int = "a"
+"#;
+
+#[test]
+fn json_type_errors_carry_code_location_and_labels() {
+    let build = command("build", "json-errors", TYPE_ERRORS, &["--message-format=json"]);
+    assert!(!build.success);
+    let diagnostics = json_lines(&build.stdout);
+    assert_eq!(diagnostics.len(), 2, "{}", build.stdout);
+    assert_eq!(diagnostics[0]["level"], "error");
+    assert_eq!(diagnostics[0]["code"], "E0201");
+    assert_eq!((diagnostics[0]["line"].as_u64(), diagnostics[0]["col"].as_u64()), (Some(3), Some(10)));
+    assert!(diagnostics[0]["file"].as_str().unwrap().ends_with("json-errors.joel"));
+    // Labels point at the annotation rather than the start of the declaration
+    let label = &diagnostics[0]["labels"][0];
+    assert_eq!((label["line"].as_u64(), label["col"].as_u64()), (Some(2), Some(15)));
+    assert_eq!(label["message"], "declared to return str here");
+    assert_eq!(diagnostics[1]["code"], "E0200");
+    assert_eq!(diagnostics[1]["line"], 5);
+    let label = &diagnostics[1]["labels"][0];
+    assert_eq!((label["line"].as_u64(), label["col"].as_u64()), (Some(5), Some(8)));
+    assert_eq!(label["message"], "'y' declared as int here");
+}
+
+#[test]
+fn sarif_log_lists_each_diagnostic_as_a_result() {
+    let sarif = std::env::temp_dir().join(format!("joel-test-{}-errors.sarif", std::process::id()));
+    let build = command("build", "sarif-errors", TYPE_ERRORS, &["--sarif", sarif.to_str().unwrap()]);
+    let log: Value = serde_json::from_str(&fs::read_to_string(&sarif).unwrap()).unwrap();
+    let _ = fs::remove_file(&sarif);
+
+    assert!(!build.success);
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(log["runs"][0]["tool"]["driver"]["name"], "joel");
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    let rules: Vec<&str> = results.iter().map(|result| result["ruleId"].as_str().unwrap()).collect();
+    assert_eq!(rules, ["E0201", "E0200"]);
+    assert_eq!(results[0]["level"], "error");
+    let region = &results[0]["locations"][0]["physicalLocation"]["region"];
+    assert_eq!((region["startLine"].as_u64(), region["startColumn"].as_u64()), (Some(3), Some(10)));
+    let related = &results[0]["relatedLocations"][0];
+    assert_eq!(related["message"]["text"], "declared to return str here");
+    assert_eq!(related["physicalLocation"]["region"]["startLine"], 2);
+}
+
+#[test]
+fn missing_imports_are_json_diagnostics_at_the_import() {
+    let source = "[Interpreted]\nimport ops\nimport \"missing/helpers.joel\"\nprint(1)\n";
+    let run = command("run", "missing-imports", source, &["--message-format=json"]);
+    assert!(!run.success);
+    let diagnostics = json_lines(&run.stdout);
+    assert_eq!(diagnostics.len(), 2, "{}", run.stdout);
+    assert_eq!(diagnostics[0]["code"], "W0501");
+    assert_eq!(diagnostics[0]["line"], 2);
+    assert_eq!(diagnostics[1]["code"], "E0501");
+    assert_eq!(diagnostics[1]["level"], "error");
+    assert_eq!(diagnostics[1]["line"], 3);
+}
+
+#[test]
+fn sarif_log_records_import_cycles() {
+    let dir = std::env::temp_dir().join(format!("joel-test-{}-import-cycle", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("first.joel"), "[Interpreted]\nimport second\n").unwrap();
+    fs::write(dir.join("second.joel"), "[Interpreted]\n\nimport first\n").unwrap();
+    let sarif = dir.join("log.sarif");
+    let run = joel(&["run", dir.join("first.joel").to_str().unwrap(), "--sarif", sarif.to_str().unwrap()]);
+    let log: Value = serde_json::from_str(&fs::read_to_string(&sarif).unwrap()).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(!run.success);
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1, "{}", log);
+    assert_eq!(results[0]["ruleId"], "E0502");
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert!(location["artifactLocation"]["uri"].as_str().unwrap().ends_with("second.joel"), "{}", location);
+    assert_eq!(location["region"]["startLine"], 3);
+}
@@ -0,0 +1,40 @@
+//! Behaviour tests for enum declarations and exhaustive matching
+
+mod common;
+
+use common::run;
+
+const MISSING_VARIANT: &str = r#"enum Shape { Circle(f64), Rect(f64, f64), Empty }
+let s = Shape::Rect(2.0, 3.0)
+match s {
+  Shape::Circle(r) => print("circle", r),
+  Shape::Rect(w, h) => print("rect", w * h)
+}
+"#;
+
+#[test]
+fn non_exhaustive_match_warns_when_interpreted() {
+    for vm in ["--vm=tree", "--vm=bytecode"] {
+        let run = run("non-exhaustive-interpreted", &format!("[Interpreted]\n{}", MISSING_VARIANT), &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert!(run.stdout.contains("Warning[E0401]: Non-exhaustive match, missing: Shape::Empty"), "{}: {}", vm, run.stdout);
+        assert!(run.lines().ends_with(&["rect 6"]), "{}: {}", vm, run.stdout);
+    }
+}
+
+#[test]
+fn non_exhaustive_match_is_an_error_when_compiled() {
+    let run = run("non-exhaustive-compiled", &format!("[Compiled]\n{}", MISSING_VARIANT), &[]);
+    assert!(!run.success);
+    assert!(run.stdout.contains("Error[E0401]: Non-exhaustive match, missing: Shape::Empty"), "{}", run.stdout);
+    assert!(!run.stdout.contains("rect 6"), "{}", run.stdout);
+}
+
+#[test]
+fn matches_covering_every_variant_pass_when_compiled() {
+    let source = MISSING_VARIANT.replace("w * h)\n", "w * h),\n  Shape::Empty => print(\"empty\")\n");
+    let run = run("exhaustive-compiled", &format!("[Compiled]\n{}", source), &[]);
+    assert!(run.success, "{}\n{}", run.stdout, run.stderr);
+    assert!(!run.stdout.contains("E0401"), "{}", run.stdout);
+    assert!(run.lines().ends_with(&["rect 6"]), "{}", run.stdout);
+}
@@ -0,0 +1,57 @@
+//! Behaviour tests for error codes, `#[allow]` and `joel explain`, run through
+//! the `joel` binary
+
+mod common;
+
+use common::{command, joel, run};
+
+#[test]
+fn explain_prints_the_long_form_explanation() {
+    let explain = joel(&["explain", "E0204"]);
+    assert!(explain.success, "{}", explain.stderr);
+    assert!(explain.stdout.starts_with("E0204: Undefined variable"), "{}", explain.stdout);
+
+    // Codes are matched case-insensitively
+    let lowercase = joel(&["explain", "e0204"]);
+    assert!(lowercase.success, "{}", lowercase.stderr);
+    assert_eq!(lowercase.stdout, explain.stdout);
+}
+
+#[test]
+fn explain_rejects_unknown_codes() {
+    let explain = joel(&["explain", "E9999"]);
+    assert!(!explain.success);
+    assert!(explain.stdout.is_empty(), "{}", explain.stdout);
+    assert!(explain.stderr.contains("Unknown error code 'E9999'"), "{}", explain.stderr);
+}
+
+#[test]
+fn allow_silences_the_named_warning_on_the_next_item() {
+    let source = r#"[Compiled]
+let xs = [1, "a"]
+#[allow(W0203)]
+let ys = [2, "b"]
+print(1)
+"#;
+    let run = run("allow-item", source, &[]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout.matches("Warning[W0203]").count(), 1, "{}", run.stdout);
+    assert!(run.stdout.contains("(line 2, col 14)"), "{}", run.stdout);
+}
+
+#[test]
+fn allow_at_the_top_silences_the_whole_file() {
+    let source = "[Compiled]\n#![allow(W0203)]\nlet xs = [1, \"a\"]\nlet ys = [2, \"b\"]\nprint(1)\n";
+    let run = run("allow-file", source, &[]);
+    assert!(run.success, "{}", run.stderr);
+    assert!(!run.stdout.contains("W0203"), "{}", run.stdout);
+}
+
+#[test]
+fn errors_cannot_be_allowed() {
+    let source = "[Compiled]\n#[allow(E0200)]\nlet y: int = \"a\"\n";
+    let build = command("build", "allow-error", source, &[]);
+    assert!(!build.success);
+    assert!(build.stdout.contains("Warning[W0001]: 'E0200' is an error; only warnings can be allowed"), "{}", build.stdout);
+    assert!(build.stdout.contains("Error[E0200]: Type mismatch"), "{}", build.stdout);
+}
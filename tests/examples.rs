@@ -0,0 +1,49 @@
+//! Runs shipped examples through the `joel` binary, so syntax they use keeps
+//! parsing
+
+mod common;
+
+use common::{run, run_file};
+use std::path::Path;
+
+#[test]
+fn examples_parse_and_run() {
+    for example in ["arithmetic", "pattern_matching", "contract", "ui_component"] {
+        let path = Path::new("examples").join(format!("{}.joel", example));
+        let run = run_file(&path, &[]);
+        assert!(run.success, "{}: {}", example, run.stderr);
+        assert!(!run.stderr.contains("Error"), "{}: {}", example, run.stderr);
+    }
+}
+
+#[test]
+fn tuple_literals_match_tuple_patterns() {
+    let run = run_file(Path::new("examples/pattern_matching.joel"), &[]);
+    assert_eq!(run.lines(), ["Zero", "One", "Other", "Zero and hello", "Number 42 and world"]);
+}
+
+#[test]
+fn as_converts_between_numeric_types() {
+    let source = r#"[Compiled]
+fn divide(a: i32, b: i32) -> f64 {
+  return a as f64 / b as f64
+}
+let ratio: f64 = divide(7, 2)
+print(ratio)
+print(ratio as i32)
+print(-ratio as i64)
+print(300 as u8)
+"#;
+    for vm in ["--vm=tree", "--vm=bytecode"] {
+        let run = run("casts", source, &[vm]);
+        assert!(run.success, "{}: {}", vm, run.stderr);
+        assert!(run.lines().ends_with(&["3.5", "3", "-3", "255"]), "{}: {}", vm, run.stdout);
+    }
+}
+
+#[test]
+fn as_rejects_non_numeric_conversions() {
+    let run = run("bad-cast", "[Compiled]\nlet n = \"7\" as i32\n", &[]);
+    assert!(!run.success);
+    assert!(run.stdout.contains("Error[E0211]: Cannot cast str to i32"), "{}", run.stdout);
+}
@@ -0,0 +1,24 @@
+//! Behaviour tests for generators, run through the `joel` binary
+
+mod common;
+
+use common::run;
+
+#[test]
+fn take_stops_an_infinite_generator() {
+    let source = r#"[Interpreted]
+fn naturals() {
+  let n = 0
+  while true {
+    yield n
+    n += 1
+  }
+}
+for n in take(naturals(), 3) {
+  print(n)
+}
+"#;
+    let run = run("generator-take", source, &[]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), ["0", "1", "2"]);
+}
@@ -0,0 +1,121 @@
+//! Behaviour tests for `import` across files
+
+mod common;
+
+use common::{run_file, run_file_with_env};
+use std::fs;
+use std::path::PathBuf;
+
+/// Writes `files` into a fresh temporary directory named after `name`
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("joel-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    for (file, source) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().expect("file in a directory")).expect("create project directory");
+        fs::write(path, source).expect("write module");
+    }
+    dir
+}
+
+#[test]
+fn export_default_exports_a_declaration_made_earlier() {
+    let dir = project("export-default", &[
+        ("shapes.joel", "[Compiled]\nfn area(w: i32, h: i32) -> i32 {\n  return w * h\n}\nexport default area\n"),
+        ("main.joel", "[Compiled]\nimport shapes\nprint(shapes.area(3, 4))\n"),
+    ]);
+    let run = run_file(&dir.join("main.joel"), &[]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(run.success, "{}\n{}", run.stdout, run.stderr);
+    assert!(run.lines().ends_with(&["12"]), "{}", run.stdout);
+}
+
+#[test]
+fn export_default_of_an_undeclared_name_is_an_error() {
+    let dir = project("export-default-undeclared", &[
+        ("main.joel", "[Compiled]\nexport default missing\n"),
+    ]);
+    let run = run_file(&dir.join("main.joel"), &[]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(!run.success);
+    assert!(run.stdout.contains("Error[E0204]: Cannot export 'missing'"), "{}", run.stdout);
+}
+
+const UTIL: &str = r#"[Interpreted]
+print("loading util")
+export fn double(x) {
+  return x * 2
+}
+fn hidden() {
+  return 1
+}
+export let greeting = "hi"
+"#;
+
+#[test]
+fn imports_see_exported_names_only() {
+    let dir = project("exports", &[
+        ("util.joel", UTIL),
+        ("main.joel", "[Interpreted]\nimport util\nprint(util.double(2))\nprint(util.greeting)\nprint(util.hidden)\n"),
+    ]);
+    let run = run_file(&dir.join("main.joel"), &[]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(!run.success);
+    assert!(run.lines().ends_with(&["4", "hi"]), "{}", run.stdout);
+    assert!(run.stderr.contains("No member 'hidden'"), "{}", run.stderr);
+}
+
+#[test]
+fn private_names_are_type_errors_in_compiled_mode() {
+    let dir = project("private-compiled", &[
+        ("util.joel", "[Compiled]\nexport fn double(x: i32) -> i32 {\n  return x * 2\n}\nfn hidden() -> i32 {\n  return 1\n}\n"),
+        ("main.joel", "[Compiled]\nimport util\nprint(util.hidden())\n"),
+    ]);
+    let run = run_file(&dir.join("main.joel"), &[]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(!run.success);
+    assert!(run.stdout.contains("Error[E0208]: Module 'util' does not export 'hidden'"), "{}", run.stdout);
+}
+
+#[test]
+fn import_aliases_bind_the_module_under_another_name() {
+    let dir = project("alias", &[
+        ("util.joel", UTIL),
+        ("main.joel", "[Interpreted]\nimport util as u\nimport \"util.joel\" as helpers\nprint(u.double(5))\nprint(helpers.greeting)\n"),
+    ]);
+    let run = run_file(&dir.join("main.joel"), &[]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout.matches("loading util").count(), 1, "{}", run.stdout);
+    assert!(run.lines().ends_with(&["10", "hi"]), "{}", run.stdout);
+}
+
+#[test]
+fn modules_imported_several_times_run_once() {
+    let dir = project("load-once", &[
+        ("util.joel", UTIL),
+        ("other.joel", "[Interpreted]\nimport util\nexport fn quad(x) {\n  return util.double(util.double(x))\n}\n"),
+        ("main.joel", "[Interpreted]\nimport util\nimport util as again\nimport other\nprint(other.quad(1))\n"),
+    ]);
+    let run = run_file(&dir.join("main.joel"), &[]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout.matches("loading util").count(), 1, "{}", run.stdout);
+    assert!(run.lines().ends_with(&["4"]), "{}", run.stdout);
+}
+
+#[test]
+fn modules_are_found_on_joel_path() {
+    let dir = project("joel-path", &[
+        ("lib/paths.joel", "[Interpreted]\nexport fn origin() {\n  return \"from JOEL_PATH\"\n}\n"),
+        ("app/main.joel", "[Interpreted]\nimport paths\nprint(paths.origin())\n"),
+    ]);
+    let lib = dir.join("lib");
+    let found = run_file_with_env(&dir.join("app/main.joel"), &[], &[("JOEL_PATH", lib.to_str().expect("UTF-8 path"))]);
+    let missing = run_file_with_env(&dir.join("app/main.joel"), &[], &[("JOEL_PATH", "")]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(found.success, "{}", found.stderr);
+    assert!(found.lines().ends_with(&["from JOEL_PATH"]), "{}", found.stdout);
+    assert!(!missing.success);
+    assert!(missing.stdout.contains("Warning[W0501]: Module 'paths' not found"), "{}", missing.stdout);
+}
@@ -0,0 +1,66 @@
+//! Behaviour tests for parallel loops, run through the `joel` binary at fixed
+//! `--threads` counts
+
+mod common;
+
+use common::run;
+
+const REDUCE: &str = r#"[Interpreted]
+let xs = [1, 2, 3, 4, 5, 6, 7, 8]
+let squares = parallel reduce s = 0, x in xs { s + x * x } combine (a, b) { a + b }
+let count = parallel reduce n = 0, x in xs { n + 1 }
+print(squares, count)
+"#;
+
+#[test]
+fn parallel_reduce_does_not_depend_on_thread_count() {
+    for threads in ["1", "3", "4"] {
+        let run = run(&format!("reduce-{}", threads), REDUCE, &["--threads", threads]);
+        assert!(run.success, "--threads {}: {}", threads, run.stderr);
+        assert_eq!(run.lines(), ["204 8"], "--threads {}", threads);
+    }
+}
+
+#[test]
+fn parallel_map_keeps_input_order() {
+    let source = r#"[Interpreted]
+let doubled = parallel map x in range(0, 50) { x * 2 }
+print(doubled)
+"#;
+    let expected: Vec<String> = (0..50).map(|x| (x * 2).to_string()).collect();
+    let run = run("map-order", source, &["--threads", "4"]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.lines(), [format!("[{}]", expected.join(", "))]);
+}
+
+#[test]
+fn parallel_map_propagates_item_errors() {
+    let source = r#"[Interpreted]
+fn check(x) {
+  if x == 7 {
+    return undefined_name
+  }
+  return x
+}
+let out = parallel map x in range(0, 20) { check(x) }
+print("unreachable", out)
+"#;
+    let run = run("map-error", source, &["--threads", "4"]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("Undefined variable: undefined_name"), "{}", run.stderr);
+    assert!(!run.stdout.contains("unreachable"));
+}
+
+#[test]
+fn parallel_for_rejects_outer_assignment() {
+    let source = r#"[Interpreted]
+let total = 0
+parallel for x in range(0, 1000) {
+  total += 1
+}
+print(total)
+"#;
+    let run = run("for-outer", source, &["--threads", "8"]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("Cannot assign to outer variable 'total'"), "{}", run.stderr);
+}
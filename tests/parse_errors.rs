@@ -0,0 +1,40 @@
+//! Behaviour tests for syntax errors and parser recovery, run through the
+//! `joel` binary
+
+mod common;
+
+use common::{command, run};
+
+const BROKEN: &str = r#"[Interpreted]
+let = 3
+fn f( {
+  return 1
+}
+let ok = 2
+let z = (1 +
+print(ok)
+"#;
+
+#[test]
+fn every_syntax_error_is_reported_before_aborting() {
+    let run = run("syntax-errors", BROKEN, &[]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("Parsing failed. Execution aborted."), "{}", run.stderr);
+    // The parser recovers at the next statement, so later errors are found too
+    let errors: Vec<&str> = run.lines().into_iter().filter(|line| line.starts_with("❌ Error[E0100]")).collect();
+    assert_eq!(errors.len(), 3, "{}", run.stdout);
+    assert!(errors[0].ends_with("(line 2, col 5)"), "{}", errors[0]);
+    assert!(errors[1].ends_with("(line 3, col 7)"), "{}", errors[1]);
+    assert!(errors[2].ends_with("(line 7, col 13)"), "{}", errors[2]);
+    assert!(run.stdout.contains("  2 | let = 3\n    |     ^"), "{}", run.stdout);
+    // Nothing runs
+    assert!(!run.stdout.contains("JOEL Runtime"), "{}", run.stdout);
+}
+
+#[test]
+fn build_aborts_on_syntax_errors() {
+    let build = command("build", "syntax-errors-build", BROKEN, &[]);
+    assert!(!build.success);
+    assert!(build.stderr.contains("Parsing failed. Build aborted."), "{}", build.stderr);
+    assert_eq!(build.stdout.matches("Error[E0100]").count(), 3, "{}", build.stdout);
+}
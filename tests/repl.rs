@@ -0,0 +1,44 @@
+//! Behaviour tests for `joel repl`, run with its input piped through stdin
+
+mod common;
+
+use common::repl;
+
+#[test]
+fn type_of_an_empty_map_literal() {
+    let run = repl("type-map", ":type {}\n:type {a: 1, b: \"x\"}\n");
+    assert!(run.success, "{}", run.stderr);
+    assert!(run.stdout.contains("{} : map[str, any]"), "{}", run.stdout);
+    assert!(run.stdout.contains("{a: 1, b: \"x\"} : map[str, any]"), "{}", run.stdout);
+    assert!(!run.stderr.contains("expects a single expression"), "{}", run.stderr);
+}
+
+#[test]
+fn type_of_a_function_infers_its_return_type() {
+    let input = "fn half(x: f64) {\n  return x / 2.0\n}\nfn greet(name: str) {\n  print(name)\n}\n:type half\n:type greet\n:type half(3.0)\n";
+    let run = repl("type-fn", input);
+    assert!(run.success, "{}", run.stderr);
+    assert!(run.stdout.contains("half : fn(f64) -> f64"), "{}", run.stdout);
+    assert!(run.stdout.contains("greet : fn(str) -> None"), "{}", run.stdout);
+    assert!(run.stdout.contains("half(3.0) : f64"), "{}", run.stdout);
+}
+
+#[test]
+fn multi_line_input_runs_once_its_brackets_close() {
+    let input = "fn add(a, b) {\n  return a + b\n}\nlet total = add(\n  2,\n  3\n)\ntotal * 10\n";
+    let run = repl("multi-line", input);
+    assert!(run.success, "{}", run.stderr);
+    assert!(run.stdout.contains("  ... "), "{}", run.stdout);
+    assert!(run.stdout.contains("50"), "{}", run.stdout);
+    assert!(run.stderr.is_empty(), "{}", run.stderr);
+}
+
+#[test]
+fn history_lists_previous_inputs_including_multi_line_ones() {
+    let input = "let x = 1\nfn one() {\n  return 1\n}\n:type x\n:history\n";
+    let run = repl("history", input);
+    assert!(run.success, "{}", run.stderr);
+    for entry in ["   1  let x = 1", "   2  fn one() {\n  return 1\n}", "   3  :type x", "   4  :history"] {
+        assert!(run.stdout.contains(entry), "{:?} in {}", entry, run.stdout);
+    }
+}
@@ -0,0 +1,62 @@
+//! Behaviour tests for the standard library modules callable from programs
+
+mod common;
+
+use common::run;
+
+const VMS: [&str; 2] = ["--vm=tree", "--vm=bytecode"];
+
+/// Runs `body` after importing `module` on each VM and checks the output ends with `expected`
+fn check_happy_path(module: &str, body: &str, expected: &[&str]) {
+    let source = format!("[Interpreted]\nimport {}\n{}", module, body);
+    for vm in VMS {
+        let run = run(&format!("stdlib-{}", module), &source, &[vm]);
+        assert!(run.success, "{} {}: {}", module, vm, run.stderr);
+        assert!(run.lines().ends_with(expected), "{} {}: {}", module, vm, run.stdout);
+    }
+}
+
+/// Checks that `call` fails at runtime on each VM when interpreted, and is
+/// rejected with `code` by the type checker when compiled, both with `message`
+fn check_misuse(module: &str, call: &str, code: &str, message: &str) {
+    let interpreted = format!("[Interpreted]\nimport {}\nprint({})\n", module, call);
+    for vm in VMS {
+        let run = run(&format!("stdlib-{}-misuse", module), &interpreted, &[vm]);
+        assert!(!run.success, "{} {}", call, vm);
+        assert!(run.stderr.contains(message), "{} {}: {}", call, vm, run.stderr);
+    }
+    let compiled = format!("[Compiled]\nimport {}\nprint({})\n", module, call);
+    let run = run(&format!("stdlib-{}-misuse-compiled", module), &compiled, &[]);
+    assert!(!run.success, "{}", call);
+    assert!(run.stdout.contains(&format!("Error[{}]: {}", code, message)), "{}: {}", call, run.stdout);
+}
+
+#[test]
+fn math_functions_compute_results() {
+    check_happy_path("math", "print(math.sqrt(16.0), math.max(2, 7), math.abs(-3))\nprint(math.pow(2.0, 10.0))\n", &["4 7 3", "1024"]);
+}
+
+#[test]
+fn math_functions_check_their_arity() {
+    check_misuse("math", "math.sqrt(1.0, 2.0)", "E0203", "Function 'math.sqrt' expects 1 arguments, got 2");
+}
+
+#[test]
+fn string_functions_compute_results() {
+    check_happy_path("string", "print(string.to_upper(\"joel\"), string.len(\"abc\"))\nprint(string.split(\"a,b\", \",\"), string.contains(\"joel\", \"oe\"))\n", &["JOEL 3", "[a, b] true"]);
+}
+
+#[test]
+fn string_functions_check_argument_types() {
+    check_misuse("string", "string.trim(5)", "E0202", "Argument 1 to 'string.trim': expected str");
+}
+
+#[test]
+fn collections_functions_compute_results() {
+    check_happy_path("collections", "let xs = collections.push([1, 2], 3)\nprint(xs, collections.len(xs))\nprint(collections.map_get({a: 1}, \"a\"))\n", &["[1, 2, 3] 3", "1"]);
+}
+
+#[test]
+fn collections_functions_check_their_arity() {
+    check_misuse("collections", "collections.push([1])", "E0203", "Function 'collections.push' expects 2 arguments, got 1");
+}
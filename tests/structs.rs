@@ -0,0 +1,49 @@
+//! Behaviour tests for struct declarations and literals
+
+mod common;
+
+use common::run;
+
+fn program(mode: &str, body: &str) -> String {
+    format!("[{}]\nstruct Point {{ x: f64, y: f64 }}\n{}\n", mode, body)
+}
+
+#[test]
+fn unknown_fields_are_e0208() {
+    for (name, body, line) in [
+        ("literal", "let p = Point { x: 1.0, y: 2.0, z: 3.0 }", 3),
+        ("access", "let p = Point { x: 1.0, y: 2.0 }\nprint(p.z)", 4),
+    ] {
+        let run = run(&format!("unknown-field-{}", name), &program("Compiled", body), &[]);
+        assert!(!run.success, "{}", name);
+        assert!(run.stdout.contains(&format!("Error[E0208]: Struct 'Point' has no field 'z' (line {}", line)), "{}: {}", name, run.stdout);
+    }
+}
+
+#[test]
+fn missing_fields_are_e0209() {
+    let run = run("missing-field", &program("Compiled", "let p = Point { x: 1.0 }"), &[]);
+    assert!(!run.success);
+    assert!(run.stdout.contains("Error[E0209]: Missing field 'y' in 'Point' literal (line 3"), "{}", run.stdout);
+}
+
+#[test]
+fn field_errors_stop_interpreted_programs_on_both_vms() {
+    for vm in ["--vm=tree", "--vm=bytecode"] {
+        for (body, message) in [
+            ("let p = Point { x: 1.0, y: 2.0, z: 3.0 }", "Struct 'Point' has no field 'z'"),
+            ("let p = Point { x: 1.0 }", "Missing field 'y' in 'Point' literal"),
+        ] {
+            let run = run("field-errors", &program("Interpreted", body), &[vm]);
+            assert!(!run.success, "{}: {}", vm, body);
+            assert!(run.stderr.contains(message), "{}: {}", vm, run.stderr);
+        }
+    }
+}
+
+#[test]
+fn declared_fields_can_be_read_and_written() {
+    let run = run("fields", &program("Compiled", "let p = Point { x: 1.0, y: 2.0 }\np.x = 4.5\nprint(p.x + p.y)"), &[]);
+    assert!(run.success, "{}\n{}", run.stdout, run.stderr);
+    assert!(run.lines().ends_with(&["6.5"]), "{}", run.stdout);
+}
@@ -0,0 +1,21 @@
+//! Behaviour tests for actor supervisors, run through the `joel` binary under
+//! `--deterministic`
+
+mod common;
+
+use common::{run, LOG};
+
+#[test]
+fn supervisor_restarts_a_failed_actor_from_its_initializers() {
+    let source = format!("{}{}", LOG, r#"
+send log.bump()
+send log.add(7)
+let sup = supervise([log], "one_for_one", 3, 5000)
+send log.fail()
+print(await log.count(), await log.get() == "")
+"#);
+    let run = run("actor-restart", &source, &["--deterministic"]);
+    assert!(run.success, "{}", run.stderr);
+    assert!(run.stderr.contains("Actor Log failed in 'fail'"), "{}", run.stderr);
+    assert_eq!(run.lines(), ["0 true"]);
+}
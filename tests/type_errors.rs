@@ -0,0 +1,57 @@
+//! Behaviour tests for type errors, run through the `joel` binary
+
+mod common;
+
+use common::run;
+
+#[test]
+fn return_mismatch_points_at_the_annotation_only_when_there_is_one() {
+    let source = r#"[Compiled]
+fn double(x: i32) {
+  return x * 2
+}
+
+fn name(x) -> str {
+  return 1
+}
+"#;
+    let run = run("return-label", source, &[]);
+    assert!(!run.success);
+    // Diagnostics are printed with the rest of the compiler's output
+    assert!(run.stdout.contains("the function has no return annotation"), "{}", run.stdout);
+    assert!(!run.stdout.contains("declared to return None"), "{}", run.stdout);
+    assert!(run.stdout.contains("6 | fn name(x) -> str {"), "{}", run.stdout);
+    assert!(run.stdout.contains("declared to return str here"), "{}", run.stdout);
+}
+
+#[test]
+fn results_of_unannotated_function_values_fit_any_annotation() {
+    let source = r#"[Compiled]
+let f = (x) => x + 1
+let y: i32 = f(1)
+fn apply(h, v: i32) -> i32 {
+  return h(v)
+}
+print(y)
+print(apply(f, 2))
+"#;
+    let run = run("any-result", source, &[]);
+    assert!(run.success, "{}\n{}", run.stdout, run.stderr);
+    assert!(!run.stdout.contains("E020"), "{}", run.stdout);
+    assert!(run.lines().ends_with(&["2", "3"]), "{}", run.stdout);
+}
+
+#[test]
+fn map_literals_infer_from_their_values_or_the_annotation() {
+    let source = r#"[Compiled]
+let m = {a: 1, b: "x"}
+let v: i32 = m.a
+let e: map[str, i32] = {}
+let l: list[i32] = []
+print(v)
+"#;
+    let run = run("map-inference", source, &[]);
+    assert!(run.success, "{}\n{}", run.stdout, run.stderr);
+    assert!(!run.stdout.contains("E0200"), "{}", run.stdout);
+    assert!(run.lines().ends_with(&["1"]), "{}", run.stdout);
+}